@@ -160,6 +160,7 @@ typedef enum StateTypeEnum {
   None = 0,
   InflateType,
   DeflateType,
+  InflateBackType,
 } StateTypeEnum;
 
 typedef enum tdefl_status {
@@ -243,6 +244,11 @@ typedef struct DecompressorOxide DecompressorOxide;
 
 typedef struct InternalState InternalState;
 
+/**
+ * A gzip file opened for reading or writing.
+ */
+typedef struct mz_gzFile_s mz_gzFile_s;
+
 typedef void *(*mz_alloc_callback)(void*, size_t, size_t);
 
 typedef void (*mz_free_callback)(void*, void*);
@@ -310,8 +316,86 @@ typedef struct mz_stream {
   unsigned long reserved;
 } mz_stream;
 
+/**
+ * Fields of a gzip header, used with `mz_deflateSetHeader` and `mz_inflateGetHeader`.
+ */
+typedef struct mz_gz_header {
+  /**
+   * Whether the data is probably text.
+   */
+  int text;
+  /**
+   * Modification time as a unix timestamp.
+   */
+  unsigned long time;
+  /**
+   * Extra flags, ignored when compressing.
+   */
+  int xflags;
+  /**
+   * Operating system the data originated on.
+   */
+  int os;
+  /**
+   * Buffer for the extra field, or null if there is none.
+   */
+  uint8_t *extra;
+  /**
+   * Length of the extra field.
+   */
+  unsigned int extra_len;
+  /**
+   * Space in `extra` when decompressing.
+   */
+  unsigned int extra_max;
+  /**
+   * Zero-terminated file name, or null if there is none.
+   */
+  uint8_t *name;
+  /**
+   * Space in `name` when decompressing.
+   */
+  unsigned int name_max;
+  /**
+   * Zero-terminated comment, or null if there is none.
+   */
+  uint8_t *comment;
+  /**
+   * Space in `comment` when decompressing.
+   */
+  unsigned int comm_max;
+  /**
+   * Whether the header is protected by a CRC16.
+   */
+  int hcrc;
+  /**
+   * Set to 1 when the header has been read, or -1 if the data is not gzip.
+   */
+  int done;
+} mz_gz_header;
+
 typedef int32_t (*tdefl_put_buf_func_ptr)(const void*, int, void*);
 
+/**
+ * Signature of the function `mz_inflateBack` calls to get more input.
+ *
+ * Sets the pointer to the start of the input and returns its length, or 0 if there is
+ * no more input.
+ */
+typedef unsigned int (*mz_in_func)(void*, const uint8_t**);
+
+/**
+ * Signature of the function `mz_inflateBack` calls to write output.
+ *
+ * Returns 0 on success, or a non-zero value to stop decompression.
+ */
+typedef int (*mz_out_func)(void*, uint8_t*, unsigned int);
+
+/**
+ * Decompression callback function type.
+ */
+typedef int (*tinfl_put_buf_func_ptr)(const void*, int, void*);
+
 typedef struct tinfl_decompressor {
   struct DecompressorOxide *inner;
 } tinfl_decompressor;
@@ -328,6 +412,11 @@ typedef void (*mz_free_func)(void*, void*);
 
 typedef void *(*mz_realloc_func)(void*, void*, size_t, size_t);
 
+/**
+ * Handle to a gz file, as returned by `mz_gzopen` and `mz_gzdopen`.
+ */
+typedef struct mz_gzFile_s *mz_gzFile;
+
 #ifdef __cplusplus
 extern "C" {
 #endif // __cplusplus
@@ -338,10 +427,28 @@ int mz_deflateEnd(struct mz_stream *stream);
 
 int mz_deflateReset(struct mz_stream *stream);
 
+int mz_deflateParams(struct mz_stream *stream, int level, int strategy);
+
+int mz_deflatePrime(struct mz_stream *stream, int bits, int value);
+
+int mz_deflateCopy(struct mz_stream *dest, struct mz_stream *source);
+
 int mz_inflate(struct mz_stream *stream, int flush);
 
 int mz_inflateEnd(struct mz_stream *stream);
 
+int mz_inflateReset(struct mz_stream *stream);
+
+int mz_inflateReset2(struct mz_stream *stream, int window_bits);
+
+int mz_inflateSync(struct mz_stream *stream);
+
+int mz_inflatePrime(struct mz_stream *stream, int bits, int value);
+
+int mz_inflateCopy(struct mz_stream *dest, struct mz_stream *source);
+
+int mz_inflateBackEnd(struct mz_stream *stream);
+
 int mz_deflateInit(struct mz_stream *stream, int level);
 
 int mz_deflateInit2(struct mz_stream *stream,
@@ -351,8 +458,52 @@ int mz_deflateInit2(struct mz_stream *stream,
                     int mem_level,
                     int strategy);
 
+int mz_deflateSetDictionary(struct mz_stream *stream,
+                            const uint8_t *dictionary,
+                            unsigned int dict_length);
+
+int mz_inflateSetDictionary(struct mz_stream *stream,
+                            const uint8_t *dictionary,
+                            unsigned int dict_length);
+
+/**
+ * Set the header to write when compressing with the gzip wrapper.
+ *
+ * The fields of `head` are read right away, so it doesn't have to stay valid.
+ */
+int mz_deflateSetHeader(struct mz_stream *stream, const struct mz_gz_header *head);
+
+/**
+ * Have `mz_inflate` fill in `head` with the gzip header when it has been read.
+ *
+ * `head` must stay valid until then, or until the stream is reset or ended.
+ */
+int mz_inflateGetHeader(struct mz_stream *stream, struct mz_gz_header *head);
+
 int mz_inflateInit2(struct mz_stream *stream, int window_bits);
 
+/**
+ * Initialize a stream for `mz_inflateBack`, using `window` as the sliding window.
+ *
+ * `window` must point to `1 << window_bits` bytes, and `window_bits` must be in `8..=15`.
+ * The window has to stay valid until `mz_inflateBackEnd` is called.
+ */
+int mz_inflateBackInit(struct mz_stream *stream, int window_bits, uint8_t *window);
+
+/**
+ * Decompress a raw deflate stream, pulling input with `in_fn` and pushing output to
+ * `out_fn`, using only the window passed to `mz_inflateBackInit` as the output buffer.
+ *
+ * Input in `next_in` is used first. Returns `MZ_STREAM_END` on success, and
+ * `MZ_BUF_ERROR` if `in_fn` returned no input, in which case `next_in` is set to null,
+ * or if `out_fn` returned a non-zero value. Unused input is left in `next_in`.
+ */
+int mz_inflateBack(struct mz_stream *stream,
+                   mz_in_func in_fn,
+                   void *in_desc,
+                   mz_out_func out_fn,
+                   void *out_desc);
+
 int mz_compress(uint8_t *dest,
                 unsigned long *dest_len,
                 const uint8_t *source,
@@ -373,6 +524,15 @@ int mz_uncompress(uint8_t *dest,
                   const uint8_t *source,
                   unsigned long source_len);
 
+/**
+ * Like `mz_uncompress`, but `source_len` is a pointer, which is set to the number of
+ * bytes of input that were used.
+ */
+int mz_uncompress2(uint8_t *dest,
+                   unsigned long *dest_len,
+                   const uint8_t *source,
+                   unsigned long *source_len);
+
 unsigned long mz_compressBound(unsigned long source_len);
 
 enum tdefl_status tdefl_compress(struct tdefl_compressor *d,
@@ -387,6 +547,23 @@ enum tdefl_status tdefl_compress_buffer(struct tdefl_compressor *d,
                                         uintptr_t in_size,
                                         enum tdefl_flush flush);
 
+/**
+ * Decompress data from `p_in_buf`, passing the output to `p_put_buf_func` in chunks of at
+ * most `TINFL_LZ_DICT_SIZE` bytes.
+ *
+ * The output is decompressed into a 32 KiB buffer that is handed to the callback whenever
+ * it is full, and once more at the end, so this needs no memory besides the buffer and
+ * the decompressor. Decompression stops if the callback returns 0.
+ *
+ * Sets `p_in_buf_size` to the number of input bytes consumed.
+ * Returns 1 on success and 0 if decompression fails or is stopped by the callback.
+ */
+int tinfl_decompress_mem_to_callback(const void *p_in_buf,
+                                     size_t *p_in_buf_size,
+                                     tinfl_put_buf_func_ptr p_put_buf_func,
+                                     void *p_put_buf_user,
+                                     int flags);
+
 /**
  * Allocate a compressor.
  *
@@ -497,6 +674,26 @@ void miniz_def_free_func(void *_opaque, void *address);
 
 void *miniz_def_realloc_func(void *_opaque, void *address, size_t items, size_t size);
 
+/**
+ * Get a description of the return code `err`, or null if it is not a known code.
+ */
+const char *mz_error(int err);
+
+/**
+ * Get the version of the library as a string.
+ */
+const char *mz_version(void);
+
+/**
+ * Get flags describing the library, laid out like the ones returned by
+ * `zlibCompileFlags` in zlib.
+ *
+ * Bits 0-1, 2-3, 4-5 and 6-7 hold the size of `c_uint`, `c_ulong`, pointers and the
+ * `c_long` offsets of `mz_gzseek`, as 0 for 16 bits, 1 for 32 bits, 2 for 64 bits and
+ * 3 for other sizes. Bit 16 is set if the gz file functions are not provided.
+ */
+unsigned long mz_compileFlags(void);
+
 /**
  * Calculate adler32 checksum of the provided buffer with the initial adler32 checksum of `adler`.
  * If c_ulong is wider than 32 bits, only the lower 32 bits will be used.
@@ -513,6 +710,140 @@ unsigned long mz_adler32(unsigned long adler, const uint8_t *ptr, uintptr_t buf_
  */
 unsigned long mz_crc32(unsigned long crc, const uint8_t *ptr, size_t buf_len);
 
+/**
+ * Open the gzip file at `path` for reading or writing.
+ *
+ * `mode` is `"r"` to read, `"w"` to write or `"a"` to append a new gzip member. When
+ * writing, it can also hold a compression level digit, a strategy (`f`, `h`, `R` or
+ * `F`), `T` to write the data without compressing it, and `x` to fail if the file exists.
+ *
+ * Returns null if the file can't be opened or the mode is invalid.
+ */
+mz_gzFile mz_gzopen(const char *path, const char *mode);
+
+/**
+ * Open a gz file on the file descriptor `fd`, with a mode like `mz_gzopen`.
+ *
+ * The file descriptor is closed by `mz_gzclose`. Returns null if it can't be used with
+ * the mode or the mode is invalid.
+ */
+mz_gzFile mz_gzdopen(int fd, const char *mode);
+
+/**
+ * Read up to `len` bytes of uncompressed data into `buf`.
+ *
+ * Returns the number of bytes read, which is less than `len` only at the end of the
+ * data or after an error, or -1 if nothing could be read because of an error.
+ */
+int mz_gzread(mz_gzFile file, void *buf, unsigned int len);
+
+/**
+ * Compress and write `len` bytes from `buf`.
+ *
+ * Returns the number of bytes written, or 0 on error.
+ */
+int mz_gzwrite(mz_gzFile file, const void *buf, unsigned int len);
+
+/**
+ * Compress and write the zero-terminated string `s`, without the terminator.
+ *
+ * Returns the number of bytes written, or -1 on error.
+ */
+int mz_gzputs(mz_gzFile file, const char *s);
+
+/**
+ * Compress and write the byte `c`.
+ *
+ * Returns the byte written, or -1 on error.
+ */
+int mz_gzputc(mz_gzFile file, int c);
+
+/**
+ * Read a line of at most `len - 1` bytes into `buf`, stopping after a newline, and
+ * terminate it with a zero byte.
+ *
+ * Returns `buf`, or null if nothing could be read because of the end of the data or
+ * an error.
+ */
+char *mz_gzgets(mz_gzFile file, char *buf, int len);
+
+/**
+ * Read one byte of uncompressed data.
+ *
+ * Returns the byte, or -1 at the end of the data or on error.
+ */
+int mz_gzgetc(mz_gzFile file);
+
+/**
+ * Flush the data written so far with the flush mode `flush`, and flush the underlying
+ * file.
+ *
+ * `MZ_FINISH` ends the current gzip member, so data written afterwards goes into a new
+ * one. Returns `MZ_OK` on success, or an error code.
+ */
+int mz_gzflush(mz_gzFile file, int flush);
+
+/**
+ * Move to `offset` in the uncompressed data, relative to the start if `whence` is
+ * `SEEK_SET` or to the current position if it is `SEEK_CUR`.
+ *
+ * When reading, seeking backwards restarts decompression from the start of the file.
+ * When writing, only seeking forwards is possible, which writes zeros.
+ * Returns the new position, or -1 on error.
+ */
+long mz_gzseek(mz_gzFile file, long offset, int whence);
+
+/**
+ * Get the position in the uncompressed data, or -1 on error.
+ */
+long mz_gztell(mz_gzFile file);
+
+/**
+ * Go back to the start of a file opened for reading.
+ *
+ * Returns 0 on success, or -1 on error.
+ */
+int mz_gzrewind(mz_gzFile file);
+
+/**
+ * Returns 1 if a read has reached the end of the uncompressed data, otherwise 0.
+ */
+int mz_gzeof(mz_gzFile file);
+
+/**
+ * Returns 1 if the file is read or written without decompressing or compressing the
+ * data, otherwise 0.
+ */
+int mz_gzdirect(mz_gzFile file);
+
+/**
+ * Change the compression level (0-10) and strategy of a file opened for writing.
+ *
+ * Data written so far is compressed with the old settings first.
+ * Returns `MZ_OK` on success, or an error code.
+ */
+int mz_gzsetparams(mz_gzFile file, int level, int strategy);
+
+/**
+ * Get a description of the last error, and store its code in `errnum` if it isn't null.
+ *
+ * Returns an empty string if there was no error, or null if `file` is null.
+ */
+const char *mz_gzerror(mz_gzFile file, int *errnum);
+
+/**
+ * Clear the error and end of file state of `file`.
+ */
+void mz_gzclearerr(mz_gzFile file);
+
+/**
+ * Finish writing if the file was opened for writing, then close it and free its state.
+ *
+ * Returns `MZ_OK` on success, `MZ_BUF_ERROR` if the data read ended unexpectedly,
+ * `MZ_ERRNO` if closing the file failed, or the error that occurred when finishing.
+ */
+int mz_gzclose(mz_gzFile file);
+
 #ifdef __cplusplus
 }  // extern "C"
 #endif  // __cplusplus
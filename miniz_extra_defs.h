@@ -14,7 +14,12 @@ typedef int64_t mz_int64;
 typedef uint64_t mz_uint64;
 typedef int mz_bool;
 
-#define MZ_VERSION "0.0"
+#define MZ_VERSION "0.3.2"
+#define MZ_VERNUM 0x0320
+#define MZ_VER_MAJOR 0
+#define MZ_VER_MINOR 3
+#define MZ_VER_REVISION 2
+#define MZ_VER_SUBREVISION 0
 
 #define MZ_FALSE (0)
 #define MZ_TRUE (1)
@@ -133,9 +138,15 @@ typedef void *const voidpc;
 #define free_func mz_free_func
 #define internal_state mz_internal_state
 #define z_stream mz_stream
+#define gz_header mz_gz_header
+#define gz_headerp mz_gz_header *
 #define deflateInit mz_deflateInit
 #define deflateInit2 mz_deflateInit2
 #define deflateReset mz_deflateReset
+#define deflateParams mz_deflateParams
+#define deflatePrime mz_deflatePrime
+#define deflateCopy mz_deflateCopy
+#define deflateSetHeader mz_deflateSetHeader
 #define deflate mz_deflate
 #define deflateEnd mz_deflateEnd
 #define deflateBound mz_deflateBound
@@ -145,8 +156,18 @@ typedef void *const voidpc;
 #define inflateInit mz_inflateInit
 #define inflateInit2 mz_inflateInit2
 #define inflateReset mz_inflateReset
+#define inflateReset2 mz_inflateReset2
 #define inflate mz_inflate
 #define inflateEnd mz_inflateEnd
+#define inflateSync mz_inflateSync
+#define inflatePrime mz_inflatePrime
+#define inflateCopy mz_inflateCopy
+#define inflateGetHeader mz_inflateGetHeader
+#define inflateBackInit mz_inflateBackInit
+#define inflateBack mz_inflateBack
+#define inflateBackEnd mz_inflateBackEnd
+#define in_func mz_in_func
+#define out_func mz_out_func
 #define uncompress mz_uncompress
 #define uncompress2 mz_uncompress2
 #define crc32 mz_crc32
@@ -162,14 +183,30 @@ typedef void *const voidpc;
 #define ZLIB_VER_SUBREVISION MZ_VER_SUBREVISION
 #define zlibVersion mz_version
 #define zlib_version mz_version()
+#define zlibCompileFlags mz_compileFlags
+#define gzFile mz_gzFile
+#define gzopen mz_gzopen
+#define gzdopen mz_gzdopen
+#define gzread mz_gzread
+#define gzwrite mz_gzwrite
+#define gzputs mz_gzputs
+#define gzputc mz_gzputc
+#define gzgets mz_gzgets
+#define gzgetc mz_gzgetc
+#define gzflush mz_gzflush
+#define gzseek mz_gzseek
+#define gztell mz_gztell
+#define gzrewind mz_gzrewind
+#define gzeof mz_gzeof
+#define gzdirect mz_gzdirect
+#define gzsetparams mz_gzsetparams
+#define gzerror mz_gzerror
+#define gzclearerr mz_gzclearerr
+#define gzclose mz_gzclose
 #endif /* #ifndef MINIZ_NO_ZLIB_COMPATIBLE_NAMES */
 
 void mz_free(void *p);
 
-typedef int (*tinfl_put_buf_func_ptr)(const void *pBuf, int len, void *pUser);
-/* Not implemented yet.*/
-int tinfl_decompress_mem_to_callback(const void *pIn_buf, size_t *pIn_buf_size, tinfl_put_buf_func_ptr pPut_buf_func, void *pPut_buf_user, int flags);
-
 /* Compresses an image to a compressed PNG file in memory. */
 /* On entry: */
 /*  pImage, w, h, and num_chans describe the image to compress. num_chans may be 1, 2, 3, or 4. */
@@ -0,0 +1,143 @@
+//! Reading and writing [BGZF](https://samtools.github.io/hts-specs/SAMv1.pdf) (blocked gzip)
+//! files, as used by BAM, tabix and other bioinformatics formats.
+//!
+//! A BGZF file is a series of gzip members that each hold at most 64 KiB of compressed
+//! data. Every member has a `BC` extra subfield storing the size of the member, so blocks
+//! can be located without decompressing them. The file ends with a fixed empty block,
+//! [`BGZF_EOF`].
+//!
+//! Positions in the uncompressed data are addressed with 64-bit virtual offsets, see
+//! [`virtual_offset`].
+
+use crate::gzip::{gzip_flags, GZIP_CM_DEFLATE, GZIP_ID1, GZIP_ID2, GZIP_OS_UNKNOWN};
+use crate::inflate::TINFLStatus;
+#[cfg(feature = "std")]
+use std::error::Error;
+
+mod read;
+mod write;
+
+#[cfg(feature = "std")]
+pub use self::read::IoSource;
+pub use self::read::{BgzfReader, BgzfSource};
+pub use self::write::BgzfWriter;
+
+/// Maximum size of a whole block, including the header and trailer.
+pub const BGZF_MAX_BLOCK_SIZE: usize = 0x10000;
+/// Maximum amount of uncompressed data stored in a block.
+///
+/// This is less than 64 KiB so that the block still fits if the data does not compress.
+pub const BGZF_MAX_BLOCK_DATA: usize = 0xFF00;
+
+/// Length of the block header, including the `BC` subfield.
+pub(crate) const BGZF_HEADER_LEN: usize = 18;
+/// Length of the crc32 and uncompressed size at the end of a block.
+pub(crate) const BGZF_TRAILER_LEN: usize = 8;
+
+/// The empty block that marks the end of a BGZF file.
+pub const BGZF_EOF: [u8; 28] = [
+    0x1F, 0x8B, 0x08, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00, 0xFF, 0x06, 0x00, 0x42, 0x43, 0x02, 0x00,
+    0x1B, 0x00, 0x03, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+];
+
+/// Errors that can occur when reading a BGZF file.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum BgzfError {
+    /// The data is not a BGZF block, or the header of the block is corrupt.
+    InvalidBlock,
+    /// The file ends in the middle of a block.
+    Truncated,
+    /// The compressed data of a block is invalid.
+    ///
+    /// The status is the status the decompressor failed with, or
+    /// [`TINFLStatus::Failed`] if the size of the data did not match the size in the trailer.
+    InvalidData(TINFLStatus),
+    /// The crc32 checksum of the data of a block did not match the one in the trailer.
+    ChecksumMismatch,
+    /// The virtual offset is past the end of its block, or its block offset past the end of
+    /// the file.
+    InvalidOffset,
+    /// Reading from the underlying file failed.
+    #[cfg(feature = "std")]
+    Io(std::io::ErrorKind),
+}
+
+impl core::fmt::Display for BgzfError {
+    #[cold]
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            BgzfError::InvalidBlock => f.write_str("Invalid or corrupt BGZF block"),
+            BgzfError::Truncated => f.write_str("Truncated BGZF block"),
+            BgzfError::InvalidData(_) => f.write_str("Invalid compressed data"),
+            BgzfError::ChecksumMismatch => f.write_str("Checksum mismatch"),
+            BgzfError::InvalidOffset => {
+                f.write_str("Virtual offset past the end of the block or file")
+            }
+            #[cfg(feature = "std")]
+            BgzfError::Io(kind) => write!(f, "I/O error: {:?}", kind),
+        }
+    }
+}
+
+/// Implement Error trait only if std feature is requested as it requires std.
+#[cfg(feature = "std")]
+impl Error for BgzfError {}
+
+/// Build a virtual offset from the offset of a block in the compressed file and an offset
+/// in the uncompressed data of that block.
+///
+/// The block offset must be less than 2^48 and the in-block offset less than 2^16.
+#[inline]
+pub const fn virtual_offset(block_offset: u64, in_block_offset: u16) -> u64 {
+    (block_offset << 16) | in_block_offset as u64
+}
+
+/// Split a virtual offset into the offset of the block in the compressed file and the
+/// offset in the uncompressed data of that block.
+#[inline]
+pub const fn split_virtual_offset(offset: u64) -> (u64, u16) {
+    (offset >> 16, offset as u16)
+}
+
+/// Header of a block holding `block_size` bytes in total.
+pub(crate) fn block_header(block_size: usize) -> [u8; BGZF_HEADER_LEN] {
+    let bsize = ((block_size - 1) as u16).to_le_bytes();
+    [
+        GZIP_ID1,
+        GZIP_ID2,
+        GZIP_CM_DEFLATE,
+        gzip_flags::FEXTRA,
+        0,
+        0,
+        0,
+        0,
+        0,
+        GZIP_OS_UNKNOWN,
+        6,
+        0,
+        b'B',
+        b'C',
+        2,
+        0,
+        bsize[0],
+        bsize[1],
+    ]
+}
+
+#[cfg(test)]
+mod test {
+    use super::{block_header, split_virtual_offset, virtual_offset, BGZF_EOF};
+
+    #[test]
+    fn virtual_offsets() {
+        let offset = virtual_offset(0x1234_5678, 0xABCD);
+        assert_eq!(offset, 0x1234_5678_ABCD);
+        assert_eq!(split_virtual_offset(offset), (0x1234_5678, 0xABCD));
+    }
+
+    #[test]
+    fn eof_block_header() {
+        assert_eq!(block_header(BGZF_EOF.len()), BGZF_EOF[..18]);
+    }
+}
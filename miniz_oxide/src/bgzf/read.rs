@@ -0,0 +1,484 @@
+//! Decompression of BGZF blocks with random access by virtual offset.
+
+use crate::alloc::boxed::Box;
+use crate::alloc::vec::Vec;
+use crate::gzip::{gzip_flags, ExtraSubfields, GZIP_CM_DEFLATE, GZIP_ID1, GZIP_ID2};
+use crate::inflate::core::inflate_flags::TINFL_FLAG_USING_NON_WRAPPING_OUTPUT_BUF;
+use crate::inflate::core::{decompress, DecompressorOxide};
+use crate::inflate::TINFLStatus;
+use crate::shared::{update_crc32, MZ_CRC32_INIT};
+
+use super::{
+    split_virtual_offset, virtual_offset, BgzfError, BGZF_MAX_BLOCK_SIZE, BGZF_TRAILER_LEN,
+};
+
+/// Length of the gzip header up to and including the `XLEN` field.
+const FIXED_HEADER_LEN: usize = 12;
+
+/// Number of decompressed blocks kept by default, besides the one being read.
+const DEFAULT_CACHE_SIZE: usize = 8;
+
+/// Random access to the compressed data of a BGZF file.
+pub trait BgzfSource {
+    /// Read bytes starting at `offset` in the file into `buf`, returning the number of bytes
+    /// read.
+    ///
+    /// Fewer bytes than requested may be returned, and 0 is returned at the end of the file.
+    fn read_at(&mut self, offset: u64, buf: &mut [u8]) -> Result<usize, BgzfError>;
+}
+
+impl BgzfSource for &[u8] {
+    fn read_at(&mut self, offset: u64, buf: &mut [u8]) -> Result<usize, BgzfError> {
+        let data = usize::try_from(offset)
+            .ok()
+            .and_then(|offset| self.get(offset..))
+            .unwrap_or(&[]);
+        let n = buf.len().min(data.len());
+        buf[..n].copy_from_slice(&data[..n]);
+        Ok(n)
+    }
+}
+
+/// Adapter to use a [`std::io::Read`] + [`std::io::Seek`] implementation, e.g. a
+/// [`std::fs::File`], as a [`BgzfSource`].
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub struct IoSource<R>(pub R);
+
+#[cfg(feature = "std")]
+impl<R: std::io::Read + std::io::Seek> BgzfSource for IoSource<R> {
+    fn read_at(&mut self, offset: u64, buf: &mut [u8]) -> Result<usize, BgzfError> {
+        use std::io::{ErrorKind, SeekFrom};
+
+        self.0
+            .seek(SeekFrom::Start(offset))
+            .map_err(|e| BgzfError::Io(e.kind()))?;
+        loop {
+            match self.0.read(buf) {
+                Err(e) if e.kind() == ErrorKind::Interrupted => (),
+                res => return res.map_err(|e| BgzfError::Io(e.kind())),
+            }
+        }
+    }
+}
+
+/// A decompressed block.
+struct Block {
+    /// Offset of the block in the compressed file.
+    offset: u64,
+    /// Offset of the block following this one.
+    next: u64,
+    data: Vec<u8>,
+}
+
+/// Reader that decompresses a BGZF file one block at a time.
+///
+/// Blocks are only read and decompressed when data from them is needed, and the crc32
+/// checksum of each block is verified. Recently used blocks are kept in a small cache so
+/// seeking back and forth between nearby offsets does not decompress the same blocks again.
+///
+/// ```
+/// use miniz_oxide::bgzf::{BgzfReader, BgzfWriter};
+///
+/// let mut writer = BgzfWriter::new(6);
+/// writer.write(b"chr1\t100\n");
+/// let offset = writer.write(b"chr1\t200\n");
+/// let bgzf = writer.finish();
+///
+/// let mut reader = BgzfReader::new(&bgzf[..]);
+/// reader.seek_virtual(offset).unwrap();
+/// let mut record = [0; 9];
+/// assert_eq!(reader.read(&mut record), Ok(9));
+/// assert_eq!(&record, b"chr1\t200\n");
+/// ```
+pub struct BgzfReader<S> {
+    source: S,
+    /// The block being read. Not part of the cache.
+    current: Block,
+    /// Position in the data of the current block.
+    pos: usize,
+    /// Recently used blocks, the most recently used last.
+    cache: Vec<Block>,
+    cache_size: usize,
+    decompressor: Box<DecompressorOxide>,
+    /// Buffer for the header extra field and the compressed data of a block.
+    scratch: Vec<u8>,
+}
+
+impl<S: BgzfSource> BgzfReader<S> {
+    /// Create a reader positioned at the start of the file.
+    pub fn new(source: S) -> BgzfReader<S> {
+        BgzfReader::with_cache_size(source, DEFAULT_CACHE_SIZE)
+    }
+
+    /// Create a reader that keeps up to `cache_size` decompressed blocks besides the one
+    /// being read.
+    pub fn with_cache_size(source: S, cache_size: usize) -> BgzfReader<S> {
+        BgzfReader {
+            source,
+            current: Block {
+                offset: 0,
+                next: 0,
+                data: Vec::new(),
+            },
+            pos: 0,
+            cache: Vec::new(),
+            cache_size,
+            decompressor: Box::default(),
+            scratch: Vec::new(),
+        }
+    }
+
+    /// Consume the reader, returning the source.
+    pub fn into_inner(self) -> S {
+        self.source
+    }
+
+    /// The virtual offset of the next byte that will be read.
+    ///
+    /// At the end of a block this is the start of the next block.
+    pub fn virtual_offset(&self) -> u64 {
+        if self.pos == self.current.data.len() {
+            virtual_offset(self.current.next, 0)
+        } else {
+            virtual_offset(self.current.offset, self.pos as u16)
+        }
+    }
+
+    /// Move to the given virtual offset, reading the block it points into.
+    ///
+    /// Returns [`BgzfError::InvalidOffset`] if the in-block offset is past the end of the
+    /// block, or the block offset past the end of the file. Seeking to the end of the file is
+    /// allowed.
+    pub fn seek_virtual(&mut self, offset: u64) -> Result<(), BgzfError> {
+        let (block_offset, in_block) = split_virtual_offset(offset);
+        let in_block = usize::from(in_block);
+        if !self.load_block(block_offset)? {
+            // Past the last block, only the very end is a valid position, which is right
+            // after the last byte of the file.
+            let mut last = [0];
+            if in_block != 0
+                || (block_offset > 0
+                    && read_full(&mut self.source, block_offset - 1, &mut last)? == 0)
+            {
+                return Err(BgzfError::InvalidOffset);
+            }
+        } else if in_block > self.current.data.len() {
+            return Err(BgzfError::InvalidOffset);
+        }
+        self.pos = in_block;
+        Ok(())
+    }
+
+    /// Read data into `buf`, continuing into the following blocks as needed.
+    ///
+    /// Returns the number of bytes read, which is only less than the length of `buf` at
+    /// the end of the file.
+    pub fn read(&mut self, buf: &mut [u8]) -> Result<usize, BgzfError> {
+        let mut written = 0;
+        while written < buf.len() {
+            if self.pos == self.current.data.len() {
+                if !self.load_block(self.current.next)? {
+                    break;
+                }
+                continue;
+            }
+            let data = &self.current.data[self.pos..];
+            let n = data.len().min(buf.len() - written);
+            buf[written..written + n].copy_from_slice(&data[..n]);
+            written += n;
+            self.pos += n;
+        }
+        Ok(written)
+    }
+
+    /// Read the rest of the file, appending it to `out`. Returns the number of bytes read.
+    pub fn read_to_end(&mut self, out: &mut Vec<u8>) -> Result<usize, BgzfError> {
+        let start = out.len();
+        loop {
+            out.extend_from_slice(&self.current.data[self.pos..]);
+            self.pos = self.current.data.len();
+            if !self.load_block(self.current.next)? {
+                return Ok(out.len() - start);
+            }
+        }
+    }
+
+    /// Make the block at `offset` the current one, taking it from the cache if possible, and
+    /// move to its start unless it already was the current block.
+    ///
+    /// Returns `false` if `offset` is at or past the end of the file, in which case the current
+    /// block is replaced by an empty one at `offset`.
+    fn load_block(&mut self, offset: u64) -> Result<bool, BgzfError> {
+        if self.current.offset == offset && self.current.next != offset {
+            return Ok(true);
+        }
+
+        let previous = if let Some(i) = self.cache.iter().position(|b| b.offset == offset) {
+            let cached = self.cache.remove(i);
+            core::mem::replace(&mut self.current, cached)
+        } else {
+            let mut data = if self.cache.len() >= self.cache_size && !self.cache.is_empty() {
+                self.cache.remove(0).data
+            } else {
+                Vec::new()
+            };
+            let next = self.read_block(offset, &mut data)?;
+            let block = Block {
+                offset,
+                next: next.unwrap_or(offset),
+                data,
+            };
+            core::mem::replace(&mut self.current, block)
+        };
+        if previous.next != previous.offset && self.cache_size > 0 {
+            if self.cache.len() >= self.cache_size {
+                self.cache.remove(0);
+            }
+            self.cache.push(previous);
+        }
+        self.pos = 0;
+        Ok(self.current.next != offset)
+    }
+
+    /// Read and decompress the block at `offset` into `data`, returning the offset of the
+    /// next block, or `None` if `offset` is at the end of the file.
+    fn read_block(&mut self, offset: u64, data: &mut Vec<u8>) -> Result<Option<u64>, BgzfError> {
+        data.clear();
+        let mut header = [0; FIXED_HEADER_LEN];
+        match read_full(&mut self.source, offset, &mut header)? {
+            0 => return Ok(None),
+            FIXED_HEADER_LEN => (),
+            _ => return Err(BgzfError::Truncated),
+        }
+        if header[..4] != [GZIP_ID1, GZIP_ID2, GZIP_CM_DEFLATE, gzip_flags::FEXTRA] {
+            return Err(BgzfError::InvalidBlock);
+        }
+
+        let xlen = usize::from(u16::from_le_bytes([header[10], header[11]]));
+        self.scratch.resize(xlen, 0);
+        let extra_offset = offset + FIXED_HEADER_LEN as u64;
+        if read_full(&mut self.source, extra_offset, &mut self.scratch)? != xlen {
+            return Err(BgzfError::Truncated);
+        }
+        let block_size = ExtraSubfields::new(&self.scratch)
+            .find(|&(id, sub)| id == *b"BC" && sub.len() == 2)
+            .map(|(_, sub)| usize::from(u16::from_le_bytes([sub[0], sub[1]])) + 1)
+            .ok_or(BgzfError::InvalidBlock)?;
+        let header_len = FIXED_HEADER_LEN + xlen;
+        if block_size < header_len + BGZF_TRAILER_LEN {
+            return Err(BgzfError::InvalidBlock);
+        }
+
+        // The compressed data followed by the trailer.
+        let rest = block_size - header_len;
+        self.scratch.resize(rest, 0);
+        let data_offset = offset + header_len as u64;
+        if read_full(&mut self.source, data_offset, &mut self.scratch)? != rest {
+            return Err(BgzfError::Truncated);
+        }
+        let (compressed, trailer) = self.scratch.split_at(rest - BGZF_TRAILER_LEN);
+        let crc = u32::from_le_bytes([trailer[0], trailer[1], trailer[2], trailer[3]]);
+        let size = u32::from_le_bytes([trailer[4], trailer[5], trailer[6], trailer[7]]) as usize;
+        if size > BGZF_MAX_BLOCK_SIZE {
+            return Err(BgzfError::InvalidBlock);
+        }
+
+        data.resize(size, 0);
+        self.decompressor.init();
+        let (status, _, out_len) = decompress(
+            &mut self.decompressor,
+            compressed,
+            data,
+            0,
+            TINFL_FLAG_USING_NON_WRAPPING_OUTPUT_BUF,
+        );
+        match status {
+            TINFLStatus::Done if out_len == size => (),
+            TINFLStatus::Done | TINFLStatus::HasMoreOutput => {
+                return Err(BgzfError::InvalidData(TINFLStatus::Failed))
+            }
+            status => return Err(BgzfError::InvalidData(status)),
+        }
+        if update_crc32(MZ_CRC32_INIT, data) != crc {
+            return Err(BgzfError::ChecksumMismatch);
+        }
+        Ok(Some(offset + block_size as u64))
+    }
+}
+
+/// Read from `source` until `buf` is full or the end of the file is reached.
+fn read_full<S: BgzfSource>(
+    source: &mut S,
+    offset: u64,
+    buf: &mut [u8],
+) -> Result<usize, BgzfError> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        let n = source.read_at(offset + filled as u64, &mut buf[filled..])?;
+        if n == 0 {
+            break;
+        }
+        filled += n;
+    }
+    Ok(filled)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{BgzfReader, BgzfSource};
+    use crate::alloc::vec;
+    use crate::alloc::vec::Vec;
+    use crate::bgzf::{BgzfError, BgzfWriter, BGZF_EOF};
+
+    /// Source that counts how many times the start of a block is read.
+    struct CountingSource<'a> {
+        data: &'a [u8],
+        header_reads: usize,
+    }
+
+    impl BgzfSource for CountingSource<'_> {
+        fn read_at(&mut self, offset: u64, buf: &mut [u8]) -> Result<usize, BgzfError> {
+            if buf.len() == 12 {
+                self.header_reads += 1;
+            }
+            // Return at most 1000 bytes at a time to exercise short reads.
+            let len = buf.len().min(1000);
+            self.data.read_at(offset, &mut buf[..len])
+        }
+    }
+
+    fn test_file() -> (Vec<u8>, Vec<(u64, Vec<u8>)>) {
+        let mut writer = BgzfWriter::new(6);
+        let mut records = Vec::new();
+        for i in 0..30000u32 {
+            let record = [&b"record "[..], &i.to_le_bytes(), b"\n"].concat();
+            records.push((writer.write(&record), record));
+        }
+        (writer.finish(), records)
+    }
+
+    #[test]
+    fn sequential_read() {
+        let (file, records) = test_file();
+        let expected: Vec<u8> = records.iter().flat_map(|r| r.1.iter().copied()).collect();
+
+        let mut reader = BgzfReader::new(&file[..]);
+        let mut data = Vec::new();
+        assert_eq!(reader.read_to_end(&mut data), Ok(expected.len()));
+        assert_eq!(data, expected);
+        assert_eq!(reader.virtual_offset(), (file.len() as u64) << 16);
+
+        // Small reads that cross block boundaries.
+        let mut reader = BgzfReader::new(&file[..]);
+        let mut data = Vec::new();
+        let mut buf = [0; 777];
+        loop {
+            let n = reader.read(&mut buf).unwrap();
+            data.extend_from_slice(&buf[..n]);
+            if n < buf.len() {
+                break;
+            }
+        }
+        assert_eq!(data, expected);
+        assert_eq!(reader.read(&mut buf), Ok(0));
+    }
+
+    #[test]
+    fn seek_and_cache() {
+        let (file, records) = test_file();
+        let mut reader = BgzfReader::with_cache_size(
+            CountingSource {
+                data: &file,
+                header_reads: 0,
+            },
+            2,
+        );
+
+        let mut buf = vec![0; 16];
+        for &i in &[29999, 0, 12345, 1, 29998, 20000] {
+            let (offset, record) = &records[i];
+            reader.seek_virtual(*offset).unwrap();
+            assert_eq!(reader.virtual_offset(), *offset);
+            let n = record.len();
+            assert_eq!(reader.read(&mut buf[..n]), Ok(n));
+            assert_eq!(&buf[..n], &record[..]);
+        }
+        let reads = reader.source.header_reads;
+
+        // Going back to the last few blocks hits the cache.
+        for &i in &[29999, 20000, 0, 1] {
+            reader.seek_virtual(records[i].0).unwrap();
+        }
+        assert_eq!(reader.source.header_reads, reads);
+
+        // The end of the file is a valid position, past it is not.
+        let end = (file.len() as u64) << 16;
+        assert_eq!(reader.seek_virtual(end), Ok(()));
+        assert_eq!(reader.read(&mut buf), Ok(0));
+        assert_eq!(reader.seek_virtual(end + 1), Err(BgzfError::InvalidOffset));
+        assert_eq!(
+            reader.seek_virtual(end + (1 << 16)),
+            Err(BgzfError::InvalidOffset)
+        );
+        assert_eq!(
+            reader.seek_virtual(end + (1000 << 16)),
+            Err(BgzfError::InvalidOffset)
+        );
+        assert_eq!(BgzfReader::new(&[][..]).seek_virtual(0), Ok(()));
+        assert_eq!(
+            reader.seek_virtual(records[0].0 + 0xFFFF),
+            Err(BgzfError::InvalidOffset)
+        );
+    }
+
+    #[test]
+    fn corrupt_blocks() {
+        let mut writer = BgzfWriter::new(6);
+        writer.write(b"Some data that will be damaged");
+        let file = writer.finish();
+
+        let mut data = Vec::new();
+        let mut damaged = file.clone();
+        let crc_pos = file.len() - BGZF_EOF.len() - 8;
+        damaged[crc_pos] ^= 1;
+        assert_eq!(
+            BgzfReader::new(&damaged[..]).read_to_end(&mut data),
+            Err(BgzfError::ChecksumMismatch)
+        );
+
+        let mut damaged = file.clone();
+        damaged[12] = b'X';
+        assert_eq!(
+            BgzfReader::new(&damaged[..]).read_to_end(&mut data),
+            Err(BgzfError::InvalidBlock)
+        );
+
+        let truncated = &file[..file.len() - BGZF_EOF.len() - 1];
+        assert_eq!(
+            BgzfReader::new(truncated).read_to_end(&mut data),
+            Err(BgzfError::Truncated)
+        );
+
+        let mut damaged = file.clone();
+        damaged[18] = 0xFF;
+        assert!(matches!(
+            BgzfReader::new(&damaged[..]).read_to_end(&mut data),
+            Err(BgzfError::InvalidData(_))
+        ));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn io_source() {
+        use super::IoSource;
+
+        let (file, records) = test_file();
+        let mut reader = BgzfReader::new(IoSource(std::io::Cursor::new(file)));
+        let (offset, record) = &records[23456];
+        reader.seek_virtual(*offset).unwrap();
+        let mut buf = vec![0; record.len()];
+        assert_eq!(reader.read(&mut buf), Ok(record.len()));
+        assert_eq!(&buf, record);
+    }
+}
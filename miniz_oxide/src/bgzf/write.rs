@@ -0,0 +1,266 @@
+//! Compression of data into BGZF blocks.
+
+use crate::alloc::boxed::Box;
+use crate::alloc::vec::Vec;
+use crate::deflate::core::{compress_to_output, CompressorOxide, TDEFLFlush, TDEFLStatus};
+use crate::shared::{update_crc32, MZ_CRC32_INIT};
+use crate::DataFormat;
+
+use super::{
+    block_header, virtual_offset, BGZF_EOF, BGZF_HEADER_LEN, BGZF_MAX_BLOCK_DATA,
+    BGZF_MAX_BLOCK_SIZE, BGZF_TRAILER_LEN,
+};
+
+/// Writer that compresses data into BGZF blocks in memory.
+///
+/// Data is buffered until a block is full, or until [`flush_block`](Self::flush_block) is
+/// called, and then compressed into a block of its own. The compressed blocks can be taken out
+/// as they are produced with [`take_output`](Self::take_output), so the whole file does not
+/// need to be kept in memory.
+///
+/// ```
+/// use miniz_oxide::bgzf::BgzfWriter;
+///
+/// let mut writer = BgzfWriter::new(6);
+/// let first = writer.write(b"chr1\t100\n");
+/// let second = writer.write(b"chr1\t200\n");
+/// assert_eq!(first, 0);
+/// assert_eq!(second, 9);
+/// let bgzf = writer.finish();
+/// # let _ = bgzf;
+/// ```
+pub struct BgzfWriter {
+    compressor: Box<CompressorOxide<'static>>,
+    level: u8,
+    /// Uncompressed data of the current block, always less than a full block.
+    block: Vec<u8>,
+    /// Compressed blocks that have not been taken out yet.
+    out: Vec<u8>,
+    /// Offset in the file of the start of `out`.
+    out_offset: u64,
+    /// Scratch buffer for the compressed data of a block.
+    scratch: Vec<u8>,
+}
+
+impl Default for BgzfWriter {
+    /// Create a writer using compression level 6.
+    fn default() -> Self {
+        BgzfWriter::new(6)
+    }
+}
+
+impl BgzfWriter {
+    /// Create a writer that compresses blocks with the given compression level (0-10).
+    pub fn new(level: u8) -> BgzfWriter {
+        BgzfWriter::with_offset(level, 0)
+    }
+
+    /// Create a writer that continues a file whose compressed data is `offset` bytes long.
+    ///
+    /// This is used to append to an existing file, after stripping the [`BGZF_EOF`] block
+    /// from its end. The virtual offsets returned take the existing data into account.
+    pub fn with_offset(level: u8, offset: u64) -> BgzfWriter {
+        BgzfWriter {
+            compressor: Box::default(),
+            level,
+            block: Vec::with_capacity(BGZF_MAX_BLOCK_DATA),
+            out: Vec::new(),
+            out_offset: offset,
+            scratch: Vec::new(),
+        }
+    }
+
+    /// The virtual offset at which the next byte written will be stored.
+    pub fn virtual_offset(&self) -> u64 {
+        let block_offset = self.out_offset + self.out.len() as u64;
+        virtual_offset(block_offset, self.block.len() as u16)
+    }
+
+    /// Write a record, returning the virtual offset of its start.
+    ///
+    /// Records that don't fit in the current block are continued in the next one. Call
+    /// [`flush_block`](Self::flush_block) first to make a record start in a new block.
+    pub fn write(&mut self, mut data: &[u8]) -> u64 {
+        let offset = self.virtual_offset();
+        while !data.is_empty() {
+            let n = data.len().min(BGZF_MAX_BLOCK_DATA - self.block.len());
+            self.block.extend_from_slice(&data[..n]);
+            data = &data[n..];
+            if self.block.len() == BGZF_MAX_BLOCK_DATA {
+                self.flush_block();
+            }
+        }
+        offset
+    }
+
+    /// Compress the buffered data into a block, so the next record starts in a new block.
+    ///
+    /// Does nothing if no data is buffered.
+    pub fn flush_block(&mut self) {
+        if self.block.is_empty() {
+            return;
+        }
+
+        self.deflate_block(self.level);
+        if BGZF_HEADER_LEN + self.scratch.len() + BGZF_TRAILER_LEN > BGZF_MAX_BLOCK_SIZE {
+            // Only possible with data that doesn't compress, storing it always fits.
+            self.deflate_block(0);
+        }
+
+        let block_size = BGZF_HEADER_LEN + self.scratch.len() + BGZF_TRAILER_LEN;
+        let crc = update_crc32(MZ_CRC32_INIT, &self.block);
+        self.out.extend_from_slice(&block_header(block_size));
+        self.out.extend_from_slice(&self.scratch);
+        self.out.extend_from_slice(&crc.to_le_bytes());
+        self.out
+            .extend_from_slice(&(self.block.len() as u32).to_le_bytes());
+        self.block.clear();
+    }
+
+    /// Take out the blocks that have been completed so far.
+    ///
+    /// Buffered data that has not been compressed into a block yet is kept.
+    pub fn take_output(&mut self) -> Vec<u8> {
+        self.out_offset += self.out.len() as u64;
+        core::mem::take(&mut self.out)
+    }
+
+    /// Compress the remaining buffered data and add the [`BGZF_EOF`] block, returning
+    /// the output that has not been taken out yet.
+    pub fn finish(mut self) -> Vec<u8> {
+        self.flush_block();
+        self.out.extend_from_slice(&BGZF_EOF);
+        self.out
+    }
+
+    /// Compress the buffered data into `scratch` as a raw deflate stream.
+    fn deflate_block(&mut self, level: u8) {
+        let compressor = &mut self.compressor;
+        compressor.reset();
+        compressor.set_format_and_level(DataFormat::Raw, level);
+
+        let scratch = &mut self.scratch;
+        scratch.clear();
+        let mut data = &self.block[..];
+        loop {
+            let (status, bytes_in) =
+                compress_to_output(compressor, data, TDEFLFlush::Finish, |buf| {
+                    scratch.extend_from_slice(buf);
+                    true
+                });
+            data = &data[bytes_in..];
+            match status {
+                TDEFLStatus::Done => break,
+                TDEFLStatus::Okay => (),
+                // Not supposed to happen unless there is a bug.
+                _ => panic!("Bug! Unexpectedly failed to compress!"),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::BgzfWriter;
+    use crate::alloc::vec::Vec;
+    use crate::bgzf::{split_virtual_offset, BGZF_EOF, BGZF_MAX_BLOCK_DATA, BGZF_MAX_BLOCK_SIZE};
+    use crate::inflate::decompress_to_vec;
+    use crate::shared::update_crc32;
+
+    /// Split the output into blocks, checking the header and trailer of each.
+    ///
+    /// Returns the offset and decompressed data of each block.
+    fn decode_blocks(data: &[u8]) -> Vec<(u64, Vec<u8>)> {
+        let mut blocks = Vec::new();
+        let mut offset = 0;
+        while offset < data.len() {
+            let data = &data[offset..];
+            assert_eq!(data[..4], [0x1F, 0x8B, 8, 4]);
+            assert_eq!(data[10..16], [6, 0, b'B', b'C', 2, 0]);
+            let size = usize::from(u16::from_le_bytes([data[16], data[17]])) + 1;
+            assert!(size <= BGZF_MAX_BLOCK_SIZE);
+            let block = &data[..size];
+            let decoded = decompress_to_vec(&block[18..size - 8]).unwrap();
+            assert_eq!(
+                block[size - 8..size - 4],
+                update_crc32(0, &decoded).to_le_bytes()
+            );
+            assert_eq!(block[size - 4..], (decoded.len() as u32).to_le_bytes());
+            blocks.push((offset as u64, decoded));
+            offset += size;
+        }
+        blocks
+    }
+
+    #[test]
+    fn records_and_offsets() {
+        let mut writer = BgzfWriter::new(6);
+        let mut records = Vec::new();
+        for i in 0..20000u32 {
+            let record = [&b"record "[..], &i.to_le_bytes(), b"\n"].concat();
+            records.push((writer.write(&record), record));
+        }
+        let output = writer.finish();
+        assert!(output.ends_with(&BGZF_EOF));
+
+        let blocks = decode_blocks(&output);
+        assert!(blocks.len() > 3);
+        assert!(blocks[..blocks.len() - 2]
+            .iter()
+            .all(|(_, b)| b.len() == BGZF_MAX_BLOCK_DATA));
+        assert!(blocks.last().unwrap().1.is_empty());
+
+        // Map each block to the position of its data in the uncompressed stream.
+        let mut data = Vec::new();
+        let mut starts = Vec::new();
+        for (offset, block) in &blocks {
+            starts.push((*offset, data.len()));
+            data.extend_from_slice(block);
+        }
+
+        for (offset, record) in records {
+            let (block_offset, in_block) = split_virtual_offset(offset);
+            let start = starts.iter().find(|s| s.0 == block_offset).unwrap().1;
+            let start = start + usize::from(in_block);
+            assert_eq!(&data[start..start + record.len()], &record[..]);
+        }
+    }
+
+    #[test]
+    fn incompressible_data_fits() {
+        let mut state = 0x1234_5678u32;
+        let data: Vec<u8> = (0..3 * BGZF_MAX_BLOCK_DATA)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                state as u8
+            })
+            .collect();
+        for level in [0, 1, 10] {
+            let mut writer = BgzfWriter::new(level);
+            writer.write(&data);
+            let blocks = decode_blocks(&writer.finish());
+            let decoded: Vec<u8> = blocks.into_iter().flat_map(|b| b.1).collect();
+            assert_eq!(decoded, data);
+        }
+    }
+
+    #[test]
+    fn take_output_and_flush() {
+        let mut writer = BgzfWriter::with_offset(1, 100);
+        assert_eq!(writer.write(b"first"), 100 << 16);
+        writer.flush_block();
+        writer.flush_block();
+        let first = writer.take_output();
+        assert_eq!(writer.write(b"second"), (100 + first.len() as u64) << 16);
+        assert_eq!(
+            writer.write(b"third"),
+            ((100 + first.len() as u64) << 16) | 6
+        );
+        let rest = writer.finish();
+        let blocks = decode_blocks(&[first, rest].concat());
+        let blocks: Vec<&[u8]> = blocks.iter().map(|b| &b.1[..]).collect();
+        assert_eq!(blocks, [&b"first"[..], b"secondthird", b""]);
+    }
+}
@@ -2,7 +2,9 @@
 //! to avoid stack copies. Box::new() doesn't at the moment, and using a vec means we would lose
 //! static length info.
 
-use crate::deflate::core::{LZ_DICT_SIZE, MAX_MATCH_LEN};
+use core::ops::{Deref, DerefMut};
+
+use crate::deflate::core::{BufferAllocator, CompressorBuffer, LZ_DICT_SIZE, MAX_MATCH_LEN};
 use alloc::boxed::Box;
 use alloc::vec;
 
@@ -25,14 +27,136 @@ pub const fn update_hash(current_hash: u16, byte: u8) -> u16 {
     ((current_hash << LZ_HASH_SHIFT) ^ byte as u16) & (LZ_HASH_SIZE as u16 - 1)
 }
 
+/// Buffer types that can be lent to the compressor by a [`BufferAllocator`].
+///
+/// This is the private part of [`CompressorBuffer`], so it can't be implemented outside of
+/// this crate.
+pub trait Sealed: 'static {
+    /// Allocate a zeroed buffer in a box.
+    fn new_boxed() -> Box<Self>;
+    /// Copy the contents of `other` into `self`.
+    fn copy_from(&mut self, other: &Self);
+}
+
+impl Sealed for [u8; LZ_DICT_FULL_SIZE] {
+    fn new_boxed() -> Box<Self> {
+        vec![0; LZ_DICT_FULL_SIZE]
+            .into_boxed_slice()
+            .try_into()
+            .unwrap()
+    }
+
+    fn copy_from(&mut self, other: &Self) {
+        self.copy_from_slice(other);
+    }
+}
+
+impl Sealed for [u16; LZ_DICT_SIZE] {
+    fn new_boxed() -> Box<Self> {
+        vec![0; LZ_DICT_SIZE].into_boxed_slice().try_into().unwrap()
+    }
+
+    fn copy_from(&mut self, other: &Self) {
+        self.copy_from_slice(other);
+    }
+}
+
+impl Sealed for LocalBuf {
+    fn new_boxed() -> Box<Self> {
+        Box::default()
+    }
+
+    fn copy_from(&mut self, other: &Self) {
+        self.b.copy_from_slice(&other.b);
+    }
+}
+
+/// One of the large buffers of the compressor, either in a box or lent to it for `'a` by a
+/// [`BufferAllocator`].
+pub(crate) enum Buf<'a, T> {
+    Boxed(Box<T>),
+    Borrowed(&'a mut T),
+}
+
+impl<'a, T: CompressorBuffer> Buf<'a, T> {
+    /// Allocate a zeroed buffer in a box.
+    pub fn new() -> Buf<'a, T> {
+        Buf::Boxed(T::new_boxed())
+    }
+
+    /// Get a zeroed buffer from `allocator`, returning `None` if that fails.
+    pub fn alloc(allocator: &mut impl BufferAllocator<'a>) -> Option<Buf<'a, T>> {
+        allocator.alloc().map(Buf::Borrowed)
+    }
+
+    /// Get a buffer from `allocator` and copy the contents of `self` into it.
+    pub fn clone_with_allocator(
+        &self,
+        allocator: &mut impl BufferAllocator<'a>,
+    ) -> Option<Buf<'a, T>> {
+        let mut buf: Buf<T> = Buf::alloc(allocator)?;
+        buf.copy_from(self);
+        Some(buf)
+    }
+}
+
+impl<T: CompressorBuffer> Clone for Buf<'_, T> {
+    fn clone(&self) -> Self {
+        let mut buf: Buf<T> = Buf::new();
+        buf.copy_from(self);
+        buf
+    }
+}
+
+impl<T> Deref for Buf<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        match self {
+            Buf::Boxed(b) => b,
+            Buf::Borrowed(b) => b,
+        }
+    }
+}
+
+impl<T> DerefMut for Buf<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        match self {
+            Buf::Boxed(b) => b,
+            Buf::Borrowed(b) => b,
+        }
+    }
+}
+
 #[derive(Clone)]
-pub struct HashBuffers {
-    pub dict: Box<[u8; LZ_DICT_FULL_SIZE]>,
-    pub next: Box<[u16; LZ_DICT_SIZE]>,
-    pub hash: Box<[u16; LZ_DICT_SIZE]>,
+pub struct HashBuffers<'a> {
+    pub(crate) dict: Buf<'a, [u8; LZ_DICT_FULL_SIZE]>,
+    pub(crate) next: Buf<'a, [u16; LZ_DICT_SIZE]>,
+    pub(crate) hash: Buf<'a, [u16; LZ_DICT_SIZE]>,
 }
 
-impl HashBuffers {
+impl<'a> HashBuffers<'a> {
+    /// Get the buffers from `allocator`, returning `None` if that fails.
+    pub(crate) fn alloc(allocator: &mut impl BufferAllocator<'a>) -> Option<HashBuffers<'a>> {
+        Some(HashBuffers {
+            dict: Buf::alloc(allocator)?,
+            next: Buf::alloc(allocator)?,
+            hash: Buf::alloc(allocator)?,
+        })
+    }
+
+    /// Get buffers from `allocator` and copy the contents of `self` into them.
+    pub(crate) fn clone_with_allocator(
+        &self,
+        allocator: &mut impl BufferAllocator<'a>,
+    ) -> Option<HashBuffers<'a>> {
+        Some(HashBuffers {
+            dict: self.dict.clone_with_allocator(allocator)?,
+            next: self.next.clone_with_allocator(allocator)?,
+            hash: self.hash.clone_with_allocator(allocator)?,
+        })
+    }
+
     #[inline]
     pub fn reset(&mut self) {
         self.dict.fill(0);
@@ -41,15 +165,12 @@ impl HashBuffers {
     }
 }
 
-impl Default for HashBuffers {
-    fn default() -> HashBuffers {
+impl Default for HashBuffers<'_> {
+    fn default() -> Self {
         HashBuffers {
-            dict: vec![0; LZ_DICT_FULL_SIZE]
-                .into_boxed_slice()
-                .try_into()
-                .unwrap(),
-            next: vec![0; LZ_DICT_SIZE].into_boxed_slice().try_into().unwrap(),
-            hash: vec![0; LZ_DICT_SIZE].into_boxed_slice().try_into().unwrap(),
+            dict: Buf::new(),
+            next: Buf::new(),
+            hash: Buf::new(),
         }
     }
 }
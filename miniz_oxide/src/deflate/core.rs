@@ -10,12 +10,15 @@ use super::super::*;
 use super::deflate_flags::*;
 use super::CompressionLevel;
 use crate::deflate::buffer::{
-    update_hash, HashBuffers, LocalBuf, LZ_CODE_BUF_MASK, LZ_CODE_BUF_SIZE, LZ_DICT_FULL_SIZE,
-    LZ_HASH_BITS, LZ_HASH_SHIFT, LZ_HASH_SIZE, OUT_BUF_SIZE,
+    update_hash, Buf, HashBuffers, LocalBuf, Sealed, LZ_CODE_BUF_MASK, LZ_CODE_BUF_SIZE,
+    LZ_DICT_FULL_SIZE, LZ_HASH_BITS, LZ_HASH_SHIFT, LZ_HASH_SIZE, OUT_BUF_SIZE,
 };
 use crate::deflate::stored::compress_stored;
 use crate::deflate::zlib;
-use crate::shared::{update_adler32, HUFFMAN_LENGTH_ORDER, MZ_ADLER32_INIT};
+use crate::gzip::GzHeader;
+use crate::shared::{
+    update_adler32, update_crc32, HUFFMAN_LENGTH_ORDER, MZ_ADLER32_INIT, MZ_CRC32_INIT,
+};
 use crate::DataFormat;
 
 // Currently not bubbled up outside this module, so can fill in with more
@@ -201,6 +204,9 @@ pub mod deflate_flags {
     pub const TDEFL_FORCE_ALL_STATIC_BLOCKS: u32 = 0x0004_0000;
     /// Force the compressor to only output raw/uncompressed blocks.
     pub const TDEFL_FORCE_ALL_RAW_BLOCKS: u32 = 0x0008_0000;
+    /// Whether to use a gzip wrapper. Takes precedence over [`TDEFL_WRITE_ZLIB_HEADER`].
+    /// (Not present in miniz.)
+    pub const TDEFL_WRITE_GZIP_HEADER: u32 = 0x0010_0000;
 }
 
 /// Strategy setting for compression.
@@ -365,21 +371,45 @@ const fn read_u16_le<const N: usize>(slice: &[u8; N], pos: usize) -> u16 {
     slice[pos] as u16 | ((slice[pos + 1] as u16) << 8)
 }
 
+/// A buffer type of the compressor that can be lent to it by a [`BufferAllocator`].
+///
+/// It is implemented only for types made up of integers, for which memory filled with zeroes
+/// is a valid value, and can't be implemented outside of this crate.
+#[doc(hidden)]
+pub trait CompressorBuffer: Sealed {}
+
+impl<T: Sealed> CompressorBuffer for T {}
+
+/// Source of the large buffers of a compressor, which lends them to it for `'a`, so they can
+/// be kept in memory that does not come from the Rust allocator.
+///
+/// This is used by the C API to honour the allocation functions of a stream, and is not
+/// meant to be used otherwise.
+#[doc(hidden)]
+pub trait BufferAllocator<'a> {
+    /// Get a zero-filled value of type `T`, returning `None` if the allocation failed.
+    fn alloc<T: CompressorBuffer>(&mut self) -> Option<&'a mut T>;
+}
+
 /// Main compression struct.
+///
+/// The lifetime is that of buffers lent to the compressor by a [`BufferAllocator`]. It is
+/// unconstrained for a compressor that keeps its buffers in boxes.
 #[derive(Clone)]
-pub struct CompressorOxide {
+pub struct CompressorOxide<'a> {
     pub(crate) lz: LZOxide,
-    pub(crate) params: ParamsOxide,
-    /// Put HuffmanOxide on the heap with default trick to avoid
+    pub(crate) params: ParamsOxide<'a>,
+    /// Put HuffmanOxide on the heap, or in borrowed memory, to avoid
     /// excessive stack copies.
-    pub(crate) huff: Box<HuffmanOxide>,
-    pub(crate) dict: DictOxide,
+    pub(crate) huff: Buf<'a, HuffmanOxide>,
+    pub(crate) dict: DictOxide<'a>,
 }
 
 const fn change_window_bits_from_format(window_bits: u8, data_format: DataFormat) -> i32 {
     match data_format {
-        DataFormat::Zlib | DataFormat::ZLibIgnoreChecksum => window_bits as i32,
+        DataFormat::Zlib | DataFormat::ZLibIgnoreChecksum | DataFormat::Auto => window_bits as i32,
         DataFormat::Raw => -(window_bits as i32),
+        DataFormat::Gzip => window_bits as i32 + 16,
     }
 }
 
@@ -405,7 +435,7 @@ fn limit_level_by_window_bits(
     }
 }
 
-impl CompressorOxide {
+impl<'a> CompressorOxide<'a> {
     /// Create a new `CompressorOxide` with the given flags.
     ///
     /// # Notes
@@ -415,20 +445,66 @@ impl CompressorOxide {
         CompressorOxide {
             lz: LZOxide::new(),
             params: ParamsOxide::new(flags, MZ_DEFAULT_WINDOW_BITS as u8),
-            huff: Box::default(),
+            huff: Buf::new(),
             dict: DictOxide::new(flags),
         }
     }
 
+    /// Create a new `CompressorOxide` with the given flags, with the buffers for the
+    /// dictionary, hash chains, huffman tables and output borrowed from `allocator` instead
+    /// of in boxes.
+    ///
+    /// Returns `None` if an allocation failed.
+    #[doc(hidden)]
+    pub fn with_allocator(
+        flags: u32,
+        allocator: &mut impl BufferAllocator<'a>,
+    ) -> Option<CompressorOxide<'a>> {
+        Some(CompressorOxide {
+            lz: LZOxide::new(),
+            params: ParamsOxide::with_local_buf(
+                flags,
+                MZ_DEFAULT_WINDOW_BITS as u8,
+                Buf::alloc(allocator)?,
+            ),
+            huff: Buf::alloc(allocator)?,
+            dict: DictOxide::with_buffers(flags, HashBuffers::alloc(allocator)?),
+        })
+    }
+
+    /// Create a copy of the compressor, with its buffers borrowed from `allocator` as with
+    /// [`with_allocator`](Self::with_allocator).
+    ///
+    /// Returns `None` if an allocation failed.
+    #[doc(hidden)]
+    pub fn clone_with_allocator(
+        &self,
+        allocator: &mut impl BufferAllocator<'a>,
+    ) -> Option<CompressorOxide<'a>> {
+        Some(CompressorOxide {
+            lz: self.lz.clone(),
+            params: ParamsOxide {
+                gzip_header: self.params.gzip_header.clone(),
+                local_buf: self.params.local_buf.clone_with_allocator(allocator)?,
+                ..self.params
+            },
+            huff: self.huff.clone_with_allocator(allocator)?,
+            dict: DictOxide {
+                b: self.dict.b.clone_with_allocator(allocator)?,
+                ..self.dict
+            },
+        })
+    }
+
     /// Create a new `CompressorOxide` with the given flags.
     ///
     pub fn with_format_and_level(
         data_format: DataFormat,
         level: CompressionLevel,
-    ) -> CompressorOxide {
+    ) -> CompressorOxide<'a> {
         let flags = create_comp_flags_from_zip_params(
             level as i32,
-            data_format.to_window_bits(),
+            change_window_bits_from_format(MZ_DEFAULT_WINDOW_BITS as u8, data_format),
             CompressionStrategy::Default as i32,
         );
         CompressorOxide::new(flags)
@@ -443,7 +519,7 @@ impl CompressorOxide {
         level: u8,
         strategy: CompressionStrategy,
         window_bits: u8,
-    ) -> CompressorOxide {
+    ) -> CompressorOxide<'a> {
         let window_bits = cmp::min(window_bits, 15);
         let level = cmp::min(level, 10);
         let (level, strategy) = limit_level_by_window_bits(window_bits, level as i32, strategy);
@@ -457,7 +533,7 @@ impl CompressorOxide {
         CompressorOxide {
             lz: LZOxide::new(),
             params: ParamsOxide::new(flags, window_bits),
-            huff: Box::default(),
+            huff: Buf::new(),
             dict: DictOxide::new(flags),
         }
     }
@@ -467,6 +543,13 @@ impl CompressorOxide {
         self.params.adler32
     }
 
+    /// Get the crc32 checksum of the currently encoded data.
+    ///
+    /// This is only computed when using the gzip wrapper.
+    pub const fn crc32(&self) -> u32 {
+        self.params.crc32
+    }
+
     /// Get the return status of the previous [`compress`](fn.compress.html)
     /// call with this compressor.
     pub const fn prev_return_status(&self) -> TDEFLStatus {
@@ -481,9 +564,11 @@ impl CompressorOxide {
         self.params.flags as i32
     }
 
-    /// Returns whether the compressor is wrapping the data in a zlib format or not.
+    /// Returns whether the compressor is wrapping the data in a zlib or gzip format or not.
     pub const fn data_format(&self) -> DataFormat {
-        if (self.params.flags & TDEFL_WRITE_ZLIB_HEADER) != 0 {
+        if (self.params.flags & TDEFL_WRITE_GZIP_HEADER) != 0 {
+            DataFormat::Gzip
+        } else if (self.params.flags & TDEFL_WRITE_ZLIB_HEADER) != 0 {
             DataFormat::Zlib
         } else {
             DataFormat::Raw
@@ -551,7 +636,7 @@ impl CompressorOxide {
     pub fn set_format_and_level(&mut self, data_format: DataFormat, level: u8) {
         let flags = create_comp_flags_from_zip_params(
             level.into(),
-            data_format.to_window_bits(),
+            change_window_bits_from_format(MZ_DEFAULT_WINDOW_BITS as u8, data_format),
             CompressionStrategy::Default as i32,
         );
         if data_format == DataFormat::Zlib
@@ -563,6 +648,136 @@ impl CompressorOxide {
         self.dict.update_flags(flags);
     }
 
+    /// Change the compression level and strategy, keeping the data format and the other flags.
+    ///
+    /// The level is limited to what the window size the compressor was created with allows,
+    /// as in [`with_params`](Self::with_params).
+    ///
+    /// Input that has been passed to the compressor but not yet output as part of a block
+    /// is compressed with the new settings. Use [`deflate_params`](super::stream::deflate_params)
+    /// to flush it with the old settings first when changing them in the middle of a stream.
+    pub fn set_level_and_strategy(&mut self, level: u8, strategy: CompressionStrategy) {
+        let level = cmp::min(level, 10);
+        let (level, strategy) =
+            limit_level_by_window_bits(self.params.window_bits_max, level as i32, strategy);
+        let kept = self.params.flags
+            & (TDEFL_WRITE_ZLIB_HEADER
+                | TDEFL_WRITE_GZIP_HEADER
+                | TDEFL_COMPUTE_ADLER32
+                | TDEFL_NONDETERMINISTIC_PARSING_FLAG);
+        let flags = kept | create_comp_flags_from_zip_params(level, 0, strategy as i32);
+        self.params.update_flags(flags);
+        self.dict.update_flags(flags);
+    }
+
+    /// Whether there is input or output held in the compressor that has not been written
+    /// out as part of a complete block yet.
+    pub(crate) fn has_pending_data(&self) -> bool {
+        self.lz.total_bytes != 0
+            || self.dict.lookahead_size != 0
+            || self.params.saved_match_len != 0
+            || self.params.flush_remaining != 0
+    }
+
+    /// Set the header to write when using the gzip wrapper.
+    ///
+    /// If this is not called, a header with no optional fields, a modification time of 0 and
+    /// an unknown OS is written. The `xfl` field is ignored and filled in from the
+    /// compression level. The header is kept when calling [`reset`](Self::reset).
+    ///
+    /// # Errors
+    /// Returns [`MZError::Param`] if the file name or comment contains a zero byte,
+    /// if the header is too large to fit in the internal output buffer or if the
+    /// header has already been written.
+    pub fn set_gzip_header(&mut self, header: GzHeader) -> Result<(), MZError> {
+        if !header.is_valid()
+            || header.encoded_len() > OUT_BUF_SIZE - 16
+            || self.params.gzip_header_written
+        {
+            return Err(MZError::Param);
+        }
+        self.params.gzip_header = Some(Box::new(header));
+        Ok(())
+    }
+
+    /// Get the header that will be written when using the gzip wrapper, if one has been set.
+    pub fn gzip_header(&self) -> Option<&GzHeader> {
+        self.params.gzip_header.as_deref()
+    }
+
+    /// Use `dictionary` as a preset dictionary for the stream.
+    ///
+    /// The data is loaded into the sliding window, so that the start of the input can
+    /// reference it, without being output itself. Only the last 32 KiB of the dictionary
+    /// are used. The decompressor needs the same dictionary to decode the stream.
+    /// When using the zlib wrapper, the FDICT flag and the adler32 checksum of the
+    /// dictionary are written to the header. Calling this more than once appends to the
+    /// dictionary.
+    ///
+    /// This must be called before any data is compressed, and the dictionary is cleared by
+    /// [`reset`](Self::reset). The dictionary is indexed for the current compression level,
+    /// so the level should be set before calling this.
+    ///
+    /// # Errors
+    /// Returns [`MZError::Param`] if compression has already started or if the gzip
+    /// wrapper is used, as gzip does not support preset dictionaries.
+    pub fn set_dictionary(&mut self, dictionary: &[u8]) -> Result<(), MZError> {
+        let started = self.params.block_index != 0
+            || self.params.gzip_header_written
+            || self.lz.total_bytes != 0
+            || self.dict.lookahead_size != 0
+            || self.params.saved_match_len != 0;
+        if started || self.params.flags & TDEFL_WRITE_GZIP_HEADER != 0 {
+            return Err(MZError::Param);
+        }
+
+        let flags = self.params.flags;
+        let fast = flags & MAX_PROBES_MASK == 1
+            && flags & TDEFL_GREEDY_PARSING_FLAG != 0
+            && flags & (TDEFL_FILTER_MATCHES | TDEFL_FORCE_ALL_RAW_BLOCKS) == 0;
+        self.dict.add_preset(dictionary, fast);
+
+        let adler = self.params.dict_adler32.unwrap_or(MZ_ADLER32_INIT);
+        self.params.dict_adler32 = Some(update_adler32(adler, dictionary));
+        Ok(())
+    }
+
+    /// Get the adler32 checksum of the preset dictionary, if one has been set.
+    pub const fn dictionary_adler32(&self) -> Option<u32> {
+        self.params.dict_adler32
+    }
+
+    /// Insert the lowest `bits` bits of `value` into the output, before the next block.
+    ///
+    /// This can be used to start the deflate data at a bit offset, or to emit custom bits
+    /// ahead of it, typically when producing raw deflate data. Up to 16 bits can be inserted
+    /// at a time, and at most 24 can be waiting to be output, including the unwritten bits
+    /// from the last flush.
+    ///
+    /// # Errors
+    /// Returns [`MZError::Param`] if `bits` is more than 16, if the stream is finished, or if
+    /// the zlib or gzip header has not been written yet, as the bits would end up before it.
+    /// Returns [`MZError::Buf`] if there is no room for the bits before the next block.
+    pub fn prime(&mut self, bits: u32, value: u32) -> Result<(), MZError> {
+        let flags = self.params.flags;
+        let header_pending = if flags & TDEFL_WRITE_GZIP_HEADER != 0 {
+            !self.params.gzip_header_written
+        } else {
+            flags & TDEFL_WRITE_ZLIB_HEADER != 0 && self.params.block_index == 0
+        };
+        if bits > 16 || header_pending || self.params.finished {
+            return Err(MZError::Param);
+        }
+        if self.params.saved_bits_in + bits > 24 {
+            return Err(MZError::Buf);
+        }
+
+        let value = value & ((1 << bits) - 1);
+        self.params.saved_bit_buffer |= value << self.params.saved_bits_in;
+        self.params.saved_bits_in += bits;
+        Ok(())
+    }
+
     /// Check the number of unwritten bits after the last flush.
     /// After a `NoSync` flush it can be used to test whether the
     /// stream is aligned with a byte boundary.
@@ -572,14 +787,14 @@ impl CompressorOxide {
     }
 }
 
-impl Default for CompressorOxide {
+impl Default for CompressorOxide<'_> {
     /// Initialize the compressor with a level of 4, zlib wrapper and
     /// the default strategy.
     fn default() -> Self {
         CompressorOxide {
             lz: LZOxide::new(),
             params: ParamsOxide::new(DEFAULT_FLAGS, MZ_DEFAULT_WINDOW_BITS as u8),
-            huff: Box::default(),
+            huff: Buf::new(),
             dict: DictOxide::new(DEFAULT_FLAGS),
         }
     }
@@ -941,6 +1156,16 @@ impl Default for HuffmanOxide {
     }
 }
 
+impl Sealed for HuffmanOxide {
+    fn new_boxed() -> Box<Self> {
+        Box::default()
+    }
+
+    fn copy_from(&mut self, other: &Self) {
+        self.clone_from(other);
+    }
+}
+
 impl HuffmanOxide {
     fn radix_sort_symbols<'a>(
         symbols0: &'a mut [SymFreq],
@@ -1282,13 +1507,13 @@ impl HuffmanOxide {
 }
 
 #[derive(Clone)]
-pub(crate) struct DictOxide {
+pub(crate) struct DictOxide<'a> {
     /// The maximum number of checks in the hash chain, for the initial,
     /// and the lazy match respectively.
     pub max_probes: [u32; 2],
     /// Buffer of input data.
     /// Padded with 1 byte to simplify matching code in `compress_fast`.
-    pub b: HashBuffers,
+    pub b: HashBuffers<'a>,
 
     pub code_buf_dict_pos: usize,
     pub lookahead_size: usize,
@@ -1304,11 +1529,15 @@ const fn probes_from_flags(flags: u32) -> [u32; 2] {
     ]
 }
 
-impl DictOxide {
+impl<'a> DictOxide<'a> {
     fn new(flags: u32) -> Self {
+        DictOxide::with_buffers(flags, HashBuffers::default())
+    }
+
+    fn with_buffers(flags: u32, b: HashBuffers<'a>) -> Self {
         DictOxide {
             max_probes: probes_from_flags(flags),
-            b: HashBuffers::default(),
+            b,
             code_buf_dict_pos: 0,
             lookahead_size: 0,
             lookahead_pos: 0,
@@ -1329,6 +1558,50 @@ impl DictOxide {
         self.size = 0;
     }
 
+    /// Load preset dictionary data into the window in front of the lookahead and insert it
+    /// into the hash chains so the following input can reference it.
+    ///
+    /// `fast` selects the single hash table layout used by `compress_fast` instead of the
+    /// hash chains used by `compress_normal`.
+    fn add_preset(&mut self, data: &[u8], fast: bool) {
+        let data = &data[data.len().saturating_sub(LZ_DICT_SIZE)..];
+        let start = self.lookahead_pos;
+        let end = start + data.len();
+        let dictb = &mut self.b;
+
+        for (i, &c) in data.iter().enumerate() {
+            let dst_pos = (start + i) & LZ_DICT_SIZE_MASK;
+            dictb.dict[dst_pos] = c;
+            if dst_pos < MAX_MATCH_LEN - 1 {
+                dictb.dict[LZ_DICT_SIZE + dst_pos] = c;
+            }
+        }
+
+        // Only insert positions where the full trigram is known, the next input
+        // will insert the last two positions.
+        let first = start.saturating_sub(2);
+        for ins_pos in first..end.saturating_sub(2) {
+            let b0 = u32::from(dictb.dict[ins_pos & LZ_DICT_SIZE_MASK]);
+            let b1 = u32::from(dictb.dict[(ins_pos + 1) & LZ_DICT_SIZE_MASK]);
+            let b2 = u32::from(dictb.dict[(ins_pos + 2) & LZ_DICT_SIZE_MASK]);
+            if fast {
+                let trigram = b0 | (b1 << 8) | (b2 << 16);
+                let hash =
+                    (trigram ^ (trigram >> (24 - (LZ_HASH_BITS - 8)))) & LEVEL1_HASH_SIZE_MASK;
+                dictb.hash[hash as usize] = ins_pos as u16;
+            } else {
+                let hash = ((b0 << (LZ_HASH_SHIFT * 2)) ^ (b1 << LZ_HASH_SHIFT) ^ b2)
+                    & (LZ_HASH_SIZE as u32 - 1);
+                dictb.next[ins_pos & LZ_DICT_SIZE_MASK] = dictb.hash[hash as usize];
+                dictb.hash[hash as usize] = ins_pos as u16;
+            }
+        }
+
+        self.lookahead_pos = end;
+        self.code_buf_dict_pos = end;
+        self.size = cmp::min(self.size + data.len(), LZ_DICT_SIZE);
+    }
+
     /// Do an unaligned read of the data at `pos` in the dictionary and treat it as if it was of
     /// type T.
     #[inline]
@@ -1496,7 +1769,7 @@ impl DictOxide {
 }
 
 #[derive(Clone)]
-pub(crate) struct ParamsOxide {
+pub(crate) struct ParamsOxide<'a> {
     pub flags: u32,
     pub greedy_parsing: bool,
     // If using a zlib header
@@ -1518,6 +1791,15 @@ pub(crate) struct ParamsOxide {
 
     pub adler32: u32,
 
+    // Checksum, total input length and header used with the gzip wrapper.
+    pub crc32: u32,
+    pub gzip_isize: u32,
+    pub gzip_header: Option<Box<GzHeader>>,
+    pub gzip_header_written: bool,
+
+    // Adler32 of the preset dictionary, if one has been set.
+    pub dict_adler32: Option<u32>,
+
     pub src_pos: usize,
 
     pub out_buf_ofs: usize,
@@ -1526,11 +1808,15 @@ pub(crate) struct ParamsOxide {
     pub saved_bit_buffer: u32,
     pub saved_bits_in: u32,
 
-    pub local_buf: Box<LocalBuf>,
+    pub local_buf: Buf<'a, LocalBuf>,
 }
 
-impl ParamsOxide {
+impl<'a> ParamsOxide<'a> {
     fn new(flags: u32, window_bits: u8) -> Self {
+        ParamsOxide::with_local_buf(flags, window_bits, Buf::new())
+    }
+
+    fn with_local_buf(flags: u32, window_bits: u8, local_buf: Buf<'a, LocalBuf>) -> Self {
         ParamsOxide {
             flags,
             greedy_parsing: flags & TDEFL_GREEDY_PARSING_FLAG != 0,
@@ -1544,12 +1830,17 @@ impl ParamsOxide {
             flush_remaining: 0,
             finished: false,
             adler32: MZ_ADLER32_INIT,
+            crc32: MZ_CRC32_INIT,
+            gzip_isize: 0,
+            gzip_header: None,
+            gzip_header_written: false,
+            dict_adler32: None,
             src_pos: 0,
             out_buf_ofs: 0,
             prev_return_status: TDEFLStatus::Okay,
             saved_bit_buffer: 0,
             saved_bits_in: 0,
-            local_buf: Box::default(),
+            local_buf,
         }
     }
 
@@ -1569,6 +1860,10 @@ impl ParamsOxide {
         self.flush_remaining = 0;
         self.finished = false;
         self.adler32 = MZ_ADLER32_INIT;
+        self.crc32 = MZ_CRC32_INIT;
+        self.gzip_isize = 0;
+        self.gzip_header_written = false;
+        self.dict_adler32 = None;
         self.src_pos = 0;
         self.out_buf_ofs = 0;
         self.prev_return_status = TDEFLStatus::Okay;
@@ -1786,15 +2081,29 @@ pub(crate) fn flush_block(
             .new_output_buffer(&mut d.params.local_buf.b, d.params.out_buf_ofs);
         output.bit_buffer = d.params.saved_bit_buffer;
         output.bits_in = d.params.saved_bits_in;
+        // Output any whole bytes of bits inserted with `prime`.
+        output.put_bits(0, 0);
 
         // If we are at the start of the stream, write the zlib header
         // if requested.  Note: Even if block-writing is skipped
         // below, `block_index` is still incremented, so this is done
         // only once
-        if d.params.flags & TDEFL_WRITE_ZLIB_HEADER != 0 && d.params.block_index == 0 {
-            let header = zlib::header_from_flags(d.params.flags, d.params.window_bits_max);
+        if d.params.flags & TDEFL_WRITE_ZLIB_HEADER != 0
+            && d.params.flags & TDEFL_WRITE_GZIP_HEADER == 0
+            && d.params.block_index == 0
+        {
+            let header = zlib::header_from_flags(
+                d.params.flags,
+                d.params.window_bits_max,
+                d.params.dict_adler32.is_some(),
+            );
             output.put_bits_no_flush(header[0].into(), 8);
             output.put_bits(header[1].into(), 8);
+            if let Some(dict_adler) = d.params.dict_adler32 {
+                for &b in dict_adler.to_be_bytes().iter() {
+                    output.put_bits(b.into(), 8);
+                }
+            }
         }
 
         if d.lz.total_bytes > 0 || flush == TDEFLFlush::Finish {
@@ -1873,7 +2182,19 @@ pub(crate) fn flush_block(
         match flush {
             TDEFLFlush::Finish => {
                 output.pad_to_bytes();
-                if d.params.flags & TDEFL_WRITE_ZLIB_HEADER != 0 {
+                if d.params.flags & TDEFL_WRITE_GZIP_HEADER != 0 {
+                    // The gzip trailer stores the crc32 and input size in little endian order.
+                    let mut crc = d.params.crc32;
+                    let mut isize = d.params.gzip_isize;
+                    for _ in 0..4 {
+                        output.put_bits(crc & 0xFF, 8);
+                        crc >>= 8;
+                    }
+                    for _ in 0..4 {
+                        output.put_bits(isize & 0xFF, 8);
+                        isize >>= 8;
+                    }
+                } else if d.params.flags & TDEFL_WRITE_ZLIB_HEADER != 0 {
                     let mut adler = d.params.adler32;
                     for _ in 0..4 {
                         output.put_bits((adler >> 24) & 0xFF, 8);
@@ -2363,6 +2684,40 @@ fn compress_fast(d: &mut CompressorOxide, callback: &mut CallbackOxide) -> bool
     true
 }
 
+/// Write the gzip header to the output.
+///
+/// The header is written separately from the first block so that a large header
+/// does not have to share the output buffer with compressed data.
+fn write_gzip_header(d: &mut CompressorOxide, callback: &mut CallbackOxide) -> i32 {
+    let xfl = gzip_xfl_from_flags(d.params.flags);
+    let saved_buffer = {
+        let mut output = callback
+            .out
+            .new_output_buffer(&mut d.params.local_buf.b, d.params.out_buf_ofs);
+        match d.params.gzip_header.as_deref() {
+            Some(header) => header.write_to(xfl, |bytes| output.write_bytes(bytes)),
+            None => GzHeader::default().write_to(xfl, |bytes| output.write_bytes(bytes)),
+        }
+        output.save()
+    };
+
+    callback.flush_output(saved_buffer, &mut d.params)
+}
+
+/// Get the value of the gzip `XFL` header field for the compression settings in `flags`.
+const fn gzip_xfl_from_flags(flags: u32) -> u8 {
+    let probes = flags & MAX_PROBES_MASK;
+    if probes >= NUM_PROBES[9] as u32 {
+        // Slowest algorithm.
+        2
+    } else if probes <= NUM_PROBES[1] as u32 {
+        // Fastest algorithm.
+        4
+    } else {
+        0
+    }
+}
+
 fn flush_output_buffer(c: &mut CallbackOxide, p: &mut ParamsOxide) -> (TDEFLStatus, usize, usize) {
     let mut res = (TDEFLStatus::Okay, p.src_pos, 0);
     if let CallbackOut::Buf(ref mut cb) = c.out {
@@ -2466,6 +2821,19 @@ fn compress_inner(
         return res;
     }
 
+    if d.params.flags & TDEFL_WRITE_GZIP_HEADER != 0 && !d.params.gzip_header_written {
+        d.params.gzip_header_written = true;
+        if write_gzip_header(d, callback) < 0 {
+            return (d.params.prev_return_status, 0, d.params.out_buf_ofs);
+        }
+        if d.params.flush_remaining != 0 {
+            // The header did not fit in the output buffer, output the rest on the next call(s).
+            let res = flush_output_buffer(callback, &mut d.params);
+            d.params.prev_return_status = res.0;
+            return res;
+        }
+    }
+
     let one_probe = d.params.flags & MAX_PROBES_MASK == 1;
     let greedy = d.params.flags & TDEFL_GREEDY_PARSING_FLAG != 0;
     let filter_or_rle = d.params.flags & (TDEFL_FILTER_MATCHES | TDEFL_FORCE_ALL_RAW_BLOCKS) != 0;
@@ -2492,6 +2860,10 @@ fn compress_inner(
         if d.params.flags & (TDEFL_WRITE_ZLIB_HEADER | TDEFL_COMPUTE_ADLER32) != 0 {
             d.params.adler32 = update_adler32(d.params.adler32, &in_buf[..d.params.src_pos]);
         }
+        if d.params.flags & TDEFL_WRITE_GZIP_HEADER != 0 {
+            d.params.crc32 = update_crc32(d.params.crc32, &in_buf[..d.params.src_pos]);
+            d.params.gzip_isize = d.params.gzip_isize.wrapping_add(d.params.src_pos as u32);
+        }
     }
 
     let flush_none = d.params.flush == TDEFLFlush::None;
@@ -2538,8 +2910,8 @@ fn compress_inner(
 /// # Parameters
 /// `level` determines compression level. Clamped to maximum of 10. Negative values result in
 /// `CompressionLevel::DefaultLevel`.
-/// `window_bits`: Above 0, wraps the stream in a zlib wrapper, 0 or negative for a raw deflate
-/// stream.
+/// `window_bits`: Above 15 (i.e window bits + 16), wraps the stream in a gzip wrapper, 1 to 15
+/// wraps the stream in a zlib wrapper, 0 or negative for a raw deflate stream.
 /// `strategy`: Sets the strategy if this conforms to any of the values in `CompressionStrategy`.
 ///
 /// # Notes
@@ -2563,7 +2935,9 @@ pub const fn create_comp_flags_from_zip_params(level: i32, window_bits: i32, str
     };
     let mut comp_flags = NUM_PROBES[num_probes] as u32 | greedy;
 
-    if window_bits > 0 {
+    if window_bits > MZ_DEFAULT_WINDOW_BITS {
+        comp_flags |= TDEFL_WRITE_GZIP_HEADER;
+    } else if window_bits > 0 {
         comp_flags |= TDEFL_WRITE_ZLIB_HEADER;
     }
 
@@ -2727,4 +3101,234 @@ mod test {
         assert_eq!(cmf, 8);
         assert_eq!(output, slice)
     }
+
+    #[test]
+    fn gzip_header_small_output() {
+        use super::compress;
+        use crate::gzip::GzHeader;
+        use crate::DataFormat;
+        let data = b"gzip header test data gzip header test data";
+        let header = GzHeader {
+            mtime: 1234,
+            os: 3,
+            filename: Some(vec![b'f'; 300]),
+            comment: Some(b"comment".to_vec()),
+            hcrc: true,
+            ..GzHeader::default()
+        };
+        let header_len = 10 + 301 + 8 + 2;
+
+        let mut d =
+            CompressorOxide::with_params(DataFormat::Gzip, 6, CompressionStrategy::Default, 15);
+        assert_eq!(d.data_format(), DataFormat::Gzip);
+        d.set_gzip_header(header.clone()).unwrap();
+
+        // Use a tiny output buffer so the header has to be output over several calls.
+        let mut encoded = vec![];
+        let mut input = &data[..];
+        let mut out = [0; 16];
+        loop {
+            let (status, bytes_in, bytes_out) =
+                compress(&mut d, input, &mut out, TDEFLFlush::Finish);
+            input = &input[bytes_in..];
+            encoded.extend_from_slice(&out[..bytes_out]);
+            match status {
+                TDEFLStatus::Done => break,
+                TDEFLStatus::Okay => (),
+                _ => panic!("Compression failed!"),
+            }
+        }
+        assert!(d.set_gzip_header(header).is_err());
+
+        assert_eq!(&encoded[..4], &[0x1F, 0x8B, 8, 0x1A]);
+        assert_eq!(&encoded[4..8], &1234u32.to_le_bytes());
+        let body = &encoded[header_len..encoded.len() - 8];
+        assert_eq!(decompress_to_vec(body).unwrap(), &data[..]);
+        assert_eq!(d.crc32(), crate::mz_crc32_oxide(0, data));
+
+        // The header is kept after a reset.
+        d.reset();
+        let mut encoded2 = vec![0; 1024];
+        let (status, _, bytes_out) = compress(&mut d, data, &mut encoded2, TDEFLFlush::Finish);
+        assert_eq!(status, TDEFLStatus::Done);
+        assert_eq!(&encoded2[..bytes_out], &encoded[..]);
+    }
+
+    #[test]
+    fn preset_dictionary() {
+        use super::compress;
+        use crate::inflate::core::inflate_flags::TINFL_FLAG_USING_NON_WRAPPING_OUTPUT_BUF;
+        use crate::inflate::core::{decompress, DecompressorOxide};
+        use crate::inflate::TINFLStatus;
+        use crate::DataFormat;
+
+        let dict = b"The quick brown fox jumps over the lazy dog. Lorem ipsum dolor sit amet.";
+        let data = b"Lorem ipsum dolor sit amet. The lazy dog jumps over the quick brown fox.";
+
+        for level in [1, 6, 9] {
+            let mut d = CompressorOxide::with_params(
+                DataFormat::Zlib,
+                level,
+                CompressionStrategy::Default,
+                15,
+            );
+            d.set_dictionary(dict).unwrap();
+            assert_eq!(
+                d.dictionary_adler32(),
+                Some(crate::shared::update_adler32(1, dict))
+            );
+
+            let mut encoded = vec![0; 1024];
+            let (status, _, bytes_out) = compress(&mut d, data, &mut encoded, TDEFLFlush::Finish);
+            assert_eq!(status, TDEFLStatus::Done);
+            encoded.truncate(bytes_out);
+            assert!(d.set_dictionary(dict).is_err());
+
+            // FDICT is set and the dictionary id follows the header.
+            assert_eq!(encoded[1] & 0x20, 0x20);
+            assert_eq!(
+                (usize::from(encoded[0]) * 256 + usize::from(encoded[1])) % 31,
+                0
+            );
+            assert_eq!(
+                &encoded[2..6],
+                &crate::shared::update_adler32(1, dict).to_be_bytes()
+            );
+
+            // Decode the raw deflate body with the dictionary already in the output buffer.
+            let body = &encoded[6..encoded.len() - 4];
+            let mut out = vec![0; dict.len() + data.len() + 16];
+            out[..dict.len()].copy_from_slice(dict);
+            let mut r = DecompressorOxide::new();
+            let (status, _, out_len) = decompress(
+                &mut r,
+                body,
+                &mut out,
+                dict.len(),
+                TINFL_FLAG_USING_NON_WRAPPING_OUTPUT_BUF,
+            );
+            assert_eq!(status, TINFLStatus::Done);
+            assert_eq!(&out[dict.len()..dict.len() + out_len], &data[..]);
+
+            // The dictionary should make the output smaller.
+            let plain = crate::deflate::compress_to_vec_zlib(data, level);
+            assert!(encoded.len() < plain.len());
+        }
+
+        // Not supported with the gzip wrapper.
+        let mut d =
+            CompressorOxide::with_params(DataFormat::Gzip, 6, CompressionStrategy::Default, 15);
+        assert!(d.set_dictionary(dict).is_err());
+    }
+
+    #[test]
+    fn prime_bits() {
+        use super::compress;
+        use crate::inflate::core::{decompress, DecompressorOxide};
+        use crate::inflate::decompress_to_vec_zlib;
+        use crate::inflate::TINFLStatus;
+        use crate::{DataFormat, MZError};
+
+        let data = b"Some data that starts twelve bits into the output. Some data.";
+
+        let mut d =
+            CompressorOxide::with_params(DataFormat::Raw, 6, CompressionStrategy::Default, 15);
+        assert_eq!(d.prime(17, 0), Err(MZError::Param));
+        d.prime(3, 5).unwrap();
+        d.prime(9, 0xFFAB).unwrap();
+        assert_eq!(d.prime(16, 0), Err(MZError::Buf));
+        let mut encoded = vec![0; 200];
+        let (status, _, out_len) = compress(&mut d, data, &mut encoded, TDEFLFlush::Finish);
+        assert_eq!(status, TDEFLStatus::Done);
+        assert_eq!(d.prime(1, 0), Err(MZError::Param));
+        assert_eq!(encoded[0], 0x5D);
+        assert_eq!(encoded[1] & 0xF, 0xD);
+
+        // Load the last 4 bits into the decompressor and decode the rest.
+        let mut r = DecompressorOxide::new();
+        r.prime(4, u32::from(encoded[1] >> 4)).unwrap();
+        let mut out = vec![0; 200];
+        let (status, _, out_len) = decompress(
+            &mut r,
+            &encoded[2..out_len],
+            &mut out,
+            0,
+            crate::inflate::core::inflate_flags::TINFL_FLAG_USING_NON_WRAPPING_OUTPUT_BUF,
+        );
+        assert_eq!(status, TINFLStatus::Done);
+        assert_eq!(&out[..out_len], &data[..]);
+
+        // With the zlib wrapper the header has to be written first. Insert an empty static
+        // block after a flush.
+        let mut d =
+            CompressorOxide::with_params(DataFormat::Zlib, 6, CompressionStrategy::Default, 15);
+        assert_eq!(d.prime(3, 0), Err(MZError::Param));
+        let mut encoded = vec![0; 200];
+        let (status, _, pos) = compress(&mut d, &data[..20], &mut encoded, TDEFLFlush::Sync);
+        assert_eq!(status, TDEFLStatus::Okay);
+        d.prime(10, 2).unwrap();
+        let (status, _, len) =
+            compress(&mut d, &data[20..], &mut encoded[pos..], TDEFLFlush::Finish);
+        assert_eq!(status, TDEFLStatus::Done);
+        assert_eq!(decompress_to_vec_zlib(&encoded[..pos + len]).unwrap(), data);
+    }
+
+    #[test]
+    fn buffer_allocator() {
+        use super::{compress, BufferAllocator, CompressorBuffer, HuffmanOxide, LZ_DICT_SIZE};
+        use crate::deflate::buffer::{Buf, LocalBuf, Sealed, LZ_DICT_FULL_SIZE};
+        use crate::inflate::decompress_to_vec_zlib;
+        use alloc::boxed::Box;
+        use alloc::vec::Vec;
+        use core::any::Any;
+
+        /// Allocator that lends out buffers owned by the test.
+        struct Lend<'a>(Vec<&'a mut dyn Any>);
+
+        impl<'a> BufferAllocator<'a> for Lend<'a> {
+            fn alloc<T: CompressorBuffer>(&mut self) -> Option<&'a mut T> {
+                let i = self.0.iter().position(|b| b.is::<T>())?;
+                self.0.swap_remove(i).downcast_mut()
+            }
+        }
+
+        fn buffers() -> [Box<dyn Any>; 5] {
+            [
+                HuffmanOxide::new_boxed() as Box<dyn Any>,
+                LocalBuf::new_boxed() as Box<dyn Any>,
+                <[u8; LZ_DICT_FULL_SIZE]>::new_boxed() as Box<dyn Any>,
+                <[u16; LZ_DICT_SIZE]>::new_boxed() as Box<dyn Any>,
+                <[u16; LZ_DICT_SIZE]>::new_boxed() as Box<dyn Any>,
+            ]
+        }
+
+        let data: Vec<u8> = (0..100_000u32).map(|i| (i % 997) as u8).collect();
+        let mut first = buffers();
+        let mut second = buffers();
+        let mut lend = Lend(first.iter_mut().map(|b| &mut **b).collect());
+        let mut d = CompressorOxide::with_allocator(DEFAULT_FLAGS, &mut lend).unwrap();
+        assert!(lend.0.is_empty());
+        assert!(matches!(d.huff, Buf::Borrowed(_)));
+
+        let mut encoded = vec![0; 200_000];
+        let (status, _, pos) = compress(&mut d, &data[..50_000], &mut encoded, TDEFLFlush::None);
+        assert_eq!(status, TDEFLStatus::Okay);
+        // Running out of buffers.
+        assert!(d.clone_with_allocator(&mut lend).is_none());
+
+        // A copy with borrowed buffers continues where the original left off, and a plain
+        // clone has boxed ones.
+        let mut lend = Lend(second.iter_mut().map(|b| &mut **b).collect());
+        let mut copy = d.clone_with_allocator(&mut lend).unwrap();
+        assert!(matches!(copy.dict.b.dict, Buf::Borrowed(_)));
+        assert!(matches!(d.clone().params.local_buf, Buf::Boxed(_)));
+        let (status, _, len) = compress(
+            &mut copy,
+            &data[50_000..],
+            &mut encoded[pos..],
+            TDEFLFlush::Finish,
+        );
+        assert_eq!(status, TDEFLStatus::Done);
+        assert_eq!(decompress_to_vec_zlib(&encoded[..pos + len]).unwrap(), data);
+    }
 }
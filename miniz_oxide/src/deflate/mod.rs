@@ -144,9 +144,19 @@ pub fn compress_to_vec_zlib(input: &[u8], level: u8) -> Vec<u8> {
     compress_to_vec_inner(input, level, 1, 0)
 }
 
+/// Compress the input data to a vector, using the specified compression level (0-10), and with a
+/// gzip wrapper.
+///
+/// The gzip header has no optional fields set. Use a [`CompressorOxide`] with
+/// [`CompressorOxide::set_gzip_header`] to write a custom header.
+pub fn compress_to_vec_gzip(input: &[u8], level: u8) -> Vec<u8> {
+    compress_to_vec_inner(input, level, crate::MZ_DEFAULT_WINDOW_BITS + 16, 0)
+}
+
 /// Simple function to compress data to a vec.
 fn compress_to_vec_inner(mut input: &[u8], level: u8, window_bits: i32, strategy: i32) -> Vec<u8> {
-    // The comp flags function sets the zlib flag if the window_bits parameter is > 0.
+    // The comp flags function sets the zlib flag if the window_bits parameter is > 0,
+    // or the gzip flag if it's > 15.
     let flags = create_comp_flags_from_zip_params(level.into(), window_bits, strategy);
     let mut compressor = CompressorOxide::new(flags);
     let mut output = vec![0; ::core::cmp::max(input.len() / 2, 2)];
@@ -184,7 +194,9 @@ fn compress_to_vec_inner(mut input: &[u8], level: u8, window_bits: i32, strategy
 
 #[cfg(test)]
 mod test {
-    use super::{compress_to_vec, compress_to_vec_inner, CompressionStrategy};
+    use super::{
+        compress_to_vec, compress_to_vec_gzip, compress_to_vec_inner, CompressionStrategy,
+    };
     use crate::inflate::decompress_to_vec;
     use alloc::vec;
 
@@ -259,4 +271,28 @@ mod test {
         // as neither checks matches against the byte at index 0.)
         assert!(c.len() <= 6);
     }
+
+    #[test]
+    fn compress_gzip() {
+        let test_data = b"Deflate late, deflate late, gzip it up";
+        for level in [0, 1, 6, 9] {
+            let c = compress_to_vec_gzip(test_data, level);
+            assert_eq!(&c[..4], &[0x1F, 0x8B, 8, 0]);
+            let xfl = match level {
+                0 | 1 => 4,
+                9 => 2,
+                _ => 0,
+            };
+            assert_eq!(c[8], xfl);
+            assert_eq!(c[9], 255);
+            let d = decompress_to_vec(&c[10..c.len() - 8]).expect("Failed to decompress!");
+            assert_eq!(test_data, d.as_slice());
+            let trailer = &c[c.len() - 8..];
+            assert_eq!(
+                trailer[..4],
+                crate::mz_crc32_oxide(0, test_data).to_le_bytes()
+            );
+            assert_eq!(trailer[4..], (test_data.len() as u32).to_le_bytes());
+        }
+    }
 }
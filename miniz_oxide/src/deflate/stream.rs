@@ -4,7 +4,9 @@
 //!
 //! There is no DeflateState as the needed state is contained in the compressor struct itself.
 
-use crate::deflate::core::{compress, CompressorOxide, TDEFLFlush, TDEFLStatus};
+use crate::deflate::core::{
+    compress, CompressionStrategy, CompressorOxide, TDEFLFlush, TDEFLStatus,
+};
 use crate::{MZError, MZFlush, MZStatus, StreamResult};
 
 /// Try to compress from input to output with the given [`CompressorOxide`].
@@ -96,12 +98,64 @@ pub fn deflate(
     }
 }
 
+/// Change the compression level and strategy of `compressor` in the middle of a stream.
+///
+/// Input that the compressor holds that has not been output yet is first compressed with
+/// the old settings and flushed to `output` as with [`MZFlush::Sync`], so the new settings
+/// only apply to data passed in afterwards. Nothing is flushed if no data is pending.
+/// The level is limited by the window size of the compressor, see
+/// [`CompressorOxide::set_level_and_strategy`].
+///
+/// # Errors
+///
+/// Returns [`MZError::Buf`] if `output` is too small to hold all of the flushed data. Some
+/// of it may have been written. The settings are left unchanged in this case; call this
+/// again with more output space.
+///
+/// Returns [`MZError::Param`] if the compressor is in an error state.
+pub fn deflate_params(
+    compressor: &mut CompressorOxide,
+    output: &mut [u8],
+    level: u8,
+    strategy: CompressionStrategy,
+) -> StreamResult {
+    let mut bytes_written = 0;
+    if compressor.has_pending_data() && compressor.prev_return_status() != TDEFLStatus::Done {
+        if output.is_empty() {
+            return StreamResult::error(MZError::Buf);
+        }
+        let (status, _, out_bytes) = compress(compressor, &[], output, TDEFLFlush::Sync);
+        bytes_written = out_bytes;
+        let status = match status {
+            TDEFLStatus::BadParam => Err(MZError::Param),
+            TDEFLStatus::PutBufFailed => Err(MZError::Stream),
+            _ if compressor.has_pending_data() => Err(MZError::Buf),
+            _ => Ok(MZStatus::Ok),
+        };
+        if status.is_err() {
+            return StreamResult {
+                bytes_consumed: 0,
+                bytes_written,
+                status,
+            };
+        }
+    }
+
+    compressor.set_level_and_strategy(level, strategy);
+    StreamResult {
+        bytes_consumed: 0,
+        bytes_written,
+        status: Ok(MZStatus::Ok),
+    }
+}
+
 #[cfg(test)]
 mod test {
-    use super::deflate;
+    use super::{deflate, deflate_params};
+    use crate::deflate::core::CompressionStrategy;
     use crate::deflate::CompressorOxide;
     use crate::inflate::decompress_to_vec_zlib;
-    use crate::{MZFlush, MZStatus};
+    use crate::{MZError, MZFlush, MZStatus};
     use alloc::boxed::Box;
     use alloc::vec;
 
@@ -121,4 +175,59 @@ mod test {
         assert_eq!(decomp[..], data[..]);
         assert_eq!(res.bytes_consumed, data.len());
     }
+
+    #[test]
+    fn change_params_mid_stream() {
+        let data = b"Some data that is compressed with a few different settings. ".repeat(2000);
+        let mut compressor = Box::<CompressorOxide>::default();
+        compressor.set_level_and_strategy(1, CompressionStrategy::Default);
+        let mut compressed = vec![0; data.len()];
+        let mut out_pos = 0;
+
+        let settings = [
+            (9, CompressionStrategy::Default),
+            (0, CompressionStrategy::Default),
+            (6, CompressionStrategy::RLE),
+            (3, CompressionStrategy::Filtered),
+            (1, CompressionStrategy::Fixed),
+        ];
+        for (chunk, &(level, strategy)) in data.chunks(data.len() / 5).zip(settings.iter()) {
+            let res = deflate(
+                &mut compressor,
+                chunk,
+                &mut compressed[out_pos..],
+                MZFlush::None,
+            );
+            assert_eq!(res.status, Ok(MZStatus::Ok));
+            assert_eq!(res.bytes_consumed, chunk.len());
+            out_pos += res.bytes_written;
+
+            // Too little output space to flush leaves the settings alone.
+            let flags = compressor.flags();
+            let mut small = [0; 2];
+            let res = deflate_params(&mut compressor, &mut small, level, strategy);
+            assert_eq!(res.status, Err(MZError::Buf));
+            assert_eq!(compressor.flags(), flags);
+            compressed[out_pos..out_pos + res.bytes_written]
+                .copy_from_slice(&small[..res.bytes_written]);
+            out_pos += res.bytes_written;
+
+            let res = deflate_params(&mut compressor, &mut compressed[out_pos..], level, strategy);
+            assert_eq!(res.status, Ok(MZStatus::Ok));
+            out_pos += res.bytes_written;
+            assert!(!compressor.has_pending_data());
+        }
+
+        let res = deflate(
+            &mut compressor,
+            &[],
+            &mut compressed[out_pos..],
+            MZFlush::Finish,
+        );
+        assert_eq!(res.status, Ok(MZStatus::StreamEnd));
+        out_pos += res.bytes_written;
+
+        let decomp = decompress_to_vec_zlib(&compressed[..out_pos]).unwrap();
+        assert_eq!(decomp, data);
+    }
 }
@@ -3,6 +3,8 @@ use crate::deflate::core::deflate_flags::{TDEFL_GREEDY_PARSING_FLAG, TDEFL_RLE_M
 const DEFAULT_CM: u8 = 8;
 const _DEFAULT_CINFO: u8 = 7 << 4;
 const _DEFAULT_FDICT: u8 = 0;
+/// Bit in FLG that signals that a preset dictionary id follows the header.
+const FDICT: u8 = 1 << 5;
 const _DEFAULT_CMF: u8 = DEFAULT_CM | _DEFAULT_CINFO;
 // CMF used for RLE (technically it uses a window size of 0 but the lowest that can
 // be specified in the header corresponds to a window size of 1 << (0 + 8) aka 256.
@@ -43,22 +45,25 @@ const fn zlib_level_from_flags(flags: u32) -> u8 {
     }
 }
 
-/// Get the zlib header for the level using the default window size and no
-/// dictionary.
+/// Get the zlib header for the level using the given window size.
+///
+/// If `has_dict` is true, the FDICT bit is set, signalling that the adler32 of a preset
+/// dictionary follows the header.
 #[inline]
-fn header_from_level(level: u8, window_bits: u8) -> [u8; 2] {
+fn header_from_level(level: u8, window_bits: u8, has_dict: bool) -> [u8; 2] {
     // bits 0 to 3 compression method (always 8)
     // bits 4 to 7, log 2 of window size - 7
     let cmf = DEFAULT_CM | (window_bits.saturating_sub(8) << 4);
-    [cmf, add_fcheck(cmf, level << 6)]
+    let fdict = if has_dict { FDICT } else { 0 };
+    [cmf, add_fcheck(cmf, (level << 6) | fdict)]
 }
 
 /// Create a zlib header from the given compression flags.
 /// Only level is considered.
 #[inline]
-pub fn header_from_flags(flags: u32, window_bits: u8) -> [u8; 2] {
+pub fn header_from_flags(flags: u32, window_bits: u8, has_dict: bool) -> [u8; 2] {
     let level = zlib_level_from_flags(flags);
-    header_from_level(level, window_bits)
+    header_from_level(level, window_bits, has_dict)
 }
 
 #[cfg(test)]
@@ -92,7 +97,14 @@ mod test {
 
     #[test]
     fn test_header() {
-        let header = super::header_from_level(3, 8);
+        let header = super::header_from_level(3, 8, false);
+        assert_eq!(
+            ((usize::from(header[0]) * 256) + usize::from(header[1])) % 31,
+            0
+        );
+
+        let header = super::header_from_level(2, 15, true);
+        assert_eq!(header[1] & super::FDICT, super::FDICT);
         assert_eq!(
             ((usize::from(header[0]) * 256) + usize::from(header[1])) % 31,
             0
@@ -0,0 +1,510 @@
+//! Types and constants for the [gzip](https://www.rfc-editor.org/rfc/rfc1952) wrapper format.
+
+#[cfg(feature = "with-alloc")]
+use crate::alloc::vec::Vec;
+use crate::shared::{update_crc32, MZ_CRC32_INIT};
+use core::cmp;
+
+/// First byte of the gzip magic number.
+pub const GZIP_ID1: u8 = 0x1F;
+/// Second byte of the gzip magic number.
+pub const GZIP_ID2: u8 = 0x8B;
+/// Compression method value for deflate, the only one defined.
+pub const GZIP_CM_DEFLATE: u8 = 8;
+
+/// Header flag bits.
+pub mod gzip_flags {
+    /// The data is probably ASCII text.
+    pub const FTEXT: u8 = 0x01;
+    /// A CRC16 of the header is present.
+    pub const FHCRC: u8 = 0x02;
+    /// An extra field is present.
+    pub const FEXTRA: u8 = 0x04;
+    /// A zero-terminated original file name is present.
+    pub const FNAME: u8 = 0x08;
+    /// A zero-terminated file comment is present.
+    pub const FCOMMENT: u8 = 0x10;
+    /// Bits that are reserved and must be zero.
+    pub const FRESERVED: u8 = 0xE0;
+}
+
+/// Operating system value used when the originating system is not known.
+pub const GZIP_OS_UNKNOWN: u8 = 255;
+
+/// Maximum number of bytes of the file name and comment that are stored when
+/// parsing a header. Anything past this is skipped.
+pub const GZIP_MAX_STORED_FIELD_LEN: usize = 65535;
+
+/// Length of the fixed part of the header.
+pub(crate) const GZIP_HEADER_FIXED_LEN: usize = 10;
+
+/// Contents of a gzip member header.
+///
+/// When compressing, the fields are used to fill in the header that is written. The `XFL`
+/// byte is derived from the compression level and is not set here.
+///
+/// When decompressing, the fields are filled in from the header that was read.
+#[cfg(feature = "with-alloc")]
+#[derive(Clone, PartialEq, Eq)]
+#[cfg_attr(not(feature = "rustc-dep-of-std"), derive(Hash, Debug))]
+pub struct GzHeader {
+    /// Whether the `FTEXT` flag is set.
+    pub text: bool,
+    /// Modification time of the original file as a unix timestamp, 0 if not available.
+    pub mtime: u32,
+    /// Extra flags (`XFL`). Ignored when compressing.
+    pub xfl: u8,
+    /// Operating system the data originated on.
+    pub os: u8,
+    /// Raw contents of the extra field (`FEXTRA`), excluding the length prefix.
+    pub extra: Option<Vec<u8>>,
+    /// Original file name (`FNAME`), without the terminating zero byte.
+    pub filename: Option<Vec<u8>>,
+    /// File comment (`FCOMMENT`), without the terminating zero byte.
+    pub comment: Option<Vec<u8>>,
+    /// Whether the header is (or is to be) protected by a CRC16 (`FHCRC`).
+    pub hcrc: bool,
+}
+
+#[cfg(feature = "with-alloc")]
+impl Default for GzHeader {
+    fn default() -> Self {
+        GzHeader {
+            text: false,
+            mtime: 0,
+            xfl: 0,
+            os: GZIP_OS_UNKNOWN,
+            extra: None,
+            filename: None,
+            comment: None,
+            hcrc: false,
+        }
+    }
+}
+
+#[cfg(feature = "with-alloc")]
+impl GzHeader {
+    /// Append a subfield with the two byte identifier `id` to the extra field.
+    ///
+    /// Returns `false` and leaves the header unchanged if the extra field would
+    /// get larger than the 65535 bytes the format allows.
+    pub fn add_extra_subfield(&mut self, id: [u8; 2], data: &[u8]) -> bool {
+        let current = self.extra.as_ref().map_or(0, |e| e.len());
+        if data.len() > usize::from(u16::MAX) || current + 4 + data.len() > usize::from(u16::MAX) {
+            return false;
+        }
+        let extra = self.extra.get_or_insert_with(Vec::new);
+        extra.extend_from_slice(&id);
+        extra.extend_from_slice(&(data.len() as u16).to_le_bytes());
+        extra.extend_from_slice(data);
+        true
+    }
+
+    /// Iterate over the subfields of the extra field as `(id, data)` pairs.
+    ///
+    /// Iteration stops at the first subfield that runs past the end of the extra field.
+    pub fn extra_subfields(&self) -> ExtraSubfields<'_> {
+        ExtraSubfields::new(self.extra.as_deref().unwrap_or(&[]))
+    }
+
+    /// Look up the data of the first extra subfield with the identifier `id`.
+    pub fn extra_subfield(&self, id: [u8; 2]) -> Option<&[u8]> {
+        self.extra_subfields()
+            .find(|&(sub_id, _)| sub_id == id)
+            .map(|(_, data)| data)
+    }
+
+    /// Total length of the header when written out.
+    pub(crate) fn encoded_len(&self) -> usize {
+        GZIP_HEADER_FIXED_LEN
+            + self.extra.as_ref().map_or(0, |e| 2 + e.len())
+            + self.filename.as_ref().map_or(0, |f| f.len() + 1)
+            + self.comment.as_ref().map_or(0, |c| c.len() + 1)
+            + if self.hcrc { 2 } else { 0 }
+    }
+
+    /// Check that the header can be represented in the gzip format.
+    pub(crate) fn is_valid(&self) -> bool {
+        self.extra
+            .as_ref()
+            .map_or(true, |e| e.len() <= usize::from(u16::MAX))
+            && self.filename.as_ref().map_or(true, |f| !f.contains(&0))
+            && self.comment.as_ref().map_or(true, |c| !c.contains(&0))
+    }
+
+    fn flags(&self) -> u8 {
+        use self::gzip_flags::*;
+        let mut flags = 0;
+        if self.text {
+            flags |= FTEXT;
+        }
+        if self.hcrc {
+            flags |= FHCRC;
+        }
+        if self.extra.is_some() {
+            flags |= FEXTRA;
+        }
+        if self.filename.is_some() {
+            flags |= FNAME;
+        }
+        if self.comment.is_some() {
+            flags |= FCOMMENT;
+        }
+        flags
+    }
+
+    /// Serialize the header, passing the pieces to `write` in order.
+    pub(crate) fn write_to(&self, xfl: u8, mut write: impl FnMut(&[u8])) {
+        let mut crc = MZ_CRC32_INIT;
+        let mut put = |bytes: &[u8]| {
+            crc = update_crc32(crc, bytes);
+            write(bytes);
+        };
+
+        let mtime = self.mtime.to_le_bytes();
+        put(&[
+            GZIP_ID1,
+            GZIP_ID2,
+            GZIP_CM_DEFLATE,
+            self.flags(),
+            mtime[0],
+            mtime[1],
+            mtime[2],
+            mtime[3],
+            xfl,
+            self.os,
+        ]);
+        if let Some(extra) = &self.extra {
+            put(&(extra.len() as u16).to_le_bytes());
+            put(extra);
+        }
+        if let Some(filename) = &self.filename {
+            put(filename);
+            put(&[0]);
+        }
+        if let Some(comment) = &self.comment {
+            put(comment);
+            put(&[0]);
+        }
+        if self.hcrc {
+            let crc16 = (crc & 0xFFFF) as u16;
+            write(&crc16.to_le_bytes());
+        }
+    }
+}
+
+/// Iterator over the subfields of a gzip extra field.
+///
+/// Created by [`GzHeader::extra_subfields`].
+#[cfg(feature = "with-alloc")]
+#[derive(Clone)]
+pub struct ExtraSubfields<'a> {
+    data: &'a [u8],
+}
+
+#[cfg(feature = "with-alloc")]
+impl<'a> ExtraSubfields<'a> {
+    /// Iterate over the subfields of the raw extra field `data`.
+    pub(crate) fn new(data: &'a [u8]) -> ExtraSubfields<'a> {
+        ExtraSubfields { data }
+    }
+}
+
+#[cfg(feature = "with-alloc")]
+impl<'a> Iterator for ExtraSubfields<'a> {
+    type Item = ([u8; 2], &'a [u8]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.data.len() < 4 {
+            return None;
+        }
+        let id = [self.data[0], self.data[1]];
+        let len = usize::from(u16::from_le_bytes([self.data[2], self.data[3]]));
+        let sub = self.data.get(4..4 + len);
+        match sub {
+            Some(sub) => {
+                self.data = &self.data[4 + len..];
+                Some((id, sub))
+            }
+            None => {
+                self.data = &[];
+                None
+            }
+        }
+    }
+}
+
+/// Which part of the header the parser is currently reading.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+enum HeaderStage {
+    Fixed,
+    ExtraLen,
+    Extra,
+    Name,
+    Comment,
+    Hcrc,
+    Done,
+}
+
+/// Incremental parser for a gzip member header.
+///
+/// The header can be fed to the parser in arbitrarily small chunks.
+#[derive(Clone)]
+pub(crate) struct GzHeaderParser {
+    stage: HeaderStage,
+    flags: u8,
+    /// Buffer for the fixed-size fields.
+    buf: [u8; GZIP_HEADER_FIXED_LEN],
+    /// Bytes read of the current field.
+    pos: usize,
+    /// Length of the extra field.
+    xlen: usize,
+    /// Running crc32 of the header, used to check the `FHCRC` field.
+    crc: u32,
+    #[cfg(feature = "with-alloc")]
+    header: GzHeader,
+}
+
+impl GzHeaderParser {
+    pub(crate) fn new() -> Self {
+        GzHeaderParser {
+            stage: HeaderStage::Fixed,
+            flags: 0,
+            buf: [0; GZIP_HEADER_FIXED_LEN],
+            pos: 0,
+            xlen: 0,
+            crc: MZ_CRC32_INIT,
+            #[cfg(feature = "with-alloc")]
+            header: GzHeader::default(),
+        }
+    }
+
+    /// Whether the whole header has been read.
+    #[inline]
+    pub(crate) fn is_done(&self) -> bool {
+        self.stage == HeaderStage::Done
+    }
+
+    /// The parsed header, if the whole header has been read.
+    #[cfg(feature = "with-alloc")]
+    pub(crate) fn header(&self) -> Option<&GzHeader> {
+        if self.is_done() {
+            Some(&self.header)
+        } else {
+            None
+        }
+    }
+
+    /// Move on to the first stage after `from` that is present according to the header flags.
+    fn advance(&mut self, from: HeaderStage) {
+        use self::gzip_flags::*;
+        use self::HeaderStage::*;
+        self.pos = 0;
+        self.stage = if from < ExtraLen && self.flags & FEXTRA != 0 {
+            ExtraLen
+        } else if from < Name && self.flags & FNAME != 0 {
+            Name
+        } else if from < Comment && self.flags & FCOMMENT != 0 {
+            Comment
+        } else if from < Hcrc && self.flags & FHCRC != 0 {
+            Hcrc
+        } else {
+            Done
+        };
+    }
+
+    /// Fill `self.buf` up to `len` bytes from `input`, returning the number of bytes used.
+    fn fill_buf(&mut self, input: &[u8], len: usize) -> usize {
+        let n = cmp::min(len - self.pos, input.len());
+        self.buf[self.pos..self.pos + n].copy_from_slice(&input[..n]);
+        self.pos += n;
+        n
+    }
+
+    /// Read a zero-terminated field, returning the number of bytes used and whether the
+    /// terminator was found.
+    fn read_zero_terminated(&mut self, input: &[u8]) -> (usize, bool) {
+        let (data, found) = match input.iter().position(|&b| b == 0) {
+            Some(end) => (&input[..end], true),
+            None => (input, false),
+        };
+        #[cfg(feature = "with-alloc")]
+        {
+            let field = if self.stage == HeaderStage::Name {
+                self.header.filename.get_or_insert_with(Vec::new)
+            } else {
+                self.header.comment.get_or_insert_with(Vec::new)
+            };
+            let n = cmp::min(
+                GZIP_MAX_STORED_FIELD_LEN.saturating_sub(field.len()),
+                data.len(),
+            );
+            field.extend_from_slice(&data[..n]);
+        }
+        (data.len() + found as usize, found)
+    }
+
+    /// Parse as much of the header as possible from `input`.
+    ///
+    /// Returns the number of bytes consumed, or `Err` if the header is invalid.
+    pub(crate) fn parse(&mut self, input: &[u8]) -> Result<usize, ()> {
+        use self::HeaderStage::*;
+        let mut consumed = 0;
+        while self.stage != Done && consumed < input.len() {
+            let stage = self.stage;
+            let chunk = &input[consumed..];
+            let used = match stage {
+                Fixed => {
+                    let used = self.fill_buf(chunk, GZIP_HEADER_FIXED_LEN);
+                    if self.pos == GZIP_HEADER_FIXED_LEN {
+                        let b = self.buf;
+                        if b[0] != GZIP_ID1
+                            || b[1] != GZIP_ID2
+                            || b[2] != GZIP_CM_DEFLATE
+                            || b[3] & gzip_flags::FRESERVED != 0
+                        {
+                            return Err(());
+                        }
+                        self.flags = b[3];
+                        #[cfg(feature = "with-alloc")]
+                        {
+                            self.header.text = b[3] & gzip_flags::FTEXT != 0;
+                            self.header.hcrc = b[3] & gzip_flags::FHCRC != 0;
+                            self.header.mtime = u32::from_le_bytes([b[4], b[5], b[6], b[7]]);
+                            self.header.xfl = b[8];
+                            self.header.os = b[9];
+                        }
+                        self.advance(Fixed);
+                    }
+                    used
+                }
+                ExtraLen => {
+                    let used = self.fill_buf(chunk, 2);
+                    if self.pos == 2 {
+                        self.xlen = usize::from(u16::from_le_bytes([self.buf[0], self.buf[1]]));
+                        self.pos = 0;
+                        self.stage = Extra;
+                        #[cfg(feature = "with-alloc")]
+                        {
+                            self.header.extra = Some(Vec::with_capacity(self.xlen));
+                        }
+                    }
+                    used
+                }
+                Extra => {
+                    let n = cmp::min(self.xlen - self.pos, chunk.len());
+                    #[cfg(feature = "with-alloc")]
+                    if let Some(extra) = self.header.extra.as_mut() {
+                        extra.extend_from_slice(&chunk[..n]);
+                    }
+                    self.pos += n;
+                    if self.pos == self.xlen {
+                        self.advance(Extra);
+                    }
+                    n
+                }
+                Name | Comment => {
+                    let (used, found) = self.read_zero_terminated(chunk);
+                    if found {
+                        self.advance(stage);
+                    }
+                    used
+                }
+                Hcrc => {
+                    let used = self.fill_buf(chunk, 2);
+                    if self.pos == 2 {
+                        let expected = u16::from_le_bytes([self.buf[0], self.buf[1]]);
+                        if !cfg!(fuzzing) && expected != (self.crc & 0xFFFF) as u16 {
+                            return Err(());
+                        }
+                        self.advance(Hcrc);
+                    }
+                    used
+                }
+                Done => 0,
+            };
+            if stage != Hcrc {
+                self.crc = update_crc32(self.crc, &chunk[..used]);
+            }
+            consumed += used;
+        }
+        Ok(consumed)
+    }
+}
+
+#[cfg(all(test, feature = "with-alloc"))]
+mod test {
+    use super::{GzHeader, GzHeaderParser};
+    use alloc::vec::Vec;
+
+    #[test]
+    fn extra_subfields() {
+        let mut header = GzHeader::default();
+        assert!(header.add_extra_subfield(*b"BC", &[0x1B, 0x00]));
+        assert!(header.add_extra_subfield(*b"AB", b"hello"));
+        let fields: Vec<_> = header.extra_subfields().collect();
+        assert_eq!(
+            fields,
+            [(*b"BC", &[0x1B, 0x00][..]), (*b"AB", &b"hello"[..])]
+        );
+        assert_eq!(header.extra_subfield(*b"AB"), Some(&b"hello"[..]));
+        assert_eq!(header.extra_subfield(*b"XX"), None);
+        assert!(!header.add_extra_subfield(*b"ZZ", &[0; 65535]));
+    }
+
+    #[test]
+    fn encode_header() {
+        let header = GzHeader {
+            mtime: 0x0102_0304,
+            os: 3,
+            filename: Some(b"a.txt".to_vec()),
+            hcrc: true,
+            ..GzHeader::default()
+        };
+        let mut out = Vec::new();
+        header.write_to(0, |b| out.extend_from_slice(b));
+        assert_eq!(out.len(), header.encoded_len());
+        assert_eq!(
+            &out[..16],
+            &[0x1F, 0x8B, 8, 0x0A, 4, 3, 2, 1, 0, 3, b'a', b'.', b't', b'x', b't', 0]
+        );
+        let crc = crate::shared::update_crc32(0, &out[..16]);
+        assert_eq!(&out[16..], &(crc as u16).to_le_bytes());
+    }
+
+    #[test]
+    fn parse_header_bytewise() {
+        let mut header = GzHeader {
+            text: true,
+            mtime: 99,
+            os: 3,
+            filename: Some(b"name".to_vec()),
+            comment: Some(b"a comment".to_vec()),
+            hcrc: true,
+            ..GzHeader::default()
+        };
+        header.add_extra_subfield(*b"XY", b"data");
+        let mut out = Vec::new();
+        header.write_to(2, |b| out.extend_from_slice(b));
+        out.extend_from_slice(b"trailing");
+
+        let mut parser = GzHeaderParser::new();
+        let mut pos = 0;
+        while !parser.is_done() {
+            pos += parser.parse(&out[pos..pos + 1]).unwrap();
+        }
+        assert_eq!(pos, header.encoded_len());
+        header.xfl = 2;
+        assert_eq!(parser.header(), Some(&header));
+
+        // Corrupt the header crc.
+        let len = header.encoded_len();
+        out[len - 1] ^= 1;
+        assert!(GzHeaderParser::new().parse(&out).is_err());
+        // Bad magic.
+        assert_eq!(GzHeaderParser::new().parse(&[0x1F, 0x8C]), Ok(2));
+        assert!(GzHeaderParser::new()
+            .parse(&[0x1F, 0x8C, 8, 0, 0, 0, 0, 0, 0, 0])
+            .is_err());
+    }
+}
@@ -40,6 +40,8 @@ use crate::serde::big_array::BigArray;
 use serde::{Deserialize, Serialize};
 
 pub const TINFL_LZ_DICT_SIZE: usize = 32_768;
+/// Size of the window used by Deflate64, see [`inflate_flags::TINFL_FLAG_DEFLATE64`].
+pub const TINFL_LZ_DICT_SIZE_DEFLATE64: usize = 65_536;
 
 /// A struct containing huffman code lengths and the huffman code tree used by the decompressor.
 #[cfg_attr(not(feature = "rustc-dep-of-std"), derive(Clone))]
@@ -191,12 +193,24 @@ pub mod inflate_flags {
     /// again will resume decompression of the next block.
     #[cfg(feature = "block-boundary")]
     pub const TINFL_FLAG_STOP_ON_BLOCK_BOUNDARY: u32 = 128;
+
+    /// Decode Deflate64 (also known as enhanced deflate, ZIP compression method 9) data
+    /// instead of regular deflate data.
+    ///
+    /// Deflate64 uses a 64 KiB window, so a wrapping output buffer needs to be at least
+    /// [`TINFL_LZ_DICT_SIZE_DEFLATE64`][super::TINFL_LZ_DICT_SIZE_DEFLATE64] bytes.
+    ///
+    /// NOTE: This flag does not exist in miniz and is a custom addition for miniz_oxide.
+    pub const TINFL_FLAG_DEFLATE64: u32 = 256;
 }
 
 use self::inflate_flags::*;
 
 const MIN_TABLE_SIZES: [u16; 3] = [257, 1, 4];
 
+/// Bit in the zlib FLG byte that signals that a preset dictionary was used.
+const ZLIB_FDICT: u32 = 0b0010_0000;
+
 #[cfg(target_pointer_width = "64")]
 type BitBuffer = u64;
 
@@ -269,6 +283,8 @@ pub struct DecompressorOxide {
     z_header1: u32,
     /// Adler32 checksum from the zlib header.
     z_adler32: u32,
+    /// Adler32 checksum of the preset dictionary from the zlib header, if FDICT is set.
+    z_dict_id: u32,
     /// 1 if the current block is the last block, 0 otherwise.
     finish: u8,
     /// The type of the current block.
@@ -314,6 +330,9 @@ impl DecompressorOxide {
     pub fn init(&mut self) {
         // The rest of the data is reset or overwritten when used.
         self.state = core::State::Start;
+        // Cleared here rather than in the `Start` state so bits can be added with `prime`.
+        self.bit_buf = 0;
+        self.num_bits = 0;
     }
 
     /// Returns the adler32 checksum of the currently decompressed data.
@@ -339,6 +358,92 @@ impl DecompressorOxide {
         }
     }
 
+    /// Returns the adler32 checksum of the preset dictionary needed to decompress the stream,
+    /// if the zlib header signals that one was used and it has been read.
+    #[inline]
+    #[cfg(not(feature = "rustc-dep-of-std"))]
+    pub fn dictionary_id(&self) -> Option<u32> {
+        let header_read = !matches!(
+            self.state,
+            State::Start | State::ReadZlibCmf | State::ReadZlibFlg | State::ReadZlibDictId
+        );
+        if header_read && !self.state.is_failure() && self.z_header1 & ZLIB_FDICT != 0 {
+            Some(self.z_dict_id)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the reason decompression failed, if it failed due to invalid data.
+    ///
+    /// Checksum mismatches are only reported through [`TINFLStatus::Adler32Mismatch`].
+    #[inline]
+    #[cfg(not(feature = "rustc-dep-of-std"))]
+    pub const fn data_error(&self) -> Option<DataError> {
+        Some(match self.state {
+            BadZlibHeader => DataError::InvalidHeader,
+            BlockTypeUnexpected => DataError::InvalidBlockType,
+            BadRawLength => DataError::InvalidStoredLength,
+            BadDistOrLiteralTableLength => DataError::TooManySymbols,
+            BadTotalSymbols => DataError::InvalidCodeLengths,
+            BadCodeSizeSum | BadCodeSizeDistPrevLookup => DataError::InvalidRepeat,
+            InvalidLitlen => DataError::InvalidLiteralLength,
+            InvalidDist => DataError::InvalidDistance,
+            DistanceOutOfBounds => DataError::DistanceTooFarBack,
+            _ => return None,
+        })
+    }
+
+    /// Continue at the header of a new block on the next call, dropping any input bits
+    /// that are buffered, e.g. after finding a flush point in damaged data.
+    ///
+    /// The whole bytes that were buffered are written to `buf`, and their number returned.
+    /// The zlib header fields are kept.
+    pub(crate) fn restart_at_block(&mut self, buf: &mut [u8; 8]) -> usize {
+        // Skip the rest of the partially read byte.
+        let mut bits = self.bit_buf >> (self.num_bits & 7);
+        let n = (self.num_bits / 8) as usize;
+        for b in &mut buf[..n] {
+            *b = bits as u8;
+            bits >>= 8;
+        }
+
+        self.state = core::State::ReadBlockHeader;
+        self.bit_buf = 0;
+        self.num_bits = 0;
+        self.finish = 0;
+        self.dist = 0;
+        self.counter = 0;
+        self.num_extra = 0;
+        n
+    }
+
+    /// Insert the lowest `bits` bits of `value` into the input, to be read before the next
+    /// byte of input.
+    ///
+    /// This can be used when the deflate data does not start on a byte boundary, by
+    /// loading the bits of the first partial byte here and passing the rest of the input as
+    /// usual. With a zlib header, the bits are read after the header. Up to 16 bits can be
+    /// inserted at a time, and at most 32 can be buffered in total.
+    ///
+    /// # Errors
+    /// Returns [`MZError::Param`] if `bits` is more than 16, or [`MZError::Buf`] if there
+    /// is no room for the bits.
+    pub fn prime(&mut self, bits: u32, value: u32) -> Result<(), crate::MZError> {
+        if bits > 16 {
+            return Err(crate::MZError::Param);
+        }
+        if self.num_bits + bits > 32 {
+            return Err(crate::MZError::Buf);
+        }
+        if bits != 0 {
+            let value = value & ((1 << bits) - 1);
+            self.bit_buf |= BitBuffer::from(value) << self.num_bits;
+            self.num_bits += bits;
+        }
+        Ok(())
+    }
+
     // Get zlib header for tests
     // Only for tests for now, may provide a proper function for this for later.
     #[cfg(all(test, feature = "with-alloc"))]
@@ -407,6 +512,7 @@ impl Default for DecompressorOxide {
             z_header0: 0,
             z_header1: 0,
             z_adler32: 0,
+            z_dict_id: 0,
             finish: 0,
             block_type: 0,
             check_adler32: 0,
@@ -458,6 +564,7 @@ enum State {
     HuffDecodeOuterLoop1,
     HuffDecodeOuterLoop2,
     ReadAdler32,
+    ReadZlibDictId,
 
     DoneForever,
 
@@ -523,13 +630,33 @@ const LENGTH_EXTRA: [u8; 32] = [
 ];
 
 /// Base length for each distance code.
+///
+/// The last two codes are only valid in Deflate64.
 #[rustfmt::skip]
-const DIST_BASE: [u16; 30] = [
+const DIST_BASE: [u16; 32] = [
     1,    2,    3,    4,    5,    7,      9,      13,     17,     25,    33,
     49,   65,   97,   129,  193,  257,    385,    513,    769,    1025,  1537,
-    2049, 3073, 4097, 6145, 8193, 12_289, 16_385, 24_577
+    2049, 3073, 4097, 6145, 8193, 12_289, 16_385, 24_577, 32_769, 49_153
 ];
 
+/// Length code that has a different meaning in Deflate64.
+///
+/// In regular deflate it means a length of 258, in Deflate64 it has a base of 3 and
+/// 16 extra bits.
+const DEFLATE64_LONG_LENGTH_CODE: u32 = 285;
+const DEFLATE64_LONG_LENGTH_BASE: u32 = 3;
+const DEFLATE64_LONG_LENGTH_EXTRA: u8 = 16;
+
+/// Get the highest valid distance code.
+#[inline(always)]
+const fn max_dist_code(flags: u32) -> usize {
+    if flags & TINFL_FLAG_DEFLATE64 != 0 {
+        31
+    } else {
+        29
+    }
+}
+
 /// Get the number of extra bits used for a distance code.
 /// (Code numbers above `NUM_DISTANCE_CODES` will give some garbage
 /// value.)
@@ -594,9 +721,6 @@ const fn validate_zlib_header(cmf: u32, flg: u32, flags: u32, mask: usize) -> Ac
     let mut failed =
     // cmf + flg should be divisible by 31.
         (((cmf * 256) + flg) % 31 != 0) ||
-    // If this flag is set, a dictionary was used for this zlib compressed data.
-    // This is currently not supported by miniz or miniz-oxide
-        ((flg & 0b0010_0000) != 0) ||
     // Compression method. Only 8(DEFLATE) is defined by the standard.
         ((cmf & 15) != 8);
 
@@ -611,6 +735,10 @@ const fn validate_zlib_header(cmf: u32, flg: u32, flags: u32, mask: usize) -> Ac
 
     if failed {
         Action::Jump(BadZlibHeader)
+    } else if (flg & ZLIB_FDICT) != 0 {
+        // A preset dictionary was used for this zlib compressed data,
+        // read the id and let the caller provide it.
+        Action::Jump(ReadZlibDictId)
     } else {
         Action::Jump(ReadBlockHeader)
     }
@@ -1290,6 +1418,13 @@ fn decompress_fast(
             // We already verified earlier that the code is > 256.
             state.begin(InvalidLitlen);
             break 'o TINFLStatus::Failed;
+        } else if l.counter == DEFLATE64_LONG_LENGTH_CODE && flags & TINFL_FLAG_DEFLATE64 != 0 {
+            // Long Deflate64 matches may not fit in the space this function assumes,
+            // so let the main loop handle them.
+            l.num_extra = DEFLATE64_LONG_LENGTH_EXTRA;
+            l.counter = DEFLATE64_LONG_LENGTH_BASE;
+            state.begin(ReadExtraBitsLitlen);
+            break 'o TINFLStatus::Done;
         } else {
             // The symbol was a length code.
             // # Optimization
@@ -1320,13 +1455,13 @@ fn decompress_fast(
             symbol &= 511;
             l.bit_buf >>= code_len;
             l.num_bits -= code_len;
-            if symbol > 29 {
+            if symbol as usize > max_dist_code(flags) {
                 state.begin(InvalidDist);
                 break 'o TINFLStatus::Failed;
             }
 
             l.num_extra = num_extra_bits_for_distance_code(symbol as u8);
-            l.dist = u32::from(DIST_BASE[symbol as usize]);
+            l.dist = u32::from(DIST_BASE[symbol as usize & BASE_EXTRA_MASK]);
 
             if l.num_extra != 0 {
                 fill_bit_buffer(&mut l, in_iter);
@@ -1387,6 +1522,8 @@ fn decompress_fast(
 ///       less than this, or alternatively an RLE mode where matches will only refer to the previous byte
 ///       and thus allows a smaller output buffer. The window size can be specified in the zlib
 ///       header structure, however, the header data should not be relied on to be correct.
+///     - When decoding Deflate64 with [`TINFL_FLAG_DEFLATE64`], 64KiB of previous data is needed
+///       instead.
 ///
 /// `flags` indicates settings and status to the decompression function.
 /// * The [`TINFL_FLAG_HAS_MORE_INPUT`] has to be specified if more compressed data is to be provided
@@ -1460,14 +1597,13 @@ pub fn decompress_with_limit(
     let mut status = 'state_machine: loop {
         match state {
             Start => generate_state!(state, 'state_machine, {
-                l.bit_buf = 0;
-                l.num_bits = 0;
                 l.dist = 0;
                 l.counter = 0;
                 l.num_extra = 0;
                 r.z_header0 = 0;
                 r.z_header1 = 0;
                 r.z_adler32 = 1;
+                r.z_dict_id = 0;
                 r.check_adler32 = 1;
                 if flags & TINFL_FLAG_PARSE_ZLIB_HEADER != 0 {
                     Action::Jump(State::ReadZlibCmf)
@@ -1639,7 +1775,11 @@ pub fn decompress_with_limit(
                     // NOTE this the final sizes after adding back predefined values, not
                     // raw value in the data.
                     // See miniz_oxide issue #130 and https://github.com/madler/zlib/issues/82.
-                    if r.table_sizes[LITLEN_TABLE] <= 286 && r.table_sizes[DIST_TABLE] <= 30 {
+                    // Deflate64 can use all 32 distance codes.
+                    let max_dist_table_size = max_dist_code(flags) as u16 + 1;
+                    if r.table_sizes[LITLEN_TABLE] <= 286
+                        && r.table_sizes[DIST_TABLE] <= max_dist_table_size
+                    {
                         Action::Jump(ReadHufflenTableCodeSize)
                     }
                     else {
@@ -1836,6 +1976,12 @@ pub fn decompress_with_limit(
                     // Invalid code.
                     // We already verified earlier that the code is > 256.
                     Action::Jump(InvalidLitlen)
+                } else if l.counter == DEFLATE64_LONG_LENGTH_CODE
+                    && flags & TINFL_FLAG_DEFLATE64 != 0
+                {
+                    l.num_extra = DEFLATE64_LONG_LENGTH_EXTRA;
+                    l.counter = DEFLATE64_LONG_LENGTH_BASE;
+                    Action::Jump(ReadExtraBitsLitlen)
                 } else {
                     // # Optimization
                     // Mask the value to avoid bounds checks
@@ -1873,12 +2019,12 @@ pub fn decompress_with_limit(
                     // to make the assumption that it can't be negative and thus
                     // overflow if it's converted after the check.
                     let symbol = symbol as usize;
-                    if symbol > 29 {
+                    if symbol > max_dist_code(flags) {
                         // Invalid distance code.
                         return Action::Jump(InvalidDist)
                     }
                     l.num_extra = num_extra_bits_for_distance_code(symbol as u8);
-                    l.dist = u32::from(DIST_BASE[symbol]);
+                    l.dist = u32::from(DIST_BASE[symbol & BASE_EXTRA_MASK]);
                     if l.num_extra != 0 {
                         // ReadEXTRA_BITS_DISTACNE
                         Action::Jump(ReadExtraBitsDistance)
@@ -2014,6 +2160,20 @@ pub fn decompress_with_limit(
                 }
             }),
 
+            ReadZlibDictId => generate_state!(state, 'state_machine, {
+                if l.counter < 4 {
+                    read_byte(&mut in_iter, flags, |byte| {
+                        r.z_dict_id <<= 8;
+                        r.z_dict_id |= u32::from(byte);
+                        l.counter += 1;
+                        Action::None
+                    })
+                } else {
+                    l.counter = 0;
+                    Action::End(TINFLStatus::NeedsDictionary)
+                }
+            }),
+
             // We are done.
             DoneForever => break TINFLStatus::Done,
 
@@ -2040,6 +2200,12 @@ pub fn decompress_with_limit(
         state = State::ReadBlockHeader;
     }
 
+    // The caller is expected to provide the dictionary before calling again, at which
+    // point we continue with the first block.
+    if status == TINFLStatus::NeedsDictionary {
+        state = State::ReadBlockHeader;
+    }
+
     // Make sure HasMoreOutput overrides NeedsMoreInput if the output buffer is full.
     // (Unless the missing input is the adler32 value in which case we don't need to write anything.)
     // TODO: May want to see if we can do this in a better way.
@@ -2,7 +2,13 @@
 
 #[cfg(feature = "with-alloc")]
 use crate::alloc::{boxed::Box, vec, vec::Vec};
-#[cfg(all(feature = "std", feature = "with-alloc"))]
+#[cfg(feature = "with-alloc")]
+use crate::gzip::GzHeaderParser;
+#[cfg(feature = "with-alloc")]
+use crate::shared::{update_crc32, MZ_CRC32_INIT};
+#[cfg(feature = "with-alloc")]
+use crate::DataFormat;
+#[cfg(feature = "std")]
 use std::error::Error;
 
 pub mod core;
@@ -12,6 +18,7 @@ pub mod stream;
 #[cfg(not(feature = "rustc-dep-of-std"))]
 use self::core::*;
 
+const TINFL_STATUS_CRC32_MISMATCH: i32 = -5;
 const TINFL_STATUS_FAILED_CANNOT_MAKE_PROGRESS: i32 = -4;
 const TINFL_STATUS_BAD_PARAM: i32 = -3;
 const TINFL_STATUS_ADLER32_MISMATCH: i32 = -2;
@@ -21,6 +28,7 @@ const TINFL_STATUS_NEEDS_MORE_INPUT: i32 = 1;
 const TINFL_STATUS_HAS_MORE_OUTPUT: i32 = 2;
 #[cfg(feature = "block-boundary")]
 const TINFL_STATUS_BLOCK_BOUNDARY: i32 = 3;
+const TINFL_STATUS_NEEDS_DICTIONARY: i32 = 4;
 
 /// Return status codes.
 #[repr(i8)]
@@ -45,6 +53,13 @@ pub enum TINFLStatus {
     /// provided in the header.
     Adler32Mismatch = TINFL_STATUS_ADLER32_MISMATCH as i8,
 
+    /// The decompression went fine, but the crc32 checksum did not match the one in the gzip
+    /// trailer.
+    ///
+    /// This is never returned by [`decompress()`][core::decompress], only when decompressing
+    /// gzip data.
+    Crc32Mismatch = TINFL_STATUS_CRC32_MISMATCH as i8,
+
     /// Failed to decompress due to invalid data.
     Failed = TINFL_STATUS_FAILED as i8,
 
@@ -77,6 +92,15 @@ pub enum TINFLStatus {
     /// [`TINFL_FLAG_STOP_ON_BLOCK_BOUNDARY`][core::inflate_flags::TINFL_FLAG_STOP_ON_BLOCK_BOUNDARY] flag.
     #[cfg(feature = "block-boundary")]
     BlockBoundary = TINFL_STATUS_BLOCK_BOUNDARY as i8,
+
+    /// The zlib header signals that a preset dictionary was used to compress the data.
+    ///
+    /// The id of the dictionary, which is its adler32 checksum, can be retrieved with
+    /// [`DecompressorOxide::dictionary_id()`][core::DecompressorOxide::dictionary_id].
+    /// Provide the dictionary by placing it in the output buffer before the current
+    /// position (or by using [`InflateState::set_dictionary`][stream::InflateState::set_dictionary]),
+    /// then call [`decompress()`][core::decompress] again to continue.
+    NeedsDictionary = TINFL_STATUS_NEEDS_DICTIONARY as i8,
 }
 
 impl TINFLStatus {
@@ -86,17 +110,74 @@ impl TINFLStatus {
             TINFL_STATUS_FAILED_CANNOT_MAKE_PROGRESS => Some(FailedCannotMakeProgress),
             TINFL_STATUS_BAD_PARAM => Some(BadParam),
             TINFL_STATUS_ADLER32_MISMATCH => Some(Adler32Mismatch),
+            TINFL_STATUS_CRC32_MISMATCH => Some(Crc32Mismatch),
             TINFL_STATUS_FAILED => Some(Failed),
             TINFL_STATUS_DONE => Some(Done),
             TINFL_STATUS_NEEDS_MORE_INPUT => Some(NeedsMoreInput),
             TINFL_STATUS_HAS_MORE_OUTPUT => Some(HasMoreOutput),
             #[cfg(feature = "block-boundary")]
             TINFL_STATUS_BLOCK_BOUNDARY => Some(BlockBoundary),
+            TINFL_STATUS_NEEDS_DICTIONARY => Some(NeedsDictionary),
             _ => None,
         }
     }
 }
 
+/// The reason decompression failed due to invalid data.
+///
+/// This gives more detail than [`TINFLStatus::Failed`] and the checksum mismatch statuses,
+/// see [`DecompressorOxide::data_error()`][core::DecompressorOxide::data_error] and
+/// [`InflateState::data_error`][stream::InflateState::data_error].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum DataError {
+    /// The zlib or gzip header is invalid.
+    InvalidHeader,
+    /// A block has the reserved block type 3.
+    InvalidBlockType,
+    /// The length of a stored block does not match its one's complement.
+    InvalidStoredLength,
+    /// A dynamic block uses too many length or distance symbols.
+    TooManySymbols,
+    /// The code lengths of a huffman table do not form a valid code.
+    InvalidCodeLengths,
+    /// A code length repeat code has nothing to repeat or repeats past the end of the table.
+    InvalidRepeat,
+    /// A literal/length code is not valid.
+    InvalidLiteralLength,
+    /// A distance code is not valid.
+    InvalidDistance,
+    /// A distance points back to before the start of the output.
+    DistanceTooFarBack,
+    /// The checksum of the data does not match the one in the zlib or gzip trailer.
+    ChecksumMismatch,
+    /// The length of the data does not match the one in the gzip trailer.
+    LengthMismatch,
+}
+
+impl ::core::fmt::Display for DataError {
+    #[cold]
+    fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
+        f.write_str(match self {
+            DataError::InvalidHeader => "incorrect header check",
+            DataError::InvalidBlockType => "invalid block type",
+            DataError::InvalidStoredLength => "invalid stored block lengths",
+            DataError::TooManySymbols => "too many length or distance symbols",
+            DataError::InvalidCodeLengths => "invalid code lengths set",
+            DataError::InvalidRepeat => "invalid bit length repeat",
+            DataError::InvalidLiteralLength => "invalid literal/length code",
+            DataError::InvalidDistance => "invalid distance code",
+            DataError::DistanceTooFarBack => "invalid distance too far back",
+            DataError::ChecksumMismatch => "incorrect data check",
+            DataError::LengthMismatch => "incorrect length check",
+        })
+    }
+}
+
+/// Implement Error trait only if std feature is requested as it requires std.
+#[cfg(feature = "std")]
+impl Error for DataError {}
+
 /// Struct return when decompress_to_vec functions fail.
 #[cfg(feature = "with-alloc")]
 #[derive(Debug)]
@@ -115,12 +196,14 @@ impl alloc::fmt::Display for DecompressError {
             TINFLStatus::FailedCannotMakeProgress => "Truncated input stream",
             TINFLStatus::BadParam => "Invalid output buffer size",
             TINFLStatus::Adler32Mismatch => "Adler32 checksum mismatch",
+            TINFLStatus::Crc32Mismatch => "Crc32 checksum mismatch",
             TINFLStatus::Failed => "Invalid input data",
             TINFLStatus::Done => "", // Unreachable
             TINFLStatus::NeedsMoreInput => "Truncated input stream",
             TINFLStatus::HasMoreOutput => "Output size exceeded the specified limit",
             #[cfg(feature = "block-boundary")]
             TINFLStatus::BlockBoundary => "Reached end of a deflate block",
+            TINFLStatus::NeedsDictionary => "A preset dictionary is required",
         })
     }
 }
@@ -130,7 +213,7 @@ impl alloc::fmt::Display for DecompressError {
 impl Error for DecompressError {}
 
 #[cfg(feature = "with-alloc")]
-fn decompress_error(status: TINFLStatus, output: Vec<u8>) -> Result<Vec<u8>, DecompressError> {
+fn decompress_error<T>(status: TINFLStatus, output: Vec<u8>) -> Result<T, DecompressError> {
     Err(DecompressError { status, output })
 }
 
@@ -144,7 +227,7 @@ fn decompress_error(status: TINFLStatus, output: Vec<u8>) -> Result<Vec<u8>, Dec
 #[inline]
 #[cfg(feature = "with-alloc")]
 pub fn decompress_to_vec(input: &[u8]) -> Result<Vec<u8>, DecompressError> {
-    decompress_to_vec_inner(input, 0, usize::MAX)
+    decompress_to_vec_inner(input, 0, usize::MAX).map(|(v, _)| v)
 }
 
 /// Decompress the deflate-encoded data (with a zlib wrapper) in `input` to a vector.
@@ -162,6 +245,40 @@ pub fn decompress_to_vec_zlib(input: &[u8]) -> Result<Vec<u8>, DecompressError>
         inflate_flags::TINFL_FLAG_PARSE_ZLIB_HEADER,
         usize::MAX,
     )
+    .map(|(v, _)| v)
+}
+
+/// Decompress the deflate-encoded data (with a gzip wrapper) in `input` to a vector.
+///
+/// Only the first gzip member is decompressed, and any data following it is ignored.
+/// Use [`InflateState`][stream::InflateState] to get the contents of the gzip header.
+///
+/// NOTE: This function will not bound the output, so if the output is large enough it can result in an out of memory error.
+/// It is therefore suggested to not use this for anything other than test programs, use the functions with a specified limit, or
+/// ideally streaming decompression via the [flate2](https://github.com/alexcrichton/flate2-rs) library instead.
+///
+/// Returns a [`Result`] containing the [`Vec`] of decompressed data on success, and a [struct][DecompressError] containing the status and so far decompressed data if any on failure.
+/// A crc32 mismatch is reported as [`TINFLStatus::Crc32Mismatch`].
+#[inline]
+#[cfg(feature = "with-alloc")]
+pub fn decompress_to_vec_gzip(input: &[u8]) -> Result<Vec<u8>, DecompressError> {
+    decompress_to_vec_gzip_inner(input, usize::MAX)
+}
+
+/// Decompress the deflate-encoded data in `input` to a vector, detecting whether it has a
+/// gzip or zlib wrapper or none as described in [`DataFormat::detect`].
+///
+/// For gzip, only the first member is decompressed, and any data following it is ignored.
+///
+/// NOTE: This function will not bound the output, so if the output is large enough it can result in an out of memory error.
+/// It is therefore suggested to not use this for anything other than test programs, use the functions with a specified limit, or
+/// ideally streaming decompression via the [flate2](https://github.com/alexcrichton/flate2-rs) library instead.
+///
+/// Returns a [`Result`] containing the [`Vec`] of decompressed data and the detected format on success, and a [struct][DecompressError] containing the status and so far decompressed data if any on failure.
+#[inline]
+#[cfg(feature = "with-alloc")]
+pub fn decompress_to_vec_auto(input: &[u8]) -> Result<(Vec<u8>, DataFormat), DecompressError> {
+    decompress_to_vec_auto_inner(input, usize::MAX)
 }
 
 /// Decompress the deflate-encoded data in `input` to a vector.
@@ -179,7 +296,7 @@ pub fn decompress_to_vec_with_limit(
     input: &[u8],
     max_size: usize,
 ) -> Result<Vec<u8>, DecompressError> {
-    decompress_to_vec_inner(input, 0, max_size)
+    decompress_to_vec_inner(input, 0, max_size).map(|(v, _)| v)
 }
 
 /// Decompress the deflate-encoded data (with a zlib wrapper) in `input` to a vector.
@@ -197,17 +314,109 @@ pub fn decompress_to_vec_zlib_with_limit(
     max_size: usize,
 ) -> Result<Vec<u8>, DecompressError> {
     decompress_to_vec_inner(input, inflate_flags::TINFL_FLAG_PARSE_ZLIB_HEADER, max_size)
+        .map(|(v, _)| v)
+}
+
+/// Decompress the deflate-encoded data (with a gzip wrapper) in `input` to a vector.
+/// The vector is grown to at most `max_size` bytes; if the data does not fit in that size,
+/// the error [struct][DecompressError] will contain the status [`TINFLStatus::HasMoreOutput`] and the data that was decompressed on failure.
+///
+/// As this function tries to decompress everything in one go, it's not ideal for general use outside of tests or where the output size is expected to be small.
+/// It is suggested to use streaming decompression via the [flate2](https://github.com/alexcrichton/flate2-rs) library instead.
+///
+/// Returns a [`Result`] containing the [`Vec`] of decompressed data on success, and a [struct][DecompressError] on failure.
+#[inline]
+#[cfg(feature = "with-alloc")]
+pub fn decompress_to_vec_gzip_with_limit(
+    input: &[u8],
+    max_size: usize,
+) -> Result<Vec<u8>, DecompressError> {
+    decompress_to_vec_gzip_inner(input, max_size)
+}
+
+/// Decompress the deflate-encoded data in `input` to a vector, detecting the wrapper as in
+/// [`decompress_to_vec_auto`].
+/// The vector is grown to at most `max_size` bytes; if the data does not fit in that size,
+/// the error [struct][DecompressError] will contain the status [`TINFLStatus::HasMoreOutput`] and the data that was decompressed on failure.
+///
+/// As this function tries to decompress everything in one go, it's not ideal for general use outside of tests or where the output size is expected to be small.
+/// It is suggested to use streaming decompression via the [flate2](https://github.com/alexcrichton/flate2-rs) library instead.
+///
+/// Returns a [`Result`] containing the [`Vec`] of decompressed data and the detected format on success, and a [struct][DecompressError] on failure.
+#[inline]
+#[cfg(feature = "with-alloc")]
+pub fn decompress_to_vec_auto_with_limit(
+    input: &[u8],
+    max_size: usize,
+) -> Result<(Vec<u8>, DataFormat), DecompressError> {
+    decompress_to_vec_auto_inner(input, max_size)
+}
+
+/// Backend of the auto-detecting to-[`Vec`] decompressions.
+#[cfg(feature = "with-alloc")]
+fn decompress_to_vec_auto_inner(
+    input: &[u8],
+    max_output_size: usize,
+) -> Result<(Vec<u8>, DataFormat), DecompressError> {
+    let format = match DataFormat::detect(input) {
+        Some(format) => format,
+        None => return decompress_error(TINFLStatus::FailedCannotMakeProgress, Vec::new()),
+    };
+    let ret = match format {
+        DataFormat::Gzip => decompress_to_vec_gzip_inner(input, max_output_size)?,
+        DataFormat::Zlib => {
+            let flags = inflate_flags::TINFL_FLAG_PARSE_ZLIB_HEADER;
+            decompress_to_vec_inner(input, flags, max_output_size)?.0
+        }
+        _ => decompress_to_vec_inner(input, 0, max_output_size)?.0,
+    };
+    Ok((ret, format))
+}
+
+/// Backend of the gzip to-[`Vec`] decompressions.
+///
+/// Parses the header, decompresses the deflate data and then checks the trailer.
+#[cfg(feature = "with-alloc")]
+fn decompress_to_vec_gzip_inner(
+    input: &[u8],
+    max_output_size: usize,
+) -> Result<Vec<u8>, DecompressError> {
+    let mut header = GzHeaderParser::new();
+    let header_len = match header.parse(input) {
+        Ok(n) if header.is_done() => n,
+        Ok(_) => return decompress_error(TINFLStatus::FailedCannotMakeProgress, Vec::new()),
+        Err(()) => return decompress_error(TINFLStatus::Failed, Vec::new()),
+    };
+
+    let (ret, body_len) = decompress_to_vec_inner(&input[header_len..], 0, max_output_size)?;
+
+    let trailer = match input[header_len + body_len..].get(..8) {
+        Some(t) => t,
+        None => return decompress_error(TINFLStatus::FailedCannotMakeProgress, ret),
+    };
+    let crc = u32::from_le_bytes([trailer[0], trailer[1], trailer[2], trailer[3]]);
+    let isize = u32::from_le_bytes([trailer[4], trailer[5], trailer[6], trailer[7]]);
+    if !cfg!(fuzzing) {
+        if crc != update_crc32(MZ_CRC32_INIT, &ret) {
+            return decompress_error(TINFLStatus::Crc32Mismatch, ret);
+        } else if isize != ret.len() as u32 {
+            return decompress_error(TINFLStatus::Failed, ret);
+        }
+    }
+    Ok(ret)
 }
 
 /// Backend of various to-[`Vec`] decompressions.
 ///
-/// Returns [`Vec`] of decompressed data on success and the [error struct][DecompressError] with details on failure.
+/// Returns [`Vec`] of decompressed data and the number of input bytes used on success and the
+/// [error struct][DecompressError] with details on failure.
 #[cfg(feature = "with-alloc")]
 fn decompress_to_vec_inner(
     mut input: &[u8],
     flags: u32,
     max_output_size: usize,
-) -> Result<Vec<u8>, DecompressError> {
+) -> Result<(Vec<u8>, usize), DecompressError> {
+    let input_len = input.len();
     let flags = flags | inflate_flags::TINFL_FLAG_USING_NON_WRAPPING_OUTPUT_BUF;
     let mut ret: Vec<u8> = vec![0; input.len().saturating_mul(2).min(max_output_size)];
 
@@ -224,7 +433,8 @@ fn decompress_to_vec_inner(
         match status {
             TINFLStatus::Done => {
                 ret.truncate(out_pos);
-                return Ok(ret);
+                let consumed = input_len - input.len() + in_consumed;
+                return Ok((ret, consumed));
             }
 
             TINFLStatus::HasMoreOutput => {
@@ -344,6 +554,42 @@ mod test {
         assert_eq!(res.as_slice(), &b"Hello, zlib!"[..]);
     }
 
+    #[test]
+    fn decompress_vec_auto() {
+        use super::{decompress_to_vec_auto, decompress_to_vec_auto_with_limit};
+        use crate::deflate::{compress_to_vec, compress_to_vec_gzip};
+        use crate::DataFormat;
+
+        let data = &b"Hello, zlib!"[..];
+        let gzip = compress_to_vec_gzip(data, 6);
+        let raw = compress_to_vec(data, 6);
+        for (encoded, format) in [
+            (&ENCODED[..], DataFormat::Zlib),
+            (&gzip[..], DataFormat::Gzip),
+            (&raw[..], DataFormat::Raw),
+        ] {
+            assert_eq!(DataFormat::detect(encoded), Some(format));
+            let (res, detected) = decompress_to_vec_auto(encoded).unwrap();
+            assert_eq!(res.as_slice(), data);
+            assert_eq!(detected, format);
+            let res = decompress_to_vec_auto_with_limit(encoded, 8);
+            assert!(matches!(
+                res,
+                Err(DecompressError {
+                    status: TINFLStatus::HasMoreOutput,
+                    ..
+                })
+            ));
+        }
+
+        assert_eq!(DataFormat::detect(&[]), None);
+        assert_eq!(DataFormat::detect(&[0x78]), None);
+        assert_eq!(DataFormat::detect(&[0x1F]), Some(DataFormat::Gzip));
+        // The zlib header check value is wrong.
+        assert_eq!(DataFormat::detect(&[0x78, 0x9D]), Some(DataFormat::Raw));
+        assert!(decompress_to_vec_auto(&[0x78]).is_err());
+    }
+
     #[test]
     fn decompress_vec_with_high_limit() {
         let res = decompress_to_vec_zlib_with_limit(&ENCODED[..], 100_000).unwrap();
@@ -2,11 +2,17 @@
 //!
 //! As of now this is mainly intended for use to build a higher-level wrapper.
 #[cfg(feature = "with-alloc")]
-use crate::alloc::boxed::Box;
+use crate::alloc::{boxed::Box, vec};
 use core::{cmp, mem};
 
+#[cfg(feature = "with-alloc")]
+use crate::gzip::GzHeader;
+use crate::gzip::GzHeaderParser;
+#[cfg(feature = "with-alloc")]
+use crate::inflate::core::TINFL_LZ_DICT_SIZE_DEFLATE64;
 use crate::inflate::core::{decompress, inflate_flags, DecompressorOxide, TINFL_LZ_DICT_SIZE};
-use crate::inflate::TINFLStatus;
+use crate::inflate::{DataError, TINFLStatus};
+use crate::shared::{update_adler32, update_crc32, MZ_ADLER32_INIT, MZ_CRC32_INIT};
 use crate::{DataFormat, MZError, MZFlush, MZResult, MZStatus, StreamResult};
 
 /// Tag that determines reset policy of [InflateState](struct.InflateState.html)
@@ -28,6 +34,15 @@ impl ResetPolicy for MinReset {
         state.first_call = true;
         state.has_flushed = false;
         state.last_status = TINFLStatus::NeedsMoreInput;
+        state.gzip = GzipState::new();
+        state.member_done = false;
+        state.members_finished = 0;
+        state.sync_progress = None;
+        state.synced = false;
+        if state.detect_format {
+            state.data_format = DataFormat::Auto;
+        }
+        state.detect_byte = None;
     }
 }
 
@@ -39,6 +54,10 @@ impl ResetPolicy for ZeroReset {
     fn reset(&self, state: &mut InflateState) {
         MinReset.reset(state);
         state.dict = [0; TINFL_LZ_DICT_SIZE];
+        #[cfg(feature = "with-alloc")]
+        if let Some(dict64) = state.dict64.as_mut() {
+            dict64.fill(0);
+        }
     }
 }
 
@@ -52,6 +71,42 @@ impl ResetPolicy for FullReset {
     fn reset(&self, state: &mut InflateState) {
         ZeroReset.reset(state);
         state.data_format = self.0;
+        state.detect_format = self.0 == DataFormat::Auto;
+    }
+}
+
+/// Which part of a gzip member is currently being read.
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum GzipStage {
+    Header,
+    Body,
+    Trailer,
+    Done,
+}
+
+/// State needed to handle the gzip wrapper.
+#[derive(Clone)]
+struct GzipState {
+    stage: GzipStage,
+    header: GzHeaderParser,
+    /// Checksum of the data output so far.
+    crc32: u32,
+    /// Length of the data output so far, modulo 2^32.
+    isize: u32,
+    trailer: [u8; 8],
+    trailer_len: usize,
+}
+
+impl GzipState {
+    fn new() -> Self {
+        GzipState {
+            stage: GzipStage::Header,
+            header: GzHeaderParser::new(),
+            crc32: MZ_CRC32_INIT,
+            isize: 0,
+            trailer: [0; 8],
+            trailer_len: 0,
+        }
     }
 }
 
@@ -72,6 +127,9 @@ pub struct InflateState {
     dict_ofs: usize,
     /// How many bytes of data to be flushed is there currently in the buffer?
     dict_avail: usize,
+    /// Larger buffer used instead of `dict` when decoding Deflate64.
+    #[cfg(feature = "with-alloc")]
+    dict64: Option<Box<[u8]>>,
 
     first_call: bool,
     has_flushed: bool,
@@ -79,7 +137,29 @@ pub struct InflateState {
     /// Whether the input data is wrapped in a zlib header and checksum.
     /// TODO: This should be stored in the decompressor.
     data_format: DataFormat,
+    /// Whether the format was set to [`DataFormat::Auto`], so it is detected again after a
+    /// reset.
+    detect_format: bool,
+    /// The first byte of the data, if it was not enough to detect the format.
+    detect_byte: Option<u8>,
     last_status: TINFLStatus,
+
+    /// State of the gzip wrapper, only used with [`DataFormat::Gzip`].
+    gzip: GzipState,
+
+    /// Whether to continue with the next member after the end of a gzip member or zlib stream.
+    multi_member: bool,
+    /// Whether the current member has been fully decompressed.
+    member_done: bool,
+    /// Number of members that have been fully decompressed.
+    members_finished: u32,
+
+    /// Number of bytes of the flush marker matched so far while searching with
+    /// [`inflate_sync`], or `None` if not searching.
+    sync_progress: Option<u8>,
+    /// Whether data was skipped with [`inflate_sync`], in which case the checksums can't
+    /// be verified.
+    synced: bool,
 }
 
 impl Default for InflateState {
@@ -89,10 +169,20 @@ impl Default for InflateState {
             dict: [0; TINFL_LZ_DICT_SIZE],
             dict_ofs: 0,
             dict_avail: 0,
+            #[cfg(feature = "with-alloc")]
+            dict64: None,
             first_call: true,
             has_flushed: false,
             data_format: DataFormat::Raw,
+            detect_format: false,
+            detect_byte: None,
             last_status: TINFLStatus::NeedsMoreInput,
+            gzip: GzipState::new(),
+            multi_member: false,
+            member_done: false,
+            members_finished: 0,
+            sync_progress: None,
+            synced: false,
         }
     }
 }
@@ -108,6 +198,7 @@ impl InflateState {
     pub fn new(data_format: DataFormat) -> InflateState {
         InflateState {
             data_format,
+            detect_format: data_format == DataFormat::Auto,
             ..Default::default()
         }
     }
@@ -121,6 +212,7 @@ impl InflateState {
     pub fn new_boxed(data_format: DataFormat) -> Box<InflateState> {
         let mut b: Box<InflateState> = Box::default();
         b.data_format = data_format;
+        b.detect_format = data_format == DataFormat::Auto;
         b
     }
 
@@ -129,21 +221,202 @@ impl InflateState {
         &mut self.decomp
     }
 
+    /// Get the format of the data being decompressed.
+    ///
+    /// With [`DataFormat::Auto`], this is `Auto` until enough of the data has been seen to
+    /// detect the format, and the detected format after that.
+    pub const fn data_format(&self) -> DataFormat {
+        self.data_format
+    }
+
     /// Return the status of the last call to `inflate` with this `InflateState`.
     pub const fn last_status(&self) -> TINFLStatus {
         self.last_status
     }
 
+    /// Get the header of the gzip stream being decompressed.
+    ///
+    /// Returns `None` if the data format is not gzip, or the whole header has not been
+    /// read yet.
+    #[cfg(feature = "with-alloc")]
+    pub fn gzip_header(&self) -> Option<&GzHeader> {
+        if self.data_format == DataFormat::Gzip {
+            self.gzip.header.header()
+        } else {
+            None
+        }
+    }
+
+    /// Get the crc32 checksum of the data decompressed so far.
+    ///
+    /// This is only computed when the data format is gzip.
+    pub const fn crc32(&self) -> u32 {
+        self.gzip.crc32
+    }
+
+    /// Get the reason the last call to [`inflate`] failed with [`MZError::Data`], if it was due to
+    /// invalid data.
+    ///
+    /// Returns `None` if there was no such failure, or if [`inflate_sync`] did not find a flush
+    /// point.
+    pub fn data_error(&self) -> Option<DataError> {
+        match self.last_status {
+            TINFLStatus::Adler32Mismatch | TINFLStatus::Crc32Mismatch => {
+                Some(DataError::ChecksumMismatch)
+            }
+            TINFLStatus::Failed if self.sync_progress.is_none() => {
+                self.decomp.data_error().or_else(|| {
+                    if self.data_format != DataFormat::Gzip {
+                        return None;
+                    }
+                    match self.gzip.stage {
+                        GzipStage::Header => Some(DataError::InvalidHeader),
+                        GzipStage::Trailer => Some(DataError::LengthMismatch),
+                        _ => None,
+                    }
+                })
+            }
+            _ => None,
+        }
+    }
+
+    /// Get the adler32 checksum of the preset dictionary the zlib header asks for.
+    ///
+    /// Returns `None` if the header has not been read yet, or does not have the FDICT flag set.
+    pub fn dictionary_id(&self) -> Option<u32> {
+        match self.data_format {
+            DataFormat::Zlib | DataFormat::ZLibIgnoreChecksum => self.decomp.dictionary_id(),
+            _ => None,
+        }
+    }
+
+    /// Provide a preset dictionary to decompress the stream with.
+    ///
+    /// For zlib streams this must be called after [`inflate`] has returned
+    /// [`MZStatus::NeedDict`], and the adler32 checksum of the dictionary has to match
+    /// [`dictionary_id`](Self::dictionary_id). For raw deflate streams it has to be called
+    /// before any data is decompressed. Only the last 32 KiB of the dictionary are used.
+    ///
+    /// # Errors
+    /// Returns [`MZError::Param`] if a dictionary can not be set at this point or the format is
+    /// gzip, and [`MZError::Data`] if the dictionary does not match the one the stream asks for.
+    pub fn set_dictionary(&mut self, dictionary: &[u8]) -> Result<(), MZError> {
+        match self.data_format {
+            DataFormat::Raw if self.first_call => (),
+            DataFormat::Zlib | DataFormat::ZLibIgnoreChecksum
+                if self.last_status == TINFLStatus::NeedsDictionary =>
+            {
+                if self.decomp.dictionary_id() != Some(update_adler32(MZ_ADLER32_INIT, dictionary))
+                {
+                    return Err(MZError::Data);
+                }
+            }
+            _ => return Err(MZError::Param),
+        }
+
+        let mut dict_ofs = self.dict_ofs;
+        let window = decomp_and_window(self).1;
+        let mask = window.len() - 1;
+        for &b in &dictionary[dictionary.len().saturating_sub(window.len())..] {
+            window[dict_ofs] = b;
+            dict_ofs = (dict_ofs + 1) & mask;
+        }
+        self.dict_ofs = dict_ofs;
+        // The dictionary is only in the internal buffer, so we can't write directly to the
+        // output buffer on the first call.
+        self.first_call = false;
+        self.last_status = TINFLStatus::NeedsMoreInput;
+        Ok(())
+    }
+
+    /// Insert the lowest `bits` bits of `value` into the input, to be read before the next
+    /// byte passed to [`inflate`].
+    ///
+    /// With a zlib or gzip header, the bits are read after the header.
+    /// See [`DecompressorOxide::prime`] for details.
+    ///
+    /// # Errors
+    /// Returns [`MZError::Param`] if `bits` is more than 16 or the stream has already ended,
+    /// and [`MZError::Buf`] if there is no room for the bits.
+    pub fn prime(&mut self, bits: u32, value: u32) -> Result<(), MZError> {
+        if self.last_status == TINFLStatus::Done {
+            return Err(MZError::Param);
+        }
+        self.decomp.prime(bits, value)
+    }
+
+    /// Enable or disable decoding Deflate64 (ZIP compression method 9) instead of deflate.
+    ///
+    /// Deflate64 needs a 64 KiB window, which is allocated when this is enabled.
+    /// The setting is kept when the state is reset.
+    ///
+    /// # Errors
+    /// Returns [`MZError::Param`] if decompression has already started.
+    #[cfg(feature = "with-alloc")]
+    pub fn set_deflate64(&mut self, enabled: bool) -> Result<(), MZError> {
+        if !self.first_call {
+            return Err(MZError::Param);
+        }
+        if !enabled {
+            self.dict64 = None;
+        } else if self.dict64.is_none() {
+            self.dict64 = Some(vec![0; TINFL_LZ_DICT_SIZE_DEFLATE64].into_boxed_slice());
+        }
+        Ok(())
+    }
+
+    /// Whether Deflate64 decoding is enabled.
+    pub fn deflate64(&self) -> bool {
+        #[cfg(feature = "with-alloc")]
+        {
+            self.dict64.is_some()
+        }
+        #[cfg(not(feature = "with-alloc"))]
+        {
+            false
+        }
+    }
+
+    /// Enable or disable decompressing multiple concatenated members.
+    ///
+    /// When enabled, [`inflate`] still stops and returns [`MZStatus::StreamEnd`] at the end of
+    /// each gzip member (or zlib stream, or raw deflate stream), with `bytes_consumed` ending at
+    /// the member boundary. The next call with input then continues with the next member
+    /// instead of returning [`MZStatus::StreamEnd`] again, while a call without input returns
+    /// [`MZError::Buf`] and leaves the state at the boundary.
+    ///
+    /// The setting is kept when the state is reset.
+    pub fn set_multi_member(&mut self, enabled: bool) {
+        self.multi_member = enabled;
+    }
+
+    /// Whether decompressing multiple concatenated members is enabled.
+    pub const fn multi_member(&self) -> bool {
+        self.multi_member
+    }
+
+    /// Number of members that have been fully decompressed since the state was created or reset.
+    pub const fn members_finished(&self) -> u32 {
+        self.members_finished
+    }
+
+    /// Whether the input so far ended exactly at the end of a member.
+    ///
+    /// In multi-member mode this can be used to check whether the end of the input is a valid
+    /// place for the data to end.
+    pub const fn at_member_boundary(&self) -> bool {
+        self.member_done
+    }
+
     /// Create a new state using miniz/zlib style window bits parameter.
     ///
     /// The decompressor does not support different window sizes. As such,
-    /// any positive (>0) value will set the zlib header flag, while a negative one
-    /// will not.
+    /// any value above 31 will detect the format, any value above 15 will set the gzip
+    /// format, any other positive (>0) value will set the zlib header flag, while a
+    /// negative one will not.
     #[cfg(feature = "with-alloc")]
     pub fn new_boxed_with_window_bits(window_bits: i32) -> Box<InflateState> {
-        let mut b: Box<InflateState> = Box::default();
-        b.data_format = DataFormat::from_window_bits(window_bits);
-        b
+        InflateState::new_boxed(DataFormat::from_window_bits(window_bits))
     }
 
     #[inline]
@@ -180,6 +453,10 @@ impl InflateState {
 /// Returns [`MZError::Data`] if this or a a previous call failed with an error return from
 /// [`TINFLStatus`]; probably indicates corrupted data.
 ///
+/// When decompressing gzip data, an invalid header, or a crc32 or length mismatch in the
+/// trailer also results in [`MZError::Data`]. A checksum mismatch is reported as
+/// [`TINFLStatus::Crc32Mismatch`] by [`InflateState::last_status`].
+///
 /// Returns [`MZError::Stream`] when called with [`MZFlush::Full`] (meaningless on
 /// decompression), or when called without [`MZFlush::Finish`] after an earlier call with
 /// [`MZFlush::Finish`] has been made.
@@ -188,6 +465,299 @@ pub fn inflate(
     input: &[u8],
     output: &mut [u8],
     flush: MZFlush,
+) -> StreamResult {
+    if state.member_done && state.multi_member {
+        // Stay at the boundary until there is input for the next member.
+        if input.is_empty() {
+            return StreamResult::error(MZError::Buf);
+        }
+        start_next_member(state);
+    }
+    if state.data_format == DataFormat::Auto {
+        match detect_format(state, input) {
+            Ok(()) => (),
+            Err(res) => return res,
+        }
+    }
+
+    let res = if state.data_format == DataFormat::Gzip {
+        inflate_gzip(state, input, output, flush)
+    } else {
+        inflate_deflate(state, input, output, flush)
+    };
+
+    if res.status == Ok(MZStatus::StreamEnd) && !state.member_done {
+        state.member_done = true;
+        state.members_finished = state.members_finished.wrapping_add(1);
+    }
+    res
+}
+
+/// Detect the format of the data for [`DataFormat::Auto`], and pass a byte held back from
+/// the previous call to the decompressor.
+///
+/// A single byte of input that is not enough to detect the format is consumed and kept, so
+/// the data can be passed one byte at a time. Returns the result to return from [`inflate`]
+/// if the format is still unknown.
+fn detect_format(state: &mut InflateState, input: &[u8]) -> Result<(), StreamResult> {
+    let format = match (state.detect_byte, input) {
+        (_, []) => return Err(StreamResult::error(MZError::Buf)),
+        (Some(first), &[second, ..]) => DataFormat::detect(&[first, second]),
+        (None, _) => DataFormat::detect(input),
+    };
+    let format = match format {
+        Some(format) => format,
+        None => {
+            state.detect_byte = Some(input[0]);
+            return Err(StreamResult {
+                bytes_consumed: 1,
+                bytes_written: 0,
+                status: Ok(MZStatus::Ok),
+            });
+        }
+    };
+    state.data_format = format;
+    if let Some(first) = state.detect_byte.take() {
+        // A single byte is never enough to produce any output, and gzip is detected from the
+        // first byte alone, so this only has to be passed on to the zlib or raw decompressor.
+        let res = inflate_deflate(state, &[first], &mut [], MZFlush::None);
+        if let Err(MZError::Data) = res.status {
+            return Err(StreamResult::error(MZError::Data));
+        }
+    }
+    Ok(())
+}
+
+/// Skip ahead in damaged data to the next full flush point, so decompression can continue
+/// from there.
+///
+/// Searches `input` for the empty stored block (`00 00 FF FF`) that ends a sync or full flush.
+/// The search includes whole bytes of input already buffered by the decompressor, and can be
+/// continued over several calls if the marker is split between them. When the marker is
+/// found, the decompressor is reset to read a new block header right after it, and
+/// [`inflate`] can be called again with the rest of the input.
+///
+/// Data after the flush point that refers back to data before it can not be decompressed
+/// correctly; this only works for data compressed with [`MZFlush::Full`] flushes. As the data
+/// in between is lost, checksums are not verified for the rest of the stream.
+///
+/// `bytes_consumed` is the number of bytes of `input` skipped, including the marker itself.
+///
+/// # Errors
+///
+/// Returns [`MZError::Data`] if no marker was found in `input`, in which case all of it was
+/// consumed. Call this again with more input to keep searching.
+///
+/// Returns [`MZError::Buf`] if `input` is empty and no input is buffered.
+pub fn inflate_sync(state: &mut InflateState, input: &[u8]) -> StreamResult {
+    let mut got = match state.sync_progress {
+        Some(got) => got,
+        None => {
+            let mut buffered = [0; 8];
+            let n = state.decomp.restart_at_block(&mut buffered);
+            if n == 0 && input.is_empty() {
+                return StreamResult::error(MZError::Buf);
+            }
+            let (_, got) = sync_search(0, &buffered[..n]);
+            got
+        }
+    };
+    let (bytes_consumed, found) = if got == 4 {
+        (0, true)
+    } else {
+        let (n, new_got) = sync_search(got, input);
+        got = new_got;
+        (n, got == 4)
+    };
+
+    if !found {
+        state.sync_progress = Some(got);
+        // Don't decompress from the middle of the data that is being skipped.
+        state.last_status = TINFLStatus::Failed;
+        return StreamResult {
+            bytes_consumed,
+            bytes_written: 0,
+            status: Err(MZError::Data),
+        };
+    }
+
+    state.sync_progress = None;
+    state.synced = true;
+    state.first_call = false;
+    state.member_done = false;
+    state.last_status = TINFLStatus::NeedsMoreInput;
+    if state.data_format == DataFormat::Gzip {
+        state.gzip.stage = GzipStage::Body;
+    }
+    StreamResult {
+        bytes_consumed,
+        bytes_written: 0,
+        status: Ok(MZStatus::Ok),
+    }
+}
+
+/// Search `data` for the `00 00 FF FF` flush marker, with `got` bytes of it matched already.
+///
+/// Returns the number of bytes looked at and the number of bytes of the marker matched, which
+/// is 4 if the whole marker was found.
+fn sync_search(mut got: u8, data: &[u8]) -> (usize, u8) {
+    let mut n = 0;
+    while n < data.len() && got < 4 {
+        let expected = if got < 2 { 0 } else { 0xFF };
+        if data[n] == expected {
+            got += 1;
+        } else if data[n] != 0 {
+            got = 0;
+        } else {
+            // Another zero, the zeros just before it can still be the start of a marker.
+            got = 4 - got;
+        }
+        n += 1;
+    }
+    (n, got)
+}
+
+/// Reset the state to decompress the next member, keeping the member count.
+fn start_next_member(state: &mut InflateState) {
+    let members_finished = state.members_finished;
+    let has_flushed = state.has_flushed;
+    MinReset.reset(state);
+    state.members_finished = members_finished;
+    // Keep requiring `MZFlush::Finish` if it has been used.
+    state.has_flushed = has_flushed;
+}
+
+/// Decompress a gzip member, parsing the header and checking the trailer, and passing the
+/// deflate data in between to [`inflate_deflate`].
+fn inflate_gzip(
+    state: &mut InflateState,
+    input: &[u8],
+    output: &mut [u8],
+    flush: MZFlush,
+) -> StreamResult {
+    if flush == MZFlush::Full {
+        return StreamResult::error(MZError::Stream);
+    }
+    if (state.last_status as i32) < 0 && state.last_status != TINFLStatus::FailedCannotMakeProgress
+    {
+        return StreamResult::error(MZError::Data);
+    }
+
+    let mut bytes_consumed = 0;
+    let mut bytes_written = 0;
+
+    if state.gzip.stage == GzipStage::Header {
+        match state.gzip.header.parse(input) {
+            Ok(n) => bytes_consumed += n,
+            Err(()) => {
+                state.last_status = TINFLStatus::Failed;
+                return StreamResult::error(MZError::Data);
+            }
+        }
+        if !state.gzip.header.is_done() {
+            return StreamResult {
+                bytes_consumed,
+                bytes_written,
+                status: if bytes_consumed == 0 || flush == MZFlush::Finish {
+                    Err(MZError::Buf)
+                } else {
+                    Ok(MZStatus::Ok)
+                },
+            };
+        }
+        state.gzip.stage = GzipStage::Body;
+        if bytes_consumed == input.len() && flush != MZFlush::Finish {
+            return StreamResult {
+                bytes_consumed,
+                bytes_written,
+                status: Ok(MZStatus::Ok),
+            };
+        }
+    }
+
+    if state.gzip.stage == GzipStage::Body {
+        let res = inflate_deflate(state, &input[bytes_consumed..], output, flush);
+        bytes_consumed += res.bytes_consumed;
+        bytes_written += res.bytes_written;
+        state.gzip.crc32 = update_crc32(state.gzip.crc32, &output[..res.bytes_written]);
+        state.gzip.isize = state.gzip.isize.wrapping_add(res.bytes_written as u32);
+        match res.status {
+            Ok(MZStatus::StreamEnd) => state.gzip.stage = GzipStage::Trailer,
+            Err(MZError::Buf) if bytes_consumed > 0 && flush != MZFlush::Finish => {
+                // The header was consumed in this call, so progress was made.
+                return StreamResult {
+                    bytes_consumed,
+                    bytes_written,
+                    status: Ok(MZStatus::Ok),
+                };
+            }
+            status => {
+                return StreamResult {
+                    bytes_consumed,
+                    bytes_written,
+                    status,
+                }
+            }
+        }
+    }
+
+    if state.gzip.stage == GzipStage::Trailer {
+        let gz = &mut state.gzip;
+        let n = cmp::min(8 - gz.trailer_len, input.len() - bytes_consumed);
+        gz.trailer[gz.trailer_len..gz.trailer_len + n]
+            .copy_from_slice(&input[bytes_consumed..bytes_consumed + n]);
+        gz.trailer_len += n;
+        bytes_consumed += n;
+
+        if gz.trailer_len < 8 {
+            let progress = bytes_consumed > 0 || bytes_written > 0;
+            return StreamResult {
+                bytes_consumed,
+                bytes_written,
+                status: if flush == MZFlush::Finish || !progress {
+                    Err(MZError::Buf)
+                } else {
+                    Ok(MZStatus::Ok)
+                },
+            };
+        }
+
+        let t = gz.trailer;
+        let crc = u32::from_le_bytes([t[0], t[1], t[2], t[3]]);
+        let isize = u32::from_le_bytes([t[4], t[5], t[6], t[7]]);
+        if !cfg!(fuzzing) && !state.synced {
+            if crc != gz.crc32 {
+                state.last_status = TINFLStatus::Crc32Mismatch;
+                return StreamResult {
+                    bytes_consumed,
+                    bytes_written,
+                    status: Err(MZError::Data),
+                };
+            } else if isize != gz.isize {
+                state.last_status = TINFLStatus::Failed;
+                return StreamResult {
+                    bytes_consumed,
+                    bytes_written,
+                    status: Err(MZError::Data),
+                };
+            }
+        }
+        gz.stage = GzipStage::Done;
+    }
+
+    StreamResult {
+        bytes_consumed,
+        bytes_written,
+        status: Ok(MZStatus::StreamEnd),
+    }
+}
+
+/// Decompress deflate data, with or without a zlib wrapper depending on the data format.
+fn inflate_deflate(
+    state: &mut InflateState,
+    input: &[u8],
+    output: &mut [u8],
+    flush: MZFlush,
 ) -> StreamResult {
     let mut bytes_consumed = 0;
     let mut bytes_written = 0;
@@ -198,7 +768,7 @@ pub fn inflate(
         return StreamResult::error(MZError::Stream);
     }
 
-    let mut decomp_flags = if state.data_format == DataFormat::Zlib {
+    let mut decomp_flags = if state.data_format == DataFormat::Zlib && !state.synced {
         inflate_flags::TINFL_FLAG_COMPUTE_ADLER32
     } else {
         inflate_flags::TINFL_FLAG_IGNORE_ADLER32
@@ -210,6 +780,10 @@ pub fn inflate(
         decomp_flags |= inflate_flags::TINFL_FLAG_PARSE_ZLIB_HEADER;
     }
 
+    if state.deflate64() {
+        decomp_flags |= inflate_flags::TINFL_FLAG_DEFLATE64;
+    }
+
     let first_call = state.first_call;
     state.first_call = false;
     if state.last_status == TINFLStatus::FailedCannotMakeProgress {
@@ -218,6 +792,14 @@ pub fn inflate(
     if (state.last_status as i32) < 0 {
         return StreamResult::error(MZError::Data);
     }
+    if state.last_status == TINFLStatus::NeedsDictionary {
+        // Can't continue until the dictionary is provided.
+        return StreamResult {
+            bytes_consumed,
+            bytes_written,
+            status: Ok(MZStatus::NeedDict),
+        };
+    }
 
     if state.has_flushed && (flush != MZFlush::Finish) {
         return StreamResult::error(MZError::Stream);
@@ -245,6 +827,8 @@ pub fn inflate(
                 Err(MZError::Buf)
             } else if (status as i32) < 0 {
                 Err(MZError::Data)
+            } else if status == TINFLStatus::NeedsDictionary {
+                Ok(MZStatus::NeedDict)
             } else if status != TINFLStatus::Done {
                 state.last_status = TINFLStatus::Failed;
                 Err(MZError::Buf)
@@ -305,13 +889,9 @@ fn inflate_loop(
 ) -> MZResult {
     let orig_in_len = next_in.len();
     loop {
-        let status = decompress(
-            &mut state.decomp,
-            next_in,
-            &mut state.dict,
-            state.dict_ofs,
-            decomp_flags,
-        );
+        let dict_ofs = state.dict_ofs;
+        let (decomp, window) = decomp_and_window(state);
+        let status = decompress(decomp, next_in, window, dict_ofs, decomp_flags);
 
         let in_bytes = status.1;
         let out_bytes = status.2;
@@ -333,6 +913,10 @@ fn inflate_loop(
         else if (status as i32) < 0 {
             return Err(MZError::Data);
         }
+        // The header asks for a preset dictionary.
+        else if status == TINFLStatus::NeedsDictionary {
+            return Ok(MZStatus::NeedDict);
+        }
 
         // The decompressor has flushed all it's data and is waiting for more input, but
         // there was no more input provided.
@@ -371,13 +955,25 @@ fn inflate_loop(
 
 fn push_dict_out(state: &mut InflateState, next_out: &mut &mut [u8]) -> usize {
     let n = cmp::min(state.dict_avail, next_out.len());
-    (next_out[..n]).copy_from_slice(&state.dict[state.dict_ofs..state.dict_ofs + n]);
+    let dict_ofs = state.dict_ofs;
+    let window = decomp_and_window(state).1;
+    let mask = window.len() - 1;
+    (next_out[..n]).copy_from_slice(&window[dict_ofs..dict_ofs + n]);
     *next_out = &mut mem::take(next_out)[n..];
     state.dict_avail -= n;
-    state.dict_ofs = (state.dict_ofs + (n)) & (TINFL_LZ_DICT_SIZE - 1);
+    state.dict_ofs = (dict_ofs + (n)) & mask;
     n
 }
 
+/// Get the decompressor and the buffer currently used as the sliding window.
+fn decomp_and_window(state: &mut InflateState) -> (&mut DecompressorOxide, &mut [u8]) {
+    #[cfg(feature = "with-alloc")]
+    if let Some(dict64) = state.dict64.as_deref_mut() {
+        return (&mut state.decomp, dict64);
+    }
+    (&mut state.decomp, &mut state.dict[..])
+}
+
 #[cfg(all(test, feature = "with-alloc"))]
 mod test {
     use super::{inflate, InflateState};
@@ -505,4 +1101,441 @@ mod test {
         );
         assert_eq!(resume.decompressor().adler32(), Some(459605011));
     }
+
+    #[test]
+    fn test_gzip() {
+        use crate::deflate::core::{compress, CompressionStrategy, CompressorOxide, TDEFLFlush};
+        use crate::gzip::GzHeader;
+        use crate::inflate::TINFLStatus;
+
+        let data = b"Hello, gzip! Hello, gzip! Hello, gzip!";
+        let header = GzHeader {
+            mtime: 7,
+            os: 3,
+            filename: Some(b"hello.txt".to_vec()),
+            comment: Some(b"greeting".to_vec()),
+            hcrc: true,
+            ..GzHeader::default()
+        };
+        let mut compressor =
+            CompressorOxide::with_params(DataFormat::Gzip, 6, CompressionStrategy::Default, 15);
+        compressor.set_gzip_header(header.clone()).unwrap();
+        let mut encoded = vec![0; 200];
+        let (_, _, len) = compress(&mut compressor, data, &mut encoded, TDEFLFlush::Finish);
+        encoded.truncate(len);
+
+        // Feed input bytes one at a time to the decompressor.
+        let mut out = vec![0; 50];
+        let mut state = InflateState::new_boxed(DataFormat::Gzip);
+        let mut part_in = 0;
+        let mut part_out = 0;
+        for i in 1..=encoded.len() {
+            assert!(state.gzip_header().is_none() || i > 10);
+            let res = inflate(
+                &mut state,
+                &encoded[part_in..i],
+                &mut out[part_out..],
+                MZFlush::None,
+            );
+            let status = res.status.expect("Failed to decompress!");
+            if i == encoded.len() {
+                assert_eq!(status, MZStatus::StreamEnd);
+            } else {
+                assert_eq!(status, MZStatus::Ok);
+            }
+            part_out += res.bytes_written;
+            part_in += res.bytes_consumed;
+        }
+        assert_eq!(&out[..part_out], &data[..]);
+        assert_eq!(part_in, encoded.len());
+        assert_eq!(state.crc32(), crate::mz_crc32_oxide(0, data));
+        let parsed = state.gzip_header().unwrap();
+        assert_eq!(parsed.filename, header.filename);
+        assert_eq!(parsed.comment, header.comment);
+        assert_eq!(parsed.mtime, 7);
+        assert_eq!(parsed.os, 3);
+
+        // All in one go, with trailing data that should not be consumed.
+        state.reset_as(super::MinReset);
+        assert!(state.gzip_header().is_none());
+        let mut with_trailing = encoded.clone();
+        with_trailing.extend_from_slice(b"junk");
+        let res = inflate(&mut state, &with_trailing, &mut out, MZFlush::Finish);
+        assert_eq!(res.status, Ok(MZStatus::StreamEnd));
+        assert_eq!(res.bytes_consumed, encoded.len());
+        assert_eq!(&out[..res.bytes_written], &data[..]);
+
+        // Corrupt the crc32 in the trailer.
+        let crc_pos = encoded.len() - 8;
+        encoded[crc_pos] ^= 0xFF;
+        state.reset_as(super::MinReset);
+        let res = inflate(&mut state, &encoded, &mut out, MZFlush::Finish);
+        assert_eq!(res.status, Err(crate::MZError::Data));
+        assert_eq!(state.last_status(), TINFLStatus::Crc32Mismatch);
+
+        // Missing trailer.
+        state.reset_as(super::MinReset);
+        let res = inflate(
+            &mut state,
+            &encoded[..crc_pos + 3],
+            &mut out,
+            MZFlush::Finish,
+        );
+        assert_eq!(res.status, Err(crate::MZError::Buf));
+    }
+
+    #[test]
+    fn test_auto_format() {
+        use crate::deflate::{compress_to_vec, compress_to_vec_gzip, compress_to_vec_zlib};
+
+        let data = b"Hello, detected world!";
+        let gzip = compress_to_vec_gzip(data, 6);
+        let zlib = compress_to_vec_zlib(data, 6);
+        let raw = compress_to_vec(data, 6);
+        let mut out = vec![0; 50];
+        let mut state = InflateState::new_boxed(DataFormat::Auto);
+        let res = inflate(&mut state, &[], &mut out, MZFlush::None);
+        assert_eq!(res.status, Err(crate::MZError::Buf));
+        assert_eq!(state.data_format(), DataFormat::Auto);
+
+        for (encoded, format) in [
+            (&gzip, DataFormat::Gzip),
+            (&zlib, DataFormat::Zlib),
+            (&raw, DataFormat::Raw),
+        ] {
+            let res = inflate(&mut state, encoded, &mut out, MZFlush::Finish);
+            assert_eq!(res.status, Ok(MZStatus::StreamEnd));
+            assert_eq!(&out[..res.bytes_written], &data[..]);
+            assert_eq!(state.data_format(), format);
+            // Resetting detects the format again.
+            state.reset_as(super::MinReset);
+            assert_eq!(state.data_format(), DataFormat::Auto);
+
+            // The format can also be detected when the data is passed one byte at a time.
+            let mut written = 0;
+            for (i, &b) in encoded.iter().enumerate() {
+                let res = inflate(&mut state, &[b], &mut out[written..], MZFlush::None);
+                assert_eq!(res.bytes_consumed, 1);
+                written += res.bytes_written;
+                let end = i + 1 == encoded.len();
+                assert_eq!(res.status == Ok(MZStatus::StreamEnd), end);
+                assert!(res.status.is_ok());
+            }
+            assert_eq!(&out[..written], &data[..]);
+            assert_eq!(state.data_format(), format);
+            state.reset_as(super::MinReset);
+        }
+
+        // A gzip header is detected from the first byte.
+        let res = inflate(&mut state, &gzip[..1], &mut out, MZFlush::None);
+        assert_eq!(res.bytes_consumed, 1);
+        assert_eq!(state.data_format(), DataFormat::Gzip);
+
+        assert_eq!(DataFormat::from_window_bits(47), DataFormat::Auto);
+        assert_eq!(DataFormat::Auto.to_window_bits(), 47);
+        state.reset(DataFormat::Zlib);
+        assert_eq!(state.data_format(), DataFormat::Zlib);
+    }
+
+    #[test]
+    fn test_multi_member() {
+        use crate::deflate::{compress_to_vec_gzip, compress_to_vec_zlib};
+
+        for format in [DataFormat::Gzip, DataFormat::Zlib] {
+            let compress = |data: &[u8]| match format {
+                DataFormat::Gzip => compress_to_vec_gzip(data, 6),
+                _ => compress_to_vec_zlib(data, 6),
+            };
+            let first = compress(b"first member, ");
+            let second = compress(b"second member");
+            let mut encoded = first.clone();
+            encoded.extend_from_slice(&second);
+
+            // Without multi-member mode only the first member is decompressed.
+            let mut out = vec![0; 50];
+            let mut state = InflateState::new_boxed(format);
+            let res = inflate(&mut state, &encoded, &mut out, MZFlush::None);
+            assert_eq!(res.status, Ok(MZStatus::StreamEnd));
+            assert_eq!(res.bytes_consumed, first.len());
+            let res = inflate(&mut state, &encoded[first.len()..], &mut out, MZFlush::None);
+            assert_eq!(res.status, Ok(MZStatus::StreamEnd));
+            assert_eq!(res.bytes_consumed, 0);
+            assert_eq!(state.members_finished(), 1);
+
+            state.reset(format);
+            state.set_multi_member(true);
+            let mut part_in = 0;
+            let mut part_out = 0;
+            let mut boundaries = vec![];
+            while part_in < encoded.len() {
+                let res = inflate(
+                    &mut state,
+                    &encoded[part_in..],
+                    &mut out[part_out..],
+                    MZFlush::None,
+                );
+                part_in += res.bytes_consumed;
+                part_out += res.bytes_written;
+                if res.status.expect("Failed to decompress!") == MZStatus::StreamEnd {
+                    assert!(state.at_member_boundary());
+                    boundaries.push((part_in, part_out));
+                }
+            }
+            assert_eq!(&out[..part_out], b"first member, second member");
+            assert_eq!(boundaries, [(first.len(), 14), (encoded.len(), 27)]);
+            assert_eq!(state.members_finished(), 2);
+
+            // No more members.
+            let res = inflate(&mut state, &[], &mut out, MZFlush::None);
+            assert_eq!(res.status, Err(crate::MZError::Buf));
+            assert!(state.multi_member());
+            assert!(state.at_member_boundary());
+            assert_eq!(state.members_finished(), 2);
+        }
+    }
+
+    #[test]
+    fn test_dictionary() {
+        use crate::deflate::core::{
+            compress, CompressionStrategy, CompressorOxide, TDEFLFlush, TDEFLStatus,
+        };
+        use crate::shared::update_adler32;
+        use crate::{MZError, MZFlush};
+
+        let dict = b"A dictionary with words that are also found in the data to compress.";
+        let data = b"Words found in the data, the dictionary and the data to compress.";
+        let compress_with_dict = |format| {
+            let mut d = CompressorOxide::with_params(format, 6, CompressionStrategy::Default, 15);
+            d.set_dictionary(dict).unwrap();
+            let mut encoded = vec![0; 256];
+            let (status, _, n) = compress(&mut d, data, &mut encoded, TDEFLFlush::Finish);
+            assert_eq!(status, TDEFLStatus::Done);
+            encoded.truncate(n);
+            encoded
+        };
+
+        // Zlib, both when writing directly to the output on the first call and when using
+        // the internal buffer.
+        let encoded = compress_with_dict(DataFormat::Zlib);
+        for flush in [MZFlush::Finish, MZFlush::None] {
+            let mut out = vec![0; 256];
+            let mut state = InflateState::new_boxed(DataFormat::Zlib);
+            let res = inflate(&mut state, &encoded, &mut out, flush);
+            assert_eq!(res.status, Ok(MZStatus::NeedDict));
+            assert_eq!(res.bytes_written, 0);
+            assert_eq!(state.dictionary_id(), Some(update_adler32(1, dict)));
+            // Calling again without a dictionary keeps asking for it.
+            let again = inflate(&mut state, &encoded[res.bytes_consumed..], &mut out, flush);
+            assert_eq!(again.status, Ok(MZStatus::NeedDict));
+
+            assert_eq!(state.set_dictionary(b"wrong"), Err(MZError::Data));
+            state.set_dictionary(dict).unwrap();
+            let res2 = inflate(&mut state, &encoded[res.bytes_consumed..], &mut out, flush);
+            assert_eq!(res2.status, Ok(MZStatus::StreamEnd));
+            assert_eq!(&out[..res2.bytes_written], &data[..]);
+            assert_eq!(res.bytes_consumed + res2.bytes_consumed, encoded.len());
+            // Can't set it again now.
+            assert_eq!(state.set_dictionary(dict), Err(MZError::Param));
+        }
+
+        // Raw deflate, the dictionary has to be provided up front.
+        let encoded = compress_with_dict(DataFormat::Raw);
+        let mut out = vec![0; 256];
+        let mut state = InflateState::new_boxed(DataFormat::Raw);
+        state.set_dictionary(dict).unwrap();
+        let res = inflate(&mut state, &encoded, &mut out, MZFlush::Finish);
+        assert_eq!(res.status, Ok(MZStatus::StreamEnd));
+        assert_eq!(&out[..res.bytes_written], &data[..]);
+        assert_eq!(state.set_dictionary(dict), Err(MZError::Param));
+        assert_eq!(state.dictionary_id(), None);
+    }
+
+    /// Compress `chunks`, with a full flush after each one but the last.
+    ///
+    /// Returns the data and the offsets just after each flush marker.
+    fn compress_with_full_flushes(
+        chunks: &[&[u8]],
+        format: DataFormat,
+    ) -> (alloc::vec::Vec<u8>, alloc::vec::Vec<usize>) {
+        use crate::deflate::core::CompressorOxide;
+        use crate::deflate::stream::deflate;
+        use crate::deflate::CompressionLevel;
+
+        let mut compressor =
+            CompressorOxide::with_format_and_level(format, CompressionLevel::DefaultLevel);
+        let mut out = vec![0; 4096];
+        let mut pos = 0;
+        let mut flush_points = vec![];
+        for (i, chunk) in chunks.iter().enumerate() {
+            let flush = if i + 1 == chunks.len() {
+                MZFlush::Finish
+            } else {
+                MZFlush::Full
+            };
+            let res = deflate(&mut compressor, chunk, &mut out[pos..], flush);
+            assert!(res.status.is_ok());
+            pos += res.bytes_written;
+            if flush == MZFlush::Full {
+                assert_eq!(out[pos - 4..pos], [0, 0, 0xFF, 0xFF]);
+                flush_points.push(pos);
+            }
+        }
+        out.truncate(pos);
+        (out, flush_points)
+    }
+
+    #[test]
+    fn test_sync() {
+        use super::inflate_sync;
+        use crate::MZError;
+
+        let chunks: [&[u8]; 3] = [
+            b"The first chunk of data, which will be damaged. ",
+            b"The second chunk, after the first full flush. ",
+            b"And the third chunk, after the second one.",
+        ];
+        for format in [DataFormat::Zlib, DataFormat::Gzip, DataFormat::Raw] {
+            let (encoded, flush_points) = compress_with_full_flushes(&chunks, format);
+
+            // Lose some data in the middle of the first chunk.
+            let mut out = vec![0; 200];
+            let mut state = InflateState::new_boxed(format);
+            let res = inflate(&mut state, &encoded[..20], &mut out, MZFlush::None);
+            assert!(res.status.is_ok());
+            let res = inflate_sync(&mut state, &encoded[25..]);
+            assert_eq!(res.status, Ok(MZStatus::Ok));
+            assert_eq!(25 + res.bytes_consumed, flush_points[0]);
+
+            let res = inflate(
+                &mut state,
+                &encoded[flush_points[0]..],
+                &mut out,
+                MZFlush::Finish,
+            );
+            assert_eq!(res.status, Ok(MZStatus::StreamEnd));
+            assert_eq!(
+                out[..res.bytes_written],
+                [chunks[1], chunks[2]].concat()[..]
+            );
+
+            // The marker can be split over several calls.
+            let mut state = InflateState::new_boxed(format);
+            let mut pos = flush_points[0] + 1;
+            loop {
+                let res = inflate_sync(&mut state, &encoded[pos..pos + 1]);
+                assert_eq!(res.bytes_consumed, 1);
+                pos += 1;
+                match res.status {
+                    Ok(_) => break,
+                    status => assert_eq!(status, Err(MZError::Data)),
+                }
+            }
+            assert_eq!(pos, flush_points[1]);
+            let res = inflate(&mut state, &encoded[pos..], &mut out, MZFlush::Finish);
+            assert_eq!(res.status, Ok(MZStatus::StreamEnd));
+            assert_eq!(&out[..res.bytes_written], chunks[2]);
+        }
+
+        let mut state = InflateState::new_boxed(DataFormat::Raw);
+        assert_eq!(inflate_sync(&mut state, &[]).status, Err(MZError::Buf));
+        let res = inflate_sync(&mut state, &[1, 2, 0, 0, 0xFF]);
+        assert_eq!(res.status, Err(MZError::Data));
+        assert_eq!(res.bytes_consumed, 5);
+        let mut out = [0; 10];
+        assert_eq!(
+            inflate(&mut state, &[3, 0], &mut out, MZFlush::None).status,
+            Err(MZError::Data)
+        );
+        assert_eq!(inflate_sync(&mut state, &[0xFF, 3, 0]).bytes_consumed, 1);
+        let res = inflate(&mut state, &[3, 0], &mut out, MZFlush::Finish);
+        assert_eq!(res.status, Ok(MZStatus::StreamEnd));
+    }
+
+    #[test]
+    fn test_prime() {
+        use crate::deflate::{compress_to_vec, compress_to_vec_zlib};
+        use crate::MZError;
+
+        let data = b"Hello, primed world! Hello, primed world!";
+
+        // Start the raw stream 3 bits into the first byte.
+        let encoded = compress_to_vec(data, 6);
+        let mut state = InflateState::new_boxed(DataFormat::Raw);
+        assert_eq!(state.prime(17, 0), Err(MZError::Param));
+        state.prime(3, u32::from(encoded[0])).unwrap();
+        state.prime(5, u32::from(encoded[0] >> 3)).unwrap();
+        let mut out = vec![0; 100];
+        let res = inflate(&mut state, &encoded[1..], &mut out, MZFlush::Finish);
+        assert_eq!(res.status, Ok(MZStatus::StreamEnd));
+        assert_eq!(&out[..res.bytes_written], &data[..]);
+        assert_eq!(state.prime(1, 0), Err(MZError::Param));
+
+        // The bits are read after the zlib header.
+        let encoded = compress_to_vec_zlib(data, 6);
+        let mut state = InflateState::new_boxed(DataFormat::Zlib);
+        state.prime(16, u32::from(encoded[2]) | 0xAB00).unwrap();
+        state.prime(16, 0).unwrap();
+        assert_eq!(state.prime(1, 0), Err(MZError::Buf));
+        state.reset(DataFormat::Zlib);
+        state.prime(8, u32::from(encoded[2]) | 0xAB00).unwrap();
+        let input = [&encoded[..2], &encoded[3..]].concat();
+        let res = inflate(&mut state, &input, &mut out, MZFlush::Finish);
+        assert_eq!(res.status, Ok(MZStatus::StreamEnd));
+        assert_eq!(&out[..res.bytes_written], &data[..]);
+    }
+
+    #[test]
+    fn test_data_error() {
+        use crate::deflate::{compress_to_vec_gzip, compress_to_vec_zlib};
+        use crate::inflate::DataError;
+
+        fn inflate_error(format: DataFormat, data: &[u8]) -> Option<DataError> {
+            let mut state = InflateState::new_boxed(format);
+            let mut out = vec![0; 200];
+            let res = inflate(&mut state, data, &mut out, MZFlush::Finish);
+            assert_eq!(res.status.is_err(), state.data_error().is_some());
+            state.data_error()
+        }
+
+        let zlib = compress_to_vec_zlib(b"Hello, hello, hello!", 6);
+        assert_eq!(inflate_error(DataFormat::Zlib, &zlib), None);
+        let mut bad = zlib.clone();
+        *bad.last_mut().unwrap() ^= 1;
+        assert_eq!(
+            inflate_error(DataFormat::Zlib, &bad),
+            Some(DataError::ChecksumMismatch)
+        );
+        assert_eq!(
+            inflate_error(DataFormat::Zlib, &[0x78, 0x98, 0, 0]),
+            Some(DataError::InvalidHeader)
+        );
+        assert_eq!(
+            inflate_error(DataFormat::Raw, &[6]),
+            Some(DataError::InvalidBlockType)
+        );
+        assert_eq!(
+            inflate_error(DataFormat::Raw, &[2, 0x7e, 0xff, 0xff]),
+            Some(DataError::InvalidDistance)
+        );
+
+        let gzip = compress_to_vec_gzip(b"Hello, hello, hello!", 6);
+        let mut bad = gzip.clone();
+        bad[0] = 0;
+        assert_eq!(
+            inflate_error(DataFormat::Gzip, &bad),
+            Some(DataError::InvalidHeader)
+        );
+        let mut bad = gzip.clone();
+        let len = bad.len();
+        bad[len - 4] ^= 1;
+        assert_eq!(
+            inflate_error(DataFormat::Gzip, &bad),
+            Some(DataError::LengthMismatch)
+        );
+        bad[len - 8] ^= 1;
+        assert_eq!(
+            inflate_error(DataFormat::Gzip, &bad),
+            Some(DataError::ChecksumMismatch)
+        );
+    }
 }
@@ -0,0 +1,154 @@
+//! Decompression of data read from a [`BufRead`] implementation.
+
+use std::io::{BufRead, Read, Result};
+
+use crate::alloc::boxed::Box;
+use crate::inflate::stream::InflateState;
+use crate::DataFormat;
+
+use super::state::Decoder;
+
+/// Reader that decompresses the data read from a buffered reader.
+///
+/// Only the compressed data is consumed from the underlying reader, so any data following
+/// the end of the stream can still be read from it with [`get_mut`](Self::get_mut) or
+/// [`into_inner`](Self::into_inner). Reading from the decoder returns 0 at the end of the
+/// stream.
+///
+/// Only the first gzip member (or zlib or raw stream) is decompressed by default. Enable
+/// [`InflateState::set_multi_member`] through [`state_mut`](Self::state_mut) to decompress
+/// concatenated members as one stream.
+///
+/// Invalid data results in an error of kind [`ErrorKind::InvalidData`](std::io::ErrorKind::InvalidData), and data ending
+/// before the end of the stream in one of kind [`ErrorKind::UnexpectedEof`](std::io::ErrorKind::UnexpectedEof).
+pub struct DeflateDecoder<R: BufRead> {
+    inner: R,
+    decoder: Decoder,
+}
+
+impl<R: BufRead> DeflateDecoder<R> {
+    /// Create a decoder reading data in the given format from `inner`.
+    ///
+    /// [`DataFormat::Auto`] detects whether the data is zlib, gzip or raw deflate.
+    pub fn new(inner: R, format: DataFormat) -> DeflateDecoder<R> {
+        DeflateDecoder::with_state(inner, InflateState::new_boxed(format))
+    }
+
+    /// Create a decoder using a decompression state that has already been set up.
+    pub fn with_state(inner: R, state: Box<InflateState>) -> DeflateDecoder<R> {
+        DeflateDecoder {
+            inner,
+            decoder: Decoder::new(state),
+        }
+    }
+
+    /// Get a reference to the underlying reader.
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Get a mutable reference to the underlying reader.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    /// Return the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    /// Get a reference to the decompression state, e.g. to look at the gzip header.
+    pub fn state(&self) -> &InflateState {
+        &self.decoder.state
+    }
+
+    /// Get a mutable reference to the decompression state, e.g. to set a preset dictionary
+    /// after reading failed with [`ErrorKind::InvalidInput`](std::io::ErrorKind::InvalidInput) because one is needed.
+    pub fn state_mut(&mut self) -> &mut InflateState {
+        &mut self.decoder.state
+    }
+
+    /// Number of compressed bytes consumed so far.
+    pub fn total_in(&self) -> u64 {
+        self.decoder.total_in
+    }
+
+    /// Number of decompressed bytes produced so far.
+    pub fn total_out(&self) -> u64 {
+        self.decoder.total_out
+    }
+}
+
+impl<R: BufRead> Read for DeflateDecoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        loop {
+            let input = self.inner.fill_buf()?;
+            let (consumed, res) = self.decoder.read(input, buf);
+            self.inner.consume(consumed);
+            if let Some(n) = res? {
+                return Ok(n);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::DeflateDecoder;
+    use crate::deflate::{compress_to_vec, compress_to_vec_zlib};
+    use crate::inflate::stream::InflateState;
+    use crate::inflate::DataError;
+    use crate::DataFormat;
+    use std::io::{BufReader, ErrorKind, Read};
+
+    #[test]
+    fn trailing_data_is_left() {
+        let mut input = compress_to_vec_zlib(b"Hello, world!", 6);
+        input.extend_from_slice(b"trailer");
+        let reader = BufReader::with_capacity(5, &input[..]);
+        let mut decoder = DeflateDecoder::new(reader, DataFormat::Auto);
+        let mut data = Vec::new();
+        decoder.read_to_end(&mut data).unwrap();
+        assert_eq!(data, b"Hello, world!");
+        assert_eq!(decoder.read(&mut [0; 16]).unwrap(), 0);
+        assert_eq!(decoder.state().data_format(), DataFormat::Zlib);
+        assert_eq!(decoder.total_in(), input.len() as u64 - 7);
+        assert_eq!(decoder.total_out(), 13);
+
+        let mut rest = Vec::new();
+        decoder.into_inner().read_to_end(&mut rest).unwrap();
+        assert_eq!(rest, b"trailer");
+    }
+
+    #[test]
+    fn truncated_and_invalid() {
+        let input = compress_to_vec(&[7; 1000], 6);
+        let mut decoder = DeflateDecoder::new(&input[..input.len() - 1], DataFormat::Raw);
+        let err = decoder.read_to_end(&mut Vec::new()).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::UnexpectedEof);
+
+        let mut input = compress_to_vec_zlib(&[7; 1000], 6);
+        let last = input.len() - 1;
+        input[last] ^= 1;
+        let mut decoder = DeflateDecoder::new(&input[..], DataFormat::Zlib);
+        let err = decoder.read_to_end(&mut Vec::new()).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        assert_eq!(
+            err.get_ref().unwrap().downcast_ref::<DataError>(),
+            Some(&DataError::ChecksumMismatch)
+        );
+    }
+
+    #[test]
+    fn multi_member() {
+        let mut input = compress_to_vec(b"one ", 6);
+        input.extend_from_slice(&compress_to_vec(b"two", 6));
+        let mut state = InflateState::new_boxed(DataFormat::Raw);
+        state.set_multi_member(true);
+        let mut decoder = DeflateDecoder::with_state(&input[..], state);
+        let mut data = Vec::new();
+        decoder.read_to_end(&mut data).unwrap();
+        assert_eq!(data, b"one two");
+        assert_eq!(decoder.state().members_finished(), 2);
+    }
+}
@@ -0,0 +1,319 @@
+//! Async compression and decompression through the [`futures_io`] traits.
+//!
+//! These work the same way as the blocking [`write::DeflateEncoder`](super::write::DeflateEncoder)
+//! and [`bufread::DeflateDecoder`](super::bufread::DeflateDecoder). Each poll compresses at
+//! most 32 KiB of input, or decompresses into the given buffer, so no single poll does an
+//! unbounded amount of work.
+//!
+//! To decompress from a reader that only implements [`AsyncRead`], wrap it in a buffered
+//! reader such as `futures::io::BufReader` first.
+
+use core::pin::Pin;
+use core::task::{Context, Poll};
+use std::io::Result;
+
+use futures_io::{AsyncBufRead, AsyncRead, AsyncWrite};
+
+use crate::alloc::boxed::Box;
+use crate::deflate::core::CompressorOxide;
+use crate::inflate::stream::InflateState;
+use crate::DataFormat;
+
+use super::state::{ready, Decoder, Encoder};
+
+/// Async writer that compresses the data written to it and writes the compressed data to
+/// another writer.
+///
+/// Flushing compresses everything written so far as with
+/// [`MZFlush::Sync`](crate::MZFlush::Sync) before flushing the underlying writer. The stream
+/// must be ended by closing the encoder with [`AsyncWrite::poll_close`], which writes the
+/// end of the stream and then closes the underlying writer. Unlike the blocking encoder, this
+/// is not done when the encoder is dropped.
+pub struct DeflateEncoder<W: AsyncWrite + Unpin> {
+    inner: W,
+    encoder: Encoder,
+}
+
+impl<W: AsyncWrite + Unpin> DeflateEncoder<W> {
+    /// Create an encoder writing data in the given format, compressed with the given level
+    /// (0-10), to `inner`.
+    ///
+    /// [`DataFormat::Auto`] has no meaning when compressing and produces zlib data.
+    pub fn new(inner: W, level: u8, format: DataFormat) -> DeflateEncoder<W> {
+        let mut compressor = Box::<CompressorOxide>::default();
+        compressor.set_format_and_level(format, level);
+        DeflateEncoder::with_compressor(inner, compressor)
+    }
+
+    /// Create an encoder using a compressor that has already been set up, e.g. with a
+    /// preset dictionary or a gzip header.
+    pub fn with_compressor(
+        inner: W,
+        compressor: Box<CompressorOxide<'static>>,
+    ) -> DeflateEncoder<W> {
+        DeflateEncoder {
+            inner,
+            encoder: Encoder::new(compressor),
+        }
+    }
+
+    /// Get a reference to the underlying writer.
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    /// Get a mutable reference to the underlying writer.
+    ///
+    /// Writing to it directly corrupts the compressed stream unless the encoder has been
+    /// flushed or closed first.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    /// Return the underlying writer. Compressed data that has not been written yet is lost.
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+
+    /// Get a reference to the compressor.
+    pub fn compressor(&self) -> &CompressorOxide {
+        &self.encoder.compressor
+    }
+
+    /// Get a mutable reference to the compressor, e.g. to change the compression level
+    /// between flushes.
+    pub fn compressor_mut(&mut self) -> &mut CompressorOxide<'static> {
+        &mut self.encoder.compressor
+    }
+
+    /// Number of uncompressed bytes written to the encoder so far.
+    pub fn total_in(&self) -> u64 {
+        self.encoder.total_in
+    }
+
+    /// Number of compressed bytes produced so far, including any not yet written to the
+    /// underlying writer.
+    pub fn total_out(&self) -> u64 {
+        self.encoder.total_out
+    }
+}
+
+impl<W: AsyncWrite + Unpin> AsyncWrite for DeflateEncoder<W> {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<Result<usize>> {
+        let this = self.get_mut();
+        this.encoder
+            .write(buf, |buf| Pin::new(&mut this.inner).poll_write(cx, buf))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        let this = self.get_mut();
+        ready!(this
+            .encoder
+            .flush(|buf| Pin::new(&mut this.inner).poll_write(cx, buf)))?;
+        Pin::new(&mut this.inner).poll_flush(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        let this = self.get_mut();
+        ready!(this
+            .encoder
+            .finish(|buf| Pin::new(&mut this.inner).poll_write(cx, buf)))?;
+        Pin::new(&mut this.inner).poll_close(cx)
+    }
+}
+
+/// Async reader that decompresses the data read from a buffered reader.
+///
+/// Only the compressed data is consumed from the underlying reader, so any data following
+/// the end of the stream can still be read from it. Errors are reported the same way as by
+/// the blocking [`bufread::DeflateDecoder`](super::bufread::DeflateDecoder).
+pub struct DeflateDecoder<R: AsyncBufRead + Unpin> {
+    inner: R,
+    decoder: Decoder,
+}
+
+impl<R: AsyncBufRead + Unpin> DeflateDecoder<R> {
+    /// Create a decoder reading data in the given format from `inner`.
+    ///
+    /// [`DataFormat::Auto`] detects whether the data is zlib, gzip or raw deflate.
+    pub fn new(inner: R, format: DataFormat) -> DeflateDecoder<R> {
+        DeflateDecoder::with_state(inner, InflateState::new_boxed(format))
+    }
+
+    /// Create a decoder using a decompression state that has already been set up.
+    pub fn with_state(inner: R, state: Box<InflateState>) -> DeflateDecoder<R> {
+        DeflateDecoder {
+            inner,
+            decoder: Decoder::new(state),
+        }
+    }
+
+    /// Get a reference to the underlying reader.
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Get a mutable reference to the underlying reader.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    /// Return the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    /// Get a reference to the decompression state, e.g. to look at the gzip header.
+    pub fn state(&self) -> &InflateState {
+        &self.decoder.state
+    }
+
+    /// Get a mutable reference to the decompression state.
+    pub fn state_mut(&mut self) -> &mut InflateState {
+        &mut self.decoder.state
+    }
+
+    /// Number of compressed bytes consumed so far.
+    pub fn total_in(&self) -> u64 {
+        self.decoder.total_in
+    }
+
+    /// Number of decompressed bytes produced so far.
+    pub fn total_out(&self) -> u64 {
+        self.decoder.total_out
+    }
+}
+
+impl<R: AsyncBufRead + Unpin> AsyncRead for DeflateDecoder<R> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<Result<usize>> {
+        let this = self.get_mut();
+        loop {
+            let input = ready!(Pin::new(&mut this.inner).poll_fill_buf(cx))?;
+            let (consumed, res) = this.decoder.read(input, buf);
+            Pin::new(&mut this.inner).consume(consumed);
+            if let Some(n) = res? {
+                return Poll::Ready(Ok(n));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ready, DeflateDecoder, DeflateEncoder};
+    use crate::io::poll_until_ready;
+    use crate::DataFormat;
+    use core::pin::Pin;
+    use core::task::{Context, Poll};
+    use futures_io::{AsyncBufRead, AsyncRead, AsyncWrite};
+    use std::io::Result;
+
+    /// Writer and reader that is only ready every other poll, and then takes or gives at
+    /// most 100 bytes.
+    #[derive(Default)]
+    struct Stutter {
+        data: Vec<u8>,
+        pos: usize,
+        ready: bool,
+        closed: bool,
+    }
+
+    impl Stutter {
+        fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+            self.ready = !self.ready;
+            if self.ready {
+                Poll::Ready(())
+            } else {
+                cx.waker().wake_by_ref();
+                Poll::Pending
+            }
+        }
+    }
+
+    impl AsyncWrite for Stutter {
+        fn poll_write(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<Result<usize>> {
+            ready!(self.poll_ready(cx));
+            let n = buf.len().min(100);
+            self.data.extend_from_slice(&buf[..n]);
+            Poll::Ready(Ok(n))
+        }
+
+        fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+            self.poll_ready(cx).map(Ok)
+        }
+
+        fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+            ready!(self.poll_ready(cx));
+            self.closed = true;
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    impl AsyncRead for Stutter {
+        fn poll_read(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &mut [u8],
+        ) -> Poll<Result<usize>> {
+            let input = ready!(self.as_mut().poll_fill_buf(cx))?;
+            let n = input.len().min(buf.len());
+            buf[..n].copy_from_slice(&input[..n]);
+            self.consume(n);
+            Poll::Ready(Ok(n))
+        }
+    }
+
+    impl AsyncBufRead for Stutter {
+        fn poll_fill_buf(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<&[u8]>> {
+            let this = self.get_mut();
+            ready!(this.poll_ready(cx));
+            let end = this.data.len().min(this.pos + 100);
+            Poll::Ready(Ok(&this.data[this.pos..end]))
+        }
+
+        fn consume(mut self: Pin<&mut Self>, amt: usize) {
+            self.pos += amt;
+        }
+    }
+
+    #[test]
+    fn roundtrip_with_pending() {
+        let data: Vec<u8> = (0..100_000u32).map(|i| (i % 1009) as u8).collect();
+        let mut encoder = DeflateEncoder::new(Stutter::default(), 6, DataFormat::Gzip);
+        for chunk in data.chunks(10_000) {
+            let mut written = 0;
+            while written < chunk.len() {
+                written +=
+                    poll_until_ready(|cx| Pin::new(&mut encoder).poll_write(cx, &chunk[written..]))
+                        .unwrap();
+            }
+            poll_until_ready(|cx| Pin::new(&mut encoder).poll_flush(cx)).unwrap();
+        }
+        poll_until_ready(|cx| Pin::new(&mut encoder).poll_close(cx)).unwrap();
+        assert_eq!(encoder.total_in(), data.len() as u64);
+        let mut compressed = encoder.into_inner();
+        assert!(compressed.closed);
+        compressed.ready = false;
+
+        let mut decoder = DeflateDecoder::new(compressed, DataFormat::Auto);
+        let mut out = Vec::new();
+        let mut buf = [0; 4096];
+        loop {
+            let n = poll_until_ready(|cx| Pin::new(&mut decoder).poll_read(cx, &mut buf)).unwrap();
+            if n == 0 {
+                break;
+            }
+            out.extend_from_slice(&buf[..n]);
+        }
+        assert_eq!(out, data);
+        assert_eq!(decoder.state().data_format(), DataFormat::Gzip);
+    }
+}
@@ -0,0 +1,60 @@
+//! Streaming compression and decompression through [`std::io::Read`] and [`std::io::Write`].
+//!
+//! These wrap [`deflate::stream::deflate`](crate::deflate::stream::deflate) and
+//! [`inflate::stream::inflate`](crate::inflate::stream::inflate), so data can be compressed
+//! or decompressed on the fly without keeping all of it in memory. The format is chosen with
+//! a [`DataFormat`](crate::DataFormat), so the same types handle raw deflate, zlib and gzip.
+//!
+//! ```
+//! use std::io::{Read, Write};
+//! use miniz_oxide::io::{read::DeflateDecoder, write::DeflateEncoder};
+//! use miniz_oxide::DataFormat;
+//!
+//! let mut encoder = DeflateEncoder::new(Vec::new(), 6, DataFormat::Zlib);
+//! encoder.write_all(b"Hello, world!").unwrap();
+//! let compressed = encoder.finish().unwrap();
+//!
+//! let mut decoder = DeflateDecoder::new(compressed.as_slice(), DataFormat::Zlib);
+//! let mut data = String::new();
+//! decoder.read_to_string(&mut data).unwrap();
+//! assert_eq!(data, "Hello, world!");
+//! ```
+//!
+//! The `futures-io` and `tokio` features add the `futures` and `tokio` modules, with the
+//! same encoder and decoder for the async I/O traits of those crates.
+
+pub mod bufread;
+#[cfg(feature = "futures-io")]
+pub mod futures;
+pub mod read;
+mod state;
+#[cfg(feature = "tokio")]
+pub mod tokio;
+pub mod write;
+
+/// Size of the buffers used for compressed data.
+const BUF_SIZE: usize = 32 * 1024;
+
+/// Poll with a waker that does nothing until the result is ready.
+#[cfg(all(test, any(feature = "futures-io", feature = "tokio")))]
+fn poll_until_ready<T>(
+    mut poll: impl FnMut(&mut core::task::Context<'_>) -> core::task::Poll<T>,
+) -> T {
+    use core::task::{Context, Poll, Waker};
+    use std::sync::Arc;
+    use std::task::Wake;
+
+    struct NoopWaker;
+
+    impl Wake for NoopWaker {
+        fn wake(self: Arc<Self>) {}
+    }
+
+    let waker = Waker::from(Arc::new(NoopWaker));
+    let mut cx = Context::from_waker(&waker);
+    loop {
+        if let Poll::Ready(res) = poll(&mut cx) {
+            return res;
+        }
+    }
+}
@@ -0,0 +1,127 @@
+//! Decompression of data read from a [`Read`] implementation.
+
+use std::io::{BufReader, Read, Result};
+
+use crate::alloc::boxed::Box;
+use crate::inflate::stream::InflateState;
+use crate::DataFormat;
+
+use super::{bufread, BUF_SIZE};
+
+/// Reader that decompresses the data read from another reader.
+///
+/// The underlying reader is wrapped in a [`BufReader`], so more data than the compressed
+/// stream may be read from it. Use [`bufread::DeflateDecoder`] to leave data following the
+/// stream in the underlying reader. Otherwise this behaves the same.
+///
+/// ```
+/// use std::io::Read;
+/// use miniz_oxide::deflate::compress_to_vec;
+/// use miniz_oxide::io::read::DeflateDecoder;
+/// use miniz_oxide::DataFormat;
+///
+/// let compressed = compress_to_vec(b"Hello, world!", 6);
+/// let mut decoder = DeflateDecoder::new(compressed.as_slice(), DataFormat::Raw);
+/// let mut data = Vec::new();
+/// decoder.read_to_end(&mut data).unwrap();
+/// assert_eq!(data, b"Hello, world!");
+/// ```
+pub struct DeflateDecoder<R: Read> {
+    inner: bufread::DeflateDecoder<BufReader<R>>,
+}
+
+impl<R: Read> DeflateDecoder<R> {
+    /// Create a decoder reading data in the given format from `inner`.
+    ///
+    /// [`DataFormat::Auto`] detects whether the data is zlib, gzip or raw deflate.
+    pub fn new(inner: R, format: DataFormat) -> DeflateDecoder<R> {
+        DeflateDecoder::with_state(inner, InflateState::new_boxed(format))
+    }
+
+    /// Create a decoder using a decompression state that has already been set up.
+    pub fn with_state(inner: R, state: Box<InflateState>) -> DeflateDecoder<R> {
+        let reader = BufReader::with_capacity(BUF_SIZE, inner);
+        DeflateDecoder {
+            inner: bufread::DeflateDecoder::with_state(reader, state),
+        }
+    }
+
+    /// Get a reference to the underlying reader.
+    pub fn get_ref(&self) -> &R {
+        self.inner.get_ref().get_ref()
+    }
+
+    /// Get a mutable reference to the underlying reader.
+    ///
+    /// Reading from it directly skips data that has not been buffered yet.
+    pub fn get_mut(&mut self) -> &mut R {
+        self.inner.get_mut().get_mut()
+    }
+
+    /// Return the underlying reader. Data that has been buffered but not decompressed is lost.
+    pub fn into_inner(self) -> R {
+        self.inner.into_inner().into_inner()
+    }
+
+    /// Get a reference to the decompression state, e.g. to look at the gzip header.
+    pub fn state(&self) -> &InflateState {
+        self.inner.state()
+    }
+
+    /// Get a mutable reference to the decompression state.
+    pub fn state_mut(&mut self) -> &mut InflateState {
+        self.inner.state_mut()
+    }
+
+    /// Number of compressed bytes consumed so far.
+    pub fn total_in(&self) -> u64 {
+        self.inner.total_in()
+    }
+
+    /// Number of decompressed bytes produced so far.
+    pub fn total_out(&self) -> u64 {
+        self.inner.total_out()
+    }
+}
+
+impl<R: Read> Read for DeflateDecoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        self.inner.read(buf)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::DeflateDecoder;
+    use crate::gzip::GzHeader;
+    use crate::io::write::DeflateEncoder;
+    use crate::DataFormat;
+    use std::io::{Read, Write};
+
+    #[test]
+    fn gzip_roundtrip_with_header() {
+        let data: Vec<u8> = (0..100_000u64).map(|i| (i * i / 7) as u8).collect();
+        let mut encoder = DeflateEncoder::new(Vec::new(), 9, DataFormat::Gzip);
+        let header = GzHeader {
+            filename: Some(b"data.bin".to_vec()),
+            ..GzHeader::default()
+        };
+        encoder.compressor_mut().set_gzip_header(header).unwrap();
+        encoder.write_all(&data).unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        let mut decoder = DeflateDecoder::new(compressed.as_slice(), DataFormat::Gzip);
+        let mut out = Vec::new();
+        let mut chunk = [0; 999];
+        loop {
+            let n = decoder.read(&mut chunk).unwrap();
+            if n == 0 {
+                break;
+            }
+            out.extend_from_slice(&chunk[..n]);
+        }
+        assert_eq!(out, data);
+        let name = decoder.state().gzip_header().unwrap().filename.as_deref();
+        assert_eq!(name, Some(&b"data.bin"[..]));
+    }
+}
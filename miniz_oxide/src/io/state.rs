@@ -0,0 +1,234 @@
+//! Compression and decompression state shared by the blocking and async adapters.
+//!
+//! The underlying reader or writer is passed in by the adapters as a function or slice, so
+//! the same code drives both. The functions return [`Poll::Pending`] only when the writer
+//! does; the blocking adapters use writers that are always ready.
+
+use core::task::Poll;
+use std::io::{Error, ErrorKind, Result};
+
+use crate::alloc::boxed::Box;
+use crate::alloc::vec::Vec;
+use crate::deflate::core::CompressorOxide;
+use crate::deflate::stream::deflate;
+use crate::inflate::stream::{inflate, InflateState};
+use crate::{MZError, MZFlush, MZStatus};
+
+use super::BUF_SIZE;
+
+/// Like `ready!` from `futures`: return early if `$e` is pending.
+macro_rules! ready {
+    ($e:expr) => {
+        match $e {
+            ::core::task::Poll::Ready(res) => res,
+            ::core::task::Poll::Pending => return ::core::task::Poll::Pending,
+        }
+    };
+}
+
+#[cfg(any(feature = "futures-io", feature = "tokio"))]
+pub(super) use ready;
+
+/// Get the result of a poll that can not be pending.
+pub(super) fn unwrap_ready<T>(poll: Poll<T>) -> T {
+    match poll {
+        Poll::Ready(res) => res,
+        Poll::Pending => unreachable!("blocking writer returned Poll::Pending"),
+    }
+}
+
+/// Convert an error from the compressor to an I/O error.
+fn deflate_error(err: MZError) -> Error {
+    match err {
+        MZError::Param => Error::new(ErrorKind::InvalidInput, "invalid compression parameters"),
+        _ => Error::new(ErrorKind::Other, "compression failed"),
+    }
+}
+
+/// Convert an error from the decompressor to an I/O error, using the reason stored in `state`
+/// for invalid data.
+fn inflate_error(state: &InflateState, err: MZError) -> Error {
+    match (err, state.data_error()) {
+        (MZError::Data, Some(reason)) => Error::new(ErrorKind::InvalidData, reason),
+        (MZError::Data, None) => Error::new(ErrorKind::InvalidData, "invalid compressed data"),
+        _ => Error::new(ErrorKind::Other, "decompression failed"),
+    }
+}
+
+/// State of an encoder.
+pub(super) struct Encoder {
+    pub(super) compressor: Box<CompressorOxide<'static>>,
+    /// Compressed data, of which the part from `pos` on has not been written yet.
+    buf: Vec<u8>,
+    pos: usize,
+    /// Flush to carry out once the buffer has been written, if a flush or finish is in
+    /// progress.
+    pending: Option<MZFlush>,
+    /// Whether everything written has been flushed, so polling a flush again after the
+    /// underlying writer was not ready does not add another empty block.
+    flushed: bool,
+    pub(super) total_in: u64,
+    pub(super) total_out: u64,
+    pub(super) finished: bool,
+}
+
+impl Encoder {
+    pub(super) fn new(compressor: Box<CompressorOxide<'static>>) -> Encoder {
+        Encoder {
+            compressor,
+            buf: Vec::with_capacity(BUF_SIZE),
+            pos: 0,
+            pending: None,
+            flushed: false,
+            total_in: 0,
+            total_out: 0,
+            finished: false,
+        }
+    }
+
+    /// Compress at most [`BUF_SIZE`] bytes of `input`, returning the number of bytes consumed.
+    ///
+    /// Any flush in progress and the compressed data are written with `write` first, so
+    /// the amount of work done per call is bounded.
+    pub(super) fn write<F>(&mut self, input: &[u8], mut write: F) -> Poll<Result<usize>>
+    where
+        F: FnMut(&[u8]) -> Poll<Result<usize>>,
+    {
+        loop {
+            ready!(self.drive(&mut write))?;
+            if self.finished {
+                return Poll::Ready(Err(Error::new(
+                    ErrorKind::Other,
+                    "write after the stream was finished",
+                )));
+            }
+            if input.is_empty() {
+                return Poll::Ready(Ok(0));
+            }
+            let input = &input[..input.len().min(BUF_SIZE)];
+            let consumed = self.compress(input, MZFlush::None)?;
+            if consumed > 0 {
+                return Poll::Ready(Ok(consumed));
+            }
+        }
+    }
+
+    /// Compress everything written so far as with [`MZFlush::Sync`], and write it with
+    /// `write`.
+    pub(super) fn flush<F>(&mut self, write: F) -> Poll<Result<()>>
+    where
+        F: FnMut(&[u8]) -> Poll<Result<usize>>,
+    {
+        if !self.finished && !self.flushed && self.pending.is_none() {
+            self.pending = Some(MZFlush::Sync);
+        }
+        self.drive(write)
+    }
+
+    /// End the stream and write the rest of the compressed data with `write`.
+    pub(super) fn finish<F>(&mut self, write: F) -> Poll<Result<()>>
+    where
+        F: FnMut(&[u8]) -> Poll<Result<usize>>,
+    {
+        if !self.finished {
+            self.pending = Some(MZFlush::Finish);
+        }
+        self.drive(write)
+    }
+
+    /// Write the buffered data and carry out the pending flush, if any.
+    fn drive<F>(&mut self, mut write: F) -> Poll<Result<()>>
+    where
+        F: FnMut(&[u8]) -> Poll<Result<usize>>,
+    {
+        loop {
+            while self.pos < self.buf.len() {
+                match ready!(write(&self.buf[self.pos..])) {
+                    Ok(0) => {
+                        return Poll::Ready(Err(Error::new(
+                            ErrorKind::WriteZero,
+                            "failed to write compressed data",
+                        )))
+                    }
+                    Ok(n) => self.pos += n,
+                    Err(e) if e.kind() == ErrorKind::Interrupted => (),
+                    Err(e) => return Poll::Ready(Err(e)),
+                }
+            }
+            let flush = match self.pending {
+                Some(flush) => flush,
+                None => return Poll::Ready(Ok(())),
+            };
+            self.compress(&[], flush)?;
+            // Keep going while the output fills the buffer, as more of the flushed data may be
+            // pending.
+            if self.buf.len() < BUF_SIZE && (flush != MZFlush::Finish || self.finished) {
+                self.pending = None;
+                self.flushed = true;
+            }
+        }
+    }
+
+    /// Compress `input` into the buffer, which must have been written out.
+    fn compress(&mut self, input: &[u8], flush: MZFlush) -> Result<usize> {
+        self.buf.resize(BUF_SIZE, 0);
+        let res = deflate(&mut self.compressor, input, &mut self.buf, flush);
+        self.buf.truncate(res.bytes_written);
+        self.pos = 0;
+        self.total_in += res.bytes_consumed as u64;
+        self.total_out += res.bytes_written as u64;
+        self.flushed &= res.bytes_consumed == 0;
+        let status = res.status.map_err(deflate_error)?;
+        self.finished |= status == MZStatus::StreamEnd;
+        Ok(res.bytes_consumed)
+    }
+}
+
+/// State of a decoder.
+pub(super) struct Decoder {
+    pub(super) state: Box<InflateState>,
+    pub(super) total_in: u64,
+    pub(super) total_out: u64,
+}
+
+impl Decoder {
+    pub(super) fn new(state: Box<InflateState>) -> Decoder {
+        Decoder {
+            state,
+            total_in: 0,
+            total_out: 0,
+        }
+    }
+
+    /// Decompress data from `input` into `buf`, where an empty `input` means the end of the
+    /// compressed data has been reached.
+    ///
+    /// Returns the number of bytes of `input` consumed, and the number of bytes to return
+    /// from the read, or `None` if more input is needed.
+    pub(super) fn read(&mut self, input: &[u8], buf: &mut [u8]) -> (usize, Result<Option<usize>>) {
+        let eof = input.is_empty();
+        if buf.is_empty() || (eof && self.state.multi_member() && self.state.at_member_boundary()) {
+            return (0, Ok(Some(0)));
+        }
+        let res = inflate(&mut self.state, input, buf, MZFlush::None);
+        self.total_in += res.bytes_consumed as u64;
+        self.total_out += res.bytes_written as u64;
+
+        let ret = match res.status {
+            Ok(MZStatus::NeedDict) => Err(Error::new(
+                ErrorKind::InvalidInput,
+                "a preset dictionary is required",
+            )),
+            Ok(MZStatus::StreamEnd) => Ok(Some(res.bytes_written)),
+            _ if res.bytes_written > 0 => Ok(Some(res.bytes_written)),
+            Ok(_) => Ok(None),
+            Err(MZError::Buf) if res.bytes_consumed > 0 => Ok(None),
+            Err(MZError::Buf) if eof => Err(Error::new(
+                ErrorKind::UnexpectedEof,
+                "compressed data ended early",
+            )),
+            Err(err) => Err(inflate_error(&self.state, err)),
+        };
+        (res.bytes_consumed, ret)
+    }
+}
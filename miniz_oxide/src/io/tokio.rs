@@ -0,0 +1,263 @@
+//! Async compression and decompression through the [`tokio`](::tokio) I/O traits.
+//!
+//! These work the same way as the blocking [`write::DeflateEncoder`](super::write::DeflateEncoder)
+//! and [`bufread::DeflateDecoder`](super::bufread::DeflateDecoder). Each poll compresses at
+//! most 32 KiB of input, or decompresses into the given buffer, so no single poll does an
+//! unbounded amount of work.
+//!
+//! To decompress from a reader that only implements [`AsyncRead`], wrap it in a buffered
+//! reader such as `tokio::io::BufReader` first.
+
+use core::pin::Pin;
+use core::task::{Context, Poll};
+use std::io::Result;
+
+use ::tokio::io::{AsyncBufRead, AsyncRead, AsyncWrite, ReadBuf};
+
+use crate::alloc::boxed::Box;
+use crate::deflate::core::CompressorOxide;
+use crate::inflate::stream::InflateState;
+use crate::DataFormat;
+
+use super::state::{ready, Decoder, Encoder};
+
+/// Async writer that compresses the data written to it and writes the compressed data to
+/// another writer.
+///
+/// Flushing compresses everything written so far as with
+/// [`MZFlush::Sync`](crate::MZFlush::Sync) before flushing the underlying writer. The stream
+/// must be ended by shutting down the encoder with [`AsyncWrite::poll_shutdown`], which writes
+/// the end of the stream and then shuts down the underlying writer. Unlike the blocking
+/// encoder, this is not done when the encoder is dropped.
+pub struct DeflateEncoder<W: AsyncWrite + Unpin> {
+    inner: W,
+    encoder: Encoder,
+}
+
+impl<W: AsyncWrite + Unpin> DeflateEncoder<W> {
+    /// Create an encoder writing data in the given format, compressed with the given level
+    /// (0-10), to `inner`.
+    ///
+    /// [`DataFormat::Auto`] has no meaning when compressing and produces zlib data.
+    pub fn new(inner: W, level: u8, format: DataFormat) -> DeflateEncoder<W> {
+        let mut compressor = Box::<CompressorOxide>::default();
+        compressor.set_format_and_level(format, level);
+        DeflateEncoder::with_compressor(inner, compressor)
+    }
+
+    /// Create an encoder using a compressor that has already been set up, e.g. with a
+    /// preset dictionary or a gzip header.
+    pub fn with_compressor(
+        inner: W,
+        compressor: Box<CompressorOxide<'static>>,
+    ) -> DeflateEncoder<W> {
+        DeflateEncoder {
+            inner,
+            encoder: Encoder::new(compressor),
+        }
+    }
+
+    /// Get a reference to the underlying writer.
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    /// Get a mutable reference to the underlying writer.
+    ///
+    /// Writing to it directly corrupts the compressed stream unless the encoder has been
+    /// flushed or shut down first.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    /// Return the underlying writer. Compressed data that has not been written yet is lost.
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+
+    /// Get a reference to the compressor.
+    pub fn compressor(&self) -> &CompressorOxide {
+        &self.encoder.compressor
+    }
+
+    /// Get a mutable reference to the compressor, e.g. to change the compression level
+    /// between flushes.
+    pub fn compressor_mut(&mut self) -> &mut CompressorOxide<'static> {
+        &mut self.encoder.compressor
+    }
+
+    /// Number of uncompressed bytes written to the encoder so far.
+    pub fn total_in(&self) -> u64 {
+        self.encoder.total_in
+    }
+
+    /// Number of compressed bytes produced so far, including any not yet written to the
+    /// underlying writer.
+    pub fn total_out(&self) -> u64 {
+        self.encoder.total_out
+    }
+}
+
+impl<W: AsyncWrite + Unpin> AsyncWrite for DeflateEncoder<W> {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<Result<usize>> {
+        let this = self.get_mut();
+        this.encoder
+            .write(buf, |buf| Pin::new(&mut this.inner).poll_write(cx, buf))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        let this = self.get_mut();
+        ready!(this
+            .encoder
+            .flush(|buf| Pin::new(&mut this.inner).poll_write(cx, buf)))?;
+        Pin::new(&mut this.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        let this = self.get_mut();
+        ready!(this
+            .encoder
+            .finish(|buf| Pin::new(&mut this.inner).poll_write(cx, buf)))?;
+        Pin::new(&mut this.inner).poll_shutdown(cx)
+    }
+}
+
+/// Async reader that decompresses the data read from a buffered reader.
+///
+/// Only the compressed data is consumed from the underlying reader, so any data following
+/// the end of the stream can still be read from it. Errors are reported the same way as by
+/// the blocking [`bufread::DeflateDecoder`](super::bufread::DeflateDecoder).
+pub struct DeflateDecoder<R: AsyncBufRead + Unpin> {
+    inner: R,
+    decoder: Decoder,
+}
+
+impl<R: AsyncBufRead + Unpin> DeflateDecoder<R> {
+    /// Create a decoder reading data in the given format from `inner`.
+    ///
+    /// [`DataFormat::Auto`] detects whether the data is zlib, gzip or raw deflate.
+    pub fn new(inner: R, format: DataFormat) -> DeflateDecoder<R> {
+        DeflateDecoder::with_state(inner, InflateState::new_boxed(format))
+    }
+
+    /// Create a decoder using a decompression state that has already been set up.
+    pub fn with_state(inner: R, state: Box<InflateState>) -> DeflateDecoder<R> {
+        DeflateDecoder {
+            inner,
+            decoder: Decoder::new(state),
+        }
+    }
+
+    /// Get a reference to the underlying reader.
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Get a mutable reference to the underlying reader.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    /// Return the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    /// Get a reference to the decompression state, e.g. to look at the gzip header.
+    pub fn state(&self) -> &InflateState {
+        &self.decoder.state
+    }
+
+    /// Get a mutable reference to the decompression state.
+    pub fn state_mut(&mut self) -> &mut InflateState {
+        &mut self.decoder.state
+    }
+
+    /// Number of compressed bytes consumed so far.
+    pub fn total_in(&self) -> u64 {
+        self.decoder.total_in
+    }
+
+    /// Number of decompressed bytes produced so far.
+    pub fn total_out(&self) -> u64 {
+        self.decoder.total_out
+    }
+}
+
+impl<R: AsyncBufRead + Unpin> AsyncRead for DeflateDecoder<R> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<Result<()>> {
+        let this = self.get_mut();
+        loop {
+            let input = ready!(Pin::new(&mut this.inner).poll_fill_buf(cx))?;
+            let (consumed, res) = this.decoder.read(input, buf.initialize_unfilled());
+            Pin::new(&mut this.inner).consume(consumed);
+            if let Some(n) = res? {
+                buf.advance(n);
+                return Poll::Ready(Ok(()));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{DeflateDecoder, DeflateEncoder};
+    use crate::deflate::compress_to_vec_zlib;
+    use crate::inflate::decompress_to_vec;
+    use crate::io::poll_until_ready;
+    use crate::DataFormat;
+    use ::tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+    use core::pin::Pin;
+    use std::io::ErrorKind;
+
+    #[test]
+    fn encode() {
+        let mut encoder = DeflateEncoder::new(Vec::new(), 6, DataFormat::Raw);
+        let n = poll_until_ready(|cx| Pin::new(&mut encoder).poll_write(cx, b"Hello, ")).unwrap();
+        assert_eq!(n, 7);
+        poll_until_ready(|cx| Pin::new(&mut encoder).poll_flush(cx)).unwrap();
+        assert!(encoder.get_ref().ends_with(&[0, 0, 0xFF, 0xFF]));
+        let n = poll_until_ready(|cx| Pin::new(&mut encoder).poll_write(cx, b"world!")).unwrap();
+        assert_eq!(n, 6);
+        poll_until_ready(|cx| Pin::new(&mut encoder).poll_shutdown(cx)).unwrap();
+        let err = poll_until_ready(|cx| Pin::new(&mut encoder).poll_write(cx, b"!")).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Other);
+        assert_eq!(
+            decompress_to_vec(encoder.get_ref()).unwrap(),
+            b"Hello, world!"
+        );
+    }
+
+    #[test]
+    fn decode() {
+        let mut input = compress_to_vec_zlib(&[42; 10_000], 6);
+        input.extend_from_slice(b"rest");
+        let mut decoder = DeflateDecoder::new(&input[..], DataFormat::Zlib);
+        let mut out = Vec::new();
+        let mut storage = [0; 1000];
+        loop {
+            let mut buf = ReadBuf::new(&mut storage);
+            poll_until_ready(|cx| Pin::new(&mut decoder).poll_read(cx, &mut buf)).unwrap();
+            if buf.filled().is_empty() {
+                break;
+            }
+            out.extend_from_slice(buf.filled());
+        }
+        assert_eq!(out, [42; 10_000]);
+        assert_eq!(decoder.into_inner(), b"rest");
+
+        let mut decoder = DeflateDecoder::new(&input[..20], DataFormat::Zlib);
+        let mut buf = ReadBuf::new(&mut storage);
+        let err = loop {
+            match poll_until_ready(|cx| Pin::new(&mut decoder).poll_read(cx, &mut buf)) {
+                Ok(()) => buf.clear(),
+                Err(err) => break err,
+            }
+        };
+        assert_eq!(err.kind(), ErrorKind::UnexpectedEof);
+    }
+}
@@ -0,0 +1,208 @@
+//! Compression of data written to a [`Write`] implementation.
+
+use core::task::Poll;
+use std::io::{Result, Write};
+
+use crate::alloc::boxed::Box;
+use crate::deflate::core::CompressorOxide;
+use crate::DataFormat;
+
+use super::state::{unwrap_ready, Encoder};
+
+/// Writer that compresses the data written to it and writes the compressed data to another
+/// writer.
+///
+/// [`flush`](Write::flush) compresses everything written so far and ends it with an empty
+/// stored block, as with [`MZFlush::Sync`](crate::MZFlush::Sync), so the receiver can decompress all of it. The
+/// stream must be ended with [`finish`](Self::finish) or [`try_finish`](Self::try_finish);
+/// if neither was called it is finished when the encoder is dropped, ignoring any errors.
+///
+/// ```
+/// use std::io::Write;
+/// use miniz_oxide::io::write::DeflateEncoder;
+/// use miniz_oxide::DataFormat;
+///
+/// let mut encoder = DeflateEncoder::new(Vec::new(), 6, DataFormat::Gzip);
+/// encoder.write_all(b"Hello, world!").unwrap();
+/// let gzip = encoder.finish().unwrap();
+/// # assert_eq!(&gzip[..2], &[0x1F, 0x8B]);
+/// ```
+pub struct DeflateEncoder<W: Write> {
+    inner: Option<W>,
+    encoder: Encoder,
+}
+
+impl<W: Write> DeflateEncoder<W> {
+    /// Create an encoder writing data in the given format, compressed with the given level
+    /// (0-10), to `inner`.
+    ///
+    /// [`DataFormat::Auto`] has no meaning when compressing and produces zlib data.
+    pub fn new(inner: W, level: u8, format: DataFormat) -> DeflateEncoder<W> {
+        let mut compressor = Box::<CompressorOxide>::default();
+        compressor.set_format_and_level(format, level);
+        DeflateEncoder::with_compressor(inner, compressor)
+    }
+
+    /// Create an encoder using a compressor that has already been set up, e.g. with a
+    /// preset dictionary or a gzip header.
+    pub fn with_compressor(
+        inner: W,
+        compressor: Box<CompressorOxide<'static>>,
+    ) -> DeflateEncoder<W> {
+        DeflateEncoder {
+            inner: Some(inner),
+            encoder: Encoder::new(compressor),
+        }
+    }
+
+    /// Get a reference to the underlying writer.
+    pub fn get_ref(&self) -> &W {
+        self.inner.as_ref().unwrap()
+    }
+
+    /// Get a mutable reference to the underlying writer.
+    ///
+    /// Writing to it directly corrupts the compressed stream unless the encoder has been
+    /// flushed or finished first.
+    pub fn get_mut(&mut self) -> &mut W {
+        self.inner.as_mut().unwrap()
+    }
+
+    /// Get a reference to the compressor.
+    pub fn compressor(&self) -> &CompressorOxide {
+        &self.encoder.compressor
+    }
+
+    /// Get a mutable reference to the compressor, e.g. to change the compression level
+    /// between flushes.
+    pub fn compressor_mut(&mut self) -> &mut CompressorOxide<'static> {
+        &mut self.encoder.compressor
+    }
+
+    /// Number of uncompressed bytes written to the encoder so far.
+    pub fn total_in(&self) -> u64 {
+        self.encoder.total_in
+    }
+
+    /// Number of compressed bytes produced so far, including any not yet written to the
+    /// underlying writer.
+    pub fn total_out(&self) -> u64 {
+        self.encoder.total_out
+    }
+
+    /// Write all the compressed data and the end of the stream to the underlying writer.
+    ///
+    /// Further writes fail after this. The underlying writer is not flushed. If this fails
+    /// it can be called again to retry.
+    pub fn try_finish(&mut self) -> Result<()> {
+        let inner = self.inner.as_mut().unwrap();
+        unwrap_ready(self.encoder.finish(|buf| Poll::Ready(inner.write(buf))))
+    }
+
+    /// Finish the stream with [`try_finish`](Self::try_finish) and return the underlying
+    /// writer.
+    pub fn finish(mut self) -> Result<W> {
+        self.try_finish()?;
+        Ok(self.inner.take().unwrap())
+    }
+}
+
+impl<W: Write> Write for DeflateEncoder<W> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        let inner = self.inner.as_mut().unwrap();
+        unwrap_ready(self.encoder.write(buf, |buf| Poll::Ready(inner.write(buf))))
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        let inner = self.inner.as_mut().unwrap();
+        unwrap_ready(self.encoder.flush(|buf| Poll::Ready(inner.write(buf))))?;
+        inner.flush()
+    }
+}
+
+impl<W: Write> Drop for DeflateEncoder<W> {
+    fn drop(&mut self) {
+        if self.inner.is_some() {
+            let _ = self.try_finish();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::DeflateEncoder;
+    use crate::inflate::stream::{inflate, InflateState};
+    use crate::inflate::{decompress_to_vec, decompress_to_vec_auto, decompress_to_vec_zlib};
+    use crate::{DataFormat, MZFlush};
+    use std::io::{Result, Write};
+
+    /// Writer that accepts at most a few bytes at a time.
+    struct Trickle(Vec<u8>);
+
+    impl Write for Trickle {
+        fn write(&mut self, buf: &[u8]) -> Result<usize> {
+            let n = buf.len().min(3);
+            self.0.extend_from_slice(&buf[..n]);
+            Ok(n)
+        }
+
+        fn flush(&mut self) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    fn test_data() -> Vec<u8> {
+        (0..200_000u32)
+            .map(|i| (i % 251) as u8 ^ (i / 1000) as u8)
+            .collect()
+    }
+
+    #[test]
+    fn roundtrip_formats() {
+        let data = test_data();
+        for &format in &[DataFormat::Raw, DataFormat::Zlib, DataFormat::Gzip] {
+            let mut encoder = DeflateEncoder::new(Trickle(Vec::new()), 6, format);
+            for chunk in data.chunks(7000) {
+                encoder.write_all(chunk).unwrap();
+            }
+            assert_eq!(encoder.total_in(), data.len() as u64);
+            let compressed = encoder.finish().unwrap().0;
+            let (decompressed, detected) = decompress_to_vec_auto(&compressed).unwrap();
+            assert_eq!(detected, format);
+            assert_eq!(decompressed, data);
+        }
+    }
+
+    #[test]
+    fn flush_makes_data_available() {
+        let mut encoder = DeflateEncoder::new(Vec::new(), 6, DataFormat::Raw);
+        encoder.write_all(b"Hello, ").unwrap();
+        encoder.flush().unwrap();
+        let flushed = encoder.get_ref().clone();
+        assert!(flushed.ends_with(&[0, 0, 0xFF, 0xFF]));
+        let mut state = InflateState::new(DataFormat::Raw);
+        let mut out = [0; 64];
+        let written = inflate(&mut state, &flushed, &mut out, MZFlush::None).bytes_written;
+        assert_eq!(&out[..written], b"Hello, ");
+
+        encoder.write_all(b"world!").unwrap();
+        let compressed = encoder.finish().unwrap();
+        assert_eq!(decompress_to_vec(&compressed).unwrap(), b"Hello, world!");
+    }
+
+    #[test]
+    fn finish_on_drop_and_write_after_finish() {
+        let mut out = Vec::new();
+        {
+            let mut encoder = DeflateEncoder::new(&mut out, 1, DataFormat::Zlib);
+            encoder.write_all(b"dropped").unwrap();
+        }
+        assert_eq!(decompress_to_vec_zlib(&out).unwrap(), b"dropped");
+
+        let mut encoder = DeflateEncoder::new(Vec::new(), 1, DataFormat::Zlib);
+        encoder.try_finish().unwrap();
+        encoder.try_finish().unwrap();
+        assert!(encoder.write(b"more").is_err());
+        assert_eq!(decompress_to_vec_zlib(encoder.get_ref()).unwrap(), b"");
+    }
+}
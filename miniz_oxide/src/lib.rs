@@ -28,15 +28,27 @@ fn roundtrip(data: &[u8]) {
 #[cfg(feature = "with-alloc")]
 extern crate alloc;
 
+#[cfg(all(feature = "with-alloc", not(feature = "rustc-dep-of-std")))]
+pub mod bgzf;
 #[cfg(feature = "with-alloc")]
 pub mod deflate;
+pub mod gzip;
 pub mod inflate;
+#[cfg(all(
+    feature = "std",
+    feature = "with-alloc",
+    not(feature = "rustc-dep-of-std")
+))]
+pub mod io;
 #[cfg(feature = "serde")]
 pub mod serde;
 mod shared;
+#[cfg(all(feature = "with-alloc", not(feature = "rustc-dep-of-std")))]
+pub mod zip;
 
 pub use crate::shared::update_adler32 as mz_adler32_oxide;
-pub use crate::shared::{MZ_ADLER32_INIT, MZ_DEFAULT_WINDOW_BITS};
+pub use crate::shared::update_crc32 as mz_crc32_oxide;
+pub use crate::shared::{MZ_ADLER32_INIT, MZ_CRC32_INIT, MZ_DEFAULT_WINDOW_BITS};
 
 /// A list of flush types.
 ///
@@ -102,7 +114,9 @@ pub enum MZStatus {
     /// respectively.
     StreamEnd = 1,
 
-    /// Unused
+    /// A preset dictionary is needed to continue decompressing.
+    ///
+    /// X-ref [`TINFLStatus::NeedsDictionary`][inflate::TINFLStatus::NeedsDictionary].
     NeedDict = 2,
 }
 
@@ -162,12 +176,27 @@ pub enum DataFormat {
     ZLibIgnoreChecksum,
     /// Raw DEFLATE.
     Raw,
+    /// Wrapped using the [gzip](https://www.rfc-editor.org/rfc/rfc1952) format.
+    Gzip,
+    /// Gzip, zlib or raw DEFLATE, detected from the first bytes of the data as described in
+    /// [`DataFormat::detect`].
+    /// Currently only used for inflate, behaves the same as Zlib for compression.
+    Auto,
 }
 
 #[cfg(not(feature = "rustc-dep-of-std"))]
 impl DataFormat {
+    /// Get the data format from a zlib-style `window_bits` value.
+    ///
+    /// Values above 31 (i.e window bits + 32) select detecting the format, values above 15
+    /// (i.e window bits + 16) gzip, other positive values zlib and 0 or negative values a
+    /// raw stream.
     pub fn from_window_bits(window_bits: i32) -> DataFormat {
-        if window_bits > 0 {
+        if window_bits > shared::MZ_DEFAULT_WINDOW_BITS + 16 {
+            DataFormat::Auto
+        } else if window_bits > shared::MZ_DEFAULT_WINDOW_BITS {
+            DataFormat::Gzip
+        } else if window_bits > 0 {
             DataFormat::Zlib
         } else {
             DataFormat::Raw
@@ -178,6 +207,36 @@ impl DataFormat {
         match self {
             DataFormat::Zlib | DataFormat::ZLibIgnoreChecksum => shared::MZ_DEFAULT_WINDOW_BITS,
             DataFormat::Raw => -shared::MZ_DEFAULT_WINDOW_BITS,
+            DataFormat::Gzip => shared::MZ_DEFAULT_WINDOW_BITS + 16,
+            DataFormat::Auto => shared::MZ_DEFAULT_WINDOW_BITS + 32,
+        }
+    }
+}
+
+impl DataFormat {
+    /// Detect the format of compressed data from its first bytes, as done for
+    /// [`DataFormat::Auto`].
+    ///
+    /// Data starting with the first byte of the gzip magic number is gzip, which can't be
+    /// the start of a zlib or raw stream. Data starting with a valid zlib header (compression
+    /// method 8, a window size of at most 32 KiB and a correct check value) is zlib, and
+    /// anything else raw DEFLATE. In rare cases raw data can start with bytes that look like
+    /// a zlib header, and is then detected as zlib.
+    ///
+    /// Returns `None` if more data is needed, which is the case for less than two bytes that
+    /// are not the start of a gzip header.
+    pub fn detect(data: &[u8]) -> Option<DataFormat> {
+        match *data {
+            [gzip::GZIP_ID1, ..] => Some(DataFormat::Gzip),
+            [cmf, flg, ..] => {
+                let check = (u16::from(cmf) << 8 | u16::from(flg)) % 31;
+                if cmf & 0x0F == 8 && cmf >> 4 <= 7 && check == 0 {
+                    Some(DataFormat::Zlib)
+                } else {
+                    Some(DataFormat::Raw)
+                }
+            }
+            _ => None,
         }
     }
 }
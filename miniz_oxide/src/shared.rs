@@ -23,3 +23,88 @@ pub fn update_adler32(adler: u32, data: &[u8]) -> u32 {
     hash.write(data);
     hash.finish()
 }
+
+#[doc(hidden)]
+pub const MZ_CRC32_INIT: u32 = 0;
+
+/// Build the lookup tables for the slice-by-8 CRC32 (IEEE 802.3, reflected polynomial
+/// `0xEDB88320`) used by the gzip format.
+const fn make_crc32_tables() -> [[u32; 256]; 8] {
+    let mut tables = [[0u32; 256]; 8];
+    let mut n = 0;
+    while n < 256 {
+        let mut c = n as u32;
+        let mut k = 0;
+        while k < 8 {
+            c = if c & 1 != 0 {
+                0xEDB8_8320 ^ (c >> 1)
+            } else {
+                c >> 1
+            };
+            k += 1;
+        }
+        tables[0][n] = c;
+        n += 1;
+    }
+
+    let mut n = 0;
+    while n < 256 {
+        let mut t = 1;
+        while t < 8 {
+            let prev = tables[t - 1][n];
+            tables[t][n] = (prev >> 8) ^ tables[0][(prev & 0xFF) as usize];
+            t += 1;
+        }
+        n += 1;
+    }
+    tables
+}
+
+static CRC32_TABLES: [[u32; 256]; 8] = make_crc32_tables();
+
+/// Update a running CRC32 checksum with the bytes in `data`.
+///
+/// `crc` is the checksum of the previous data, or [`MZ_CRC32_INIT`] to start a new one.
+#[doc(hidden)]
+pub fn update_crc32(crc: u32, data: &[u8]) -> u32 {
+    let t = &CRC32_TABLES;
+    let mut crc = !crc;
+
+    let mut chunks = data.chunks_exact(8);
+    for chunk in &mut chunks {
+        let lo = crc ^ u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+        crc = t[7][(lo & 0xFF) as usize]
+            ^ t[6][((lo >> 8) & 0xFF) as usize]
+            ^ t[5][((lo >> 16) & 0xFF) as usize]
+            ^ t[4][(lo >> 24) as usize]
+            ^ t[3][chunk[4] as usize]
+            ^ t[2][chunk[5] as usize]
+            ^ t[1][chunk[6] as usize]
+            ^ t[0][chunk[7] as usize];
+    }
+
+    for &b in chunks.remainder() {
+        crc = (crc >> 8) ^ t[0][((crc ^ u32::from(b)) & 0xFF) as usize];
+    }
+
+    !crc
+}
+
+#[cfg(test)]
+mod test {
+    use super::{update_crc32, MZ_CRC32_INIT};
+
+    #[test]
+    fn crc32_check_value() {
+        assert_eq!(update_crc32(MZ_CRC32_INIT, b""), 0);
+        assert_eq!(update_crc32(MZ_CRC32_INIT, b"123456789"), 0xCBF4_3926);
+        let data = b"The quick brown fox jumps over the lazy dog";
+        assert_eq!(update_crc32(MZ_CRC32_INIT, data), 0x414F_A339);
+        // Running updates should match a single call.
+        let (a, b) = data.split_at(13);
+        assert_eq!(
+            update_crc32(update_crc32(MZ_CRC32_INIT, a), b),
+            update_crc32(MZ_CRC32_INIT, data)
+        );
+    }
+}
@@ -0,0 +1,279 @@
+//! Reading and writing ZIP archives.
+//!
+//! Only single-disk archives are supported. Entries can be stored, deflated or
+//! compressed with Deflate64 when reading, and stored or deflated when writing.
+//!
+//! ZIP64 records are read when present. They are written only when an archive has too many
+//! entries, or an entry or offset is too large for the classic fields.
+
+use crate::alloc::vec::Vec;
+use crate::inflate::TINFLStatus;
+#[cfg(feature = "std")]
+use std::error::Error;
+
+mod read;
+mod write;
+
+pub use self::read::{
+    local_header_len, read_central_dir, CentralDirLocation, EntryDecoder, ZipArchive,
+};
+pub use self::write::{EntryOptions, ZipWriter};
+
+pub(crate) const LOCAL_FILE_HEADER_SIG: u32 = 0x0403_4b50;
+pub(crate) const CENTRAL_DIR_HEADER_SIG: u32 = 0x0201_4b50;
+pub(crate) const END_OF_CENTRAL_DIR_SIG: u32 = 0x0605_4b50;
+pub(crate) const ZIP64_END_OF_CENTRAL_DIR_SIG: u32 = 0x0606_4b50;
+pub(crate) const ZIP64_END_OF_CENTRAL_DIR_LOCATOR_SIG: u32 = 0x0706_4b50;
+
+/// Length of the fixed part of a local file header.
+pub const LOCAL_FILE_HEADER_LEN: usize = 30;
+pub(crate) const CENTRAL_DIR_HEADER_LEN: usize = 46;
+pub(crate) const END_OF_CENTRAL_DIR_LEN: usize = 22;
+pub(crate) const ZIP64_END_OF_CENTRAL_DIR_LEN: usize = 56;
+pub(crate) const ZIP64_END_OF_CENTRAL_DIR_LOCATOR_LEN: usize = 20;
+
+/// Header id of the ZIP64 extended information extra field.
+pub(crate) const ZIP64_EXTRA_FIELD_ID: u16 = 0x0001;
+/// Version of the specification needed for ZIP64 records.
+pub(crate) const VERSION_NEEDED_ZIP64: u16 = 45;
+
+/// Compression methods that can be used for the entries in an archive.
+pub mod methods {
+    /// The data is stored without compression.
+    pub const STORED: u16 = 0;
+    /// The data is compressed with deflate.
+    pub const DEFLATED: u16 = 8;
+    /// The data is compressed with Deflate64. Only supported for reading.
+    pub const DEFLATE64: u16 = 9;
+}
+
+/// Bits of the general purpose flag field of an entry.
+pub mod general_purpose_flags {
+    /// The entry is encrypted.
+    pub const ENCRYPTED: u16 = 1;
+    /// The crc32 and sizes are stored in a data descriptor after the data.
+    pub const DATA_DESCRIPTOR: u16 = 1 << 3;
+    /// The file name and comment are encoded using UTF-8.
+    pub const UTF8: u16 = 1 << 11;
+}
+
+/// Errors that can occur when reading or writing an archive.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ZipError {
+    /// The data is not a valid ZIP archive, or the headers are corrupt.
+    InvalidArchive,
+    /// The archive is split over several disks, which is not supported.
+    UnsupportedArchive,
+    /// The entry is compressed with a method that is not supported.
+    UnsupportedMethod(u16),
+    /// The entry is encrypted, which is not supported.
+    Encrypted,
+    /// There is no entry with the given index or name.
+    NotFound,
+    /// The compressed data of the entry is invalid.
+    ///
+    /// The status is the status the decompressor failed with, or
+    /// [`TINFLStatus::Failed`] if the size of the data did not match the size in the header.
+    InvalidData(TINFLStatus),
+    /// The crc32 checksum of the extracted data did not match the one in the header.
+    ChecksumMismatch,
+    /// The callback receiving the extracted data asked to stop.
+    Aborted,
+    /// A name or comment is longer than 65535 bytes, or an entry is too large for the local
+    /// header, see [`EntryOptions::large_file`].
+    TooLarge,
+}
+
+impl core::fmt::Display for ZipError {
+    #[cold]
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            ZipError::InvalidArchive => f.write_str("Invalid or corrupt zip archive"),
+            ZipError::UnsupportedArchive => f.write_str("Multi-disk archives are not supported"),
+            ZipError::UnsupportedMethod(method) => {
+                write!(f, "Unsupported compression method {}", method)
+            }
+            ZipError::Encrypted => f.write_str("Encrypted entries are not supported"),
+            ZipError::NotFound => f.write_str("Entry not found"),
+            ZipError::InvalidData(_) => f.write_str("Invalid compressed data"),
+            ZipError::ChecksumMismatch => f.write_str("Checksum mismatch"),
+            ZipError::Aborted => f.write_str("Extraction aborted"),
+            ZipError::TooLarge => f.write_str("Name, comment or entry too large"),
+        }
+    }
+}
+
+/// Implement Error trait only if std feature is requested as it requires std.
+#[cfg(feature = "std")]
+impl Error for ZipError {}
+
+/// Date and time in the MS-DOS format used by ZIP archives.
+///
+/// The format has a resolution of two seconds and can represent the years 1980 to 2107.
+/// No time zone is stored, the time is normally local time.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct DateTime {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+}
+
+impl Default for DateTime {
+    /// The earliest time that can be represented, 1980-01-01 00:00:00.
+    fn default() -> Self {
+        DateTime {
+            year: 1980,
+            month: 1,
+            day: 1,
+            hour: 0,
+            minute: 0,
+            second: 0,
+        }
+    }
+}
+
+impl DateTime {
+    /// Convert from the MS-DOS date and time fields.
+    pub const fn from_dos(date: u16, time: u16) -> DateTime {
+        DateTime {
+            year: 1980 + (date >> 9),
+            month: ((date >> 5) & 0xF) as u8,
+            day: (date & 0x1F) as u8,
+            hour: (time >> 11) as u8,
+            minute: ((time >> 5) & 0x3F) as u8,
+            second: ((time & 0x1F) * 2) as u8,
+        }
+    }
+
+    /// Convert to the MS-DOS `(date, time)` fields.
+    ///
+    /// Years outside the range that can be represented are clamped, and odd seconds are
+    /// rounded down.
+    pub const fn to_dos(&self) -> (u16, u16) {
+        let year = if self.year < 1980 {
+            0
+        } else if self.year > 1980 + 127 {
+            127
+        } else {
+            self.year - 1980
+        };
+        let date = (year << 9) | ((self.month as u16 & 0xF) << 5) | (self.day as u16 & 0x1F);
+        let time = ((self.hour as u16 & 0x1F) << 11)
+            | ((self.minute as u16 & 0x3F) << 5)
+            | ((self.second as u16 / 2) & 0x1F);
+        (date, time)
+    }
+}
+
+/// Metadata of an entry in the central directory of an archive.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ZipEntry {
+    /// The file name, using `/` as the path separator. Directories end with a `/`.
+    ///
+    /// The name is UTF-8 if [`general_purpose_flags::UTF8`] is set in `flags`,
+    /// and otherwise normally uses code page 437.
+    pub name: Vec<u8>,
+    /// The comment of the entry.
+    pub comment: Vec<u8>,
+    /// The extra field from the central directory.
+    pub extra: Vec<u8>,
+    /// Version of the ZIP specification and the host system of the program that made the entry.
+    pub version_made_by: u16,
+    /// Minimum version of the ZIP specification needed to extract the entry.
+    pub version_needed: u16,
+    /// General purpose flags, see [`general_purpose_flags`].
+    pub flags: u16,
+    /// Compression method, see [`methods`].
+    pub method: u16,
+    /// Time of the last modification.
+    pub last_modified: DateTime,
+    /// Crc32 checksum of the uncompressed data.
+    pub crc32: u32,
+    /// Size of the compressed data.
+    pub compressed_size: u64,
+    /// Size of the uncompressed data.
+    pub uncompressed_size: u64,
+    /// Internal file attributes.
+    pub internal_attributes: u16,
+    /// External file attributes, their meaning depends on the host system.
+    pub external_attributes: u32,
+    /// Offset of the local header of the entry from the start of the archive.
+    pub local_header_offset: u64,
+}
+
+impl ZipEntry {
+    /// Whether the entry is a directory.
+    ///
+    /// This is the case if the name ends with a `/`, or if the MS-DOS directory attribute is
+    /// set in the external attributes.
+    pub fn is_dir(&self) -> bool {
+        const DOS_DIRECTORY_ATTRIBUTE: u32 = 0x10;
+        self.name.last() == Some(&b'/') || self.external_attributes & DOS_DIRECTORY_ATTRIBUTE != 0
+    }
+
+    /// Whether the entry is encrypted.
+    pub const fn is_encrypted(&self) -> bool {
+        self.flags & general_purpose_flags::ENCRYPTED != 0
+    }
+
+    /// Whether the name and comment are encoded using UTF-8.
+    pub const fn is_utf8(&self) -> bool {
+        self.flags & general_purpose_flags::UTF8 != 0
+    }
+}
+
+/// Read a little endian u16 at `pos`.
+#[inline]
+pub(crate) fn read_u16(data: &[u8], pos: usize) -> u16 {
+    u16::from_le_bytes([data[pos], data[pos + 1]])
+}
+
+/// Read a little endian u32 at `pos`.
+#[inline]
+pub(crate) fn read_u32(data: &[u8], pos: usize) -> u32 {
+    u32::from_le_bytes([data[pos], data[pos + 1], data[pos + 2], data[pos + 3]])
+}
+
+/// Read a little endian u64 at `pos`.
+#[inline]
+pub(crate) fn read_u64(data: &[u8], pos: usize) -> u64 {
+    u64::from(read_u32(data, pos)) | (u64::from(read_u32(data, pos + 4)) << 32)
+}
+
+#[cfg(test)]
+mod test {
+    use super::DateTime;
+
+    #[test]
+    fn dos_date_time() {
+        let dt = DateTime {
+            year: 2024,
+            month: 5,
+            day: 17,
+            hour: 13,
+            minute: 45,
+            second: 30,
+        };
+        let (date, time) = dt.to_dos();
+        assert_eq!(DateTime::from_dos(date, time), dt);
+        assert_eq!(DateTime::default().to_dos(), (0x21, 0));
+
+        // Odd seconds are rounded down and years are clamped.
+        let dt = DateTime {
+            year: 1970,
+            second: 59,
+            ..DateTime::default()
+        };
+        assert_eq!(
+            DateTime::from_dos(dt.to_dos().0, dt.to_dos().1),
+            DateTime {
+                second: 58,
+                ..DateTime::default()
+            }
+        );
+    }
+}
@@ -0,0 +1,679 @@
+//! Parsing of the central directory and extraction of entries.
+
+use crate::alloc::boxed::Box;
+use crate::alloc::vec;
+use crate::alloc::vec::Vec;
+use crate::inflate::stream::{inflate, InflateState};
+use crate::inflate::TINFLStatus;
+use crate::shared::update_crc32;
+use crate::{DataFormat, MZError, MZFlush, MZStatus};
+
+use super::{
+    methods, read_u16, read_u32, read_u64, DateTime, ZipEntry, ZipError, CENTRAL_DIR_HEADER_LEN,
+    CENTRAL_DIR_HEADER_SIG, END_OF_CENTRAL_DIR_LEN, END_OF_CENTRAL_DIR_SIG, LOCAL_FILE_HEADER_LEN,
+    LOCAL_FILE_HEADER_SIG, ZIP64_END_OF_CENTRAL_DIR_LEN, ZIP64_END_OF_CENTRAL_DIR_LOCATOR_LEN,
+    ZIP64_END_OF_CENTRAL_DIR_LOCATOR_SIG, ZIP64_END_OF_CENTRAL_DIR_SIG, ZIP64_EXTRA_FIELD_ID,
+};
+
+/// Size of the buffer entries are decompressed into before being passed to the callback.
+const OUT_BUF_SIZE: usize = 32 * 1024;
+
+/// A ZIP archive held in memory.
+///
+/// The central directory is parsed when the archive is opened, the data of the entries is
+/// only read when they are extracted.
+///
+/// # Example
+///
+/// ```no_run
+/// use miniz_oxide::zip::ZipArchive;
+///
+/// # fn example(data: &[u8]) -> Result<(), miniz_oxide::zip::ZipError> {
+/// let archive = ZipArchive::new(data)?;
+/// for (i, entry) in archive.entries().iter().enumerate() {
+///     if !entry.is_dir() {
+///         let contents = archive.extract_to_vec(i)?;
+///         println!("{}: {} bytes", String::from_utf8_lossy(&entry.name), contents.len());
+///     }
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct ZipArchive<'a> {
+    data: &'a [u8],
+    entries: Vec<ZipEntry>,
+    comment: Vec<u8>,
+}
+
+impl<'a> ZipArchive<'a> {
+    /// Open an archive, reading the end of central directory record and the central
+    /// directory.
+    ///
+    /// If a ZIP64 end of central directory record is present, its values are used instead
+    /// of the ones in the classic record.
+    ///
+    /// Returns [`ZipError::UnsupportedArchive`] if the archive spans several disks, and
+    /// [`ZipError::InvalidArchive`] if the records can't be found or are truncated.
+    pub fn new(data: &'a [u8]) -> Result<ZipArchive<'a>, ZipError> {
+        let location = CentralDirLocation::find(data, 0)?;
+        let central_dir =
+            &data[location.offset as usize..(location.offset + location.size) as usize];
+        let entries = read_central_dir(central_dir, location.num_entries)?;
+
+        Ok(ZipArchive {
+            data,
+            entries,
+            comment: location.comment,
+        })
+    }
+
+    /// Number of entries in the archive.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether the archive has no entries.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The entries of the archive, in the order of the central directory.
+    pub fn entries(&self) -> &[ZipEntry] {
+        &self.entries
+    }
+
+    /// The entry at `index`, if any.
+    pub fn entry(&self, index: usize) -> Option<&ZipEntry> {
+        self.entries.get(index)
+    }
+
+    /// Index of the first entry with the given name, if any.
+    pub fn index_of(&self, name: &[u8]) -> Option<usize> {
+        self.entries.iter().position(|e| e.name == name)
+    }
+
+    /// The archive comment.
+    pub fn comment(&self) -> &[u8] {
+        &self.comment
+    }
+
+    /// The data of the entry at `index` as stored in the archive, without decompressing it.
+    pub fn raw_data(&self, index: usize) -> Result<&'a [u8], ZipError> {
+        let entry = self.entries.get(index).ok_or(ZipError::NotFound)?;
+        let header = usize::try_from(entry.local_header_offset)
+            .ok()
+            .and_then(|offset| self.data.get(offset..))
+            .ok_or(ZipError::InvalidArchive)?;
+        let start = local_header_len(header)? as usize;
+        let len = usize::try_from(entry.compressed_size).map_err(|_| ZipError::InvalidArchive)?;
+        start
+            .checked_add(len)
+            .and_then(|end| header.get(start..end))
+            .ok_or(ZipError::InvalidArchive)
+    }
+
+    /// Extract the entry at `index`, passing the uncompressed data to `callback` in chunks.
+    ///
+    /// Extraction stops with [`ZipError::Aborted`] if the callback returns `false`.
+    /// The size and crc32 checksum of the data are verified once all of it has been
+    /// passed to the callback. Returns the number of bytes extracted.
+    pub fn extract_with<F>(&self, index: usize, mut callback: F) -> Result<u64, ZipError>
+    where
+        F: FnMut(&[u8]) -> bool,
+    {
+        let entry = self.entries.get(index).ok_or(ZipError::NotFound)?;
+        let mut decoder = EntryDecoder::new(entry)?;
+        let mut raw = self.raw_data(index)?;
+
+        let mut buf = vec![0; OUT_BUF_SIZE];
+        while !decoder.is_done() {
+            let (consumed, written) = decoder.decode(raw, &mut buf)?;
+            raw = &raw[consumed..];
+            if written > 0 && !callback(&buf[..written]) {
+                return Err(ZipError::Aborted);
+            }
+        }
+        decoder.finish()
+    }
+
+    /// Extract the entry at `index` into a vector.
+    pub fn extract_to_vec(&self, index: usize) -> Result<Vec<u8>, ZipError> {
+        let entry = self.entries.get(index).ok_or(ZipError::NotFound)?;
+        // Don't trust the size in the header with more than the compressed data could
+        // possibly expand to.
+        let capacity = entry
+            .uncompressed_size
+            .min(entry.compressed_size.saturating_mul(1032))
+            .min(isize::MAX as u64) as usize;
+        let mut out = Vec::with_capacity(capacity);
+        self.extract_with(index, |data| {
+            out.extend_from_slice(data);
+            true
+        })?;
+        Ok(out)
+    }
+}
+
+/// Location and size of the central directory, read from the end of central directory record
+/// and the ZIP64 end of central directory record if there is one.
+///
+/// Together with [`read_central_dir`], [`local_header_len`] and [`EntryDecoder`] this can be
+/// used to read archives that are not held in memory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CentralDirLocation {
+    /// Number of entries in the central directory.
+    pub num_entries: u64,
+    /// Offset of the central directory from the start of the archive.
+    pub offset: u64,
+    /// Size of the central directory.
+    pub size: u64,
+    /// The archive comment.
+    pub comment: Vec<u8>,
+    /// Whether the location was read from a ZIP64 end of central directory record.
+    pub zip64: bool,
+}
+
+impl CentralDirLocation {
+    /// The number of bytes from the end of the archive [`find`](Self::find) needs to find the
+    /// records when the archive has a comment of the maximum length.
+    pub const MAX_TAIL_LEN: usize = END_OF_CENTRAL_DIR_LEN
+        + u16::MAX as usize
+        + ZIP64_END_OF_CENTRAL_DIR_LOCATOR_LEN
+        + ZIP64_END_OF_CENTRAL_DIR_LEN;
+
+    /// Find the end of central directory records in `tail`, the end of an archive starting
+    /// at offset `tail_offset`.
+    ///
+    /// Reading the last [`MAX_TAIL_LEN`](Self::MAX_TAIL_LEN) bytes of the archive, or all of
+    /// it if it is smaller, is enough unless the ZIP64 record has extensible data.
+    ///
+    /// Returns [`ZipError::UnsupportedArchive`] if the archive spans several disks, and
+    /// [`ZipError::InvalidArchive`] if the records can't be found or don't point to a central
+    /// directory in front of them.
+    pub fn find(tail: &[u8], tail_offset: u64) -> Result<CentralDirLocation, ZipError> {
+        let eocd = find_end_of_central_dir(tail).ok_or(ZipError::InvalidArchive)?;
+        let comment_start = eocd + END_OF_CENTRAL_DIR_LEN;
+        let comment_len = usize::from(read_u16(tail, eocd + 20));
+        let comment = tail
+            .get(comment_start..comment_start + comment_len)
+            .ok_or(ZipError::InvalidArchive)?
+            .to_vec();
+
+        let (info, records_start) = match read_zip64_end_of_central_dir(tail, tail_offset, eocd)? {
+            Some(zip64) => zip64,
+            None => (
+                EndOfCentralDir {
+                    disk: read_u16(tail, eocd + 4).into(),
+                    cd_disk: read_u16(tail, eocd + 6).into(),
+                    disk_entries: read_u16(tail, eocd + 8).into(),
+                    total_entries: read_u16(tail, eocd + 10).into(),
+                    size: read_u32(tail, eocd + 12).into(),
+                    offset: read_u32(tail, eocd + 16).into(),
+                },
+                eocd,
+            ),
+        };
+        if info.disk != 0 || info.cd_disk != 0 || info.disk_entries != info.total_entries {
+            return Err(ZipError::UnsupportedArchive);
+        }
+
+        // The central directory has to end before the records, and every header takes at
+        // least `CENTRAL_DIR_HEADER_LEN` bytes, so a corrupt count can't cause a huge
+        // allocation.
+        let records_start = tail_offset + records_start as u64;
+        if info
+            .offset
+            .checked_add(info.size)
+            .map_or(true, |end| end > records_start)
+            || info.total_entries > info.size / CENTRAL_DIR_HEADER_LEN as u64
+        {
+            return Err(ZipError::InvalidArchive);
+        }
+
+        Ok(CentralDirLocation {
+            num_entries: info.total_entries,
+            offset: info.offset,
+            size: info.size,
+            comment,
+            zip64: records_start != tail_offset + eocd as u64,
+        })
+    }
+}
+
+/// Parse `num_entries` headers from `central_dir`, the data of the central directory.
+pub fn read_central_dir(central_dir: &[u8], num_entries: u64) -> Result<Vec<ZipEntry>, ZipError> {
+    if num_entries > (central_dir.len() / CENTRAL_DIR_HEADER_LEN) as u64 {
+        return Err(ZipError::InvalidArchive);
+    }
+    let mut entries = Vec::with_capacity(num_entries as usize);
+    let mut pos = 0;
+    for _ in 0..num_entries {
+        let (entry, len) = parse_central_dir_header(&central_dir[pos..])?;
+        entries.push(entry);
+        pos += len;
+    }
+    Ok(entries)
+}
+
+/// Length of the local header at the start of `header`, including the file name and extra
+/// field. The data of the entry follows the header.
+///
+/// `header` has to hold at least the fixed part of the header, [`LOCAL_FILE_HEADER_LEN`]
+/// bytes.
+pub fn local_header_len(header: &[u8]) -> Result<u64, ZipError> {
+    if header.len() < LOCAL_FILE_HEADER_LEN || read_u32(header, 0) != LOCAL_FILE_HEADER_SIG {
+        return Err(ZipError::InvalidArchive);
+    }
+    Ok((LOCAL_FILE_HEADER_LEN
+        + usize::from(read_u16(header, 26))
+        + usize::from(read_u16(header, 28))) as u64)
+}
+
+/// Decompresses the data of an entry in pieces, verifying its size and crc32 checksum.
+///
+/// # Example
+///
+/// ```
+/// use miniz_oxide::zip::{EntryDecoder, EntryOptions, ZipArchive, ZipWriter};
+///
+/// let mut writer = ZipWriter::new();
+/// writer.add_entry(b"a", &[7; 1000], &EntryOptions::default()).unwrap();
+/// let data = writer.finish().unwrap();
+/// let archive = ZipArchive::new(&data).unwrap();
+///
+/// let mut decoder = EntryDecoder::new(&archive.entries()[0]).unwrap();
+/// let mut raw = archive.raw_data(0).unwrap();
+/// let mut out = [0; 100];
+/// let mut total = 0;
+/// while !decoder.is_done() {
+///     // Feed the data a byte at a time.
+///     let (consumed, written) = decoder.decode(&raw[..raw.len().min(1)], &mut out).unwrap();
+///     raw = &raw[consumed..];
+///     total += written;
+/// }
+/// assert_eq!(decoder.finish(), Ok(1000));
+/// assert_eq!(total, 1000);
+/// ```
+pub struct EntryDecoder {
+    inflate: Option<Box<InflateState>>,
+    crc32: u32,
+    size: u64,
+    expected_crc32: u32,
+    expected_size: u64,
+    /// Input left of a stored entry.
+    stored_left: u64,
+    done: bool,
+}
+
+impl EntryDecoder {
+    /// Create a decoder for the data of `entry`.
+    ///
+    /// Returns [`ZipError::Encrypted`] for encrypted entries, and
+    /// [`ZipError::UnsupportedMethod`] for methods other than stored, deflated and
+    /// Deflate64.
+    pub fn new(entry: &ZipEntry) -> Result<EntryDecoder, ZipError> {
+        if entry.is_encrypted() {
+            return Err(ZipError::Encrypted);
+        }
+        let inflate = match entry.method {
+            methods::STORED => None,
+            methods::DEFLATED | methods::DEFLATE64 => {
+                let mut state = InflateState::new_boxed(DataFormat::Raw);
+                if entry.method == methods::DEFLATE64 {
+                    state
+                        .set_deflate64(true)
+                        .map_err(|_| ZipError::UnsupportedMethod(entry.method))?;
+                }
+                Some(state)
+            }
+            method => return Err(ZipError::UnsupportedMethod(method)),
+        };
+        Ok(EntryDecoder {
+            inflate,
+            crc32: 0,
+            size: 0,
+            expected_crc32: entry.crc32,
+            expected_size: entry.uncompressed_size,
+            stored_left: entry.compressed_size,
+            done: entry.method == methods::STORED && entry.compressed_size == 0,
+        })
+    }
+
+    /// Decode the next piece of compressed data from `input` into `output`, returning the
+    /// number of bytes consumed and written.
+    ///
+    /// Returns [`ZipError::InvalidData`] if the data is corrupt, if it decompresses to more
+    /// than the size in the header, or if no progress can be made because `input` is empty
+    /// before the end of the data has been reached.
+    pub fn decode(&mut self, input: &[u8], output: &mut [u8]) -> Result<(usize, usize), ZipError> {
+        if self.done || output.is_empty() {
+            return Ok((0, 0));
+        }
+
+        let (consumed, written) = match &mut self.inflate {
+            None => {
+                let len = input
+                    .len()
+                    .min(output.len())
+                    .min(usize::try_from(self.stored_left).unwrap_or(usize::MAX));
+                if len == 0 {
+                    return Err(ZipError::InvalidData(TINFLStatus::FailedCannotMakeProgress));
+                }
+                output[..len].copy_from_slice(&input[..len]);
+                self.stored_left -= len as u64;
+                self.done = self.stored_left == 0;
+                (len, len)
+            }
+            Some(state) => {
+                let res = inflate(state, input, output, MZFlush::None);
+                match res.status {
+                    Ok(MZStatus::StreamEnd) => self.done = true,
+                    Ok(_) => (),
+                    Err(MZError::Buf) => {
+                        return Err(ZipError::InvalidData(TINFLStatus::FailedCannotMakeProgress))
+                    }
+                    Err(_) => return Err(ZipError::InvalidData(state.last_status())),
+                }
+                (res.bytes_consumed, res.bytes_written)
+            }
+        };
+
+        self.crc32 = update_crc32(self.crc32, &output[..written]);
+        self.size += written as u64;
+        if self.size > self.expected_size {
+            return Err(ZipError::InvalidData(TINFLStatus::Failed));
+        }
+        Ok((consumed, written))
+    }
+
+    /// Whether the end of the data has been reached.
+    pub const fn is_done(&self) -> bool {
+        self.done
+    }
+
+    /// Check the size and crc32 checksum once all of the data has been decoded, returning
+    /// the size.
+    pub fn finish(&self) -> Result<u64, ZipError> {
+        if !self.done {
+            return Err(ZipError::InvalidData(TINFLStatus::FailedCannotMakeProgress));
+        }
+        if self.size != self.expected_size {
+            return Err(ZipError::InvalidData(TINFLStatus::Failed));
+        }
+        if self.crc32 != self.expected_crc32 {
+            return Err(ZipError::ChecksumMismatch);
+        }
+        Ok(self.size)
+    }
+}
+
+/// Fields of the end of central directory record or its ZIP64 counterpart.
+struct EndOfCentralDir {
+    disk: u32,
+    cd_disk: u32,
+    disk_entries: u64,
+    total_entries: u64,
+    size: u64,
+    offset: u64,
+}
+
+/// Read the ZIP64 end of central directory record, if there is a locator for it in front of
+/// the end of central directory record at `eocd`. Also returns the position of the record.
+fn read_zip64_end_of_central_dir(
+    tail: &[u8],
+    tail_offset: u64,
+    eocd: usize,
+) -> Result<Option<(EndOfCentralDir, usize)>, ZipError> {
+    let locator = match eocd.checked_sub(ZIP64_END_OF_CENTRAL_DIR_LOCATOR_LEN) {
+        Some(pos) if read_u32(tail, pos) == ZIP64_END_OF_CENTRAL_DIR_LOCATOR_SIG => pos,
+        _ => return Ok(None),
+    };
+    let record_disk = read_u32(tail, locator + 4);
+    let total_disks = read_u32(tail, locator + 16);
+    if record_disk != 0 || total_disks > 1 {
+        return Err(ZipError::UnsupportedArchive);
+    }
+
+    let record = read_u64(tail, locator + 8)
+        .checked_sub(tail_offset)
+        .and_then(|pos| usize::try_from(pos).ok())
+        .filter(|&pos| {
+            pos.checked_add(ZIP64_END_OF_CENTRAL_DIR_LEN)
+                .map_or(false, |end| end <= locator)
+        })
+        .ok_or(ZipError::InvalidArchive)?;
+    if read_u32(tail, record) != ZIP64_END_OF_CENTRAL_DIR_SIG {
+        return Err(ZipError::InvalidArchive);
+    }
+
+    Ok(Some((
+        EndOfCentralDir {
+            disk: read_u32(tail, record + 16),
+            cd_disk: read_u32(tail, record + 20),
+            disk_entries: read_u64(tail, record + 24),
+            total_entries: read_u64(tail, record + 32),
+            size: read_u64(tail, record + 40),
+            offset: read_u64(tail, record + 48),
+        },
+        record,
+    )))
+}
+
+/// Find the position of the end of central directory record, searching backwards past the
+/// archive comment.
+fn find_end_of_central_dir(data: &[u8]) -> Option<usize> {
+    let last = data.len().checked_sub(END_OF_CENTRAL_DIR_LEN)?;
+    let first = last.saturating_sub(usize::from(u16::MAX));
+    (first..=last)
+        .rev()
+        .find(|&pos| read_u32(data, pos) == END_OF_CENTRAL_DIR_SIG)
+}
+
+/// Parse a central directory header at the start of `data`, returning the entry and the
+/// length of the header.
+fn parse_central_dir_header(data: &[u8]) -> Result<(ZipEntry, usize), ZipError> {
+    if data.len() < CENTRAL_DIR_HEADER_LEN || read_u32(data, 0) != CENTRAL_DIR_HEADER_SIG {
+        return Err(ZipError::InvalidArchive);
+    }
+    let name_len = usize::from(read_u16(data, 28));
+    let extra_len = usize::from(read_u16(data, 30));
+    let comment_len = usize::from(read_u16(data, 32));
+    let len = CENTRAL_DIR_HEADER_LEN + name_len + extra_len + comment_len;
+    if data.len() < len {
+        return Err(ZipError::InvalidArchive);
+    }
+
+    let name_start = CENTRAL_DIR_HEADER_LEN;
+    let extra_start = name_start + name_len;
+    let comment_start = extra_start + extra_len;
+    let mut entry = ZipEntry {
+        name: data[name_start..extra_start].to_vec(),
+        extra: data[extra_start..comment_start].to_vec(),
+        comment: data[comment_start..len].to_vec(),
+        version_made_by: read_u16(data, 4),
+        version_needed: read_u16(data, 6),
+        flags: read_u16(data, 8),
+        method: read_u16(data, 10),
+        last_modified: DateTime::from_dos(read_u16(data, 14), read_u16(data, 12)),
+        crc32: read_u32(data, 16),
+        compressed_size: read_u32(data, 20).into(),
+        uncompressed_size: read_u32(data, 24).into(),
+        internal_attributes: read_u16(data, 36),
+        external_attributes: read_u32(data, 38),
+        local_header_offset: read_u32(data, 42).into(),
+    };
+    read_zip64_extra_field(&mut entry)?;
+    Ok((entry, len))
+}
+
+/// Replace the sizes and offset of the entry that are set to `u32::MAX` with the values from
+/// the ZIP64 extended information extra field.
+fn read_zip64_extra_field(entry: &mut ZipEntry) -> Result<(), ZipError> {
+    const MAX: u64 = u32::MAX as u64;
+    if entry.uncompressed_size != MAX
+        && entry.compressed_size != MAX
+        && entry.local_header_offset != MAX
+    {
+        return Ok(());
+    }
+
+    let mut extra = &entry.extra[..];
+    while extra.len() >= 4 {
+        let id = read_u16(extra, 0);
+        let len = usize::from(read_u16(extra, 2));
+        let field = extra.get(4..4 + len).ok_or(ZipError::InvalidArchive)?;
+        if id == ZIP64_EXTRA_FIELD_ID {
+            // Only the values that don't fit in the header are present, in this order.
+            let mut values = field.chunks_exact(8).map(|v| read_u64(v, 0));
+            for value in [
+                &mut entry.uncompressed_size,
+                &mut entry.compressed_size,
+                &mut entry.local_header_offset,
+            ] {
+                if *value == MAX {
+                    *value = values.next().ok_or(ZipError::InvalidArchive)?;
+                }
+            }
+            return Ok(());
+        }
+        extra = &extra[4 + len..];
+    }
+    Err(ZipError::InvalidArchive)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{local_header_len, read_central_dir, CentralDirLocation, EntryDecoder, ZipArchive};
+    use crate::inflate::TINFLStatus;
+    use crate::zip::ZipError;
+
+    /// An archive with a single stored entry "a" containing "abc".
+    const STORED: &[u8] = &[
+        0x50, 0x4b, 0x03, 0x04, 0x0a, 0, 0, 0, 0, 0, 0, 0, 0x21, 0, 0xc2, 0x41, 0x24, 0x35, 3, 0,
+        0, 0, 3, 0, 0, 0, 1, 0, 0, 0, b'a', b'a', b'b', b'c', //
+        0x50, 0x4b, 0x01, 0x02, 0x14, 0, 0x0a, 0, 0, 0, 0, 0, 0, 0, 0x21, 0, 0xc2, 0x41, 0x24,
+        0x35, 3, 0, 0, 0, 3, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, b'a',
+        //
+        0x50, 0x4b, 0x05, 0x06, 0, 0, 0, 0, 1, 0, 1, 0, 47, 0, 0, 0, 34, 0, 0, 0, 0, 0,
+    ];
+
+    #[test]
+    fn stored_entry() {
+        let archive = ZipArchive::new(STORED).unwrap();
+        assert_eq!(archive.len(), 1);
+        assert_eq!(archive.index_of(b"a"), Some(0));
+        assert_eq!(archive.extract_to_vec(0).unwrap(), b"abc");
+        assert_eq!(archive.extract_to_vec(1), Err(ZipError::NotFound));
+        assert_eq!(archive.extract_with(0, |_| false), Err(ZipError::Aborted));
+    }
+
+    #[test]
+    fn corrupt_archive() {
+        assert_eq!(
+            ZipArchive::new(&STORED[..STORED.len() - 1]).unwrap_err(),
+            ZipError::InvalidArchive
+        );
+        assert_eq!(ZipArchive::new(&[]).unwrap_err(), ZipError::InvalidArchive);
+
+        let mut data = STORED.to_vec();
+        data[33] = b'd';
+        let archive = ZipArchive::new(&data).unwrap();
+        assert_eq!(archive.extract_to_vec(0), Err(ZipError::ChecksumMismatch));
+
+        // Second disk of a split archive.
+        let mut data = STORED.to_vec();
+        let eocd = data.len() - 22;
+        data[eocd + 4] = 1;
+        assert_eq!(
+            ZipArchive::new(&data).unwrap_err(),
+            ZipError::UnsupportedArchive
+        );
+    }
+
+    #[test]
+    fn zip64_records() {
+        // The archive from `STORED` with all sizes, offsets and counts moved into the ZIP64
+        // extra field and end of central directory record.
+        let mut data = STORED[..34].to_vec();
+        let mut header = STORED[34..81].to_vec();
+        header[20..28].copy_from_slice(&[0xFF; 8]);
+        header[30..32].copy_from_slice(&28u16.to_le_bytes());
+        header[42..46].copy_from_slice(&[0xFF; 4]);
+        data.extend_from_slice(&header);
+        data.extend_from_slice(&[1, 0, 24, 0]);
+        for value in [3u64, 3, 0] {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+
+        let record = data.len() as u64;
+        data.extend_from_slice(&[0x50, 0x4b, 0x06, 0x06]);
+        data.extend_from_slice(&44u64.to_le_bytes());
+        data.extend_from_slice(&[0x1E, 3, 45, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        for value in [1u64, 1, 75, 34] {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+        data.extend_from_slice(&[0x50, 0x4b, 0x06, 0x07, 0, 0, 0, 0]);
+        data.extend_from_slice(&record.to_le_bytes());
+        data.extend_from_slice(&1u32.to_le_bytes());
+        data.extend_from_slice(&[0x50, 0x4b, 0x05, 0x06, 0, 0, 0, 0]);
+        data.extend_from_slice(&[0xFF; 12]);
+        data.extend_from_slice(&[0, 0]);
+
+        let archive = ZipArchive::new(&data).unwrap();
+        assert_eq!(archive.len(), 1);
+        let entry = &archive.entries()[0];
+        assert_eq!(entry.compressed_size, 3);
+        assert_eq!(entry.uncompressed_size, 3);
+        assert_eq!(entry.local_header_offset, 0);
+        assert_eq!(archive.extract_to_vec(0).unwrap(), b"abc");
+
+        // The ZIP64 extra field is missing a value.
+        let mut broken = data.clone();
+        broken[81 + 2] = 16;
+        assert_eq!(
+            ZipArchive::new(&broken).unwrap_err(),
+            ZipError::InvalidArchive
+        );
+    }
+
+    #[test]
+    fn read_from_tail() {
+        // Only the end of the archive is needed, with its offset in the archive.
+        let tail = &STORED[81..];
+        let location = CentralDirLocation::find(tail, 81).unwrap();
+        assert_eq!(
+            (location.num_entries, location.offset, location.size),
+            (1, 34, 47)
+        );
+        assert!(!location.zip64);
+        // The central directory can't be behind the records.
+        assert_eq!(
+            CentralDirLocation::find(tail, 0),
+            Err(ZipError::InvalidArchive)
+        );
+
+        let entries = read_central_dir(&STORED[34..81], 1).unwrap();
+        assert_eq!(entries[0].name, b"a");
+        assert_eq!(
+            read_central_dir(&STORED[34..81], 2),
+            Err(ZipError::InvalidArchive)
+        );
+        assert_eq!(local_header_len(STORED), Ok(31));
+        assert_eq!(
+            local_header_len(&STORED[34..]),
+            Err(ZipError::InvalidArchive)
+        );
+
+        let mut decoder = EntryDecoder::new(&entries[0]).unwrap();
+        let mut out = [0; 2];
+        assert_eq!(decoder.decode(&STORED[31..34], &mut out), Ok((2, 2)));
+        assert_eq!(
+            decoder.finish(),
+            Err(ZipError::InvalidData(TINFLStatus::FailedCannotMakeProgress))
+        );
+        assert_eq!(decoder.decode(&STORED[33..34], &mut out), Ok((1, 1)));
+        assert!(decoder.is_done());
+        assert_eq!(decoder.finish(), Ok(3));
+    }
+}
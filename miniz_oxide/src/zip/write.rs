@@ -0,0 +1,806 @@
+//! Writing of entries, the central directory and the end of central directory record.
+
+use crate::alloc::boxed::Box;
+use crate::alloc::vec::Vec;
+use crate::deflate::core::{compress_to_output, CompressorOxide, TDEFLFlush, TDEFLStatus};
+use crate::shared::update_crc32;
+use crate::DataFormat;
+
+use super::{
+    general_purpose_flags, methods, read_u16, DateTime, ZipEntry, ZipError, CENTRAL_DIR_HEADER_SIG,
+    END_OF_CENTRAL_DIR_SIG, LOCAL_FILE_HEADER_LEN, LOCAL_FILE_HEADER_SIG, VERSION_NEEDED_ZIP64,
+    ZIP64_END_OF_CENTRAL_DIR_LEN, ZIP64_END_OF_CENTRAL_DIR_LOCATOR_SIG,
+    ZIP64_END_OF_CENTRAL_DIR_SIG, ZIP64_EXTRA_FIELD_ID,
+};
+
+/// Version 3.0 of the specification, on a Unix host.
+const VERSION_MADE_BY: u16 = 0x031E;
+const VERSION_NEEDED_STORED: u16 = 10;
+const VERSION_NEEDED_DEFLATED: u16 = 20;
+
+/// Values at or above this don't fit in the classic fields.
+const ZIP64_LIMIT: u64 = u32::MAX as u64;
+
+/// Options for an entry added to a [`ZipWriter`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EntryOptions {
+    /// Compression method, either [`methods::STORED`] or [`methods::DEFLATED`].
+    pub method: u16,
+    /// Compression level (0-10) used when the method is [`methods::DEFLATED`].
+    pub level: u8,
+    /// Time of the last modification.
+    pub last_modified: DateTime,
+    /// Comment stored in the central directory.
+    pub comment: Vec<u8>,
+    /// External file attributes, e.g. Unix permissions in the upper 16 bits.
+    pub external_attributes: u32,
+    /// Extra field stored in the central directory.
+    pub extra: Vec<u8>,
+    /// Extra field stored in the local header.
+    pub local_extra: Vec<u8>,
+    /// Whether the entry may be 4 GiB or larger, which needs room for the sizes in a ZIP64
+    /// extra field in the local header.
+    ///
+    /// This only matters for entries written with [`ZipWriter::start_entry`] and
+    /// [`ZipWriter::write`], where [`ZipWriter::finish_entry`] fails with
+    /// [`ZipError::TooLarge`] if the entry turns out to be that large without it.
+    /// [`ZipWriter::add_entry`] reserves the field when the data is that large.
+    pub large_file: bool,
+}
+
+impl Default for EntryOptions {
+    fn default() -> Self {
+        EntryOptions {
+            method: methods::DEFLATED,
+            level: 6,
+            last_modified: DateTime::default(),
+            comment: Vec::new(),
+            external_attributes: 0,
+            extra: Vec::new(),
+            local_extra: Vec::new(),
+            large_file: false,
+        }
+    }
+}
+
+/// The entry that is currently being written.
+struct OpenEntry {
+    entry: ZipEntry,
+    /// Position of the local header in the output buffer.
+    header_start: usize,
+    data_start: usize,
+    /// Whether the data passed to `write` is compressed by the writer.
+    compress: bool,
+    /// Whether the local extra field ends with a ZIP64 extra field to fill in the sizes.
+    zip64: bool,
+}
+
+/// Writes a ZIP archive to memory.
+///
+/// Entries can either be added in one go with [`add_entry`](ZipWriter::add_entry), or
+/// be streamed with [`start_entry`](ZipWriter::start_entry),
+/// [`write`](ZipWriter::write) and [`finish_entry`](ZipWriter::finish_entry).
+/// Since the current entry is kept in memory the sizes and checksum are filled into the local
+/// header afterwards, so no data descriptors are written.
+///
+/// The archive is returned by [`finish`](ZipWriter::finish). To write it out while it is
+/// being built, the data in front of the current entry can be taken with
+/// [`take_output`](ZipWriter::take_output).
+///
+/// # Example
+///
+/// ```
+/// use miniz_oxide::zip::{EntryOptions, ZipArchive, ZipWriter};
+///
+/// let mut writer = ZipWriter::new();
+/// writer.add_entry(b"hello.txt", b"Hello!", &EntryOptions::default()).unwrap();
+/// let data = writer.finish().unwrap();
+///
+/// let archive = ZipArchive::new(&data).unwrap();
+/// assert_eq!(archive.extract_to_vec(0).unwrap(), b"Hello!");
+/// ```
+pub struct ZipWriter {
+    out: Vec<u8>,
+    /// Offset of the start of `out` in the archive.
+    out_offset: u64,
+    entries: Vec<ZipEntry>,
+    comment: Vec<u8>,
+    current: Option<OpenEntry>,
+    compressor: Option<Box<CompressorOxide<'static>>>,
+}
+
+impl Default for ZipWriter {
+    fn default() -> Self {
+        ZipWriter::new()
+    }
+}
+
+impl ZipWriter {
+    /// Create a writer for a new, empty archive.
+    pub fn new() -> ZipWriter {
+        ZipWriter::with_entries(Vec::new(), 0)
+    }
+
+    /// Create a writer that appends to an existing archive.
+    ///
+    /// `entries` are the entries of the archive, and `offset` is where the new entries are
+    /// written, normally the offset of the old central directory. The new central directory
+    /// will list the existing entries followed by the new ones.
+    pub fn with_entries(entries: Vec<ZipEntry>, offset: u64) -> ZipWriter {
+        ZipWriter {
+            out: Vec::new(),
+            out_offset: offset,
+            entries,
+            comment: Vec::new(),
+            current: None,
+            compressor: None,
+        }
+    }
+
+    /// Set the archive comment.
+    ///
+    /// Returns [`ZipError::TooLarge`] if the comment is longer than 65535 bytes.
+    pub fn set_comment(&mut self, comment: &[u8]) -> Result<(), ZipError> {
+        if comment.len() > usize::from(u16::MAX) {
+            return Err(ZipError::TooLarge);
+        }
+        self.comment = comment.to_vec();
+        Ok(())
+    }
+
+    /// The entries that have been finished so far.
+    pub fn entries(&self) -> &[ZipEntry] {
+        &self.entries
+    }
+
+    /// Take the output that is complete so far, everything in front of the current entry.
+    ///
+    /// The data returned by the following calls, and finally by
+    /// [`finish`](ZipWriter::finish), continues where this data ends.
+    pub fn take_output(&mut self) -> Vec<u8> {
+        let end = match &mut self.current {
+            Some(current) => {
+                let end = current.header_start;
+                current.header_start = 0;
+                current.data_start -= end;
+                end
+            }
+            None => self.out.len(),
+        };
+        let rest = self.out.split_off(end);
+        self.out_offset += end as u64;
+        core::mem::replace(&mut self.out, rest)
+    }
+
+    /// Add an entry with the given data.
+    ///
+    /// If the method is [`methods::DEFLATED`] but the data does not get any smaller, the
+    /// entry is stored instead.
+    pub fn add_entry(
+        &mut self,
+        name: &[u8],
+        data: &[u8],
+        options: &EntryOptions,
+    ) -> Result<(), ZipError> {
+        let large = options.large_file || data.len() as u64 >= ZIP64_LIMIT;
+        self.start_entry_with(name, options, large)?;
+        self.write(data)?;
+        self.finish_deflate();
+
+        let current = self.current.as_mut().expect("entry was started above");
+        if current.compress && self.out.len() - current.data_start >= data.len() {
+            self.out.truncate(current.data_start);
+            self.out.extend_from_slice(data);
+            current.entry.method = methods::STORED;
+            current.entry.version_needed = VERSION_NEEDED_STORED;
+        }
+        self.finish_entry()
+    }
+
+    /// Add an entry with data that is already compressed, e.g. copied from another archive.
+    ///
+    /// The method, checksum, uncompressed size, times, attributes, name, comment and extra
+    /// field are taken from `entry`, and `data` is written as is. A ZIP64 extra field in
+    /// `entry` is replaced by a new one if needed, and the entry is written without a data
+    /// descriptor.
+    ///
+    /// Returns [`ZipError::TooLarge`] if the name, comment or extra field is longer than
+    /// 65535 bytes.
+    pub fn add_raw_entry(&mut self, entry: &ZipEntry, data: &[u8]) -> Result<(), ZipError> {
+        self.finish_entry()?;
+
+        let mut entry = ZipEntry {
+            extra: strip_zip64_extra_field(&entry.extra),
+            flags: entry.flags & !general_purpose_flags::DATA_DESCRIPTOR,
+            local_header_offset: self.offset(),
+            ..entry.clone()
+        };
+        if entry.version_needed == VERSION_NEEDED_ZIP64 {
+            // Raised again by `finish_entry` if the entry still needs ZIP64 here.
+            entry.version_needed = VERSION_NEEDED_DEFLATED;
+        }
+        let local_extra = entry.extra.clone();
+        let large = data.len() as u64 >= ZIP64_LIMIT || entry.uncompressed_size >= ZIP64_LIMIT;
+        self.start(entry, &local_extra, false, large)?;
+        self.out.extend_from_slice(data);
+        self.finish_entry()
+    }
+
+    /// Add a directory entry. A `/` is appended to the name if it doesn't end with one.
+    pub fn add_directory(&mut self, name: &[u8], options: &EntryOptions) -> Result<(), ZipError> {
+        let mut name = name.to_vec();
+        if name.last() != Some(&b'/') {
+            name.push(b'/');
+        }
+        let options = EntryOptions {
+            method: methods::STORED,
+            ..options.clone()
+        };
+        self.start_entry(&name, &options)?;
+        self.finish_entry()
+    }
+
+    /// Start a new entry, finishing the previous one if there is one.
+    ///
+    /// Returns [`ZipError::UnsupportedMethod`] for methods other than stored and
+    /// deflated, and [`ZipError::TooLarge`] if the name or comment is longer than 65535
+    /// bytes.
+    pub fn start_entry(&mut self, name: &[u8], options: &EntryOptions) -> Result<(), ZipError> {
+        self.start_entry_with(name, options, options.large_file)
+    }
+
+    /// Start a new entry, reserving a ZIP64 extra field in the local header if `large`.
+    fn start_entry_with(
+        &mut self,
+        name: &[u8],
+        options: &EntryOptions,
+        large: bool,
+    ) -> Result<(), ZipError> {
+        self.finish_entry()?;
+
+        let version_needed = match options.method {
+            methods::STORED => VERSION_NEEDED_STORED,
+            methods::DEFLATED => VERSION_NEEDED_DEFLATED,
+            method => return Err(ZipError::UnsupportedMethod(method)),
+        };
+        let mut flags = 0;
+        if needs_utf8_flag(name, &options.comment) {
+            flags |= general_purpose_flags::UTF8;
+        }
+        let entry = ZipEntry {
+            name: name.to_vec(),
+            comment: options.comment.clone(),
+            extra: options.extra.clone(),
+            version_made_by: VERSION_MADE_BY,
+            version_needed,
+            flags,
+            method: options.method,
+            last_modified: options.last_modified,
+            crc32: 0,
+            compressed_size: 0,
+            uncompressed_size: 0,
+            internal_attributes: 0,
+            external_attributes: options.external_attributes,
+            local_header_offset: self.offset(),
+        };
+        let compress = entry.method == methods::DEFLATED;
+        self.start(entry, &options.local_extra, compress, large)?;
+
+        if compress {
+            let compressor = self
+                .compressor
+                .get_or_insert_with(|| Box::new(CompressorOxide::default()));
+            compressor.reset();
+            compressor.set_format_and_level(DataFormat::Raw, options.level);
+        }
+        Ok(())
+    }
+
+    /// Write the local header of `entry` and make it the current entry.
+    ///
+    /// If `zip64` is set, a ZIP64 extra field for the sizes is added after `local_extra`.
+    fn start(
+        &mut self,
+        mut entry: ZipEntry,
+        local_extra: &[u8],
+        compress: bool,
+        zip64: bool,
+    ) -> Result<(), ZipError> {
+        let max = usize::from(u16::MAX);
+        // Leave room for a ZIP64 extra field.
+        if entry.name.len() > max
+            || entry.comment.len() > max
+            || entry.extra.len() > max - 28
+            || local_extra.len() > max - 20
+        {
+            return Err(ZipError::TooLarge);
+        }
+
+        let header_start = self.out.len();
+        if zip64 {
+            entry.version_needed = VERSION_NEEDED_ZIP64;
+            let mut extra = Vec::with_capacity(local_extra.len() + 20);
+            extra.extend_from_slice(local_extra);
+            extra.extend_from_slice(&ZIP64_EXTRA_FIELD_ID.to_le_bytes());
+            extra.extend_from_slice(&16u16.to_le_bytes());
+            // The sizes are filled in when the entry is finished.
+            extra.extend_from_slice(&[0; 16]);
+            write_local_header(&mut self.out, &entry, &extra);
+        } else {
+            write_local_header(&mut self.out, &entry, local_extra);
+        }
+        self.current = Some(OpenEntry {
+            entry,
+            header_start,
+            data_start: self.out.len(),
+            compress,
+            zip64,
+        });
+        Ok(())
+    }
+
+    /// Offset in the archive of the next byte that will be written.
+    fn offset(&self) -> u64 {
+        self.out_offset + self.out.len() as u64
+    }
+
+    /// Write data to the current entry.
+    ///
+    /// Returns [`ZipError::NotFound`] if no entry has been started.
+    pub fn write(&mut self, data: &[u8]) -> Result<(), ZipError> {
+        let current = self.current.as_mut().ok_or(ZipError::NotFound)?;
+        current.entry.crc32 = update_crc32(current.entry.crc32, data);
+        current.entry.uncompressed_size += data.len() as u64;
+
+        if current.compress {
+            let compressor = self.compressor.as_mut().expect("compressor is set up");
+            deflate_to(compressor, data, TDEFLFlush::None, &mut self.out);
+        } else {
+            self.out.extend_from_slice(data);
+        }
+        Ok(())
+    }
+
+    /// Finish the current entry, if any, filling in the sizes and checksum.
+    ///
+    /// Returns [`ZipError::TooLarge`] and drops the entry if it is 4 GiB or larger but was
+    /// started without [`EntryOptions::large_file`], as the local header has no room for
+    /// the sizes.
+    pub fn finish_entry(&mut self) -> Result<(), ZipError> {
+        self.finish_deflate();
+        let mut current = match self.current.take() {
+            Some(current) => current,
+            None => return Ok(()),
+        };
+
+        let entry = &mut current.entry;
+        entry.compressed_size = (self.out.len() - current.data_start) as u64;
+        let header_start = current.header_start;
+        let large_sizes =
+            entry.compressed_size >= ZIP64_LIMIT || entry.uncompressed_size >= ZIP64_LIMIT;
+        if large_sizes && !current.zip64 {
+            self.out.truncate(header_start);
+            return Err(ZipError::TooLarge);
+        }
+        if large_sizes || entry.local_header_offset >= ZIP64_LIMIT {
+            entry.version_needed = VERSION_NEEDED_ZIP64;
+        }
+
+        let (compressed_size, uncompressed_size) = if current.zip64 {
+            // Both sizes go in the reserved field, just in front of the data.
+            let field = &mut self.out[current.data_start - 16..current.data_start];
+            field[..8].copy_from_slice(&entry.uncompressed_size.to_le_bytes());
+            field[8..].copy_from_slice(&entry.compressed_size.to_le_bytes());
+            (u32::MAX, u32::MAX)
+        } else {
+            (entry.compressed_size as u32, entry.uncompressed_size as u32)
+        };
+
+        let header = &mut self.out[header_start..];
+        header[4..6].copy_from_slice(&entry.version_needed.to_le_bytes());
+        header[8..10].copy_from_slice(&entry.method.to_le_bytes());
+        header[14..18].copy_from_slice(&entry.crc32.to_le_bytes());
+        header[18..22].copy_from_slice(&compressed_size.to_le_bytes());
+        header[22..26].copy_from_slice(&uncompressed_size.to_le_bytes());
+
+        self.entries.push(current.entry);
+        Ok(())
+    }
+
+    /// Finish the archive, writing the central directory and the end of central directory
+    /// record, and return the archive data that has not been taken with
+    /// [`take_output`](ZipWriter::take_output).
+    ///
+    /// The ZIP64 end of central directory record and locator are written in front of the end
+    /// of central directory record if there are 65535 entries or more, or if the central
+    /// directory is too large or starts too far into the archive for the classic fields.
+    pub fn finish(mut self) -> Result<Vec<u8>, ZipError> {
+        self.finish_entry()?;
+
+        let cd_offset = self.offset();
+        for entry in &self.entries {
+            write_central_dir_header(&mut self.out, entry);
+        }
+        let cd_size = self.offset() - cd_offset;
+        let num_entries = self.entries.len() as u64;
+
+        let out = &mut self.out;
+        if num_entries >= u64::from(u16::MAX) || cd_size >= ZIP64_LIMIT || cd_offset >= ZIP64_LIMIT
+        {
+            let record_offset = self.out_offset + out.len() as u64;
+            out.extend_from_slice(&ZIP64_END_OF_CENTRAL_DIR_SIG.to_le_bytes());
+            // Size of the rest of the record.
+            out.extend_from_slice(&(ZIP64_END_OF_CENTRAL_DIR_LEN as u64 - 12).to_le_bytes());
+            out.extend_from_slice(&VERSION_MADE_BY.to_le_bytes());
+            out.extend_from_slice(&VERSION_NEEDED_ZIP64.to_le_bytes());
+            // Number of this disk and of the disk where the central directory starts.
+            out.extend_from_slice(&[0; 8]);
+            out.extend_from_slice(&num_entries.to_le_bytes());
+            out.extend_from_slice(&num_entries.to_le_bytes());
+            out.extend_from_slice(&cd_size.to_le_bytes());
+            out.extend_from_slice(&cd_offset.to_le_bytes());
+
+            out.extend_from_slice(&ZIP64_END_OF_CENTRAL_DIR_LOCATOR_SIG.to_le_bytes());
+            // Disk where the ZIP64 record is.
+            out.extend_from_slice(&[0; 4]);
+            out.extend_from_slice(&record_offset.to_le_bytes());
+            // Total number of disks.
+            out.extend_from_slice(&1u32.to_le_bytes());
+        }
+
+        let num_entries = num_entries.min(u64::from(u16::MAX)) as u16;
+        out.extend_from_slice(&END_OF_CENTRAL_DIR_SIG.to_le_bytes());
+        // Number of this disk and of the disk where the central directory starts.
+        out.extend_from_slice(&[0; 4]);
+        out.extend_from_slice(&num_entries.to_le_bytes());
+        out.extend_from_slice(&num_entries.to_le_bytes());
+        out.extend_from_slice(&clamp_u32(cd_size).to_le_bytes());
+        out.extend_from_slice(&clamp_u32(cd_offset).to_le_bytes());
+        out.extend_from_slice(&(self.comment.len() as u16).to_le_bytes());
+        out.extend_from_slice(&self.comment);
+
+        Ok(self.out)
+    }
+
+    /// Flush the remaining deflate output of the current entry.
+    fn finish_deflate(&mut self) {
+        if let Some(current) = &self.current {
+            if current.compress {
+                let compressor = self.compressor.as_mut().expect("compressor is set up");
+                if compressor.prev_return_status() != TDEFLStatus::Done {
+                    deflate_to(compressor, &[], TDEFLFlush::Finish, &mut self.out);
+                }
+            }
+        }
+    }
+}
+
+/// Compress `data`, appending the output to `out`.
+fn deflate_to(
+    compressor: &mut CompressorOxide,
+    mut data: &[u8],
+    flush: TDEFLFlush,
+    out: &mut Vec<u8>,
+) {
+    loop {
+        let (status, bytes_in) = compress_to_output(compressor, data, flush, |buf| {
+            out.extend_from_slice(buf);
+            true
+        });
+        data = &data[bytes_in..];
+        match status {
+            TDEFLStatus::Done => break,
+            TDEFLStatus::Okay if data.is_empty() => break,
+            TDEFLStatus::Okay => (),
+            // Not supposed to happen unless there is a bug.
+            _ => panic!("Bug! Unexpectedly failed to compress!"),
+        }
+    }
+}
+
+/// Clamp a value to the range of a classic header field, where `u32::MAX` indicates that the
+/// value is in the ZIP64 extra field or record.
+fn clamp_u32(value: u64) -> u32 {
+    value.min(ZIP64_LIMIT) as u32
+}
+
+/// Whether the name or comment use characters outside of ASCII and both are valid UTF-8.
+fn needs_utf8_flag(name: &[u8], comment: &[u8]) -> bool {
+    !(name.is_ascii() && comment.is_ascii())
+        && core::str::from_utf8(name).is_ok()
+        && core::str::from_utf8(comment).is_ok()
+}
+
+/// Remove the ZIP64 extended information from an extra field.
+fn strip_zip64_extra_field(mut extra: &[u8]) -> Vec<u8> {
+    let mut stripped = Vec::with_capacity(extra.len());
+    while extra.len() >= 4 {
+        let len = 4 + usize::from(read_u16(extra, 2));
+        let field = &extra[..len.min(extra.len())];
+        if read_u16(extra, 0) != ZIP64_EXTRA_FIELD_ID {
+            stripped.extend_from_slice(field);
+        }
+        extra = &extra[field.len()..];
+    }
+    stripped.extend_from_slice(extra);
+    stripped
+}
+
+fn write_local_header(out: &mut Vec<u8>, entry: &ZipEntry, extra: &[u8]) {
+    let (date, time) = entry.last_modified.to_dos();
+    out.reserve(LOCAL_FILE_HEADER_LEN + entry.name.len());
+    out.extend_from_slice(&LOCAL_FILE_HEADER_SIG.to_le_bytes());
+    out.extend_from_slice(&entry.version_needed.to_le_bytes());
+    out.extend_from_slice(&entry.flags.to_le_bytes());
+    out.extend_from_slice(&entry.method.to_le_bytes());
+    out.extend_from_slice(&time.to_le_bytes());
+    out.extend_from_slice(&date.to_le_bytes());
+    // Crc32 and sizes are filled in when the entry is finished.
+    out.extend_from_slice(&[0; 12]);
+    out.extend_from_slice(&(entry.name.len() as u16).to_le_bytes());
+    out.extend_from_slice(&(extra.len() as u16).to_le_bytes());
+    out.extend_from_slice(&entry.name);
+    out.extend_from_slice(extra);
+}
+
+fn write_central_dir_header(out: &mut Vec<u8>, entry: &ZipEntry) {
+    // Values that don't fit in the header go in the ZIP64 extra field, in this order.
+    let mut zip64 = Vec::new();
+    for value in [
+        entry.uncompressed_size,
+        entry.compressed_size,
+        entry.local_header_offset,
+    ] {
+        if value >= ZIP64_LIMIT {
+            zip64.extend_from_slice(&value.to_le_bytes());
+        }
+    }
+    let mut extra_len = entry.extra.len();
+    if !zip64.is_empty() {
+        extra_len += 4 + zip64.len();
+    }
+
+    let (date, time) = entry.last_modified.to_dos();
+    out.extend_from_slice(&CENTRAL_DIR_HEADER_SIG.to_le_bytes());
+    out.extend_from_slice(&entry.version_made_by.to_le_bytes());
+    out.extend_from_slice(&entry.version_needed.to_le_bytes());
+    out.extend_from_slice(&entry.flags.to_le_bytes());
+    out.extend_from_slice(&entry.method.to_le_bytes());
+    out.extend_from_slice(&time.to_le_bytes());
+    out.extend_from_slice(&date.to_le_bytes());
+    out.extend_from_slice(&entry.crc32.to_le_bytes());
+    out.extend_from_slice(&clamp_u32(entry.compressed_size).to_le_bytes());
+    out.extend_from_slice(&clamp_u32(entry.uncompressed_size).to_le_bytes());
+    out.extend_from_slice(&(entry.name.len() as u16).to_le_bytes());
+    out.extend_from_slice(&(extra_len as u16).to_le_bytes());
+    out.extend_from_slice(&(entry.comment.len() as u16).to_le_bytes());
+    // Disk number start.
+    out.extend_from_slice(&[0; 2]);
+    out.extend_from_slice(&entry.internal_attributes.to_le_bytes());
+    out.extend_from_slice(&entry.external_attributes.to_le_bytes());
+    out.extend_from_slice(&clamp_u32(entry.local_header_offset).to_le_bytes());
+    out.extend_from_slice(&entry.name);
+    if !zip64.is_empty() {
+        out.extend_from_slice(&ZIP64_EXTRA_FIELD_ID.to_le_bytes());
+        out.extend_from_slice(&(zip64.len() as u16).to_le_bytes());
+        out.extend_from_slice(&zip64);
+    }
+    out.extend_from_slice(&entry.extra);
+    out.extend_from_slice(&entry.comment);
+}
+
+#[cfg(test)]
+mod test {
+    use super::{EntryOptions, ZipWriter};
+    use crate::alloc::vec::Vec;
+    use crate::zip::{methods, DateTime, ZipArchive, ZipEntry, ZipError};
+
+    #[test]
+    fn write_and_read_back() {
+        let text = b"The quick brown fox jumps over the lazy dog. ".repeat(100);
+        let time = DateTime {
+            year: 2020,
+            month: 2,
+            day: 29,
+            hour: 23,
+            minute: 59,
+            second: 58,
+        };
+
+        let mut writer = ZipWriter::new();
+        writer.set_comment(b"archive comment").unwrap();
+        writer
+            .add_entry(
+                b"fox.txt",
+                &text,
+                &EntryOptions {
+                    last_modified: time,
+                    comment: b"a fox".to_vec(),
+                    ..EntryOptions::default()
+                },
+            )
+            .unwrap();
+        // Too small to benefit from compression, so it is stored.
+        writer
+            .add_entry(b"tiny", b"x", &EntryOptions::default())
+            .unwrap();
+        writer
+            .add_directory(b"dir", &EntryOptions::default())
+            .unwrap();
+
+        // Stream an entry in small pieces with the highest level.
+        let options = EntryOptions {
+            level: 10,
+            ..EntryOptions::default()
+        };
+        writer
+            .start_entry("dir/ünïcode".as_bytes(), &options)
+            .unwrap();
+        for chunk in text.chunks(7) {
+            writer.write(chunk).unwrap();
+        }
+        writer.start_entry(b"dir/empty", &options).unwrap();
+        let data = writer.finish().unwrap();
+
+        let archive = ZipArchive::new(&data).unwrap();
+        assert_eq!(archive.comment(), b"archive comment");
+        let names: Vec<_> = archive.entries().iter().map(|e| &e.name[..]).collect();
+        assert_eq!(
+            names,
+            [
+                &b"fox.txt"[..],
+                b"tiny",
+                b"dir/",
+                "dir/ünïcode".as_bytes(),
+                b"dir/empty"
+            ]
+        );
+
+        let fox = &archive.entries()[0];
+        assert_eq!(fox.method, methods::DEFLATED);
+        assert!(fox.compressed_size < fox.uncompressed_size);
+        assert_eq!(fox.last_modified, time);
+        assert_eq!(fox.comment, b"a fox");
+        assert!(!fox.is_utf8());
+        assert_eq!(archive.extract_to_vec(0).unwrap(), text);
+
+        assert_eq!(archive.entries()[1].method, methods::STORED);
+        assert_eq!(archive.extract_to_vec(1).unwrap(), b"x");
+        assert!(archive.entries()[2].is_dir());
+        assert!(archive.entries()[3].is_utf8());
+        assert_eq!(archive.extract_to_vec(3).unwrap(), text);
+        assert!(archive.extract_to_vec(4).unwrap().is_empty());
+    }
+
+    #[test]
+    fn invalid_use() {
+        let mut writer = ZipWriter::new();
+        assert_eq!(writer.write(b"abc"), Err(ZipError::NotFound));
+        let options = EntryOptions {
+            method: methods::DEFLATE64,
+            ..EntryOptions::default()
+        };
+        assert_eq!(
+            writer.start_entry(b"a", &options),
+            Err(ZipError::UnsupportedMethod(methods::DEFLATE64))
+        );
+        assert_eq!(writer.set_comment(&[0; 65536]), Err(ZipError::TooLarge));
+        let data = writer.finish().unwrap();
+        assert!(ZipArchive::new(&data).unwrap().is_empty());
+    }
+
+    #[test]
+    fn zip64_entry_count() {
+        let mut writer = ZipWriter::new();
+        let options = EntryOptions {
+            method: methods::STORED,
+            ..EntryOptions::default()
+        };
+        for i in 0..70_000u32 {
+            writer.add_entry(&i.to_le_bytes(), &[], &options).unwrap();
+        }
+        let data = writer.finish().unwrap();
+
+        // The classic record points to the ZIP64 records.
+        let eocd = data.len() - 22;
+        assert_eq!(data[eocd + 8..eocd + 12], [0xFF; 4]);
+        assert_eq!(data[eocd - 20..eocd - 16], [0x50, 0x4b, 0x06, 0x07]);
+
+        let archive = ZipArchive::new(&data).unwrap();
+        assert_eq!(archive.len(), 70_000);
+        assert_eq!(archive.index_of(&69_999u32.to_le_bytes()), Some(69_999));
+        assert!(archive.extract_to_vec(69_999).unwrap().is_empty());
+    }
+
+    #[test]
+    fn no_zip64_when_not_needed() {
+        let mut writer = ZipWriter::new();
+        writer
+            .add_entry(b"a", b"abc", &EntryOptions::default())
+            .unwrap();
+        let data = writer.finish().unwrap();
+        assert!(!data.windows(4).any(|w| w == [0x50, 0x4b, 0x06, 0x06]));
+        assert_eq!(
+            ZipArchive::new(&data).unwrap().entries()[0].version_needed,
+            10
+        );
+    }
+
+    #[test]
+    fn large_file_reserves_zip64_field() {
+        let text = b"Some text to compress, some text to compress. ".repeat(20);
+        let options = EntryOptions {
+            large_file: true,
+            local_extra: b"xy\0\0".to_vec(),
+            ..EntryOptions::default()
+        };
+        let mut writer = ZipWriter::new();
+        writer.start_entry(b"a", &options).unwrap();
+        writer.write(&text).unwrap();
+        writer.finish_entry().unwrap();
+        let entry = writer.entries()[0].clone();
+        let data = writer.finish().unwrap();
+
+        // The sizes are in the ZIP64 field after the given local extra field.
+        assert_eq!(data[18..26], [0xFF; 8]);
+        assert_eq!(data[28..30], [24, 0]);
+        let field = &data[31 + 4..31 + 24];
+        assert_eq!(field[..4], [1, 0, 16, 0]);
+        assert_eq!(field[4..12], (text.len() as u64).to_le_bytes());
+        assert_eq!(field[12..], entry.compressed_size.to_le_bytes());
+
+        let archive = ZipArchive::new(&data).unwrap();
+        assert_eq!(archive.entries()[0].version_needed, 45);
+        assert_eq!(archive.extract_to_vec(0).unwrap(), text);
+
+        // Copying the entry does not reserve the field for the small sizes.
+        let mut writer = ZipWriter::new();
+        writer
+            .add_raw_entry(&archive.entries()[0], archive.raw_data(0).unwrap())
+            .unwrap();
+        let data = writer.finish().unwrap();
+        assert_eq!(data[28..30], [0, 0]);
+        let archive = ZipArchive::new(&data).unwrap();
+        assert_eq!(archive.entries()[0].version_needed, 20);
+        assert_eq!(archive.extract_to_vec(0).unwrap(), text);
+    }
+
+    #[test]
+    fn streamed_output_and_append() {
+        let text = b"Some text to compress, some text to compress. ".repeat(20);
+        let mut writer = ZipWriter::new();
+        writer
+            .add_entry(b"a", &text, &EntryOptions::default())
+            .unwrap();
+        writer.start_entry(b"b", &EntryOptions::default()).unwrap();
+        writer.write(&text).unwrap();
+        // Only the finished entry is taken, the current one stays in the writer.
+        let mut data = writer.take_output();
+        assert_eq!(data.len() as u64, writer.entries()[0].compressed_size + 31);
+        writer.finish_entry().unwrap();
+        data.extend_from_slice(&writer.take_output());
+        data.extend_from_slice(&writer.finish().unwrap());
+
+        let archive = ZipArchive::new(&data).unwrap();
+        assert_eq!(archive.extract_to_vec(1).unwrap(), text);
+
+        // Append to the archive, copying the compressed data of the first entry.
+        let offset = archive.entries()[1].local_header_offset;
+        let mut writer = ZipWriter::with_entries(archive.entries()[..1].to_vec(), offset);
+        let copy = ZipEntry {
+            name: b"c".to_vec(),
+            ..archive.entries()[0].clone()
+        };
+        writer
+            .add_raw_entry(&copy, archive.raw_data(0).unwrap())
+            .unwrap();
+        let mut appended = data[..offset as usize].to_vec();
+        appended.extend_from_slice(&writer.finish().unwrap());
+
+        let archive = ZipArchive::new(&appended).unwrap();
+        assert_eq!(archive.len(), 2);
+        assert_eq!(archive.entries()[1].name, b"c");
+        assert_eq!(archive.entries()[1].method, methods::DEFLATED);
+        assert_eq!(archive.extract_to_vec(1).unwrap(), text);
+    }
+}
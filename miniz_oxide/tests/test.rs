@@ -5,8 +5,11 @@ extern crate miniz_oxide;
 #[cfg(not(target_arch = "wasm32"))]
 use std::io::Read;
 
-use miniz_oxide::deflate::{compress_to_vec, compress_to_vec_zlib};
-use miniz_oxide::inflate::{decompress_to_vec, decompress_to_vec_zlib, TINFLStatus};
+use miniz_oxide::deflate::{compress_to_vec, compress_to_vec_gzip, compress_to_vec_zlib};
+use miniz_oxide::inflate::{
+    decompress_to_vec, decompress_to_vec_gzip, decompress_to_vec_gzip_with_limit,
+    decompress_to_vec_zlib, TINFLStatus,
+};
 use miniz_oxide::MZError;
 
 #[cfg(not(target_arch = "wasm32"))]
@@ -121,6 +124,28 @@ fn roundtrip_lvl_0() {
     roundtrip(0);
 }
 
+#[test]
+fn roundtrip_gzip() {
+    let data = get_test_data();
+    for level in [0, 1, 6, 9] {
+        let enc = compress_to_vec_gzip(data.as_slice(), level);
+        let dec = decompress_to_vec_gzip(enc.as_slice()).unwrap();
+        assert!(data == dec);
+    }
+
+    let enc = compress_to_vec_gzip(data.as_slice(), 6);
+    let err = decompress_to_vec_gzip_with_limit(enc.as_slice(), data.len() / 2).unwrap_err();
+    assert_eq!(err.status, TINFLStatus::HasMoreOutput);
+    let err = decompress_to_vec_gzip(&enc[..enc.len() - 1]).unwrap_err();
+    assert_eq!(err.status, TINFLStatus::FailedCannotMakeProgress);
+    let mut bad_size = enc.clone();
+    let last = bad_size.len() - 1;
+    bad_size[last] ^= 1;
+    let err = decompress_to_vec_gzip(&bad_size).unwrap_err();
+    assert_eq!(err.status, TINFLStatus::Failed);
+    assert!(decompress_to_vec_gzip(&compress_to_vec_zlib(&data, 6)).is_err());
+}
+
 #[test]
 fn zlib_header_level() {
     let level = 6;
@@ -625,3 +650,195 @@ fn issue_137_reject_incomplete_litlen_tree() {
         "incomplete litlen Huffman tree should be rejected"
     );
 }
+
+/// Build a Deflate64 stream with a single block using the fixed huffman codes,
+/// using the long length code and both Deflate64 distance codes.
+fn deflate64_test_data() -> (Vec<u8>, Vec<u8>) {
+    struct BitWriter {
+        out: Vec<u8>,
+        bit_buf: u32,
+        num_bits: u32,
+    }
+    impl BitWriter {
+        fn put_bits(&mut self, bits: u32, len: u32) {
+            self.bit_buf |= bits << self.num_bits;
+            self.num_bits += len;
+            while self.num_bits >= 8 {
+                self.out.push(self.bit_buf as u8);
+                self.bit_buf >>= 8;
+                self.num_bits -= 8;
+            }
+        }
+        // Huffman codes are stored starting with the most significant bit.
+        fn put_code(&mut self, code: u32, len: u32) {
+            let reversed = code.reverse_bits() >> (32 - len);
+            self.put_bits(reversed, len);
+        }
+        fn literal(&mut self, lit: u8) {
+            self.put_code(0x30 + u32::from(lit), 8);
+        }
+        fn long_match(&mut self, len: u32, dist_code: u32, dist_extra: u32, dist_extra_len: u32) {
+            // Length code 285, fixed code 0xC5, with 16 extra bits.
+            self.put_code(0xC0 + (285 - 280), 8);
+            self.put_bits(len - 3, 16);
+            self.put_code(dist_code, 5);
+            self.put_bits(dist_extra, dist_extra_len);
+        }
+    }
+
+    let mut w = BitWriter {
+        out: Vec::new(),
+        bit_buf: 0,
+        num_bits: 0,
+    };
+    let mut expected = Vec::new();
+    // Final block with fixed codes.
+    w.put_bits(0b011, 3);
+    for &c in b"xyz" {
+        w.literal(c);
+        expected.push(c);
+    }
+    // 60000 times 'z', distance 1 (code 0).
+    w.long_match(60_000, 0, 0, 0);
+    expected.extend(std::iter::repeat(b'z').take(60_000));
+    // Copy "xyz" from the start, distance 60003 (code 31, base 49153).
+    w.long_match(3, 31, 60_003 - 49_153, 14);
+    expected.extend_from_slice(b"xyz");
+    // Copy from distance 40000 (code 30, base 32769), inside the run of 'z'.
+    w.literal(b'!');
+    expected.push(b'!');
+    w.long_match(1000, 30, 40_000 - 32_769, 14);
+    for _ in 0..1000 {
+        let b = expected[expected.len() - 40_000];
+        expected.push(b);
+    }
+    // End of block.
+    w.put_code(0, 7);
+    w.put_bits(0, 7);
+    (w.out, expected)
+}
+
+#[test]
+fn deflate64() {
+    use miniz_oxide::inflate::core::{
+        decompress, inflate_flags::*, DecompressorOxide, TINFL_LZ_DICT_SIZE_DEFLATE64,
+    };
+    use miniz_oxide::inflate::stream::{inflate, InflateState};
+    use miniz_oxide::{DataFormat, MZFlush, MZStatus};
+
+    let (encoded, expected) = deflate64_test_data();
+
+    // Non-wrapping output buffer.
+    let mut out = vec![0; expected.len()];
+    let mut r = DecompressorOxide::new();
+    let flags = TINFL_FLAG_USING_NON_WRAPPING_OUTPUT_BUF | TINFL_FLAG_DEFLATE64;
+    let (status, in_consumed, out_len) = decompress(&mut r, &encoded, &mut out, 0, flags);
+    assert_eq!(status, TINFLStatus::Done);
+    assert_eq!(in_consumed, encoded.len());
+    assert_eq!(out_len, expected.len());
+    assert_eq!(out, expected);
+
+    // Wrapping 64k output buffer with small chunks of input.
+    let mut window = vec![0; TINFL_LZ_DICT_SIZE_DEFLATE64];
+    let mut r = DecompressorOxide::new();
+    let mut decoded = Vec::new();
+    let mut in_pos = 0;
+    let mut out_pos = 0;
+    loop {
+        let in_end = (in_pos + 7).min(encoded.len());
+        let flags = TINFL_FLAG_DEFLATE64 | TINFL_FLAG_HAS_MORE_INPUT;
+        let (status, n_in, n_out) = decompress(
+            &mut r,
+            &encoded[in_pos..in_end],
+            &mut window,
+            out_pos,
+            flags,
+        );
+        decoded.extend_from_slice(&window[out_pos..out_pos + n_out]);
+        in_pos += n_in;
+        out_pos = (out_pos + n_out) & (TINFL_LZ_DICT_SIZE_DEFLATE64 - 1);
+        match status {
+            TINFLStatus::Done => break,
+            TINFLStatus::NeedsMoreInput | TINFLStatus::HasMoreOutput => (),
+            s => panic!("unexpected status {:?}", s),
+        }
+    }
+    assert_eq!(decoded, expected);
+
+    // With InflateState and a small output buffer.
+    let mut state = InflateState::new_boxed(DataFormat::Raw);
+    state.set_deflate64(true).unwrap();
+    assert!(state.deflate64());
+    let mut decoded = Vec::new();
+    let mut in_pos = 0;
+    let mut out = [0; 1000];
+    loop {
+        let res = inflate(&mut state, &encoded[in_pos..], &mut out, MZFlush::None);
+        in_pos += res.bytes_consumed;
+        decoded.extend_from_slice(&out[..res.bytes_written]);
+        if res.status.unwrap() == MZStatus::StreamEnd {
+            break;
+        }
+    }
+    assert_eq!(decoded, expected);
+    assert_eq!(state.set_deflate64(false), Err(MZError::Param));
+
+    // Not valid as regular deflate.
+    assert!(decompress_to_vec(&encoded).is_err());
+}
+
+#[test]
+fn zip_archive() {
+    use miniz_oxide::zip::{methods, DateTime, ZipArchive};
+
+    let data = get_test_file_data("tests/test_data/archive.zip");
+    let archive = ZipArchive::new(&data).unwrap();
+    assert_eq!(archive.comment(), b"miniz_oxide test archive");
+    assert_eq!(archive.len(), 4);
+
+    let numbers = &archive.entries()[0];
+    assert_eq!(numbers.name, b"numbers.txt");
+    assert_eq!(numbers.comment, b"some numbers");
+    assert_eq!(numbers.method, methods::DEFLATED);
+    assert_eq!(
+        numbers.last_modified,
+        DateTime {
+            year: 2024,
+            month: 5,
+            day: 17,
+            hour: 13,
+            minute: 45,
+            second: 30,
+        }
+    );
+    assert_eq!(
+        archive.extract_to_vec(0).unwrap(),
+        get_test_file_data("tests/test_data/numbers.txt")
+    );
+
+    let hello = archive.index_of(b"hello.txt").unwrap();
+    assert_eq!(archive.entries()[hello].method, methods::STORED);
+    assert_eq!(archive.extract_to_vec(hello).unwrap(), b"Hello, zip!\n");
+
+    let dir = archive.index_of(b"dir/").unwrap();
+    assert!(archive.entries()[dir].is_dir());
+    assert!(archive.extract_to_vec(dir).unwrap().is_empty());
+
+    let empty = archive.index_of(b"dir/empty.txt").unwrap();
+    assert!(!archive.entries()[empty].is_dir());
+    assert!(archive.extract_to_vec(empty).unwrap().is_empty());
+
+    // Extract in chunks through the callback.
+    let mut chunks = 0;
+    let mut len = 0;
+    let extracted = archive
+        .extract_with(0, |chunk| {
+            chunks += 1;
+            len += chunk.len();
+            true
+        })
+        .unwrap();
+    assert_eq!(extracted, 49995);
+    assert_eq!(len, 49995);
+    assert!(chunks > 1);
+}
@@ -173,10 +173,10 @@ void mz_free(void *p)
 //    return MZ_REALLOC(address, items * size);
 //}
 
-const char *mz_version(void)
-{
-    return MZ_VERSION;
-}
+//const char *mz_version(void)
+//{
+//    return MZ_VERSION;
+//}
 
 #ifndef MINIZ_NO_ZLIB_APIS
 
@@ -174,25 +174,8 @@ typedef struct
 
 } mz_zip_archive;
 
-typedef struct
-{
-    mz_zip_archive *pZip;
-    mz_uint flags;
-
-    int status;
-#ifndef MINIZ_DISABLE_ZIP_READER_CRC32_CHECKS
-    mz_uint file_crc32;
-#endif
-    mz_uint64 read_buf_size, read_buf_ofs, read_buf_avail, comp_remaining, out_buf_ofs, cur_file_ofs;
-    mz_zip_archive_file_stat file_stat;
-    void *pRead_buf;
-    void *pWrite_buf;
-
-    size_t out_blk_remain;
-
-    tinfl_decompressor inflator;
-
-} mz_zip_reader_extract_iter_state;
+/* State of an iterative extraction, see mz_zip_reader_extract_iter_new(). */
+typedef struct mz_zip_reader_extract_iter_state_tag mz_zip_reader_extract_iter_state;
 
 /* -------- ZIP reading */
 
@@ -7,12 +7,7 @@ fn main() {}
 #[cfg(feature = "build_stub_miniz")]
 fn main() {
     cc::Build::new()
-        .files(&[
-            "miniz_stub/miniz.c",
-            "miniz_stub/miniz_zip.c",
-            "miniz_stub/miniz_tinfl.c",
-            "miniz_stub/miniz_tdef.c",
-        ])
+        .files(&["miniz_stub/miniz.c", "miniz_stub/miniz_tdef.c"])
         .compile("miniz");
 }
 
@@ -1,10 +1,11 @@
-use std::marker::PhantomData;
 /// Module that contains most of the functions exported to C.
-use std::{ptr, slice};
+use std::ffi::CStr;
+use std::marker::PhantomData;
+use std::{cmp, mem, ptr, slice};
 
 pub use crate::tinfl::{
-    tinfl_decompress, tinfl_decompress_mem_to_heap, tinfl_decompress_mem_to_mem,
-    tinfl_decompressor, tinfl_status,
+    tinfl_decompress, tinfl_decompress_mem_to_callback, tinfl_decompress_mem_to_heap,
+    tinfl_decompress_mem_to_mem, tinfl_decompressor, tinfl_put_buf_func_ptr, tinfl_status,
 };
 
 pub use crate::tdef::{
@@ -15,8 +16,11 @@ pub use crate::tdef::{
 };
 use libc::*;
 
-use crate::lib_oxide::{InternalState, StateType, StateTypeEnum, StreamOxide, MZ_ADLER32_INIT};
+use crate::lib_oxide::{
+    InternalState, StateType, StateTypeEnum, StreamAllocator, StreamOxide, MZ_ADLER32_INIT,
+};
 
+use miniz_oxide::gzip::GzHeader;
 use miniz_oxide::{mz_adler32_oxide, MZError};
 
 #[allow(bad_style)]
@@ -90,6 +94,18 @@ pub fn mz_crc32_oxide(crc32: c_uint, data: &[u8]) -> c_uint {
     digest.finalize()
 }
 
+/// Flag returned by `mz_compileFlags` if the gz file functions are not provided.
+#[cfg(not(any(
+    feature = "libc_stub",
+    all(target_arch = "wasm32", not(target_os = "emscripten"))
+)))]
+const NO_GZ_FLAG: c_ulong = 0;
+#[cfg(any(
+    feature = "libc_stub",
+    all(target_arch = "wasm32", not(target_os = "emscripten"))
+))]
+const NO_GZ_FLAG: c_ulong = 1 << 16;
+
 /// Signature of function used to allocate the compressor/decompressor structs.
 #[allow(bad_style)]
 pub type mz_alloc_func = unsafe extern "C" fn(*mut c_void, size_t, size_t) -> *mut c_void;
@@ -108,6 +124,19 @@ pub type mz_alloc_callback =
 #[allow(bad_style)]
 pub type mz_free_callback = Option<unsafe extern "C" fn(*mut c_void, *mut c_void)>;
 
+/// Signature of the function `mz_inflateBack` calls to get more input.
+///
+/// Sets the pointer to the start of the input and returns its length, or 0 if there is
+/// no more input.
+#[allow(bad_style)]
+pub type mz_in_func = unsafe extern "C" fn(*mut c_void, *mut *const u8) -> c_uint;
+
+/// Signature of the function `mz_inflateBack` calls to write output.
+///
+/// Returns 0 on success, or a non-zero value to stop decompression.
+#[allow(bad_style)]
+pub type mz_out_func = unsafe extern "C" fn(*mut c_void, *mut u8, c_uint) -> c_int;
+
 /// Inner stream state containing pointers to the used buffers and internal state.
 #[repr(C)]
 #[allow(bad_style)]
@@ -133,10 +162,12 @@ pub struct mz_stream {
     pub state: Option<Box<InternalState>>,
 
     /// Allocation function to use for allocating the internal compressor/decompressor.
-    /// Uses `mz_default_alloc_func` if set to `None`.
+    /// Uses `miniz_def_alloc_func` if set to `None` while `zfree` is set, and the Rust
+    /// allocator if neither is set.
     pub zalloc: mz_alloc_callback,
-    /// Free function to use for allocating the internal compressor/decompressor.
-    /// Uses `mz_default_free_func` if `None`.
+    /// Free function to use for freeing the internal compressor/decompressor.
+    /// Uses `miniz_def_free_func` if set to `None` while `zalloc` is set, and the Rust
+    /// allocator if neither is set.
     pub zfree: mz_free_callback,
     /// Extra data to provide the allocation/deallocation functions.
     /// (Not used for the default ones)
@@ -175,6 +206,112 @@ impl Default for mz_stream {
     }
 }
 
+/// Fields of a gzip header, used with `mz_deflateSetHeader` and `mz_inflateGetHeader`.
+#[repr(C)]
+#[allow(bad_style)]
+#[derive(Debug)]
+pub struct mz_gz_header {
+    /// Whether the data is probably text.
+    pub text: c_int,
+    /// Modification time as a unix timestamp.
+    pub time: c_ulong,
+    /// Extra flags, ignored when compressing.
+    pub xflags: c_int,
+    /// Operating system the data originated on.
+    pub os: c_int,
+    /// Buffer for the extra field, or null if there is none.
+    pub extra: *mut u8,
+    /// Length of the extra field.
+    pub extra_len: c_uint,
+    /// Space in `extra` when decompressing.
+    pub extra_max: c_uint,
+    /// Zero-terminated file name, or null if there is none.
+    pub name: *mut u8,
+    /// Space in `name` when decompressing.
+    pub name_max: c_uint,
+    /// Zero-terminated comment, or null if there is none.
+    pub comment: *mut u8,
+    /// Space in `comment` when decompressing.
+    pub comm_max: c_uint,
+    /// Whether the header is protected by a CRC16.
+    pub hcrc: c_int,
+    /// Set to 1 when the header has been read, or -1 if the data is not gzip.
+    pub done: c_int,
+}
+
+impl mz_gz_header {
+    /// Get the header to write, reading the extra field, file name and comment from the
+    /// buffers that are not null.
+    pub(crate) unsafe fn to_gz_header(&self) -> GzHeader {
+        let c_string = |s: *mut u8| CStr::from_ptr(s as *const c_char).to_bytes().to_vec();
+        GzHeader {
+            text: self.text != 0,
+            mtime: self.time as u32,
+            xfl: 0,
+            os: self.os as u8,
+            extra: (!self.extra.is_null())
+                .then(|| slice::from_raw_parts(self.extra, self.extra_len as usize).to_vec()),
+            filename: (!self.name.is_null()).then(|| c_string(self.name)),
+            comment: (!self.comment.is_null()).then(|| c_string(self.comment)),
+            hcrc: self.hcrc != 0,
+        }
+    }
+
+    /// Fill in the fields from `header` and set `done`.
+    ///
+    /// As much of the extra field, file name and comment as fits is copied to the buffers
+    /// that are not null. The pointers are set to null for fields the header doesn't have.
+    pub(crate) unsafe fn fill(&mut self, header: &GzHeader) {
+        unsafe fn copy_field(dest: &mut *mut u8, max: c_uint, field: Option<&[u8]>, zero: bool) {
+            let field = match field {
+                Some(field) => field,
+                None => {
+                    *dest = ptr::null_mut();
+                    return;
+                }
+            };
+            if dest.is_null() {
+                return;
+            }
+            let dest = slice::from_raw_parts_mut(*dest, max as usize);
+            let len = cmp::min(field.len(), dest.len());
+            dest[..len].copy_from_slice(&field[..len]);
+            if zero && len < dest.len() {
+                dest[len] = 0;
+            }
+        }
+
+        self.text = c_int::from(header.text);
+        self.time = c_ulong::from(header.mtime);
+        self.xflags = c_int::from(header.xfl);
+        self.os = c_int::from(header.os);
+        self.extra_len = header
+            .extra
+            .as_ref()
+            .map_or(0, |extra| extra.len() as c_uint);
+        copy_field(
+            &mut self.extra,
+            self.extra_max,
+            header.extra.as_deref(),
+            false,
+        );
+        copy_field(
+            &mut self.name,
+            self.name_max,
+            header.filename.as_deref(),
+            true,
+        );
+        copy_field(
+            &mut self.comment,
+            self.comm_max,
+            header.comment.as_deref(),
+            true,
+        );
+        self.hcrc = c_int::from(header.hcrc);
+        self.done = 1;
+    }
+}
+
 impl<'io, ST: StateType> StreamOxide<'io, ST> {
     pub fn into_mz_stream(mut self) -> mz_stream {
         mz_stream {
@@ -194,11 +331,13 @@ impl<'io, ST: StateType> StreamOxide<'io, ST> {
                 .map_or(0, |out_slice| out_slice.len() as c_uint),
             total_out: self.total_out,
 
-            msg: ptr::null(),
+            msg: self
+                .msg
+                .map_or(ptr::null(), |msg| msg.as_ptr() as *const c_char),
 
-            zalloc: None,
-            zfree: None,
-            opaque: ptr::null_mut(),
+            zalloc: self.allocator.zalloc,
+            zfree: self.allocator.zfree,
+            opaque: self.allocator.opaque,
             state: self.state.take(),
 
             data_type: ST::STATE_TYPE,
@@ -208,26 +347,22 @@ impl<'io, ST: StateType> StreamOxide<'io, ST> {
     }
 
     /// Create a new StreamOxide wrapper from a [mz_stream] object.
-    /// Custom allocation functions are not supported, supplying an mz_stream with allocation
-    /// function will cause creation to fail.
+    /// The allocation functions of the stream are used to allocate the internal state.
     ///
     /// Unsafe as the mz_stream object is not guaranteed to be valid. It is up to the
     /// caller to ensure it is.
     pub unsafe fn new(stream: &mut mz_stream) -> Self {
-        Self::try_new(stream).expect(
-            "Failed to create StreamOxide, wrong state type or tried to specify allocators.",
-        )
+        Self::try_new(stream).expect("Failed to create StreamOxide, wrong state type.")
     }
 
     /// Try to create a new StreamOxide wrapper from a [mz_stream] object.
-    /// Custom allocation functions are not supported, supplying an mz_stream with allocation
-    /// functions will cause creation to fail.
+    /// The allocation functions of the stream are used to allocate the internal state.
     ///
     /// Unsafe as the mz_stream object is not guaranteed to be valid. It is up to the
     /// caller to ensure it is.
     pub unsafe fn try_new(stream: &mut mz_stream) -> Result<Self, MZError> {
         // Make sure we don't make an inflate stream from a deflate stream and vice versa.
-        if stream.data_type != ST::STATE_TYPE || stream.zalloc.is_some() || stream.zfree.is_some() {
+        if stream.data_type != ST::STATE_TYPE {
             return Err(MZError::Param);
         }
 
@@ -256,6 +391,12 @@ impl<'io, ST: StateType> StreamOxide<'io, ST> {
             total_out: stream.total_out,
             state: stream.state.take(),
             adler: stream.adler as u32,
+            msg: None,
+            allocator: StreamAllocator {
+                zalloc: stream.zalloc,
+                zfree: stream.zfree,
+                opaque: stream.opaque,
+            },
             state_type: PhantomData,
         })
     }
@@ -285,6 +426,51 @@ unmangle!(
         libc::realloc(address, items * size)
     }
 
+    /// Get a description of the return code `err`, or null if it is not a known code.
+    pub extern "C" fn mz_error(err: c_int) -> *const c_char {
+        let desc = match err {
+            0 => "\0",
+            1 => "stream end\0",
+            2 => "need dictionary\0",
+            -1 => "file error\0",
+            -2 => "stream error\0",
+            -3 => "data error\0",
+            -4 => "out of memory\0",
+            -5 => "buf error\0",
+            -6 => "version error\0",
+            -10000 => "parameter error\0",
+            _ => return ptr::null(),
+        };
+        desc.as_ptr() as *const c_char
+    }
+
+    /// Get the version of the library as a string.
+    pub extern "C" fn mz_version() -> *const c_char {
+        concat!(env!("CARGO_PKG_VERSION"), "\0").as_ptr() as *const c_char
+    }
+
+    /// Get flags describing the library, laid out like the ones returned by
+    /// `zlibCompileFlags` in zlib.
+    ///
+    /// Bits 0-1, 2-3, 4-5 and 6-7 hold the size of `c_uint`, `c_ulong`, pointers and the
+    /// `c_long` offsets of `mz_gzseek`, as 0 for 16 bits, 1 for 32 bits, 2 for 64 bits and
+    /// 3 for other sizes. Bit 16 is set if the gz file functions are not provided.
+    pub extern "C" fn mz_compileFlags() -> c_ulong {
+        fn size_flag(size: usize) -> c_ulong {
+            match size {
+                2 => 0,
+                4 => 1,
+                8 => 2,
+                _ => 3,
+            }
+        }
+        size_flag(mem::size_of::<c_uint>())
+            | size_flag(mem::size_of::<c_ulong>()) << 2
+            | size_flag(mem::size_of::<*const c_void>()) << 4
+            | size_flag(mem::size_of::<c_long>()) << 6
+            | NO_GZ_FLAG
+    }
+
     /// Calculate adler32 checksum of the provided buffer with the initial adler32 checksum of `adler`.
     /// If c_ulong is wider than 32 bits, only the lower 32 bits will be used.
     ///
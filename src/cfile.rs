@@ -0,0 +1,33 @@
+//! Helpers for C `FILE` streams, shared by the ZIP archive and gz file functions.
+
+use libc::{c_int, FILE};
+
+/// `fseek` with a 64-bit offset.
+#[cfg(not(windows))]
+pub(crate) unsafe fn fseek64(file: *mut FILE, offset: i64, origin: c_int) -> c_int {
+    libc::fseeko(file, offset as libc::off_t, origin)
+}
+
+/// `ftell` with a 64-bit offset.
+#[cfg(not(windows))]
+pub(crate) unsafe fn ftell64(file: *mut FILE) -> i64 {
+    libc::ftello(file) as i64
+}
+
+#[cfg(windows)]
+extern "C" {
+    fn _fseeki64(file: *mut FILE, offset: i64, origin: c_int) -> c_int;
+    fn _ftelli64(file: *mut FILE) -> i64;
+}
+
+/// `fseek` with a 64-bit offset.
+#[cfg(windows)]
+pub(crate) unsafe fn fseek64(file: *mut FILE, offset: i64, origin: c_int) -> c_int {
+    _fseeki64(file, offset, origin)
+}
+
+/// `ftell` with a 64-bit offset.
+#[cfg(windows)]
+pub(crate) unsafe fn ftell64(file: *mut FILE) -> i64 {
+    _ftelli64(file)
+}
@@ -0,0 +1,963 @@
+//! The gzip file part of the zlib API (`mz_gz*`), implemented on top of the streaming
+//! compressor and decompressor.
+//!
+//! Files are accessed through a C `FILE`, so `mz_gzdopen` accepts any file descriptor that
+//! `fdopen` does. When reading, concatenated gzip members are decompressed one after the
+//! other, data that does not start with a gzip header is read as is, and trailing data after
+//! the last member is ignored, like in zlib. Reading and writing the same file is not
+//! supported, and neither is `gzprintf`, as it is variadic.
+
+use std::borrow::Cow;
+use std::convert::TryFrom;
+use std::ffi::{CStr, CString};
+use std::io;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::{cmp, ptr, slice};
+
+use libc::{c_char, c_int, c_long, c_uint, c_void, FILE};
+
+use miniz_oxide::deflate::core::{
+    create_comp_flags_from_zip_params, CompressionStrategy, CompressorOxide,
+};
+use miniz_oxide::deflate::stream::{deflate, deflate_params};
+use miniz_oxide::deflate::CompressionLevel;
+use miniz_oxide::gzip::{GZIP_ID1, GZIP_ID2};
+use miniz_oxide::inflate::stream::{inflate, InflateState};
+use miniz_oxide::{DataFormat, MZError, MZFlush, MZStatus, MZ_DEFAULT_WINDOW_BITS};
+
+use crate::cfile::{fseek64, ftell64};
+use crate::lib_oxide::data_error_message;
+
+/// Size of the input and output buffers of a gz file.
+const GZ_BUFFER_SIZE: usize = 32 * 1024;
+
+/// Handle to a gz file, as returned by `mz_gzopen` and `mz_gzdopen`.
+#[allow(bad_style)]
+pub type mz_gzFile = *mut mz_gzFile_s;
+
+/// Error stored in a gz file, returned by `mz_gzerror`.
+struct GzError {
+    code: MZError,
+    msg: Cow<'static, str>,
+}
+
+impl GzError {
+    fn new(code: MZError, msg: &'static str) -> GzError {
+        GzError {
+            code,
+            msg: Cow::Borrowed(msg),
+        }
+    }
+
+    /// Error from the C library, described by `errno`.
+    fn errno() -> GzError {
+        GzError {
+            code: MZError::ErrNo,
+            msg: Cow::Owned(io::Error::last_os_error().to_string()),
+        }
+    }
+}
+
+/// How the data of a file opened for reading is decoded.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum GzHow {
+    /// Checking whether the next data is a gzip member.
+    Look,
+    /// Copying data that is not compressed.
+    Copy,
+    /// Decompressing a gzip member.
+    Gzip,
+    /// At the end of the data.
+    Done,
+}
+
+/// State of a file opened for reading.
+struct GzReader {
+    inflate: Box<InflateState>,
+    how: GzHow,
+    /// Whether nothing has been read yet, so data without a gzip header is copied rather
+    /// than ignored.
+    first: bool,
+    /// Whether the data is read without decompressing it.
+    direct: bool,
+    /// Whether the end of the underlying file has been reached.
+    file_eof: bool,
+    /// Whether a read has reached the end of the uncompressed data.
+    past_end: bool,
+    in_buf: Vec<u8>,
+    in_pos: usize,
+    in_len: usize,
+    out_buf: Vec<u8>,
+    out_pos: usize,
+    out_len: usize,
+    /// An error hit while decoding data that was returned first, reported by the next
+    /// fetch.
+    error: Option<GzError>,
+}
+
+impl GzReader {
+    fn new() -> GzReader {
+        GzReader {
+            inflate: InflateState::new_boxed(DataFormat::Gzip),
+            how: GzHow::Look,
+            first: true,
+            direct: false,
+            file_eof: false,
+            past_end: false,
+            in_buf: vec![0; GZ_BUFFER_SIZE],
+            in_pos: 0,
+            in_len: 0,
+            out_buf: vec![0; GZ_BUFFER_SIZE],
+            out_pos: 0,
+            out_len: 0,
+            error: None,
+        }
+    }
+
+    /// Uncompressed data that has been decoded but not read yet.
+    fn available(&self) -> &[u8] {
+        &self.out_buf[self.out_pos..self.out_len]
+    }
+
+    /// Move the data left in the input buffer to its start and read more after it.
+    unsafe fn fill_input(&mut self, file: *mut FILE) -> Result<(), GzError> {
+        self.in_buf.copy_within(self.in_pos..self.in_len, 0);
+        self.in_len -= self.in_pos;
+        self.in_pos = 0;
+
+        let wanted = self.in_buf.len() - self.in_len;
+        if self.file_eof || wanted == 0 {
+            return Ok(());
+        }
+        let buf = self.in_buf[self.in_len..].as_mut_ptr() as *mut c_void;
+        let read = libc::fread(buf, 1, wanted, file);
+        self.in_len += read;
+        if read < wanted {
+            if libc::ferror(file) != 0 {
+                return Err(GzError::errno());
+            }
+            self.file_eof = true;
+        }
+        Ok(())
+    }
+
+    /// Decide how to decode the data that follows, if that has not been done yet.
+    unsafe fn look(&mut self, file: *mut FILE) -> Result<(), GzError> {
+        if self.how != GzHow::Look {
+            return Ok(());
+        }
+        if self.in_len - self.in_pos < 2 {
+            self.fill_input(file)?;
+        }
+
+        let input = &self.in_buf[self.in_pos..self.in_len];
+        self.how = if input.starts_with(&[GZIP_ID1, GZIP_ID2]) {
+            self.inflate.reset(DataFormat::Gzip);
+            GzHow::Gzip
+        } else if self.first && !input.is_empty() {
+            self.direct = true;
+            GzHow::Copy
+        } else {
+            // Like zlib, ignore anything after the last gzip member.
+            GzHow::Done
+        };
+        self.first = false;
+        Ok(())
+    }
+
+    /// Decode more data into the output buffer, after the data in it has been read.
+    ///
+    /// The output buffer is left empty at the end of the data.
+    unsafe fn fetch(&mut self, file: *mut FILE) -> Result<(), GzError> {
+        self.out_pos = 0;
+        self.out_len = 0;
+        if let Some(err) = self.error.take() {
+            return Err(err);
+        }
+        loop {
+            match self.how {
+                GzHow::Look => self.look(file)?,
+                GzHow::Copy => {
+                    if self.in_pos == self.in_len {
+                        if self.file_eof {
+                            self.how = GzHow::Done;
+                            continue;
+                        }
+                        self.fill_input(file)?;
+                    }
+                    let input = &self.in_buf[self.in_pos..self.in_len];
+                    let len = cmp::min(input.len(), self.out_buf.len());
+                    self.out_buf[..len].copy_from_slice(&input[..len]);
+                    self.in_pos += len;
+                    self.out_len = len;
+                    if len > 0 {
+                        return Ok(());
+                    }
+                }
+                GzHow::Gzip => {
+                    if self.in_pos == self.in_len {
+                        self.fill_input(file)?;
+                    }
+                    let input = &self.in_buf[self.in_pos..self.in_len];
+                    let res = inflate(&mut self.inflate, input, &mut self.out_buf, MZFlush::None);
+                    self.in_pos += res.bytes_consumed;
+                    self.out_len = res.bytes_written;
+                    let err = match res.status {
+                        Ok(MZStatus::StreamEnd) => {
+                            self.how = GzHow::Look;
+                            None
+                        }
+                        Err(MZError::Buf) if self.file_eof && self.in_pos == self.in_len => {
+                            Some(GzError::new(MZError::Buf, "unexpected end of file"))
+                        }
+                        Ok(_) | Err(MZError::Buf) => None,
+                        Err(MZError::Data) => {
+                            let msg = self.inflate.data_error().map_or("invalid data", |e| {
+                                data_error_message(e).trim_end_matches('\0')
+                            });
+                            Some(GzError::new(MZError::Data, msg))
+                        }
+                        Err(e) => Some(GzError::new(e, "internal error")),
+                    };
+                    if let Some(err) = err {
+                        if self.out_len == 0 {
+                            return Err(err);
+                        }
+                        // Hand out the data decoded before the error first.
+                        self.error = Some(err);
+                        return Ok(());
+                    }
+                    if self.out_len > 0 {
+                        return Ok(());
+                    }
+                }
+                GzHow::Done => {
+                    self.past_end = true;
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    /// Copy uncompressed data to `buf` until it is full or the data ends, adding the number
+    /// of bytes copied to `got`.
+    unsafe fn read(
+        &mut self,
+        file: *mut FILE,
+        buf: &mut [u8],
+        got: &mut usize,
+    ) -> Result<(), GzError> {
+        while *got < buf.len() {
+            if self.available().is_empty() {
+                self.fetch(file)?;
+                if self.available().is_empty() {
+                    break;
+                }
+            }
+            let available = self.available();
+            let len = cmp::min(available.len(), buf.len() - *got);
+            buf[*got..*got + len].copy_from_slice(&available[..len]);
+            self.out_pos += len;
+            *got += len;
+        }
+        Ok(())
+    }
+
+    /// Copy uncompressed data to `buf` up to and including the next newline, adding the
+    /// number of bytes copied to `got`.
+    unsafe fn read_line(
+        &mut self,
+        file: *mut FILE,
+        buf: &mut [u8],
+        got: &mut usize,
+    ) -> Result<(), GzError> {
+        while *got < buf.len() {
+            if self.available().is_empty() {
+                self.fetch(file)?;
+                if self.available().is_empty() {
+                    break;
+                }
+            }
+            let available = self.available();
+            let len = cmp::min(available.len(), buf.len() - *got);
+            let (len, newline) = match available[..len].iter().position(|&b| b == b'\n') {
+                Some(pos) => (pos + 1, true),
+                None => (len, false),
+            };
+            buf[*got..*got + len].copy_from_slice(&available[..len]);
+            self.out_pos += len;
+            *got += len;
+            if newline {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Skip up to `len` bytes of uncompressed data, adding the number of bytes skipped
+    /// to `skipped`.
+    unsafe fn skip(&mut self, file: *mut FILE, len: u64, skipped: &mut u64) -> Result<(), GzError> {
+        while *skipped < len {
+            if self.available().is_empty() {
+                self.fetch(file)?;
+                if self.available().is_empty() {
+                    break;
+                }
+            }
+            let n = cmp::min(self.available().len() as u64, len - *skipped);
+            self.out_pos += n as usize;
+            *skipped += n;
+        }
+        Ok(())
+    }
+}
+
+/// State of a file opened for writing.
+struct GzWriter {
+    /// The compressor, or `None` if the data is written without compressing it.
+    compressor: Option<Box<CompressorOxide<'static>>>,
+    /// Whether the current gzip member has been finished by a `MZ_FINISH` flush.
+    finished: bool,
+    out_buf: Vec<u8>,
+}
+
+impl GzWriter {
+    /// Write `data` to the file.
+    unsafe fn write_raw(file: *mut FILE, data: &[u8]) -> Result<(), GzError> {
+        if !data.is_empty()
+            && libc::fwrite(data.as_ptr() as *const c_void, 1, data.len(), file) != data.len()
+        {
+            return Err(GzError::errno());
+        }
+        Ok(())
+    }
+
+    /// Compress `data` and write the output to the file.
+    unsafe fn write(&mut self, file: *mut FILE, mut data: &[u8]) -> Result<(), GzError> {
+        let compressor = match self.compressor.as_mut() {
+            Some(compressor) => compressor,
+            None => return GzWriter::write_raw(file, data),
+        };
+        if self.finished && !data.is_empty() {
+            // Start a new gzip member.
+            compressor.reset();
+            self.finished = false;
+        }
+        while !data.is_empty() {
+            let res = deflate(compressor, data, &mut self.out_buf, MZFlush::None);
+            if res.status.is_err() {
+                return Err(GzError::new(MZError::Stream, "internal error"));
+            }
+            data = &data[res.bytes_consumed..];
+            GzWriter::write_raw(file, &self.out_buf[..res.bytes_written])?;
+        }
+        Ok(())
+    }
+
+    /// Flush the compressor with `flush`, and the underlying file.
+    unsafe fn flush(&mut self, file: *mut FILE, flush: MZFlush) -> Result<(), GzError> {
+        if let Some(compressor) = self.compressor.as_mut() {
+            if flush != MZFlush::None && !self.finished {
+                loop {
+                    let res = deflate(compressor, &[], &mut self.out_buf, flush);
+                    GzWriter::write_raw(file, &self.out_buf[..res.bytes_written])?;
+                    match res.status {
+                        Ok(MZStatus::StreamEnd) => break,
+                        Ok(_)
+                            if flush != MZFlush::Finish
+                                && res.bytes_written < self.out_buf.len() =>
+                        {
+                            break
+                        }
+                        Ok(_) => (),
+                        // Nothing was pending.
+                        Err(MZError::Buf) if flush != MZFlush::Finish => break,
+                        Err(_) => return Err(GzError::new(MZError::Stream, "internal error")),
+                    }
+                }
+                self.finished = flush == MZFlush::Finish;
+            }
+        }
+        if libc::fflush(file) != 0 {
+            return Err(GzError::errno());
+        }
+        Ok(())
+    }
+
+    /// Change the compression level and strategy, compressing the pending data with the
+    /// old ones first.
+    unsafe fn set_params(
+        &mut self,
+        file: *mut FILE,
+        level: u8,
+        strategy: CompressionStrategy,
+    ) -> Result<(), GzError> {
+        let compressor = match self.compressor.as_mut() {
+            Some(compressor) => compressor,
+            None => return Ok(()),
+        };
+        if self.finished {
+            // Nothing is pending, so start the next gzip member with the new settings.
+            compressor.reset();
+            self.finished = false;
+        }
+        loop {
+            let res = deflate_params(compressor, &mut self.out_buf, level, strategy);
+            GzWriter::write_raw(file, &self.out_buf[..res.bytes_written])?;
+            match res.status {
+                Ok(_) => return Ok(()),
+                Err(MZError::Buf) => (),
+                Err(_) => return Err(GzError::new(MZError::Stream, "internal error")),
+            }
+        }
+    }
+}
+
+enum GzState {
+    Read(GzReader),
+    Write(GzWriter),
+}
+
+/// A gzip file opened for reading or writing.
+#[allow(bad_style)]
+pub struct mz_gzFile_s {
+    file: *mut FILE,
+    /// Offset of the start of the data in the file, or -1 if it is not known.
+    start: i64,
+    /// Position in the uncompressed data.
+    pos: u64,
+    state: GzState,
+    /// The last error, as returned by `mz_gzerror`.
+    err: c_int,
+    msg: Option<CString>,
+}
+
+impl mz_gzFile_s {
+    fn set_error(&mut self, err: GzError) {
+        self.err = err.code as c_int;
+        self.msg = CString::new(err.msg.into_owned()).ok();
+    }
+
+    /// Whether an error that stops further reading and writing has occurred.
+    ///
+    /// An unexpected end of the file when reading still allows seeking back.
+    fn failed(&self) -> bool {
+        self.err != MZStatus::Ok as c_int && self.err != MZError::Buf as c_int
+    }
+
+    /// Go back to the start of the data. Only possible when reading.
+    unsafe fn rewind(&mut self) -> Result<(), GzError> {
+        if !matches!(self.state, GzState::Read(_)) || self.start < 0 {
+            return Err(GzError::new(MZError::Stream, "can't rewind"));
+        }
+        if fseek64(self.file, self.start, libc::SEEK_SET) != 0 {
+            return Err(GzError::errno());
+        }
+        self.state = GzState::Read(GzReader::new());
+        self.pos = 0;
+        self.err = MZStatus::Ok as c_int;
+        self.msg = None;
+        Ok(())
+    }
+}
+
+/// Parsed `mode` argument of `mz_gzopen` and `mz_gzdopen`.
+struct GzMode {
+    read: bool,
+    append: bool,
+    exclusive: bool,
+    transparent: bool,
+    level: i32,
+    strategy: CompressionStrategy,
+}
+
+impl GzMode {
+    /// Parse a zlib style mode, e.g. `"rb"` or `"wb9"`.
+    ///
+    /// Returns `None` if the mode is invalid, or asks to read and write the same file.
+    fn parse(mode: &CStr) -> Option<GzMode> {
+        let mut read_or_append = None;
+        let mut parsed = GzMode {
+            read: false,
+            append: false,
+            exclusive: false,
+            transparent: false,
+            level: CompressionLevel::DefaultCompression as i32,
+            strategy: CompressionStrategy::Default,
+        };
+        for &c in mode.to_bytes() {
+            match c {
+                b'0'..=b'9' => parsed.level = i32::from(c - b'0'),
+                b'r' => read_or_append = Some((true, false)),
+                b'w' => read_or_append = Some((false, false)),
+                b'a' => read_or_append = Some((false, true)),
+                b'+' => return None,
+                b'x' => parsed.exclusive = true,
+                b'f' => parsed.strategy = CompressionStrategy::Filtered,
+                b'h' => parsed.strategy = CompressionStrategy::HuffmanOnly,
+                b'R' => parsed.strategy = CompressionStrategy::RLE,
+                b'F' => parsed.strategy = CompressionStrategy::Fixed,
+                b'T' => parsed.transparent = true,
+                _ => (),
+            }
+        }
+        let (read, append) = read_or_append?;
+        parsed.read = read;
+        parsed.append = append;
+        Some(parsed)
+    }
+
+    /// The mode to open the underlying file with.
+    fn fopen_mode(&self, allow_exclusive: bool) -> &'static [u8] {
+        match (self.read, self.append, self.exclusive && allow_exclusive) {
+            (true, _, _) => b"rb\0",
+            (false, true, _) => b"ab\0",
+            (false, false, true) => b"wbx\0",
+            (false, false, false) => b"wb\0",
+        }
+    }
+
+    /// Create the state of a file opened with this mode.
+    fn new_file(&self, file: *mut FILE, start: i64) -> mz_gzFile_s {
+        let state = if self.read {
+            GzState::Read(GzReader::new())
+        } else {
+            let compressor = if self.transparent {
+                None
+            } else {
+                let flags = create_comp_flags_from_zip_params(
+                    self.level,
+                    MZ_DEFAULT_WINDOW_BITS + 16,
+                    self.strategy as i32,
+                );
+                Some(Box::new(CompressorOxide::new(flags)))
+            };
+            GzState::Write(GzWriter {
+                compressor,
+                finished: false,
+                out_buf: vec![0; GZ_BUFFER_SIZE],
+            })
+        };
+        mz_gzFile_s {
+            file,
+            start,
+            pos: 0,
+            state,
+            err: MZStatus::Ok as c_int,
+            msg: None,
+        }
+    }
+}
+
+/// Open a gz file on the C stream `file`, which is closed if that fails.
+unsafe fn open_file(file: *mut FILE, mode: &GzMode) -> mz_gzFile {
+    if file.is_null() {
+        return ptr::null_mut();
+    }
+    let start = ftell64(file);
+    match catch_unwind(AssertUnwindSafe(|| mode.new_file(file, start))) {
+        Ok(gz) => Box::into_raw(Box::new(gz)),
+        Err(_) => {
+            println!("FATAL ERROR: Caught panic!");
+            libc::fclose(file);
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Run `f` on the file pointed to by `file`, storing the error it returns in the file and
+/// returning `failed` in that case.
+unsafe fn with_gz<T, F>(file: mz_gzFile, failed: T, f: F) -> T
+where
+    F: FnOnce(&mut mz_gzFile_s) -> Result<T, GzError>,
+{
+    let gz = match file.as_mut() {
+        Some(gz) => gz,
+        None => return failed,
+    };
+    // Make sure we catch a potential panic, as this is called from C.
+    match catch_unwind(AssertUnwindSafe(|| f(gz))) {
+        Ok(Ok(res)) => res,
+        Ok(Err(err)) => {
+            gz.set_error(err);
+            failed
+        }
+        Err(_) => {
+            println!("FATAL ERROR: Caught panic!");
+            failed
+        }
+    }
+}
+
+/// Read up to `len` bytes of uncompressed data from `file` to `buf`.
+///
+/// Returns the number of bytes read, or `None` if the file is not open for reading or a
+/// previous error stopped reading. If some data was read before an error, it is returned
+/// and the error is kept for `mz_gzerror`.
+unsafe fn read(gz: &mut mz_gzFile_s, buf: &mut [u8], line: bool) -> Result<Option<usize>, GzError> {
+    let failed = gz.failed();
+    let reader = match &mut gz.state {
+        GzState::Read(reader) if !failed => reader,
+        _ => return Ok(None),
+    };
+    let mut got = 0;
+    let res = if line {
+        reader.read_line(gz.file, buf, &mut got)
+    } else {
+        reader.read(gz.file, buf, &mut got)
+    };
+    gz.pos += got as u64;
+    match res {
+        Ok(()) => Ok(Some(got)),
+        Err(err) if got > 0 => {
+            gz.set_error(err);
+            Ok(Some(got))
+        }
+        Err(err) => Err(err),
+    }
+}
+
+/// Compress `data` and write it to `file`.
+///
+/// Returns `false` without writing anything if the file is not open for writing, or a
+/// previous error stopped writing.
+unsafe fn write(gz: &mut mz_gzFile_s, data: &[u8]) -> Result<bool, GzError> {
+    let failed = gz.failed();
+    let writer = match &mut gz.state {
+        GzState::Write(writer) if !failed => writer,
+        _ => return Ok(false),
+    };
+    writer.write(gz.file, data)?;
+    gz.pos += data.len() as u64;
+    Ok(true)
+}
+
+unmangle!(
+    /// Open the gzip file at `path` for reading or writing.
+    ///
+    /// `mode` is `"r"` to read, `"w"` to write or `"a"` to append a new gzip member. When
+    /// writing, it can also hold a compression level digit, a strategy (`f`, `h`, `R` or
+    /// `F`), `T` to write the data without compressing it, and `x` to fail if the file exists.
+    ///
+    /// Returns null if the file can't be opened or the mode is invalid.
+    pub unsafe extern "C" fn mz_gzopen(path: *const c_char, mode: *const c_char) -> mz_gzFile {
+        if path.is_null() || mode.is_null() {
+            return ptr::null_mut();
+        }
+        let mode = match GzMode::parse(CStr::from_ptr(mode)) {
+            Some(mode) => mode,
+            None => return ptr::null_mut(),
+        };
+        let file = libc::fopen(path, mode.fopen_mode(true).as_ptr() as *const c_char);
+        open_file(file, &mode)
+    }
+
+    /// Open a gz file on the file descriptor `fd`, with a mode like `mz_gzopen`.
+    ///
+    /// The file descriptor is closed by `mz_gzclose`. Returns null if it can't be used with
+    /// the mode or the mode is invalid.
+    pub unsafe extern "C" fn mz_gzdopen(fd: c_int, mode: *const c_char) -> mz_gzFile {
+        if fd < 0 || mode.is_null() {
+            return ptr::null_mut();
+        }
+        let mode = match GzMode::parse(CStr::from_ptr(mode)) {
+            Some(mode) => mode,
+            None => return ptr::null_mut(),
+        };
+        let file = libc::fdopen(fd, mode.fopen_mode(false).as_ptr() as *const c_char);
+        open_file(file, &mode)
+    }
+
+    /// Read up to `len` bytes of uncompressed data into `buf`.
+    ///
+    /// Returns the number of bytes read, which is less than `len` only at the end of the
+    /// data or after an error, or -1 if nothing could be read because of an error.
+    pub unsafe extern "C" fn mz_gzread(file: mz_gzFile, buf: *mut c_void, len: c_uint) -> c_int {
+        with_gz(file, -1, |gz| {
+            if c_int::try_from(len).is_err() || (buf.is_null() && len != 0) {
+                return Err(GzError::new(
+                    MZError::Stream,
+                    "request does not fit in an int",
+                ));
+            }
+            let buf = if len == 0 {
+                &mut [][..]
+            } else {
+                slice::from_raw_parts_mut(buf as *mut u8, len as usize)
+            };
+            Ok(read(gz, buf, false)?.map_or(-1, |got| got as c_int))
+        })
+    }
+
+    /// Compress and write `len` bytes from `buf`.
+    ///
+    /// Returns the number of bytes written, or 0 on error.
+    pub unsafe extern "C" fn mz_gzwrite(file: mz_gzFile, buf: *const c_void, len: c_uint) -> c_int {
+        with_gz(file, 0, |gz| {
+            if c_int::try_from(len).is_err() || (buf.is_null() && len != 0) {
+                return Err(GzError::new(
+                    MZError::Data,
+                    "requested length does not fit in int",
+                ));
+            }
+            let data = if len == 0 {
+                &[][..]
+            } else {
+                slice::from_raw_parts(buf as *const u8, len as usize)
+            };
+            Ok(if write(gz, data)? { len as c_int } else { 0 })
+        })
+    }
+
+    /// Compress and write the zero-terminated string `s`, without the terminator.
+    ///
+    /// Returns the number of bytes written, or -1 on error.
+    pub unsafe extern "C" fn mz_gzputs(file: mz_gzFile, s: *const c_char) -> c_int {
+        if s.is_null() {
+            return -1;
+        }
+        with_gz(file, -1, |gz| {
+            let data = CStr::from_ptr(s).to_bytes();
+            let len = c_int::try_from(data.len())
+                .map_err(|_| GzError::new(MZError::Stream, "string length does not fit in int"))?;
+            Ok(if write(gz, data)? { len } else { -1 })
+        })
+    }
+
+    /// Compress and write the byte `c`.
+    ///
+    /// Returns the byte written, or -1 on error.
+    pub unsafe extern "C" fn mz_gzputc(file: mz_gzFile, c: c_int) -> c_int {
+        with_gz(file, -1, |gz| {
+            let c = c as u8;
+            Ok(if write(gz, &[c])? { c_int::from(c) } else { -1 })
+        })
+    }
+
+    /// Read a line of at most `len - 1` bytes into `buf`, stopping after a newline, and
+    /// terminate it with a zero byte.
+    ///
+    /// Returns `buf`, or null if nothing could be read because of the end of the data or
+    /// an error.
+    pub unsafe extern "C" fn mz_gzgets(
+        file: mz_gzFile,
+        buf: *mut c_char,
+        len: c_int,
+    ) -> *mut c_char {
+        if buf.is_null() || len < 1 {
+            return ptr::null_mut();
+        }
+        with_gz(file, ptr::null_mut(), |gz| {
+            let line = slice::from_raw_parts_mut(buf as *mut u8, len as usize - 1);
+            let got = read(gz, line, true)?.unwrap_or(0);
+            *buf.add(got) = 0;
+            Ok(if got == 0 { ptr::null_mut() } else { buf })
+        })
+    }
+
+    /// Read one byte of uncompressed data.
+    ///
+    /// Returns the byte, or -1 at the end of the data or on error.
+    pub unsafe extern "C" fn mz_gzgetc(file: mz_gzFile) -> c_int {
+        with_gz(file, -1, |gz| {
+            let mut c = [0];
+            let got = read(gz, &mut c, false)?.unwrap_or(0);
+            Ok(if got == 0 { -1 } else { c_int::from(c[0]) })
+        })
+    }
+
+    /// Flush the data written so far with the flush mode `flush`, and flush the underlying
+    /// file.
+    ///
+    /// `MZ_FINISH` ends the current gzip member, so data written afterwards goes into a new
+    /// one. Returns `MZ_OK` on success, or an error code.
+    pub unsafe extern "C" fn mz_gzflush(file: mz_gzFile, flush: c_int) -> c_int {
+        with_gz(file, MZError::Stream as c_int, |gz| {
+            let flush = MZFlush::new(flush)
+                .map_err(|_| GzError::new(MZError::Stream, "invalid flush mode"))?;
+            let failed = gz.failed();
+            match &mut gz.state {
+                GzState::Write(writer) if !failed => writer.flush(gz.file, flush)?,
+                GzState::Write(_) => return Ok(gz.err),
+                GzState::Read(_) => return Ok(MZError::Stream as c_int),
+            }
+            Ok(MZStatus::Ok as c_int)
+        })
+    }
+
+    /// Move to `offset` in the uncompressed data, relative to the start if `whence` is
+    /// `SEEK_SET` or to the current position if it is `SEEK_CUR`.
+    ///
+    /// When reading, seeking backwards restarts decompression from the start of the file.
+    /// When writing, only seeking forwards is possible, which writes zeros.
+    /// Returns the new position, or -1 on error.
+    pub unsafe extern "C" fn mz_gzseek(file: mz_gzFile, offset: c_long, whence: c_int) -> c_long {
+        with_gz(file, -1, |gz| {
+            if gz.failed() {
+                return Ok(-1);
+            }
+            let target = match whence {
+                libc::SEEK_SET => i128::from(offset),
+                libc::SEEK_CUR => i128::from(gz.pos) + i128::from(offset),
+                _ => return Ok(-1),
+            };
+            let target = match u64::try_from(target) {
+                Ok(target) => target,
+                Err(_) => return Ok(-1),
+            };
+
+            if let GzState::Read(_) = gz.state {
+                if target < gz.pos {
+                    gz.rewind()?;
+                }
+            }
+            match &mut gz.state {
+                GzState::Read(reader) => {
+                    let mut skipped = 0;
+                    let res = reader.skip(gz.file, target - gz.pos, &mut skipped);
+                    gz.pos += skipped;
+                    res?;
+                }
+                GzState::Write(_) => {
+                    if target < gz.pos {
+                        return Ok(-1);
+                    }
+                    let zeros = [0; 4096];
+                    while gz.pos < target {
+                        let len = cmp::min(target - gz.pos, zeros.len() as u64) as usize;
+                        if !write(gz, &zeros[..len])? {
+                            return Ok(-1);
+                        }
+                    }
+                }
+            }
+            Ok(c_long::try_from(gz.pos).unwrap_or(-1))
+        })
+    }
+
+    /// Get the position in the uncompressed data, or -1 on error.
+    pub unsafe extern "C" fn mz_gztell(file: mz_gzFile) -> c_long {
+        with_gz(file, -1, |gz| Ok(c_long::try_from(gz.pos).unwrap_or(-1)))
+    }
+
+    /// Go back to the start of a file opened for reading.
+    ///
+    /// Returns 0 on success, or -1 on error.
+    pub unsafe extern "C" fn mz_gzrewind(file: mz_gzFile) -> c_int {
+        with_gz(file, -1, |gz| gz.rewind().map(|()| 0))
+    }
+
+    /// Returns 1 if a read has reached the end of the uncompressed data, otherwise 0.
+    pub unsafe extern "C" fn mz_gzeof(file: mz_gzFile) -> c_int {
+        with_gz(file, 0, |gz| match &gz.state {
+            GzState::Read(reader) => Ok(c_int::from(reader.past_end)),
+            GzState::Write(_) => Ok(0),
+        })
+    }
+
+    /// Returns 1 if the file is read or written without decompressing or compressing the
+    /// data, otherwise 0.
+    pub unsafe extern "C" fn mz_gzdirect(file: mz_gzFile) -> c_int {
+        with_gz(file, 0, |gz| match &mut gz.state {
+            GzState::Read(reader) => {
+                reader.look(gz.file)?;
+                Ok(c_int::from(reader.direct))
+            }
+            GzState::Write(writer) => Ok(c_int::from(writer.compressor.is_none())),
+        })
+    }
+
+    /// Change the compression level (0-10) and strategy of a file opened for writing.
+    ///
+    /// Data written so far is compressed with the old settings first.
+    /// Returns `MZ_OK` on success, or an error code.
+    pub unsafe extern "C" fn mz_gzsetparams(
+        file: mz_gzFile,
+        level: c_int,
+        strategy: c_int,
+    ) -> c_int {
+        with_gz(file, MZError::Stream as c_int, |gz| {
+            let level = match level {
+                -1 => CompressionLevel::DefaultLevel as u8,
+                0..=10 => level as u8,
+                _ => return Ok(MZError::Stream as c_int),
+            };
+            let strategy = match strategy {
+                0 => CompressionStrategy::Default,
+                1 => CompressionStrategy::Filtered,
+                2 => CompressionStrategy::HuffmanOnly,
+                3 => CompressionStrategy::RLE,
+                4 => CompressionStrategy::Fixed,
+                _ => return Ok(MZError::Stream as c_int),
+            };
+            let failed = gz.failed();
+            match &mut gz.state {
+                GzState::Write(writer) if !failed => writer.set_params(gz.file, level, strategy)?,
+                GzState::Write(_) => return Ok(gz.err),
+                GzState::Read(_) => return Ok(MZError::Stream as c_int),
+            }
+            Ok(MZStatus::Ok as c_int)
+        })
+    }
+
+    /// Get a description of the last error, and store its code in `errnum` if it isn't null.
+    ///
+    /// Returns an empty string if there was no error, or null if `file` is null.
+    pub unsafe extern "C" fn mz_gzerror(file: mz_gzFile, errnum: *mut c_int) -> *const c_char {
+        match file.as_ref() {
+            None => {
+                if let Some(errnum) = errnum.as_mut() {
+                    *errnum = MZError::Stream as c_int;
+                }
+                ptr::null()
+            }
+            Some(gz) => {
+                if let Some(errnum) = errnum.as_mut() {
+                    *errnum = gz.err;
+                }
+                gz.msg
+                    .as_ref()
+                    .map_or(b"\0".as_ptr() as *const c_char, |msg| msg.as_ptr())
+            }
+        }
+    }
+
+    /// Clear the error and end of file state of `file`.
+    pub unsafe extern "C" fn mz_gzclearerr(file: mz_gzFile) {
+        if let Some(gz) = file.as_mut() {
+            gz.err = MZStatus::Ok as c_int;
+            gz.msg = None;
+            if let GzState::Read(reader) = &mut gz.state {
+                reader.past_end = false;
+                libc::clearerr(gz.file);
+            }
+        }
+    }
+
+    /// Finish writing if the file was opened for writing, then close it and free its state.
+    ///
+    /// Returns `MZ_OK` on success, `MZ_BUF_ERROR` if the data read ended unexpectedly,
+    /// `MZ_ERRNO` if closing the file failed, or the error that occurred when finishing.
+    pub unsafe extern "C" fn mz_gzclose(file: mz_gzFile) -> c_int {
+        if file.is_null() {
+            return MZError::Stream as c_int;
+        }
+        let mut ret = with_gz(file, -1, |gz| match &mut gz.state {
+            GzState::Write(writer) if gz.err == MZStatus::Ok as c_int => {
+                writer.flush(gz.file, MZFlush::Finish)?;
+                Ok(MZStatus::Ok as c_int)
+            }
+            GzState::Write(_) => Ok(gz.err),
+            GzState::Read(_) if gz.err == MZError::Buf as c_int => Ok(gz.err),
+            GzState::Read(_) => Ok(MZStatus::Ok as c_int),
+        });
+        let gz = Box::from_raw(file);
+        if ret == -1 {
+            ret = gz.err;
+        }
+        if libc::fclose(gz.file) != 0 && ret == MZStatus::Ok as c_int {
+            ret = MZError::ErrNo as c_int;
+        }
+        ret
+    }
+);
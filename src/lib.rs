@@ -62,9 +62,9 @@ mod libc {
 extern crate miniz_oxide;
 
 use std::panic::{catch_unwind, AssertUnwindSafe};
-use std::{cmp, ptr};
+use std::{cmp, ptr, slice};
 
-use libc::{c_int, c_uint, c_ulong};
+use libc::{c_int, c_uint, c_ulong, c_void};
 
 use miniz_oxide::deflate::core::CompressionStrategy;
 use miniz_oxide::deflate::CompressionLevel;
@@ -80,7 +80,38 @@ mod tinfl;
 
 mod c_export;
 pub use crate::c_export::*;
+#[cfg(not(any(
+    feature = "libc_stub",
+    all(target_arch = "wasm32", not(target_os = "emscripten"))
+)))]
+mod cfile;
+#[cfg(not(any(
+    feature = "libc_stub",
+    all(target_arch = "wasm32", not(target_os = "emscripten"))
+)))]
+mod gz;
+#[cfg(not(any(
+    feature = "libc_stub",
+    all(target_arch = "wasm32", not(target_os = "emscripten"))
+)))]
+pub use crate::gz::*;
+#[cfg(all(
+    feature = "miniz_zip",
+    not(any(
+        feature = "libc_stub",
+        all(target_arch = "wasm32", not(target_os = "emscripten"))
+    ))
+))]
+mod zip;
 pub use crate::tdef::Compressor as tdefl_compressor;
+#[cfg(all(
+    feature = "miniz_zip",
+    not(any(
+        feature = "libc_stub",
+        all(target_arch = "wasm32", not(target_os = "emscripten"))
+    ))
+))]
+pub use crate::zip::*;
 
 pub const MZ_DEFLATED: c_int = 8;
 pub use miniz_oxide::MZ_DEFAULT_WINDOW_BITS;
@@ -129,10 +160,21 @@ oxidize!(mz_deflate, mz_deflate_oxide;
          flush: c_int);
 oxidize!(mz_deflateEnd, mz_deflate_end_oxide;);
 oxidize!(mz_deflateReset, mz_deflate_reset_oxide;);
+oxidize!(mz_deflateParams, mz_deflate_params_oxide;
+         level: c_int, strategy: c_int);
+oxidize!(mz_deflatePrime, mz_deflate_prime_oxide;
+         bits: c_int, value: c_int);
 
 oxidize!(mz_inflate, mz_inflate_oxide;
          flush: c_int);
 oxidize!(mz_inflateEnd, mz_inflate_end_oxide;);
+oxidize!(mz_inflateReset, mz_inflate_reset_oxide;);
+oxidize!(mz_inflateReset2, mz_inflate_reset2_oxide;
+         window_bits: c_int);
+oxidize!(mz_inflateSync, mz_inflate_sync_oxide;);
+oxidize!(mz_inflatePrime, mz_inflate_prime_oxide;
+         bits: c_int, value: c_int);
+oxidize!(mz_inflateBackEnd, mz_inflate_back_end_oxide;);
 
 unmangle!(
     pub unsafe extern "C" fn mz_deflateInit(stream: *mut mz_stream, level: c_int) -> c_int {
@@ -185,6 +227,145 @@ unmangle!(
         }
     }
 
+    pub unsafe extern "C" fn mz_deflateSetDictionary(
+        stream: *mut mz_stream,
+        dictionary: *const u8,
+        dict_length: c_uint,
+    ) -> c_int {
+        if dictionary.is_null() && dict_length != 0 {
+            return MZError::Param as c_int;
+        }
+        match stream.as_mut() {
+            None => MZError::Stream as c_int,
+            Some(stream) => {
+                // Make sure we catch a potential panic, as
+                // this is called from C.
+                match catch_unwind(AssertUnwindSafe(|| match StreamOxide::try_new(stream) {
+                    Ok(mut stream_oxide) => {
+                        let dictionary = if dict_length == 0 {
+                            &[][..]
+                        } else {
+                            slice::from_raw_parts(dictionary, dict_length as usize)
+                        };
+                        let status = mz_deflate_set_dictionary_oxide(&mut stream_oxide, dictionary);
+                        *stream = stream_oxide.into_mz_stream();
+                        as_c_return_code(status)
+                    }
+                    Err(e) => e as c_int,
+                })) {
+                    Ok(res) => res,
+                    Err(_) => {
+                        println!("FATAL ERROR: Caught panic!");
+                        MZError::Stream as c_int
+                    }
+                }
+            }
+        }
+    }
+
+    pub unsafe extern "C" fn mz_inflateSetDictionary(
+        stream: *mut mz_stream,
+        dictionary: *const u8,
+        dict_length: c_uint,
+    ) -> c_int {
+        if dictionary.is_null() && dict_length != 0 {
+            return MZError::Param as c_int;
+        }
+        match stream.as_mut() {
+            None => MZError::Stream as c_int,
+            Some(stream) => {
+                // Make sure we catch a potential panic, as
+                // this is called from C.
+                match catch_unwind(AssertUnwindSafe(|| match StreamOxide::try_new(stream) {
+                    Ok(mut stream_oxide) => {
+                        let dictionary = if dict_length == 0 {
+                            &[][..]
+                        } else {
+                            slice::from_raw_parts(dictionary, dict_length as usize)
+                        };
+                        let status = mz_inflate_set_dictionary_oxide(&mut stream_oxide, dictionary);
+                        *stream = stream_oxide.into_mz_stream();
+                        as_c_return_code(status)
+                    }
+                    Err(e) => e as c_int,
+                })) {
+                    Ok(res) => res,
+                    Err(_) => {
+                        println!("FATAL ERROR: Caught panic!");
+                        MZError::Stream as c_int
+                    }
+                }
+            }
+        }
+    }
+
+    /// Set the header to write when compressing with the gzip wrapper.
+    ///
+    /// The fields of `head` are read right away, so it doesn't have to stay valid.
+    pub unsafe extern "C" fn mz_deflateSetHeader(
+        stream: *mut mz_stream,
+        head: *const mz_gz_header,
+    ) -> c_int {
+        let (stream, head) = match (stream.as_mut(), head.as_ref()) {
+            (Some(stream), Some(head)) => (stream, head),
+            _ => return MZError::Stream as c_int,
+        };
+        // Make sure we catch a potential panic, as
+        // this is called from C.
+        match catch_unwind(AssertUnwindSafe(|| match StreamOxide::try_new(stream) {
+            Ok(mut stream_oxide) => {
+                let status = mz_deflate_set_header_oxide(&mut stream_oxide, head.to_gz_header());
+                *stream = stream_oxide.into_mz_stream();
+                as_c_return_code(status)
+            }
+            Err(e) => e as c_int,
+        })) {
+            Ok(res) => res,
+            Err(_) => {
+                println!("FATAL ERROR: Caught panic!");
+                MZError::Stream as c_int
+            }
+        }
+    }
+
+    /// Have `mz_inflate` fill in `head` with the gzip header when it has been read.
+    ///
+    /// `head` must stay valid until then, or until the stream is reset or ended.
+    pub unsafe extern "C" fn mz_inflateGetHeader(
+        stream: *mut mz_stream,
+        head: *mut mz_gz_header,
+    ) -> c_int {
+        match stream.as_mut() {
+            None => MZError::Stream as c_int,
+            Some(stream) => {
+                // Make sure we catch a potential panic, as
+                // this is called from C.
+                match catch_unwind(AssertUnwindSafe(|| match StreamOxide::try_new(stream) {
+                    Ok(mut stream_oxide) => {
+                        let status = mz_inflate_get_header_oxide(&mut stream_oxide, head);
+                        *stream = stream_oxide.into_mz_stream();
+                        as_c_return_code(status)
+                    }
+                    Err(e) => e as c_int,
+                })) {
+                    Ok(res) => res,
+                    Err(_) => {
+                        println!("FATAL ERROR: Caught panic!");
+                        MZError::Stream as c_int
+                    }
+                }
+            }
+        }
+    }
+
+    pub unsafe extern "C" fn mz_deflateCopy(dest: *mut mz_stream, source: *mut mz_stream) -> c_int {
+        copy_stream(dest, source, mz_deflate_copy_oxide)
+    }
+
+    pub unsafe extern "C" fn mz_inflateCopy(dest: *mut mz_stream, source: *mut mz_stream) -> c_int {
+        copy_stream(dest, source, mz_inflate_copy_oxide)
+    }
+
     pub unsafe extern "C" fn mz_inflateInit2(stream: *mut mz_stream, window_bits: c_int) -> c_int {
         match stream.as_mut() {
             None => MZError::Stream as c_int,
@@ -210,6 +391,76 @@ unmangle!(
         }
     }
 
+    /// Initialize a stream for `mz_inflateBack`, using `window` as the sliding window.
+    ///
+    /// `window` must point to `1 << window_bits` bytes, and `window_bits` must be in `8..=15`.
+    /// The window has to stay valid until `mz_inflateBackEnd` is called.
+    pub unsafe extern "C" fn mz_inflateBackInit(
+        stream: *mut mz_stream,
+        window_bits: c_int,
+        window: *mut u8,
+    ) -> c_int {
+        match stream.as_mut() {
+            None => MZError::Stream as c_int,
+            Some(stream) => {
+                stream.data_type = StateTypeEnum::InflateBackType;
+                // Make sure we catch a potential panic, as
+                // this is called from C.
+                match catch_unwind(AssertUnwindSafe(|| match StreamOxide::try_new(stream) {
+                    Ok(mut stream_oxide) => {
+                        let status =
+                            mz_inflate_back_init_oxide(&mut stream_oxide, window_bits, window);
+                        *stream = stream_oxide.into_mz_stream();
+                        as_c_return_code(status)
+                    }
+                    Err(e) => e as c_int,
+                })) {
+                    Ok(res) => res,
+                    Err(_) => {
+                        println!("FATAL ERROR: Caught panic!");
+                        MZError::Stream as c_int
+                    }
+                }
+            }
+        }
+    }
+
+    /// Decompress a raw deflate stream, pulling input with `in_fn` and pushing output to
+    /// `out_fn`, using only the window passed to `mz_inflateBackInit` as the output buffer.
+    ///
+    /// Input in `next_in` is used first. Returns `MZ_STREAM_END` on success, and
+    /// `MZ_BUF_ERROR` if `in_fn` returned no input, in which case `next_in` is set to null,
+    /// or if `out_fn` returned a non-zero value. Unused input is left in `next_in`.
+    pub unsafe extern "C" fn mz_inflateBack(
+        stream: *mut mz_stream,
+        in_fn: Option<mz_in_func>,
+        in_desc: *mut c_void,
+        out_fn: Option<mz_out_func>,
+        out_desc: *mut c_void,
+    ) -> c_int {
+        let (stream, in_fn, out_fn) = match (stream.as_mut(), in_fn, out_fn) {
+            (Some(stream), Some(in_fn), Some(out_fn)) => (stream, in_fn, out_fn),
+            _ => return MZError::Stream as c_int,
+        };
+        // Make sure we catch a potential panic, as
+        // this is called from C.
+        match catch_unwind(AssertUnwindSafe(|| match StreamOxide::try_new(stream) {
+            Ok(mut stream_oxide) => {
+                let status =
+                    mz_inflate_back_oxide(&mut stream_oxide, in_fn, in_desc, out_fn, out_desc);
+                *stream = stream_oxide.into_mz_stream();
+                as_c_return_code(status)
+            }
+            Err(e) => e as c_int,
+        })) {
+            Ok(res) => res,
+            Err(_) => {
+                println!("FATAL ERROR: Caught panic!");
+                MZError::Stream as c_int
+            }
+        }
+    }
+
     pub unsafe extern "C" fn mz_compress(
         dest: *mut u8,
         dest_len: *mut c_ulong,
@@ -270,26 +521,40 @@ unmangle!(
         source: *const u8,
         source_len: c_ulong,
     ) -> c_int {
-        dest_len
-            .as_mut()
-            .map_or(MZError::Param as c_int, |dest_len| {
-                if buffer_too_large(source_len, *dest_len) {
-                    return MZError::Param as c_int;
-                }
+        let mut source_len = source_len;
+        mz_uncompress2(dest, dest_len, source, &mut source_len)
+    }
 
-                let mut stream: mz_stream = mz_stream {
-                    next_in: source,
-                    avail_in: source_len as c_uint,
-                    next_out: dest,
-                    avail_out: (*dest_len) as c_uint,
-                    data_type: StateTypeEnum::InflateType,
-                    ..Default::default()
-                };
+    /// Like `mz_uncompress`, but `source_len` is a pointer, which is set to the number of
+    /// bytes of input that were used.
+    pub unsafe extern "C" fn mz_uncompress2(
+        dest: *mut u8,
+        dest_len: *mut c_ulong,
+        source: *const u8,
+        source_len: *mut c_ulong,
+    ) -> c_int {
+        let (dest_len, source_len) = match (dest_len.as_mut(), source_len.as_mut()) {
+            (Some(dest_len), Some(source_len)) => (dest_len, source_len),
+            _ => return MZError::Param as c_int,
+        };
+        if buffer_too_large(*source_len, *dest_len) {
+            return MZError::Param as c_int;
+        }
 
-                // We don't expect this to fail since we supply the stream ourselves.
-                let mut stream_oxide = StreamOxide::new(&mut stream);
-                as_c_return_code(mz_uncompress2_oxide(&mut stream_oxide, dest_len))
-            })
+        let mut stream: mz_stream = mz_stream {
+            next_in: source,
+            avail_in: (*source_len) as c_uint,
+            next_out: dest,
+            avail_out: (*dest_len) as c_uint,
+            data_type: StateTypeEnum::InflateType,
+            ..Default::default()
+        };
+
+        // We don't expect this to fail since we supply the stream ourselves.
+        let mut stream_oxide = StreamOxide::new(&mut stream);
+        let status = mz_uncompress2_oxide(&mut stream_oxide, dest_len);
+        *source_len = stream_oxide.total_in;
+        as_c_return_code(status)
     }
 
     pub extern "C" fn mz_compressBound(source_len: c_ulong) -> c_ulong {
@@ -297,6 +562,51 @@ unmangle!(
     }
 );
 
+/// Make `dest` a copy of `source`, using `copy_func` to copy the internal state.
+///
+/// `dest` is overwritten without being read, so it does not have to be initialized. Both
+/// streams share the same input and output buffers after the copy.
+unsafe fn copy_stream<ST: StateType>(
+    dest: *mut mz_stream,
+    source: *mut mz_stream,
+    copy_func: for<'a, 'io> fn(
+        &'a mut StreamOxide<'io, ST>,
+    ) -> Result<StreamOxide<'io, ST>, MZError>,
+) -> c_int {
+    if dest.is_null() || dest == source {
+        return MZError::Stream as c_int;
+    }
+    match source.as_mut() {
+        None => MZError::Stream as c_int,
+        Some(source) => {
+            // Make sure we catch a potential panic, as
+            // this is called from C.
+            match catch_unwind(AssertUnwindSafe(|| match StreamOxide::try_new(source) {
+                Ok(mut stream_oxide) => {
+                    let copy = copy_func(&mut stream_oxide);
+                    *source = stream_oxide.into_mz_stream();
+                    match copy {
+                        Ok(copy) => {
+                            ptr::write(dest, copy.into_mz_stream());
+                            (*dest).next_out = source.next_out;
+                            (*dest).avail_out = source.avail_out;
+                            MZStatus::Ok as c_int
+                        }
+                        Err(e) => e as c_int,
+                    }
+                }
+                Err(e) => e as c_int,
+            })) {
+                Ok(res) => res,
+                Err(_) => {
+                    println!("FATAL ERROR: Caught panic!");
+                    MZError::Stream as c_int
+                }
+            }
+        }
+    }
+}
+
 #[cfg(target_pointer_width = "64")]
 #[inline]
 fn buffer_too_large(source_len: c_ulong, dest_len: c_ulong) -> bool {
@@ -1,16 +1,30 @@
 //! This module mainly contains functionality replicating the miniz higher level API.
 
+use std::convert::TryFrom;
 use std::default::Default;
-use std::{fmt, mem};
+use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut};
+use std::ptr::{self, NonNull};
+use std::{fmt, mem, slice};
 
-use libc::c_ulong;
+use libc::{c_uint, c_ulong, c_void};
 
+use crate::c_export::{
+    miniz_def_alloc_func, miniz_def_free_func, mz_alloc_callback, mz_alloc_func, mz_free_callback,
+    mz_free_func, mz_gz_header, mz_in_func, mz_out_func,
+};
 use crate::tdef::Compressor;
 use miniz_oxide::deflate::core::{
-    create_comp_flags_from_zip_params, deflate_flags, CompressionStrategy, CompressorOxide,
+    create_comp_flags_from_zip_params, deflate_flags, BufferAllocator, CompressionStrategy,
+    CompressorBuffer, CompressorOxide,
 };
-use miniz_oxide::deflate::stream::deflate;
-use miniz_oxide::inflate::stream::{inflate, InflateState};
+use miniz_oxide::deflate::stream::{deflate, deflate_params};
+use miniz_oxide::deflate::CompressionLevel;
+use miniz_oxide::gzip::GzHeader;
+use miniz_oxide::inflate::core::{decompress, inflate_flags, DecompressorOxide};
+use miniz_oxide::inflate::stream::{inflate, inflate_sync, FullReset, InflateState, MinReset};
+use miniz_oxide::inflate::DataError;
+use miniz_oxide::inflate::TINFLStatus;
 
 use miniz_oxide::*;
 
@@ -19,16 +33,182 @@ const MZ_DEFLATED: i32 = 8;
 pub use miniz_oxide::MZ_ADLER32_INIT;
 
 pub enum InternalState {
-    Inflate(Box<InflateState>),
-    Deflate(Box<Compressor>),
+    /// A decompressor, and the header struct passed to `mz_inflateGetHeader` or null.
+    Inflate(StateBox<InflateState>, *mut mz_gz_header),
+    Deflate(StateBox<Compressor>),
+    InflateBack(StateBox<InflateBackState>),
+}
+
+/// State of a decompressor that uses a window supplied by the caller, and reads and writes
+/// data through callbacks.
+pub struct InflateBackState {
+    decomp: DecompressorOxide,
+    window: *mut u8,
+    window_size: usize,
+}
+
+/// Owning pointer to the internal state, allocated either as a `Box` or with the allocation
+/// functions of the stream.
+pub struct StateBox<T> {
+    ptr: NonNull<T>,
+    /// Functions and opaque pointer the memory was allocated with, `None` if it is a `Box`.
+    funcs: Option<AllocFuncs>,
+    /// Buffers of the state allocated with `funcs`, freed after the state is dropped.
+    buffers: Vec<NonNull<c_void>>,
+}
+
+impl<T> StateBox<T> {
+    fn from_box(value: Box<T>) -> StateBox<T> {
+        StateBox {
+            // Box::into_raw never returns null.
+            ptr: unsafe { NonNull::new_unchecked(Box::into_raw(value)) },
+            funcs: None,
+            buffers: Vec::new(),
+        }
+    }
+
+    /// Get an allocator for buffers that are freed along with the state, or `None` if the
+    /// state is a `Box`, in which case the buffers should be boxes too.
+    fn buffer_allocator(&mut self) -> Option<StateBufferAllocator<'_>> {
+        let funcs = self.funcs?;
+        Some(StateBufferAllocator {
+            funcs,
+            buffers: &mut self.buffers,
+        })
+    }
+}
+
+impl<T> Deref for StateBox<T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        unsafe { self.ptr.as_ref() }
+    }
+}
+
+impl<T> DerefMut for StateBox<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { self.ptr.as_mut() }
+    }
+}
+
+impl<T> Drop for StateBox<T> {
+    fn drop(&mut self) {
+        unsafe {
+            match self.funcs {
+                None => drop(Box::from_raw(self.ptr.as_ptr())),
+                Some(funcs) => {
+                    ptr::drop_in_place(self.ptr.as_ptr());
+                    for buffer in self.buffers.drain(..) {
+                        (funcs.zfree)(funcs.opaque, buffer.as_ptr());
+                    }
+                    (funcs.zfree)(funcs.opaque, self.ptr.as_ptr() as *mut c_void);
+                }
+            }
+        }
+    }
+}
+
+/// Allocation functions and opaque pointer to allocate memory for a state with.
+#[derive(Debug, Copy, Clone)]
+struct AllocFuncs {
+    zalloc: mz_alloc_func,
+    zfree: mz_free_func,
+    opaque: *mut c_void,
+}
+
+impl AllocFuncs {
+    /// Allocate memory for a `T`, returning `None` if the allocation fails or the memory is
+    /// not suitably aligned.
+    unsafe fn alloc<T>(&self) -> Option<NonNull<T>> {
+        let p = NonNull::new((self.zalloc)(self.opaque, 1, mem::size_of::<T>()))?;
+        if p.as_ptr() as usize % mem::align_of::<T>() != 0 {
+            (self.zfree)(self.opaque, p.as_ptr());
+            return None;
+        }
+        Some(p.cast())
+    }
+}
+
+/// Allocator for the buffers of a compressor, which records them in the `StateBox` of the
+/// compressor so they are freed along with it.
+///
+/// The buffers are lent to the compressor for `'static`, which here stands for the lifetime of
+/// the `StateBox`: the compressor can only be reached through it, and is dropped in place
+/// before the buffers are freed.
+struct StateBufferAllocator<'a> {
+    funcs: AllocFuncs,
+    buffers: &'a mut Vec<NonNull<c_void>>,
+}
+
+impl BufferAllocator<'static> for StateBufferAllocator<'_> {
+    fn alloc<T: CompressorBuffer>(&mut self) -> Option<&'static mut T> {
+        unsafe {
+            let p = self.funcs.alloc::<T>()?;
+            self.buffers.push(p.cast());
+            // Buffer types are made up of integers, so zero-filled memory is a valid value.
+            ptr::write_bytes(p.as_ptr() as *mut u8, 0, mem::size_of::<T>());
+            Some(&mut *p.as_ptr())
+        }
+    }
+}
+
+/// The allocation functions and opaque pointer of an `mz_stream`.
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct StreamAllocator {
+    pub zalloc: mz_alloc_callback,
+    pub zfree: mz_free_callback,
+    pub opaque: *mut c_void,
+}
+
+impl Default for StreamAllocator {
+    fn default() -> StreamAllocator {
+        StreamAllocator {
+            zalloc: None,
+            zfree: None,
+            opaque: ptr::null_mut(),
+        }
+    }
+}
+
+impl StreamAllocator {
+    /// Move the value returned by `value` into memory from the allocation functions.
+    ///
+    /// If only one of the functions is set, `malloc` or `free` is used in place of the
+    /// other one, and if neither is, the value is put in a `Box`. `value` is called once the
+    /// memory has been allocated, so it should construct the value directly rather than
+    /// moving a large one that was built earlier. Buffers of the state can be allocated with
+    /// the same functions through [`StateBox::buffer_allocator`].
+    ///
+    /// Returns `MZError::Mem` if the allocation fails.
+    fn alloc<T>(&self, value: impl FnOnce() -> T) -> Result<StateBox<T>, MZError> {
+        if self.zalloc.is_none() && self.zfree.is_none() {
+            return Ok(StateBox::from_box(Box::new(value())));
+        }
+
+        let funcs = AllocFuncs {
+            zalloc: self.zalloc.unwrap_or(miniz_def_alloc_func),
+            zfree: self.zfree.unwrap_or(miniz_def_free_func),
+            opaque: self.opaque,
+        };
+        unsafe {
+            let p = funcs.alloc::<T>().ok_or(MZError::Mem)?;
+            ptr::write(p.as_ptr(), value());
+            Ok(StateBox {
+                ptr: p,
+                funcs: Some(funcs),
+                buffers: Vec::new(),
+            })
+        }
+    }
 }
 
 impl fmt::Debug for InternalState {
     #[cold]
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let name = match &self {
-            InternalState::Inflate(_) => "Decompressor",
+            InternalState::Inflate(..) => "Decompressor",
             InternalState::Deflate(_) => "Compressor",
+            InternalState::InflateBack(_) => "Callback decompressor",
         };
         f.write_str(name)
     }
@@ -43,6 +223,7 @@ pub enum StateTypeEnum {
     None = 0,
     InflateType,
     DeflateType,
+    InflateBackType,
 }
 
 /// Trait used for states that can be carried by BoxedState.
@@ -54,8 +235,8 @@ pub trait StateType {
 impl StateType for InflateState {
     const STATE_TYPE: StateTypeEnum = StateTypeEnum::InflateType;
     fn from_enum(value: &mut InternalState) -> Option<&mut Self> {
-        if let InternalState::Inflate(state) = value {
-            Some(state.as_mut())
+        if let InternalState::Inflate(state, _) = value {
+            Some(state)
         } else {
             None
         }
@@ -66,7 +247,18 @@ impl StateType for Compressor {
     const STATE_TYPE: StateTypeEnum = StateTypeEnum::DeflateType;
     fn from_enum(value: &mut InternalState) -> Option<&mut Self> {
         if let InternalState::Deflate(state) = value {
-            Some(state.as_mut())
+            Some(state)
+        } else {
+            None
+        }
+    }
+}
+
+impl StateType for InflateBackState {
+    const STATE_TYPE: StateTypeEnum = StateTypeEnum::InflateBackType;
+    fn from_enum(value: &mut InternalState) -> Option<&mut Self> {
+        if let InternalState::InflateBack(state) = value {
+            Some(state)
         } else {
             None
         }
@@ -84,7 +276,10 @@ pub struct StreamOxide<'io, ST: StateType> {
     pub(crate) state: Option<Box<InternalState>>,
 
     pub adler: u32,
-    pub(crate) state_type: std::marker::PhantomData<ST>,
+    /// Message describing the last error, including a terminating zero byte.
+    pub(crate) msg: Option<&'static str>,
+    pub(crate) allocator: StreamAllocator,
+    pub(crate) state_type: PhantomData<ST>,
 }
 
 impl<'io, ST: StateType> StreamOxide<'io, ST> {
@@ -93,9 +288,37 @@ impl<'io, ST: StateType> StreamOxide<'io, ST> {
     }
 }
 
-/// Returns true if the window_bits parameter is valid.
-fn invalid_window_bits(window_bits: i32) -> bool {
-    (window_bits != MZ_DEFAULT_WINDOW_BITS) && (-window_bits != MZ_DEFAULT_WINDOW_BITS)
+impl<'io> StreamOxide<'io, InflateState> {
+    /// Get the decompressor and the header struct passed to `mz_inflateGetHeader`.
+    fn inflate_state(&mut self) -> Option<(&mut InflateState, &mut *mut mz_gz_header)> {
+        match self.state.as_deref_mut()? {
+            InternalState::Inflate(state, gz_header) => Some((state, gz_header)),
+            _ => None,
+        }
+    }
+}
+
+/// Get the data format selected by a zlib-style `window_bits` value, or `None` if it is
+/// invalid.
+///
+/// Like in zlib, window bits of 8 to 15 use the zlib wrapper, negative ones a raw stream, and
+/// 16 can be added to them to use the gzip wrapper. The decompressor also accepts 32 added to
+/// the window bits to detect whether the data is gzip, zlib or, unlike in zlib, raw deflate,
+/// and a size of 0 to use the one from the header. Smaller windows than 15 bits are served by
+/// the full 32 KiB window.
+fn window_bits_format(window_bits: i32, inflate: bool) -> Option<DataFormat> {
+    let (format, bits) = match window_bits {
+        -15..=-8 => (DataFormat::Raw, -window_bits),
+        0..=15 => (DataFormat::Zlib, window_bits),
+        16..=31 => (DataFormat::Gzip, window_bits - 16),
+        32..=47 if inflate => (DataFormat::Auto, window_bits - 32),
+        _ => return None,
+    };
+    match bits {
+        8..=MZ_DEFAULT_WINDOW_BITS => Some(format),
+        0 if inflate => Some(format),
+        _ => None,
+    }
 }
 
 /// Try to fully decompress the data provided in the stream struct, with the specified
@@ -141,10 +364,11 @@ pub fn mz_deflate_init_oxide(stream_oxide: &mut StreamOxide<Compressor>, level:
 /// stream_oxide: The stream to be initialized.
 /// level: Compression level (0-10).
 /// method: Compression method. Only `MZ_DEFLATED` is accepted.
-/// window_bits: Number of bits used to represent the compression sliding window.
-///              Only `MZ_DEFAULT_WINDOW_BITS` is currently supported.
+/// window_bits: Number of bits used to represent the compression sliding window, from 8 to
+///              15. Smaller windows than `MZ_DEFAULT_WINDOW_BITS` use the default one.
 ///              A negative value, i.e `-MZ_DEFAULT_WINDOW_BITS` indicates that the stream
-///              should be not be wrapped in a zlib wrapper.
+///              should be not be wrapped in a zlib wrapper, and `MZ_DEFAULT_WINDOW_BITS + 16`
+///              that it should be wrapped in a gzip wrapper instead.
 /// mem_level: Currently unused. Only values from 1 to and including 9 are accepted.
 /// strategy: Compression strategy. See `deflate::CompressionStrategy` for accepted options.
 ///           The default, which is used in most cases, is 0.
@@ -156,20 +380,31 @@ pub fn mz_deflate_init2_oxide(
     mem_level: i32,
     strategy: i32,
 ) -> MZResult {
-    let comp_flags = deflate_flags::TDEFL_COMPUTE_ADLER32
-        | create_comp_flags_from_zip_params(level, window_bits, strategy);
-
     let invalid_level = !(1..=9).contains(&mem_level);
-    if (method != MZ_DEFLATED) || invalid_level || invalid_window_bits(window_bits) {
-        return Err(MZError::Param);
-    }
+    let format = match window_bits_format(window_bits, false) {
+        Some(format) if method == MZ_DEFLATED && !invalid_level => format,
+        _ => return Err(MZError::Param),
+    };
+
+    let comp_flags = deflate_flags::TDEFL_COMPUTE_ADLER32
+        | create_comp_flags_from_zip_params(level, format.to_window_bits(), strategy);
 
-    stream_oxide.adler = MZ_ADLER32_INIT;
+    stream_oxide.adler = if format == DataFormat::Gzip {
+        MZ_CRC32_INIT
+    } else {
+        MZ_ADLER32_INIT
+    };
     stream_oxide.total_in = 0;
     stream_oxide.total_out = 0;
 
-    let mut compr: Box<Compressor> = Box::default();
-    compr.inner = Some(CompressorOxide::new(comp_flags));
+    let mut compr = stream_oxide.allocator.alloc(Compressor::default)?;
+    let inner = match compr.buffer_allocator() {
+        Some(mut buffers) => {
+            CompressorOxide::with_allocator(comp_flags, &mut buffers).ok_or(MZError::Mem)?
+        }
+        None => CompressorOxide::new(comp_flags),
+    };
+    compr.inner = Some(inner);
     stream_oxide.state = Some(Box::new(InternalState::Deflate(compr)));
 
     Ok(MZStatus::Ok)
@@ -201,7 +436,11 @@ pub fn mz_deflate_oxide(stream_oxide: &mut StreamOxide<Compressor>, flush: i32)
     stream_oxide.total_out = stream_oxide
         .total_out
         .wrapping_add(ret.bytes_written as c_ulong);
-    stream_oxide.adler = state.adler32();
+    stream_oxide.adler = match state.inner.as_ref() {
+        // Like zlib, report the crc32 when using the gzip wrapper.
+        Some(compressor) if compressor.data_format() == DataFormat::Gzip => compressor.crc32(),
+        _ => state.adler32(),
+    };
     ret.into()
 }
 
@@ -228,6 +467,148 @@ pub fn mz_deflate_reset_oxide(stream_oxide: &mut StreamOxide<Compressor>) -> MZR
     Ok(MZStatus::Ok)
 }
 
+/// Change the compression level and strategy of the compressor.
+///
+/// Pending data is first compressed with the old settings and flushed to the output.
+///
+/// Returns `MZError::Stream` if the inner stream is missing or the level or strategy is
+/// invalid, `MZError::Buf` if there was not enough output space to flush the pending data,
+/// otherwise `MZStatus::Ok`.
+pub fn mz_deflate_params_oxide(
+    stream_oxide: &mut StreamOxide<Compressor>,
+    level: i32,
+    strategy: i32,
+) -> MZResult {
+    let level = match level {
+        -1 => CompressionLevel::DefaultLevel as u8,
+        0..=10 => level as u8,
+        _ => return Err(MZError::Stream),
+    };
+    let strategy = match strategy {
+        0 => CompressionStrategy::Default,
+        1 => CompressionStrategy::Filtered,
+        2 => CompressionStrategy::HuffmanOnly,
+        3 => CompressionStrategy::RLE,
+        4 => CompressionStrategy::Fixed,
+        _ => return Err(MZError::Stream),
+    };
+
+    let state: &mut Compressor = {
+        let enum_ref = stream_oxide.state.as_mut().ok_or(MZError::Stream)?;
+        StateType::from_enum(enum_ref)
+    }
+    .ok_or(MZError::Stream)?;
+    let compressor = state.inner.as_mut().ok_or(MZError::Stream)?;
+
+    // No output space is needed if there is no pending data to flush.
+    let ret = match stream_oxide.next_out.as_mut() {
+        Some(next_out) => {
+            let ret = deflate_params(compressor, next_out, level, strategy);
+            *next_out = &mut mem::take(next_out)[ret.bytes_written..];
+            ret
+        }
+        None => deflate_params(compressor, &mut [], level, strategy),
+    };
+    stream_oxide.total_out = stream_oxide
+        .total_out
+        .wrapping_add(ret.bytes_written as c_ulong);
+    ret.into()
+}
+
+/// Set a preset dictionary for the compressor.
+///
+/// Must be called after initialization and before any data is compressed.
+/// On success the stream's adler field is set to the adler32 checksum of the dictionary.
+///
+/// Returns `MZError::Stream` if the inner stream is missing, `MZError::Param` if the
+/// compressor has already started or uses the gzip wrapper, otherwise `MZStatus::Ok`.
+pub fn mz_deflate_set_dictionary_oxide(
+    stream_oxide: &mut StreamOxide<Compressor>,
+    dictionary: &[u8],
+) -> MZResult {
+    let state = stream_oxide.state().ok_or(MZError::Stream)?;
+    let compressor = state.inner.as_mut().ok_or(MZError::Stream)?;
+    compressor.set_dictionary(dictionary)?;
+    if let Some(adler) = compressor.dictionary_adler32() {
+        stream_oxide.adler = adler;
+    }
+    Ok(MZStatus::Ok)
+}
+
+/// Insert the lowest `bits` bits of `value` into the output, before the next block.
+///
+/// Returns `MZError::Stream` if the inner stream is missing or `bits` is not in `0..=16`,
+/// `MZError::Param` if the zlib or gzip header has not been written yet or the stream is
+/// finished, `MZError::Buf` if too many bits are waiting to be output, otherwise `MZStatus::Ok`.
+pub fn mz_deflate_prime_oxide(
+    stream_oxide: &mut StreamOxide<Compressor>,
+    bits: i32,
+    value: i32,
+) -> MZResult {
+    let bits = u32::try_from(bits).map_err(|_| MZError::Stream)?;
+    if bits > 16 {
+        return Err(MZError::Stream);
+    }
+    let state = stream_oxide.state().ok_or(MZError::Stream)?;
+    let compressor = state.inner.as_mut().ok_or(MZError::Stream)?;
+    compressor.prime(bits, value as u32)?;
+    Ok(MZStatus::Ok)
+}
+
+/// Set the header to write when using the gzip wrapper.
+///
+/// Returns `MZError::Stream` if the inner stream is missing, the compressor doesn't use the
+/// gzip wrapper, the header has already been written or it is invalid, otherwise
+/// `MZStatus::Ok`.
+pub fn mz_deflate_set_header_oxide(
+    stream_oxide: &mut StreamOxide<Compressor>,
+    header: GzHeader,
+) -> MZResult {
+    let state = stream_oxide.state().ok_or(MZError::Stream)?;
+    let compressor = state.inner.as_mut().ok_or(MZError::Stream)?;
+    if compressor.data_format() != DataFormat::Gzip {
+        return Err(MZError::Stream);
+    }
+    compressor
+        .set_gzip_header(header)
+        .map_err(|_| MZError::Stream)?;
+    Ok(MZStatus::Ok)
+}
+
+/// Create a copy of the stream, with a deep copy of the compressor.
+///
+/// The copy has no output buffer, as it can't be shared between the two streams.
+///
+/// Returns `MZError::Stream` if the inner stream is missing.
+pub fn mz_deflate_copy_oxide<'io>(
+    stream_oxide: &mut StreamOxide<'io, Compressor>,
+) -> Result<StreamOxide<'io, Compressor>, MZError> {
+    let allocator = stream_oxide.allocator;
+    let source = stream_oxide.state().ok_or(MZError::Stream)?;
+    let mut state = allocator.alloc(Compressor::default)?;
+    let inner = match (source.inner.as_ref(), state.buffer_allocator()) {
+        (Some(inner), Some(mut buffers)) => Some(
+            inner
+                .clone_with_allocator(&mut buffers)
+                .ok_or(MZError::Mem)?,
+        ),
+        (inner, _) => inner.cloned(),
+    };
+    state.inner = inner;
+    state.callback = source.callback;
+    Ok(StreamOxide {
+        next_in: stream_oxide.next_in,
+        total_in: stream_oxide.total_in,
+        next_out: None,
+        total_out: stream_oxide.total_out,
+        state: Some(Box::new(InternalState::Deflate(state))),
+        adler: stream_oxide.adler,
+        msg: stream_oxide.msg,
+        allocator,
+        state_type: PhantomData,
+    })
+}
+
 pub fn mz_inflate_init_oxide(stream_oxide: &mut StreamOxide<InflateState>) -> MZResult {
     mz_inflate_init2_oxide(stream_oxide, MZ_DEFAULT_WINDOW_BITS)
 }
@@ -236,27 +617,24 @@ pub fn mz_inflate_init2_oxide(
     stream_oxide: &mut StreamOxide<InflateState>,
     window_bits: i32,
 ) -> MZResult {
-    if invalid_window_bits(window_bits) {
-        return Err(MZError::Param);
-    }
+    let format = window_bits_format(window_bits, true).ok_or(MZError::Param)?;
 
     stream_oxide.adler = 0;
     stream_oxide.total_in = 0;
     stream_oxide.total_out = 0;
 
-    stream_oxide.state = Some(Box::new(InternalState::Inflate(
-        InflateState::new_boxed_with_window_bits(window_bits),
-    )));
+    let mut state = stream_oxide.allocator.alloc(InflateState::default)?;
+    state.reset(format);
+    stream_oxide.state = Some(Box::new(InternalState::Inflate(state, ptr::null_mut())));
 
     Ok(MZStatus::Ok)
 }
 
 pub fn mz_inflate_oxide(stream_oxide: &mut StreamOxide<InflateState>, flush: i32) -> MZResult {
-    let state: &mut InflateState = {
-        let enum_ref = stream_oxide.state.as_mut().ok_or(MZError::Stream)?;
-        StateType::from_enum(enum_ref)
-    }
-    .ok_or(MZError::Stream)?;
+    let (state, gz_header) = match stream_oxide.state.as_deref_mut() {
+        Some(InternalState::Inflate(state, gz_header)) => (state, *gz_header),
+        _ => return Err(MZError::Stream),
+    };
 
     let next_in = stream_oxide.next_in.as_mut().ok_or(MZError::Stream)?;
     let next_out = stream_oxide.next_out.as_mut().ok_or(MZError::Stream)?;
@@ -272,10 +650,180 @@ pub fn mz_inflate_oxide(stream_oxide: &mut StreamOxide<InflateState>, flush: i32
     stream_oxide.total_out = stream_oxide
         .total_out
         .wrapping_add(ret.bytes_written as c_ulong);
-    stream_oxide.adler = state.decompressor().adler32().unwrap_or(0);
+    stream_oxide.adler = if ret.status == Ok(MZStatus::NeedDict) {
+        // Like zlib, report the id of the dictionary that is needed.
+        state.dictionary_id().unwrap_or(0)
+    } else if state.data_format() == DataFormat::Gzip {
+        state.crc32()
+    } else {
+        state.decompressor().adler32().unwrap_or(0)
+    };
+    if ret.status == Err(MZError::Data) {
+        stream_oxide.msg = state.data_error().map(data_error_message);
+    }
+    // The header struct is only written to when the header has been read, or the data
+    // turned out to be zlib.
+    if let Some(gz_header) = unsafe { gz_header.as_mut() } {
+        update_gz_header(state, gz_header);
+    }
+    ret.into()
+}
+
+/// Fill in `gz_header` if it is not done yet and the gzip header has been read, or mark it
+/// with -1 if the data is not gzip.
+fn update_gz_header(state: &InflateState, gz_header: &mut mz_gz_header) {
+    if gz_header.done != 0 {
+        return;
+    }
+    match state.data_format() {
+        DataFormat::Gzip => {
+            if let Some(header) = state.gzip_header() {
+                unsafe { gz_header.fill(header) };
+            }
+        }
+        DataFormat::Auto => (),
+        _ => gz_header.done = -1,
+    }
+}
+
+/// Get the message for `mz_stream.msg` describing `error`, with a terminating zero byte.
+pub(crate) fn data_error_message(error: DataError) -> &'static str {
+    match error {
+        DataError::InvalidHeader => "incorrect header check\0",
+        DataError::InvalidBlockType => "invalid block type\0",
+        DataError::InvalidStoredLength => "invalid stored block lengths\0",
+        DataError::TooManySymbols => "too many length or distance symbols\0",
+        DataError::InvalidCodeLengths => "invalid code lengths set\0",
+        DataError::InvalidRepeat => "invalid bit length repeat\0",
+        DataError::InvalidLiteralLength => "invalid literal/length code\0",
+        DataError::InvalidDistance => "invalid distance code\0",
+        DataError::DistanceTooFarBack => "invalid distance too far back\0",
+        DataError::ChecksumMismatch => "incorrect data check\0",
+        DataError::LengthMismatch => "incorrect length check\0",
+        _ => "invalid data\0",
+    }
+}
+
+/// Provide a preset dictionary to the decompressor.
+///
+/// For zlib streams this must be called after `mz_inflate_oxide` returned `MZStatus::NeedDict`,
+/// for raw deflate streams before any data is decompressed.
+///
+/// Returns `MZError::Stream` if the inner stream is missing, `MZError::Param` if a dictionary
+/// can't be set at this point, `MZError::Data` if the dictionary does not match the one
+/// required by the stream, otherwise `MZStatus::Ok`.
+pub fn mz_inflate_set_dictionary_oxide(
+    stream_oxide: &mut StreamOxide<InflateState>,
+    dictionary: &[u8],
+) -> MZResult {
+    let state = stream_oxide.state().ok_or(MZError::Stream)?;
+    state.set_dictionary(dictionary)?;
+    Ok(MZStatus::Ok)
+}
+
+/// Create a copy of the stream, with a deep copy of the decompressor.
+///
+/// The copy has no output buffer, as it can't be shared between the two streams.
+///
+/// Returns `MZError::Stream` if the inner stream is missing.
+pub fn mz_inflate_copy_oxide<'io>(
+    stream_oxide: &mut StreamOxide<'io, InflateState>,
+) -> Result<StreamOxide<'io, InflateState>, MZError> {
+    let allocator = stream_oxide.allocator;
+    let (state, &mut gz_header) = stream_oxide.inflate_state().ok_or(MZError::Stream)?;
+    let state = allocator.alloc(|| state.clone())?;
+    Ok(StreamOxide {
+        next_in: stream_oxide.next_in,
+        total_in: stream_oxide.total_in,
+        next_out: None,
+        total_out: stream_oxide.total_out,
+        state: Some(Box::new(InternalState::Inflate(state, gz_header))),
+        adler: stream_oxide.adler,
+        msg: stream_oxide.msg,
+        allocator,
+        state_type: PhantomData,
+    })
+}
+
+/// Skip input until the next full flush point, so decompression can continue from there.
+///
+/// Returns `MZError::Stream` if the inner stream is missing, `MZError::Data` if no flush point
+/// was found in the available input, which is then all consumed, `MZError::Buf` if there is
+/// no input, otherwise `MZStatus::Ok`.
+pub fn mz_inflate_sync_oxide(stream_oxide: &mut StreamOxide<InflateState>) -> MZResult {
+    let state: &mut InflateState = {
+        let enum_ref = stream_oxide.state.as_mut().ok_or(MZError::Stream)?;
+        StateType::from_enum(enum_ref)
+    }
+    .ok_or(MZError::Stream)?;
+
+    let ret = match stream_oxide.next_in.as_mut() {
+        Some(next_in) => {
+            let ret = inflate_sync(state, next_in);
+            *next_in = &next_in[ret.bytes_consumed..];
+            ret
+        }
+        None => inflate_sync(state, &[]),
+    };
+    stream_oxide.total_in = stream_oxide
+        .total_in
+        .wrapping_add(ret.bytes_consumed as c_ulong);
     ret.into()
 }
 
+/// Insert the lowest `bits` bits of `value` into the input, to be read before the next byte.
+///
+/// Returns `MZError::Stream` if the inner stream is missing, `bits` is not in `0..=16` or
+/// the stream has ended, `MZError::Buf` if too many bits are buffered, otherwise
+/// `MZStatus::Ok`.
+pub fn mz_inflate_prime_oxide(
+    stream_oxide: &mut StreamOxide<InflateState>,
+    bits: i32,
+    value: i32,
+) -> MZResult {
+    let bits = u32::try_from(bits).map_err(|_| MZError::Stream)?;
+    let state = stream_oxide.state().ok_or(MZError::Stream)?;
+    match state.prime(bits, value as u32) {
+        Err(MZError::Param) => Err(MZError::Stream),
+        res => res.map(|()| MZStatus::Ok),
+    }
+}
+
+/// Fill in `gz_header` with the gzip header when it has been read by `mz_inflate_oxide`.
+///
+/// `gz_header` must stay valid until the header has been read, the stream is reset or it
+/// is ended. Its `done` field is set to 0 here, and to 1 once the header is filled in, or -1
+/// if the format was detected and the data is not gzip.
+///
+/// Returns `MZError::Stream` if the inner stream is missing or does not decompress gzip
+/// data, otherwise `MZStatus::Ok`.
+///
+/// # Safety
+/// `gz_header` must be null or point to a valid `mz_gz_header`, with buffers of at least the
+/// given sizes.
+pub unsafe fn mz_inflate_get_header_oxide(
+    stream_oxide: &mut StreamOxide<InflateState>,
+    gz_header: *mut mz_gz_header,
+) -> MZResult {
+    let (state, header_ptr) = stream_oxide.inflate_state().ok_or(MZError::Stream)?;
+    if !matches!(state.data_format(), DataFormat::Gzip | DataFormat::Auto) {
+        return Err(MZError::Stream);
+    }
+    *header_ptr = gz_header;
+    if let Some(gz_header) = gz_header.as_mut() {
+        gz_header.done = 0;
+        update_gz_header(state, gz_header);
+    }
+    Ok(MZStatus::Ok)
+}
+
+/// Decompress the whole zlib stream in `next_in` to `next_out` in one go.
+///
+/// On success `dest_len` is set to the size of the decompressed data. `total_in` is set to
+/// the number of bytes that were read from the input, whether it succeeded or not.
+///
+/// Returns `MZError::Buf` if the output buffer was too small, and `MZError::Data` if the
+/// input is invalid or incomplete.
 pub fn mz_uncompress2_oxide(
     stream_oxide: &mut StreamOxide<InflateState>,
     dest_len: &mut c_ulong,
@@ -293,6 +841,8 @@ pub fn mz_uncompress2_oxide(
             Ok(MZStatus::Ok)
         }
         (Err(MZError::Buf), true) => Err(MZError::Data),
+        // There is no way to provide a dictionary here.
+        (Ok(MZStatus::NeedDict), _) => Err(MZError::Data),
         (status, _) => status,
     }
 }
@@ -302,6 +852,158 @@ pub fn mz_inflate_end_oxide(stream_oxide: &mut StreamOxide<InflateState>) -> MZR
     Ok(MZStatus::Ok)
 }
 
+/// Reset the decompressor, so it can be used to decompress a new stream in the same format.
+///
+/// The sliding window is not cleared, which makes this cheaper than ending the stream and
+/// initializing a new one. Like in zlib, a header struct passed to
+/// `mz_inflate_get_header_oxide` is no longer filled in.
+///
+/// Returns `MZError::Stream` if the inner stream is missing, otherwise `MZStatus::Ok`.
+pub fn mz_inflate_reset_oxide(stream_oxide: &mut StreamOxide<InflateState>) -> MZResult {
+    let (state, gz_header) = stream_oxide.inflate_state().ok_or(MZError::Stream)?;
+    state.reset_as(MinReset);
+    *gz_header = ptr::null_mut();
+    reset_inflate_totals(stream_oxide);
+    Ok(MZStatus::Ok)
+}
+
+/// Reset the decompressor, switching to the format given by `window_bits`, as in
+/// `mz_inflate_init2_oxide`. Otherwise this works like `mz_inflate_reset_oxide`.
+///
+/// Returns `MZError::Stream` if the inner stream is missing or `window_bits` is invalid,
+/// otherwise `MZStatus::Ok`.
+pub fn mz_inflate_reset2_oxide(
+    stream_oxide: &mut StreamOxide<InflateState>,
+    window_bits: i32,
+) -> MZResult {
+    let format = window_bits_format(window_bits, true).ok_or(MZError::Stream)?;
+    let (state, gz_header) = stream_oxide.inflate_state().ok_or(MZError::Stream)?;
+    state.reset_as(FullReset(format));
+    *gz_header = ptr::null_mut();
+    reset_inflate_totals(stream_oxide);
+    Ok(MZStatus::Ok)
+}
+
+fn reset_inflate_totals(stream_oxide: &mut StreamOxide<InflateState>) {
+    stream_oxide.total_in = 0;
+    stream_oxide.total_out = 0;
+    stream_oxide.adler = 0;
+    stream_oxide.msg = None;
+}
+
+/// Initialize a decompressor that uses `window` as its sliding window, for use with
+/// `mz_inflate_back_oxide`.
+///
+/// `window` must point to `1 << window_bits` bytes that stay valid until
+/// `mz_inflate_back_end_oxide` is called.
+///
+/// Returns `MZError::Stream` if `window_bits` is not in `8..=15` or `window` is null.
+pub fn mz_inflate_back_init_oxide(
+    stream_oxide: &mut StreamOxide<InflateBackState>,
+    window_bits: i32,
+    window: *mut u8,
+) -> MZResult {
+    if !(8..=15).contains(&window_bits) || window.is_null() {
+        return Err(MZError::Stream);
+    }
+
+    stream_oxide.msg = None;
+    let state = stream_oxide.allocator.alloc(|| InflateBackState {
+        decomp: DecompressorOxide::new(),
+        window,
+        window_size: 1 << window_bits,
+    })?;
+    stream_oxide.state = Some(Box::new(InternalState::InflateBack(state)));
+
+    Ok(MZStatus::Ok)
+}
+
+/// Decompress a raw deflate stream, reading input with `in_fn` and passing output to
+/// `out_fn`.
+///
+/// Input in `next_in` is used first. When more is needed, `in_fn` is called with `in_desc`
+/// and a pointer to set to the start of the new input, and returns its length. The output
+/// is decompressed into the window and passed to `out_fn` with `out_desc` each time the
+/// window is full, and at the end of the stream. Unused input is left in `next_in`.
+///
+/// Returns `MZStatus::StreamEnd` on success, `MZError::Stream` if the inner stream is missing,
+/// `MZError::Data` if the data is invalid, and `MZError::Buf` if `in_fn` returned no input,
+/// in which case `next_in` is set to `None`, or if `out_fn` returned a non-zero value.
+///
+/// # Safety
+/// The window of the state must be valid, and the pointers returned by `in_fn` must point
+/// to the number of bytes it returns, until it is called again.
+pub unsafe fn mz_inflate_back_oxide(
+    stream_oxide: &mut StreamOxide<InflateBackState>,
+    in_fn: mz_in_func,
+    in_desc: *mut c_void,
+    out_fn: mz_out_func,
+    out_desc: *mut c_void,
+) -> MZResult {
+    let state: &mut InflateBackState = {
+        let enum_ref = stream_oxide.state.as_mut().ok_or(MZError::Stream)?;
+        StateType::from_enum(enum_ref)
+    }
+    .ok_or(MZError::Stream)?;
+    let window = slice::from_raw_parts_mut(state.window, state.window_size);
+
+    state.decomp.init();
+    stream_oxide.msg = None;
+    let mut next_in = stream_oxide.next_in.unwrap_or(&[]);
+    let mut window_pos = 0;
+    loop {
+        if next_in.is_empty() {
+            let mut buf = ptr::null();
+            let len = in_fn(in_desc, &mut buf);
+            if len == 0 || buf.is_null() {
+                stream_oxide.next_in = None;
+                return Err(MZError::Buf);
+            }
+            next_in = slice::from_raw_parts(buf, len as usize);
+        }
+
+        let (status, in_consumed, out_consumed) = decompress(
+            &mut state.decomp,
+            next_in,
+            window,
+            window_pos,
+            inflate_flags::TINFL_FLAG_HAS_MORE_INPUT,
+        );
+        next_in = &next_in[in_consumed..];
+        stream_oxide.next_in = Some(next_in);
+
+        if out_consumed != 0
+            && out_fn(
+                out_desc,
+                window[window_pos..].as_mut_ptr(),
+                out_consumed as c_uint,
+            ) != 0
+        {
+            return Err(MZError::Buf);
+        }
+        window_pos = (window_pos + out_consumed) & (window.len() - 1);
+
+        match status {
+            TINFLStatus::Done => return Ok(MZStatus::StreamEnd),
+            TINFLStatus::NeedsMoreInput | TINFLStatus::HasMoreOutput => (),
+            TINFLStatus::BadParam => return Err(MZError::Stream),
+            _ => {
+                let error = state.decomp.data_error();
+                stream_oxide.msg = Some(error.map_or("invalid data\0", data_error_message));
+                return Err(MZError::Data);
+            }
+        }
+    }
+}
+
+/// Free the inner state of a decompressor created with `mz_inflate_back_init_oxide`.
+///
+/// Currently always returns `MZStatus::Ok`.
+pub fn mz_inflate_back_end_oxide(stream_oxide: &mut StreamOxide<InflateBackState>) -> MZResult {
+    stream_oxide.state = None;
+    Ok(MZStatus::Ok)
+}
+
 /*
 #[test]
 fn roundtrip_oxide() {
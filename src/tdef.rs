@@ -16,6 +16,7 @@ pub type tdefl_put_buf_func_ptr =
 /// cbindgen.rename = "SnakeCase""
 // typedef mz_bool (*tdefl_put_buf_func_ptr)(const void *pBuf, int len, void *pUser);
 
+#[derive(Clone, Copy)]
 pub struct CallbackFunc {
     pub put_buf_func: PutBufFuncPtrNotNull,
     pub put_buf_user: *mut c_void,
@@ -23,9 +24,9 @@ pub struct CallbackFunc {
 
 /// Main compression struct. Not the same as `CompressorOxide`
 /// #[repr(C)]
-#[derive(Default)]
+#[derive(Default, Clone)]
 pub struct Compressor {
-    pub(crate) inner: Option<CompressorOxide>,
+    pub(crate) inner: Option<CompressorOxide<'static>>,
     pub(crate) callback: Option<CallbackFunc>,
 }
 
@@ -3,12 +3,17 @@
 use libc::*;
 use miniz_oxide::inflate::core::DecompressorOxide;
 // pub use miniz_oxide::inflate::core::DecompressorOxide as tinfl_decompressor;
-pub use miniz_oxide::inflate::core::{decompress, inflate_flags};
+pub use miniz_oxide::inflate::core::{decompress, inflate_flags, TINFL_LZ_DICT_SIZE};
 use miniz_oxide::inflate::TINFLStatus;
 use std::{ptr, slice};
 
 pub const TINFL_DECOMPRESS_MEM_TO_MEM_FAILED: size_t = usize::MAX;
 
+/// Decompression callback function type.
+#[allow(bad_style)]
+pub type tinfl_put_buf_func_ptr =
+    Option<unsafe extern "C" fn(*const c_void, c_int, *mut c_void) -> c_int>;
+
 #[allow(bad_style)]
 #[repr(C)]
 pub enum tinfl_status {
@@ -58,6 +63,9 @@ impl From<TINFLStatus> for tinfl_status {
             TINFLStatus::Done => TINFL_STATUS_DONE,
             TINFLStatus::NeedsMoreInput => TINFL_STATUS_NEEDS_MORE_INPUT,
             TINFLStatus::HasMoreOutput => TINFL_STATUS_HAS_MORE_OUTPUT,
+            // There is no way to provide a preset dictionary through the C API, so such
+            // streams fail as they do in miniz.
+            TINFLStatus::NeedsDictionary => TINFL_STATUS_FAILED,
             _ => TINFL_STATUS_UNKNOWN,
             // TINFLStatus::BlockBoundary => panic!("not supported in C API"),
         }
@@ -92,6 +100,13 @@ unmangle!(
         let out_size = *out_buf_size + next_pos;
         let r_ref = r.as_mut().expect("bad decompressor pointer");
         if let Some(decompressor) = r_ref.inner.as_mut() {
+            // Keep failing after a stream that needs a preset dictionary, rather than
+            // continuing without one.
+            if decompressor.dictionary_id().is_some() {
+                *in_buf_size = 0;
+                *out_buf_size = 0;
+                return tinfl_status::TINFL_STATUS_FAILED as i32;
+            }
             let (status, in_consumed, out_consumed) = decompress(
                 decompressor.as_mut(),
                 slice::from_raw_parts(in_buf, *in_buf_size),
@@ -102,7 +117,7 @@ unmangle!(
 
             *in_buf_size = in_consumed;
             *out_buf_size = out_consumed;
-            status as i32
+            tinfl_status::from(status) as i32
         } else {
             TINFLStatus::BadParam as i32
         }
@@ -175,7 +190,10 @@ unmangle!(
             );
 
             // If decompression fails or we don't have any input, bail out.
-            if (status as i32) < 0 || status == TINFLStatus::NeedsMoreInput {
+            if (status as i32) < 0
+                || status == TINFLStatus::NeedsMoreInput
+                || status == TINFLStatus::NeedsDictionary
+            {
                 crate::miniz_def_free_func(ptr::null_mut(), p_buf);
                 *p_out_len = 0;
                 return ptr::null_mut();
@@ -214,6 +232,68 @@ unmangle!(
         p_buf
     }
 
+    /// Decompress data from `p_in_buf`, passing the output to `p_put_buf_func` in chunks of at
+    /// most `TINFL_LZ_DICT_SIZE` bytes.
+    ///
+    /// The output is decompressed into a 32 KiB buffer that is handed to the callback whenever
+    /// it is full, and once more at the end, so this needs no memory besides the buffer and
+    /// the decompressor. Decompression stops if the callback returns 0.
+    ///
+    /// Sets `p_in_buf_size` to the number of input bytes consumed.
+    /// Returns 1 on success and 0 if decompression fails or is stopped by the callback.
+    pub unsafe extern "C" fn tinfl_decompress_mem_to_callback(
+        p_in_buf: *const c_void,
+        p_in_buf_size: *mut size_t,
+        p_put_buf_func: tinfl_put_buf_func_ptr,
+        p_put_buf_user: *mut c_void,
+        flags: c_int,
+    ) -> c_int {
+        let (put_buf_func, in_buf_size) = match (p_put_buf_func, p_in_buf_size.as_mut()) {
+            (Some(func), Some(size)) => (func, size),
+            _ => return 0,
+        };
+        let flags = flags as u32
+            & !(inflate_flags::TINFL_FLAG_HAS_MORE_INPUT
+                | inflate_flags::TINFL_FLAG_USING_NON_WRAPPING_OUTPUT_BUF);
+        let input = if p_in_buf.is_null() {
+            &[][..]
+        } else {
+            slice::from_raw_parts(p_in_buf as *const u8, *in_buf_size)
+        };
+
+        let mut decomp = DecompressorOxide::default();
+        let mut dict = vec![0; TINFL_LZ_DICT_SIZE];
+        let mut in_buf_ofs = 0;
+        let mut dict_ofs = 0;
+        let result = loop {
+            let (status, in_consumed, out_consumed) = decompress(
+                &mut decomp,
+                &input[in_buf_ofs..],
+                &mut dict,
+                dict_ofs,
+                flags,
+            );
+            in_buf_ofs += in_consumed;
+
+            if out_consumed != 0
+                && put_buf_func(
+                    dict[dict_ofs..].as_ptr() as *const c_void,
+                    out_consumed as c_int,
+                    p_put_buf_user,
+                ) == 0
+            {
+                break 0;
+            }
+            if status != TINFLStatus::HasMoreOutput {
+                break c_int::from(status == TINFLStatus::Done);
+            }
+            dict_ofs = (dict_ofs + out_consumed) & (TINFL_LZ_DICT_SIZE - 1);
+        };
+
+        *in_buf_size = in_buf_ofs;
+        result
+    }
+
     /// Allocate a compressor.
     ///
     /// This does initialize the struct, but not the inner constructor,
@@ -355,4 +435,103 @@ mod test {
         let out_buf = tinfl_decompress_mem_to_heap_wrapper(&mut encoded[..], flags as i32).unwrap();
         assert_eq!(out_buf.as_slice(), &b"Hello, zlib!"[..]);
     }
+
+    #[test]
+    fn mem_to_callback() {
+        unsafe extern "C" fn put_buf(buf: *const c_void, len: c_int, user: *mut c_void) -> c_int {
+            let out = &mut *(user as *mut Vec<u8>);
+            assert!(len as usize <= TINFL_LZ_DICT_SIZE);
+            out.extend_from_slice(slice::from_raw_parts(buf as *const u8, len as usize));
+            c_int::from(out.len() < 100_000)
+        }
+
+        let data: Vec<u8> = (0..200_000u32)
+            .map(|i| (i % 251) as u8 ^ (i >> 9) as u8)
+            .collect();
+        let encoded = miniz_oxide::deflate::compress_to_vec_zlib(&data, 6);
+        let flags = (TINFL_FLAG_COMPUTE_ADLER32 | TINFL_FLAG_PARSE_ZLIB_HEADER) as i32;
+        let mut out = Vec::new();
+        let mut in_size = encoded.len();
+        let res = unsafe {
+            tinfl_decompress_mem_to_callback(
+                encoded.as_ptr() as *const c_void,
+                &mut in_size,
+                Some(put_buf),
+                &mut out as *mut Vec<u8> as *mut c_void,
+                flags,
+            )
+        };
+        // The callback stops decompression after 100_000 bytes.
+        assert_eq!(res, 0);
+        assert!(out.len() >= 100_000 && out.len() < data.len());
+        assert_eq!(out[..], data[..out.len()]);
+
+        out.clear();
+        let mut padded = encoded.clone();
+        padded.extend_from_slice(b"trailing");
+        in_size = padded.len();
+        let res = unsafe {
+            unsafe extern "C" fn collect(
+                buf: *const c_void,
+                len: c_int,
+                user: *mut c_void,
+            ) -> c_int {
+                let out = &mut *(user as *mut Vec<u8>);
+                out.extend_from_slice(slice::from_raw_parts(buf as *const u8, len as usize));
+                1
+            }
+            tinfl_decompress_mem_to_callback(
+                padded.as_ptr() as *const c_void,
+                &mut in_size,
+                Some(collect),
+                &mut out as *mut Vec<u8> as *mut c_void,
+                flags,
+            )
+        };
+        assert_eq!(res, 1);
+        assert_eq!(in_size, encoded.len());
+        assert!(out == data);
+    }
+
+    #[test]
+    fn needs_dictionary() {
+        use miniz_oxide::deflate::core::{compress, CompressorOxide, TDEFLFlush, TDEFLStatus};
+        use miniz_oxide::inflate::core::inflate_flags::TINFL_FLAG_USING_NON_WRAPPING_OUTPUT_BUF;
+
+        let mut d = CompressorOxide::with_format_and_level(
+            miniz_oxide::DataFormat::Zlib,
+            miniz_oxide::deflate::CompressionLevel::DefaultLevel,
+        );
+        d.set_dictionary(b"Hello, dictionary").unwrap();
+        let mut encoded = vec![0; 100];
+        let (status, _, len) = compress(&mut d, b"Hello, zlib!", &mut encoded, TDEFLFlush::Finish);
+        assert_eq!(status, TDEFLStatus::Done);
+        encoded.truncate(len);
+
+        let flags = TINFL_FLAG_PARSE_ZLIB_HEADER | TINFL_FLAG_USING_NON_WRAPPING_OUTPUT_BUF;
+        let mut out = vec![0; 50];
+        unsafe {
+            let r = tinfl_decompressor_alloc();
+            // The decompressor keeps failing instead of going on without the dictionary.
+            for _ in 0..2 {
+                let mut in_size = encoded.len();
+                let mut out_size = out.len();
+                let status = tinfl_decompress(
+                    r,
+                    encoded.as_ptr(),
+                    &mut in_size,
+                    out.as_mut_ptr(),
+                    out.as_mut_ptr(),
+                    &mut out_size,
+                    flags,
+                );
+                assert_eq!(status, tinfl_status::TINFL_STATUS_FAILED as i32);
+                assert_eq!(out_size, 0);
+            }
+            tinfl_decompressor_free(r);
+        }
+        assert!(
+            tinfl_decompress_mem_to_mem_wrapper(&mut encoded, &mut out, flags as i32).is_none()
+        );
+    }
 }
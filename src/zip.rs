@@ -0,0 +1,2960 @@
+//! The ZIP archive part of the miniz C API (`mz_zip_*`), implemented on top of
+//! `miniz_oxide::zip`.
+//!
+//! The archive is read and written through the `m_pRead`/`m_pWrite` callbacks of the
+//! `mz_zip_archive` struct like in miniz, or directly from memory or a `FILE` for the
+//! archive types that are set up by the `_mem`, `_heap`, `_file` and `_cfile` init
+//! functions. `m_file_offset_alignment` is validated but otherwise ignored, and
+//! `MZ_ZIP_FLAG_WRITE_ZIP64` is not needed since the ZIP64 records are written
+//! automatically when the archive needs them.
+
+use std::convert::TryFrom;
+use std::ffi::CStr;
+#[cfg(unix)]
+use std::ffi::OsStr;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+use std::{cmp, mem, ptr, slice};
+
+use libc::{c_char, c_int, c_void, size_t, time_t, FILE};
+
+use miniz_oxide::zip::{
+    general_purpose_flags, local_header_len, methods, read_central_dir, CentralDirLocation,
+    DateTime, EntryDecoder, EntryOptions, ZipEntry, ZipError, ZipWriter, LOCAL_FILE_HEADER_LEN,
+};
+
+use crate::cfile::{fseek64, ftell64};
+use crate::{
+    miniz_def_alloc_func, miniz_def_free_func, miniz_def_realloc_func, mz_alloc_func, mz_bool,
+    mz_free_func, mz_realloc_func, mz_uint, mz_uint32,
+};
+
+pub const MZ_ZIP_MAX_IO_BUF_SIZE: usize = 64 * 1024;
+pub const MZ_ZIP_MAX_ARCHIVE_FILENAME_SIZE: usize = 512;
+pub const MZ_ZIP_MAX_ARCHIVE_FILE_COMMENT_SIZE: usize = 512;
+
+pub const MZ_ZIP_FLAG_CASE_SENSITIVE: mz_uint = 0x0100;
+pub const MZ_ZIP_FLAG_IGNORE_PATH: mz_uint = 0x0200;
+pub const MZ_ZIP_FLAG_COMPRESSED_DATA: mz_uint = 0x0400;
+pub const MZ_ZIP_FLAG_DO_NOT_SORT_CENTRAL_DIRECTORY: mz_uint = 0x0800;
+pub const MZ_ZIP_FLAG_VALIDATE_LOCATE_FILE_FLAG: mz_uint = 0x1000;
+pub const MZ_ZIP_FLAG_VALIDATE_HEADERS_ONLY: mz_uint = 0x2000;
+pub const MZ_ZIP_FLAG_WRITE_ZIP64: mz_uint = 0x4000;
+pub const MZ_ZIP_FLAG_WRITE_ALLOW_READING: mz_uint = 0x8000;
+pub const MZ_ZIP_FLAG_ASCII_FILENAME: mz_uint = 0x10000;
+
+/// Length of the fixed part of a central directory header.
+const CENTRAL_DIR_HEADER_LEN: u64 = 46;
+/// Length of the end of central directory record.
+const END_OF_CENTRAL_DIR_LEN: usize = 22;
+/// Length of the ZIP64 end of central directory record and its locator.
+const ZIP64_END_OF_CENTRAL_DIR_LEN: usize = 56 + 20;
+const ZIP64_END_OF_CENTRAL_DIR_SIG: u32 = 0x0606_4b50;
+const ZIP64_EXTRA_FIELD_ID: u16 = 0x0001;
+
+const COMPRESSED_PATCH_FLAG: u16 = 1 << 5;
+const STRONG_ENCRYPTION_FLAG: u16 = 1 << 6;
+
+const DOS_DIRECTORY_ATTRIBUTE: u32 = 0x10;
+
+/// Level used for a negative `level_and_flags`, e.g. `MZ_DEFAULT_COMPRESSION`.
+const DEFAULT_LEVEL: mz_uint = 6;
+
+#[allow(bad_style)]
+pub type mz_file_read_func = unsafe extern "C" fn(*mut c_void, u64, *mut c_void, size_t) -> size_t;
+#[allow(bad_style)]
+pub type mz_file_write_func =
+    unsafe extern "C" fn(*mut c_void, u64, *const c_void, size_t) -> size_t;
+#[allow(bad_style)]
+pub type mz_file_needs_keepalive = unsafe extern "C" fn(*mut c_void) -> mz_bool;
+
+#[allow(bad_style)]
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum mz_zip_mode {
+    MZ_ZIP_MODE_INVALID = 0,
+    MZ_ZIP_MODE_READING = 1,
+    MZ_ZIP_MODE_WRITING = 2,
+    MZ_ZIP_MODE_WRITING_HAS_BEEN_FINALIZED = 3,
+}
+
+#[allow(bad_style)]
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum mz_zip_type {
+    MZ_ZIP_TYPE_INVALID = 0,
+    MZ_ZIP_TYPE_USER,
+    MZ_ZIP_TYPE_MEMORY,
+    MZ_ZIP_TYPE_HEAP,
+    MZ_ZIP_TYPE_FILE,
+    MZ_ZIP_TYPE_CFILE,
+    MZ_ZIP_TOTAL_TYPES,
+}
+
+#[allow(bad_style)]
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum mz_zip_error {
+    MZ_ZIP_NO_ERROR = 0,
+    MZ_ZIP_UNDEFINED_ERROR,
+    MZ_ZIP_TOO_MANY_FILES,
+    MZ_ZIP_FILE_TOO_LARGE,
+    MZ_ZIP_UNSUPPORTED_METHOD,
+    MZ_ZIP_UNSUPPORTED_ENCRYPTION,
+    MZ_ZIP_UNSUPPORTED_FEATURE,
+    MZ_ZIP_FAILED_FINDING_CENTRAL_DIR,
+    MZ_ZIP_NOT_AN_ARCHIVE,
+    MZ_ZIP_INVALID_HEADER_OR_CORRUPTED,
+    MZ_ZIP_UNSUPPORTED_MULTIDISK,
+    MZ_ZIP_DECOMPRESSION_FAILED,
+    MZ_ZIP_COMPRESSION_FAILED,
+    MZ_ZIP_UNEXPECTED_DECOMPRESSED_SIZE,
+    MZ_ZIP_CRC_CHECK_FAILED,
+    MZ_ZIP_UNSUPPORTED_CDIR_SIZE,
+    MZ_ZIP_ALLOC_FAILED,
+    MZ_ZIP_FILE_OPEN_FAILED,
+    MZ_ZIP_FILE_CREATE_FAILED,
+    MZ_ZIP_FILE_WRITE_FAILED,
+    MZ_ZIP_FILE_READ_FAILED,
+    MZ_ZIP_FILE_CLOSE_FAILED,
+    MZ_ZIP_FILE_SEEK_FAILED,
+    MZ_ZIP_FILE_STAT_FAILED,
+    MZ_ZIP_INVALID_PARAMETER,
+    MZ_ZIP_INVALID_FILENAME,
+    MZ_ZIP_BUF_TOO_SMALL,
+    MZ_ZIP_INTERNAL_ERROR,
+    MZ_ZIP_FILE_NOT_FOUND,
+    MZ_ZIP_ARCHIVE_TOO_LARGE,
+    MZ_ZIP_VALIDATION_FAILED,
+    MZ_ZIP_WRITE_CALLBACK_FAILED,
+    MZ_ZIP_TOTAL_ERRORS,
+}
+
+use self::mz_zip_error::*;
+use self::mz_zip_mode::*;
+use self::mz_zip_type::*;
+
+/// Information about an entry of the archive, filled in by `mz_zip_reader_file_stat`.
+#[allow(bad_style)]
+#[repr(C)]
+pub struct mz_zip_archive_file_stat {
+    /// Index of the entry in the central directory.
+    pub m_file_index: mz_uint32,
+    /// Offset of the entry's header in the central directory.
+    pub m_central_dir_ofs: u64,
+    pub m_version_made_by: u16,
+    pub m_version_needed: u16,
+    pub m_bit_flag: u16,
+    pub m_method: u16,
+    /// Modification time, converted from the MS-DOS local time of the entry.
+    pub m_time: time_t,
+    pub m_crc32: mz_uint32,
+    pub m_comp_size: u64,
+    pub m_uncomp_size: u64,
+    pub m_internal_attr: u16,
+    pub m_external_attr: mz_uint32,
+    pub m_local_header_ofs: u64,
+    /// Length of the comment in `m_comment`, which may have been truncated.
+    pub m_comment_size: mz_uint32,
+    pub m_is_directory: mz_bool,
+    pub m_is_encrypted: mz_bool,
+    pub m_is_supported: mz_bool,
+    /// Name of the entry, truncated to fit and terminated with a null byte.
+    pub m_filename: [c_char; MZ_ZIP_MAX_ARCHIVE_FILENAME_SIZE],
+    /// Comment of the entry, truncated to fit and terminated with a null byte.
+    pub m_comment: [c_char; MZ_ZIP_MAX_ARCHIVE_FILE_COMMENT_SIZE],
+}
+
+#[allow(bad_style)]
+#[repr(C)]
+pub struct mz_zip_archive {
+    pub m_archive_size: u64,
+    pub m_central_directory_file_ofs: u64,
+    pub m_total_files: mz_uint32,
+    pub m_zip_mode: mz_zip_mode,
+    pub m_zip_type: mz_zip_type,
+    pub m_last_error: mz_zip_error,
+    pub m_file_offset_alignment: u64,
+    pub m_pAlloc: Option<mz_alloc_func>,
+    pub m_pFree: Option<mz_free_func>,
+    pub m_pRealloc: Option<mz_realloc_func>,
+    pub m_pAlloc_opaque: *mut c_void,
+    pub m_pRead: Option<mz_file_read_func>,
+    pub m_pWrite: Option<mz_file_write_func>,
+    pub m_pNeeds_keepalive: Option<mz_file_needs_keepalive>,
+    pub m_pIO_opaque: *mut c_void,
+    pub m_pState: *mut mz_zip_internal_state,
+}
+
+/// State of an open archive, opaque to C.
+#[allow(bad_style)]
+pub struct mz_zip_internal_state {
+    entries: Vec<ZipEntry>,
+    /// Offset of each entry's header in the central directory.
+    central_dir_offsets: Vec<u64>,
+    central_dir_size: u64,
+    zip64: bool,
+    /// The memory being read for `MZ_ZIP_TYPE_MEMORY`, or written for `MZ_ZIP_TYPE_HEAP`.
+    mem: *mut u8,
+    mem_size: usize,
+    mem_capacity: usize,
+    /// `mem` belongs to the archive and is grown with `m_pRealloc`.
+    heap: bool,
+    file: *mut FILE,
+    /// Offset of the archive in `file`.
+    file_start_ofs: u64,
+    writer: Option<ZipWriter>,
+}
+
+impl mz_zip_internal_state {
+    fn new() -> mz_zip_internal_state {
+        mz_zip_internal_state {
+            entries: Vec::new(),
+            central_dir_offsets: Vec::new(),
+            central_dir_size: 0,
+            zip64: false,
+            mem: ptr::null_mut(),
+            mem_size: 0,
+            mem_capacity: 0,
+            heap: false,
+            file: ptr::null_mut(),
+            file_start_ofs: 0,
+            writer: None,
+        }
+    }
+
+    fn entry(&self, index: mz_uint) -> Result<&ZipEntry, mz_zip_error> {
+        self.entries
+            .get(index as usize)
+            .ok_or(MZ_ZIP_INVALID_PARAMETER)
+    }
+
+    fn push_entry(&mut self, entry: ZipEntry) {
+        self.central_dir_offsets.push(self.central_dir_size);
+        self.central_dir_size += central_dir_header_len(&entry);
+        self.zip64 |= has_zip64_extra_field(&entry.extra);
+        self.entries.push(entry);
+    }
+}
+
+/// State of an iterative extraction, opaque to C.
+#[allow(bad_style)]
+pub struct mz_zip_reader_extract_iter_state {
+    zip: *mut mz_zip_archive,
+    /// `None` when the data is passed through as is.
+    decoder: Option<EntryDecoder>,
+    ofs: u64,
+    remaining: u64,
+    input: Vec<u8>,
+    input_pos: usize,
+    input_end: usize,
+    failed: bool,
+}
+
+fn central_dir_header_len(entry: &ZipEntry) -> u64 {
+    CENTRAL_DIR_HEADER_LEN + (entry.name.len() + entry.extra.len() + entry.comment.len()) as u64
+}
+
+fn has_zip64_extra_field(mut extra: &[u8]) -> bool {
+    while extra.len() >= 4 {
+        let id = u16::from_le_bytes([extra[0], extra[1]]);
+        let len = usize::from(u16::from_le_bytes([extra[2], extra[3]]));
+        if id == ZIP64_EXTRA_FIELD_ID {
+            return true;
+        }
+        extra = &extra[cmp::min(4 + len, extra.len())..];
+    }
+    false
+}
+
+fn zip_error(err: ZipError) -> mz_zip_error {
+    match err {
+        ZipError::InvalidArchive => MZ_ZIP_INVALID_HEADER_OR_CORRUPTED,
+        ZipError::UnsupportedArchive => MZ_ZIP_UNSUPPORTED_MULTIDISK,
+        ZipError::UnsupportedMethod(_) => MZ_ZIP_UNSUPPORTED_METHOD,
+        ZipError::Encrypted => MZ_ZIP_UNSUPPORTED_ENCRYPTION,
+        ZipError::NotFound => MZ_ZIP_FILE_NOT_FOUND,
+        ZipError::InvalidData(_) => MZ_ZIP_DECOMPRESSION_FAILED,
+        ZipError::ChecksumMismatch => MZ_ZIP_CRC_CHECK_FAILED,
+        ZipError::Aborted => MZ_ZIP_WRITE_CALLBACK_FAILED,
+        ZipError::TooLarge => MZ_ZIP_INVALID_PARAMETER,
+        _ => MZ_ZIP_UNDEFINED_ERROR,
+    }
+}
+
+/// Run `f` on the archive pointed to by `zip`, storing the error it returns in
+/// `m_last_error` and returning `failed` in that case.
+unsafe fn with_archive<T, F>(zip: *mut mz_zip_archive, failed: T, f: F) -> T
+where
+    F: FnOnce(&mut mz_zip_archive) -> Result<T, mz_zip_error>,
+{
+    let zip = match zip.as_mut() {
+        Some(zip) => zip,
+        None => return failed,
+    };
+    // Make sure we catch a potential panic, as this is called from C.
+    match catch_unwind(AssertUnwindSafe(|| f(zip))) {
+        Ok(Ok(res)) => res,
+        Ok(Err(err)) => {
+            zip.m_last_error = err;
+            failed
+        }
+        Err(_) => {
+            println!("FATAL ERROR: Caught panic!");
+            failed
+        }
+    }
+}
+
+fn as_bool(res: bool) -> mz_bool {
+    res as mz_bool
+}
+
+/// The state of `zip`. The state is a separate allocation, so it may be used at the same
+/// time as `zip`.
+unsafe fn state<'a>(zip: &mz_zip_archive) -> Result<&'a mut mz_zip_internal_state, mz_zip_error> {
+    zip.m_pState.as_mut().ok_or(MZ_ZIP_INVALID_PARAMETER)
+}
+
+unsafe fn c_bytes<'a>(s: *const c_char) -> Result<&'a [u8], mz_zip_error> {
+    if s.is_null() {
+        Err(MZ_ZIP_INVALID_PARAMETER)
+    } else {
+        Ok(CStr::from_ptr(s).to_bytes())
+    }
+}
+
+unsafe fn data_slice<'a>(data: *const c_void, len: usize) -> Result<&'a [u8], mz_zip_error> {
+    if len == 0 {
+        Ok(&[])
+    } else if data.is_null() {
+        Err(MZ_ZIP_INVALID_PARAMETER)
+    } else {
+        Ok(slice::from_raw_parts(data as *const u8, len))
+    }
+}
+
+#[cfg(unix)]
+fn c_path(path: &CStr) -> Option<&Path> {
+    use std::os::unix::ffi::OsStrExt;
+    Some(Path::new(OsStr::from_bytes(path.to_bytes())))
+}
+
+#[cfg(not(unix))]
+fn c_path(path: &CStr) -> Option<&Path> {
+    path.to_str().ok().map(Path::new)
+}
+
+/// Convert the MS-DOS local time of an entry to a `time_t`.
+#[cfg(unix)]
+fn dos_to_time_t(time: DateTime) -> time_t {
+    unsafe {
+        let mut tm: libc::tm = mem::zeroed();
+        tm.tm_isdst = -1;
+        tm.tm_year = c_int::from(time.year) - 1900;
+        tm.tm_mon = c_int::from(time.month) - 1;
+        tm.tm_mday = c_int::from(time.day);
+        tm.tm_hour = c_int::from(time.hour);
+        tm.tm_min = c_int::from(time.minute);
+        tm.tm_sec = c_int::from(time.second);
+        libc::mktime(&mut tm)
+    }
+}
+
+/// Convert a `time_t` to the MS-DOS local time stored in an entry.
+#[cfg(unix)]
+fn time_t_to_dos(time: time_t) -> DateTime {
+    unsafe {
+        let mut tm: libc::tm = mem::zeroed();
+        if libc::localtime_r(&time, &mut tm).is_null() {
+            return DateTime::default();
+        }
+        DateTime {
+            year: cmp::max(tm.tm_year + 1900, 1980) as u16,
+            month: (tm.tm_mon + 1) as u8,
+            day: tm.tm_mday as u8,
+            hour: tm.tm_hour as u8,
+            minute: tm.tm_min as u8,
+            second: tm.tm_sec as u8,
+        }
+    }
+}
+
+/// Convert the MS-DOS time of an entry to a `time_t`, taking it as UTC.
+#[cfg(not(unix))]
+fn dos_to_time_t(time: DateTime) -> time_t {
+    // Days since 1970-01-01 using the `days_from_civil` algorithm by Howard Hinnant.
+    let month = i64::from(time.month);
+    let year = i64::from(time.year) - i64::from(month <= 2);
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + i64::from(time.day) - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146_097 + day_of_era - 719_468;
+    (days * 86400
+        + i64::from(time.hour) * 3600
+        + i64::from(time.minute) * 60
+        + i64::from(time.second)) as time_t
+}
+
+/// Convert a `time_t` taken as UTC to the MS-DOS time stored in an entry.
+#[cfg(not(unix))]
+fn time_t_to_dos(time: time_t) -> DateTime {
+    // `civil_from_days` by Howard Hinnant.
+    let time = i64::from(time);
+    let (days, secs) = (time.div_euclid(86400), time.rem_euclid(86400));
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    if year < 1980 {
+        return DateTime::default();
+    }
+    DateTime {
+        year: cmp::min(year, i64::from(u16::MAX)) as u16,
+        month: month as u8,
+        day: day as u8,
+        hour: (secs / 3600) as u8,
+        minute: (secs / 60 % 60) as u8,
+        second: (secs % 60) as u8,
+    }
+}
+
+fn current_time() -> time_t {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |time| time.as_secs() as time_t)
+}
+
+unsafe fn zip_alloc(zip: &mz_zip_archive, size: usize) -> *mut c_void {
+    let alloc = zip.m_pAlloc.unwrap_or(miniz_def_alloc_func);
+    alloc(zip.m_pAlloc_opaque, 1, size)
+}
+
+unsafe fn zip_free(zip: &mz_zip_archive, address: *mut c_void) {
+    let free = zip.m_pFree.unwrap_or(miniz_def_free_func);
+    free(zip.m_pAlloc_opaque, address)
+}
+
+/// Read up to `buf.len()` bytes at `ofs`, returning the number of bytes read.
+unsafe fn read_raw(
+    zip: &mz_zip_archive,
+    state: &mz_zip_internal_state,
+    ofs: u64,
+    buf: &mut [u8],
+) -> usize {
+    match zip.m_zip_type {
+        MZ_ZIP_TYPE_MEMORY | MZ_ZIP_TYPE_HEAP => {
+            if ofs >= state.mem_size as u64 {
+                return 0;
+            }
+            let ofs = ofs as usize;
+            let len = cmp::min(buf.len(), state.mem_size - ofs);
+            ptr::copy_nonoverlapping(state.mem.add(ofs), buf.as_mut_ptr(), len);
+            len
+        }
+        MZ_ZIP_TYPE_FILE | MZ_ZIP_TYPE_CFILE => {
+            let pos = match state.file_start_ofs.checked_add(ofs) {
+                Some(pos) if pos <= i64::MAX as u64 => pos as i64,
+                _ => return 0,
+            };
+            if fseek64(state.file, pos, libc::SEEK_SET) != 0 {
+                return 0;
+            }
+            libc::fread(buf.as_mut_ptr() as *mut c_void, 1, buf.len(), state.file)
+        }
+        _ => match zip.m_pRead {
+            Some(read) => read(
+                zip.m_pIO_opaque,
+                ofs,
+                buf.as_mut_ptr() as *mut c_void,
+                buf.len(),
+            ),
+            None => 0,
+        },
+    }
+}
+
+/// Write `data` at `ofs`, returning the number of bytes written.
+unsafe fn write_raw(
+    zip: &mz_zip_archive,
+    state: &mut mz_zip_internal_state,
+    ofs: u64,
+    data: &[u8],
+) -> usize {
+    match zip.m_zip_type {
+        MZ_ZIP_TYPE_MEMORY | MZ_ZIP_TYPE_HEAP => {
+            let end = match ofs.checked_add(data.len() as u64) {
+                Some(end) if state.heap && end <= isize::MAX as u64 => end as usize,
+                _ => return 0,
+            };
+            if end > state.mem_capacity {
+                let mut capacity = cmp::max(state.mem_capacity, 64);
+                while capacity < end {
+                    capacity = capacity.saturating_mul(2);
+                }
+                let realloc = zip.m_pRealloc.unwrap_or(miniz_def_realloc_func);
+                let mem = realloc(zip.m_pAlloc_opaque, state.mem as *mut c_void, 1, capacity);
+                if mem.is_null() {
+                    return 0;
+                }
+                state.mem = mem as *mut u8;
+                state.mem_capacity = capacity;
+            }
+            let ofs = ofs as usize;
+            if ofs > state.mem_size {
+                // Zero the space that was reserved at the beginning.
+                ptr::write_bytes(state.mem.add(state.mem_size), 0, ofs - state.mem_size);
+            }
+            ptr::copy_nonoverlapping(data.as_ptr(), state.mem.add(ofs), data.len());
+            state.mem_size = cmp::max(state.mem_size, end);
+            data.len()
+        }
+        MZ_ZIP_TYPE_FILE | MZ_ZIP_TYPE_CFILE => {
+            let pos = match state.file_start_ofs.checked_add(ofs) {
+                Some(pos) if pos <= i64::MAX as u64 => pos as i64,
+                _ => return 0,
+            };
+            if fseek64(state.file, pos, libc::SEEK_SET) != 0 {
+                return 0;
+            }
+            libc::fwrite(data.as_ptr() as *const c_void, 1, data.len(), state.file)
+        }
+        _ => match zip.m_pWrite {
+            Some(write) => write(
+                zip.m_pIO_opaque,
+                ofs,
+                data.as_ptr() as *const c_void,
+                data.len(),
+            ),
+            None => 0,
+        },
+    }
+}
+
+/// `m_pRead` for the archives that are read from memory or a file.
+unsafe extern "C" fn archive_read_func(
+    opaque: *mut c_void,
+    ofs: u64,
+    buf: *mut c_void,
+    n: size_t,
+) -> size_t {
+    let zip = &*(opaque as *const mz_zip_archive);
+    match zip.m_pState.as_ref() {
+        Some(state) if n > 0 => read_raw(
+            zip,
+            state,
+            ofs,
+            slice::from_raw_parts_mut(buf as *mut u8, n),
+        ),
+        _ => 0,
+    }
+}
+
+/// `m_pWrite` for the archives that are written to the heap or a file.
+unsafe extern "C" fn archive_write_func(
+    opaque: *mut c_void,
+    ofs: u64,
+    data: *const c_void,
+    n: size_t,
+) -> size_t {
+    let zip = &*(opaque as *const mz_zip_archive);
+    match zip.m_pState.as_mut() {
+        Some(state) if n > 0 => {
+            write_raw(zip, state, ofs, slice::from_raw_parts(data as *const u8, n))
+        }
+        _ => 0,
+    }
+}
+
+unsafe fn read_at(
+    zip: &mz_zip_archive,
+    state: &mz_zip_internal_state,
+    ofs: u64,
+    buf: &mut [u8],
+) -> Result<(), mz_zip_error> {
+    if read_raw(zip, state, ofs, buf) == buf.len() {
+        Ok(())
+    } else {
+        Err(MZ_ZIP_FILE_READ_FAILED)
+    }
+}
+
+unsafe fn write_at(
+    zip: &mz_zip_archive,
+    state: &mut mz_zip_internal_state,
+    ofs: u64,
+    data: &[u8],
+) -> Result<(), mz_zip_error> {
+    if data.is_empty() || write_raw(zip, state, ofs, data) == data.len() {
+        Ok(())
+    } else {
+        Err(MZ_ZIP_FILE_WRITE_FAILED)
+    }
+}
+
+/// Set up the archive and its state for reading or writing.
+unsafe fn init_internal(zip: &mut mz_zip_archive, mode: mz_zip_mode) -> Result<(), mz_zip_error> {
+    if !zip.m_pState.is_null() || zip.m_zip_mode != MZ_ZIP_MODE_INVALID {
+        return Err(MZ_ZIP_INVALID_PARAMETER);
+    }
+    if zip.m_pAlloc.is_none() {
+        zip.m_pAlloc = Some(miniz_def_alloc_func);
+    }
+    if zip.m_pFree.is_none() {
+        zip.m_pFree = Some(miniz_def_free_func);
+    }
+    if zip.m_pRealloc.is_none() {
+        zip.m_pRealloc = Some(miniz_def_realloc_func);
+    }
+    zip.m_archive_size = 0;
+    zip.m_central_directory_file_ofs = 0;
+    zip.m_total_files = 0;
+    zip.m_last_error = MZ_ZIP_NO_ERROR;
+    zip.m_pState = Box::into_raw(Box::new(mz_zip_internal_state::new()));
+    zip.m_zip_mode = mode;
+    Ok(())
+}
+
+/// Free the state, closing the file opened by the `_file` init functions.
+unsafe fn end_internal(zip: &mut mz_zip_archive) -> Result<(), mz_zip_error> {
+    if zip.m_pState.is_null() {
+        return Err(MZ_ZIP_INVALID_PARAMETER);
+    }
+    let state = Box::from_raw(zip.m_pState);
+    zip.m_pState = ptr::null_mut();
+    zip.m_zip_mode = MZ_ZIP_MODE_INVALID;
+
+    if state.heap && !state.mem.is_null() {
+        zip_free(zip, state.mem as *mut c_void);
+    }
+    if zip.m_zip_type == MZ_ZIP_TYPE_FILE
+        && !state.file.is_null()
+        && libc::fclose(state.file) == libc::EOF
+    {
+        return Err(MZ_ZIP_FILE_CLOSE_FAILED);
+    }
+    Ok(())
+}
+
+/// Run `f` after a successful init, ending the archive again if it fails.
+unsafe fn init_or_end<F>(zip: &mut mz_zip_archive, f: F) -> Result<bool, mz_zip_error>
+where
+    F: FnOnce(&mut mz_zip_archive) -> Result<(), mz_zip_error>,
+{
+    match f(zip) {
+        Ok(()) => Ok(true),
+        Err(err) => {
+            let _ = end_internal(zip);
+            Err(err)
+        }
+    }
+}
+
+/// Find and read the central directory of the archive.
+unsafe fn read_central_directory(zip: &mut mz_zip_archive) -> Result<(), mz_zip_error> {
+    let state = state(zip)?;
+    let size = zip.m_archive_size;
+    if size < END_OF_CENTRAL_DIR_LEN as u64 {
+        return Err(MZ_ZIP_NOT_AN_ARCHIVE);
+    }
+
+    let tail_len = cmp::min(size, CentralDirLocation::MAX_TAIL_LEN as u64) as usize;
+    let mut tail = vec![0; tail_len];
+    read_at(zip, state, size - tail_len as u64, &mut tail)?;
+    let location =
+        CentralDirLocation::find(&tail, size - tail_len as u64).map_err(|err| match err {
+            ZipError::UnsupportedArchive => MZ_ZIP_UNSUPPORTED_MULTIDISK,
+            _ => MZ_ZIP_FAILED_FINDING_CENTRAL_DIR,
+        })?;
+    if location.num_entries > u64::from(u32::MAX) {
+        return Err(MZ_ZIP_TOO_MANY_FILES);
+    }
+    let central_dir_size =
+        usize::try_from(location.size).map_err(|_| MZ_ZIP_UNSUPPORTED_CDIR_SIZE)?;
+
+    let mut central_dir = vec![0; central_dir_size];
+    read_at(zip, state, location.offset, &mut central_dir)?;
+    let entries = read_central_dir(&central_dir, location.num_entries)
+        .map_err(|_| MZ_ZIP_INVALID_HEADER_OR_CORRUPTED)?;
+    for entry in entries {
+        let end = entry
+            .local_header_offset
+            .checked_add(LOCAL_FILE_HEADER_LEN as u64 + entry.compressed_size);
+        if end.map_or(true, |end| end > size) {
+            return Err(MZ_ZIP_INVALID_HEADER_OR_CORRUPTED);
+        }
+        state.push_entry(entry);
+    }
+
+    state.zip64 |= location.zip64;
+    zip.m_central_directory_file_ofs = location.offset;
+    zip.m_total_files = state.entries.len() as mz_uint32;
+    Ok(())
+}
+
+/// Whether the entry could be extracted, before looking at its data.
+fn check_extractable(entry: &ZipEntry, flags: mz_uint) -> Result<(), mz_zip_error> {
+    if entry.flags
+        & (general_purpose_flags::ENCRYPTED | STRONG_ENCRYPTION_FLAG | COMPRESSED_PATCH_FLAG)
+        != 0
+    {
+        return Err(MZ_ZIP_UNSUPPORTED_ENCRYPTION);
+    }
+    if flags & MZ_ZIP_FLAG_COMPRESSED_DATA == 0 && !is_supported_method(entry.method) {
+        return Err(MZ_ZIP_UNSUPPORTED_METHOD);
+    }
+    Ok(())
+}
+
+fn is_supported_method(method: u16) -> bool {
+    matches!(
+        method,
+        methods::STORED | methods::DEFLATED | methods::DEFLATE64
+    )
+}
+
+/// Offset of the data of `entry`, after its local header.
+unsafe fn data_offset(
+    zip: &mz_zip_archive,
+    state: &mz_zip_internal_state,
+    entry: &ZipEntry,
+) -> Result<u64, mz_zip_error> {
+    let mut header = [0; LOCAL_FILE_HEADER_LEN];
+    read_at(zip, state, entry.local_header_offset, &mut header)?;
+    let header_len = local_header_len(&header).map_err(|_| MZ_ZIP_INVALID_HEADER_OR_CORRUPTED)?;
+    let ofs = entry.local_header_offset + header_len;
+    match ofs.checked_add(entry.compressed_size) {
+        Some(end) if end <= zip.m_archive_size => Ok(ofs),
+        _ => Err(MZ_ZIP_INVALID_HEADER_OR_CORRUPTED),
+    }
+}
+
+fn decode_error(err: ZipError) -> mz_zip_error {
+    match err {
+        // Only returned when the data is larger than the size in the header.
+        ZipError::InvalidData(miniz_oxide::inflate::TINFLStatus::Failed) => {
+            MZ_ZIP_UNEXPECTED_DECOMPRESSED_SIZE
+        }
+        err => zip_error(err),
+    }
+}
+
+fn finish_error(err: ZipError) -> mz_zip_error {
+    match err {
+        ZipError::InvalidData(_) => MZ_ZIP_UNEXPECTED_DECOMPRESSED_SIZE,
+        err => zip_error(err),
+    }
+}
+
+/// Extract the entry at `index`, passing the data to `sink` in pieces. Stops with
+/// `MZ_ZIP_WRITE_CALLBACK_FAILED` if `sink` returns false.
+///
+/// With `MZ_ZIP_FLAG_COMPRESSED_DATA` the data is passed on without decompressing it.
+unsafe fn extract_entry<F>(
+    zip: &mz_zip_archive,
+    index: mz_uint,
+    flags: mz_uint,
+    mut sink: F,
+) -> Result<(), mz_zip_error>
+where
+    F: FnMut(&[u8]) -> bool,
+{
+    let state = state(zip)?;
+    let entry = state.entry(index)?;
+    if entry.is_dir() || entry.compressed_size == 0 {
+        return Ok(());
+    }
+    check_extractable(entry, flags)?;
+
+    let mut ofs = data_offset(zip, state, entry)?;
+    let mut remaining = entry.compressed_size;
+    let mut input = vec![0; cmp::min(remaining, MZ_ZIP_MAX_IO_BUF_SIZE as u64) as usize];
+
+    if flags & MZ_ZIP_FLAG_COMPRESSED_DATA != 0 {
+        while remaining > 0 {
+            let len = cmp::min(remaining, input.len() as u64) as usize;
+            read_at(zip, state, ofs, &mut input[..len])?;
+            if !sink(&input[..len]) {
+                return Err(MZ_ZIP_WRITE_CALLBACK_FAILED);
+            }
+            ofs += len as u64;
+            remaining -= len as u64;
+        }
+        return Ok(());
+    }
+
+    let mut decoder = EntryDecoder::new(entry).map_err(zip_error)?;
+    let mut output = vec![0; MZ_ZIP_MAX_IO_BUF_SIZE];
+    let (mut pos, mut end) = (0, 0);
+    while !decoder.is_done() {
+        if pos == end && remaining > 0 {
+            end = cmp::min(remaining, input.len() as u64) as usize;
+            read_at(zip, state, ofs, &mut input[..end])?;
+            pos = 0;
+            ofs += end as u64;
+            remaining -= end as u64;
+        }
+        let (consumed, written) = decoder
+            .decode(&input[pos..end], &mut output)
+            .map_err(decode_error)?;
+        pos += consumed;
+        if written > 0 && !sink(&output[..written]) {
+            return Err(MZ_ZIP_WRITE_CALLBACK_FAILED);
+        }
+    }
+    decoder.finish().map_err(finish_error)?;
+    Ok(())
+}
+
+/// Compare names or comments the way the locate functions do.
+fn names_equal(a: &[u8], b: &[u8], flags: mz_uint) -> bool {
+    if flags & MZ_ZIP_FLAG_CASE_SENSITIVE != 0 {
+        a == b
+    } else {
+        a.eq_ignore_ascii_case(b)
+    }
+}
+
+fn locate(
+    state: &mz_zip_internal_state,
+    name: &[u8],
+    comment: Option<&[u8]>,
+    flags: mz_uint,
+) -> Option<usize> {
+    state.entries.iter().position(|entry| {
+        if let Some(comment) = comment {
+            if !names_equal(&entry.comment, comment, flags) {
+                return false;
+            }
+        }
+        let mut entry_name = &entry.name[..];
+        if flags & MZ_ZIP_FLAG_IGNORE_PATH != 0 {
+            if let Some(sep) = entry_name
+                .iter()
+                .rposition(|&c| c == b'/' || c == b'\\' || c == b':')
+            {
+                entry_name = &entry_name[sep + 1..];
+            }
+        }
+        names_equal(entry_name, name, flags)
+    })
+}
+
+unsafe fn locate_file(
+    zip: &mz_zip_archive,
+    name: *const c_char,
+    comment: *const c_char,
+    flags: mz_uint,
+) -> Result<mz_uint32, mz_zip_error> {
+    let state = state(zip)?;
+    let name = c_bytes(name)?;
+    let comment = if comment.is_null() {
+        None
+    } else {
+        Some(c_bytes(comment)?)
+    };
+    if name.len() > usize::from(u16::MAX) || comment.map_or(0, |c| c.len()) > usize::from(u16::MAX)
+    {
+        return Err(MZ_ZIP_INVALID_PARAMETER);
+    }
+    match locate(state, name, comment, flags) {
+        Some(index) => Ok(index as mz_uint32),
+        None => Err(MZ_ZIP_FILE_NOT_FOUND),
+    }
+}
+
+/// Names with an absolute path, or with `\` or `:`, are rejected like in miniz.
+fn valid_archive_name(name: &[u8]) -> bool {
+    name.first() != Some(&b'/') && !name.iter().any(|&c| c == b'\\' || c == b':')
+}
+
+fn level_from_flags(level_and_flags: mz_uint) -> Result<(mz_uint, mz_uint), mz_zip_error> {
+    let level_and_flags = if (level_and_flags as c_int) < 0 {
+        DEFAULT_LEVEL
+    } else {
+        level_and_flags
+    };
+    let level = level_and_flags & 0xF;
+    if level > 10 {
+        return Err(MZ_ZIP_INVALID_PARAMETER);
+    }
+    Ok((level, level_and_flags))
+}
+
+/// The state of an archive that is being written.
+unsafe fn writing_state<'a>(
+    zip: &mz_zip_archive,
+) -> Result<&'a mut mz_zip_internal_state, mz_zip_error> {
+    let state = state(zip)?;
+    if zip.m_zip_mode != MZ_ZIP_MODE_WRITING || state.writer.is_none() {
+        return Err(MZ_ZIP_INVALID_PARAMETER);
+    }
+    if zip.m_total_files == u32::MAX {
+        return Err(MZ_ZIP_TOO_MANY_FILES);
+    }
+    Ok(state)
+}
+
+/// Write the output of the writer to the archive, and record the entries it has added.
+unsafe fn flush_writer(
+    zip: &mut mz_zip_archive,
+    state: &mut mz_zip_internal_state,
+) -> Result<(), mz_zip_error> {
+    let writer = state.writer.as_mut().ok_or(MZ_ZIP_INVALID_PARAMETER)?;
+    let out = writer.take_output();
+    let new_entries = writer.entries()[state.entries.len()..].to_vec();
+    write_at(zip, state, zip.m_archive_size, &out)?;
+    zip.m_archive_size += out.len() as u64;
+    for entry in new_entries {
+        state.push_entry(entry);
+    }
+    zip.m_total_files = state.entries.len() as mz_uint32;
+    Ok(())
+}
+
+/// Options for an entry added through the C API.
+fn entry_options(
+    level: mz_uint,
+    time: time_t,
+    comment: &[u8],
+    local_extra: &[u8],
+    central_extra: &[u8],
+) -> EntryOptions {
+    EntryOptions {
+        method: if level == 0 {
+            methods::STORED
+        } else {
+            methods::DEFLATED
+        },
+        level: level as u8,
+        last_modified: time_t_to_dos(time),
+        comment: comment.to_vec(),
+        external_attributes: 0,
+        extra: central_extra.to_vec(),
+        local_extra: local_extra.to_vec(),
+        large_file: false,
+    }
+}
+
+/// Fill in `stat` for the entry at `index`.
+unsafe fn file_stat(
+    zip: &mut mz_zip_archive,
+    index: mz_uint,
+    stat: &mut mz_zip_archive_file_stat,
+) -> Result<(), mz_zip_error> {
+    let state = state(zip)?;
+    let entry = state.entry(index)?;
+    stat.m_file_index = index;
+    stat.m_central_dir_ofs =
+        zip.m_central_directory_file_ofs + state.central_dir_offsets[index as usize];
+    stat.m_version_made_by = entry.version_made_by;
+    stat.m_version_needed = entry.version_needed;
+    stat.m_bit_flag = entry.flags;
+    stat.m_method = entry.method;
+    stat.m_time = dos_to_time_t(entry.last_modified);
+    stat.m_crc32 = entry.crc32;
+    stat.m_comp_size = entry.compressed_size;
+    stat.m_uncomp_size = entry.uncompressed_size;
+    stat.m_internal_attr = entry.internal_attributes;
+    stat.m_external_attr = entry.external_attributes;
+    stat.m_local_header_ofs = entry.local_header_offset;
+    stat.m_comment_size = copy_c_string(&entry.comment, &mut stat.m_comment) as mz_uint32;
+    copy_c_string(&entry.name, &mut stat.m_filename);
+    stat.m_is_directory = as_bool(entry.is_dir());
+    stat.m_is_encrypted = as_bool(is_encrypted(entry));
+    stat.m_is_supported = as_bool(match supported(entry) {
+        Ok(()) => true,
+        Err(err) => {
+            zip.m_last_error = err;
+            false
+        }
+    });
+    Ok(())
+}
+
+/// Copy `src` to `dst`, truncated to leave room for a terminating null byte. Returns the
+/// number of bytes copied, not counting the null byte.
+fn copy_c_string(src: &[u8], dst: &mut [c_char]) -> usize {
+    let len = cmp::min(src.len(), dst.len() - 1);
+    for (d, &s) in dst.iter_mut().zip(&src[..len]) {
+        *d = s as c_char;
+    }
+    dst[len] = 0;
+    len
+}
+
+fn is_encrypted(entry: &ZipEntry) -> bool {
+    entry.flags & (general_purpose_flags::ENCRYPTED | STRONG_ENCRYPTION_FLAG) != 0
+}
+
+fn supported(entry: &ZipEntry) -> Result<(), mz_zip_error> {
+    if !is_supported_method(entry.method) {
+        Err(MZ_ZIP_UNSUPPORTED_METHOD)
+    } else if is_encrypted(entry) {
+        Err(MZ_ZIP_UNSUPPORTED_ENCRYPTION)
+    } else if entry.flags & COMPRESSED_PATCH_FLAG != 0 {
+        Err(MZ_ZIP_UNSUPPORTED_FEATURE)
+    } else {
+        Ok(())
+    }
+}
+
+/// Check the local header of the entry against the central directory, and unless
+/// `MZ_ZIP_FLAG_VALIDATE_HEADERS_ONLY` is set, that the data decompresses correctly.
+unsafe fn validate_file(
+    zip: &mut mz_zip_archive,
+    index: mz_uint,
+    flags: mz_uint,
+) -> Result<(), mz_zip_error> {
+    let state = state(zip)?;
+    let entry = state.entry(index)?;
+    check_extractable(entry, 0)?;
+
+    if flags & MZ_ZIP_FLAG_VALIDATE_LOCATE_FILE_FLAG != 0
+        && locate(state, &entry.name, None, flags) != Some(index as usize)
+    {
+        return Err(MZ_ZIP_VALIDATION_FAILED);
+    }
+
+    let mut header = vec![0; LOCAL_FILE_HEADER_LEN + entry.name.len()];
+    read_at(zip, state, entry.local_header_offset, &mut header)?;
+    local_header_len(&header).map_err(|_| MZ_ZIP_INVALID_HEADER_OR_CORRUPTED)?;
+    if header[LOCAL_FILE_HEADER_LEN..] != entry.name[..] {
+        return Err(MZ_ZIP_VALIDATION_FAILED);
+    }
+    if entry.flags & general_purpose_flags::DATA_DESCRIPTOR == 0 {
+        let read_u32 = |ofs: usize| {
+            u32::from_le_bytes([
+                header[ofs],
+                header[ofs + 1],
+                header[ofs + 2],
+                header[ofs + 3],
+            ])
+        };
+        let sizes_match =
+            |ofs: usize, size: u64| read_u32(ofs) == u32::MAX || u64::from(read_u32(ofs)) == size;
+        if read_u32(14) != entry.crc32
+            || !sizes_match(18, entry.compressed_size)
+            || !sizes_match(22, entry.uncompressed_size)
+        {
+            return Err(MZ_ZIP_VALIDATION_FAILED);
+        }
+    }
+    data_offset(zip, state, entry)?;
+
+    if flags & MZ_ZIP_FLAG_VALIDATE_HEADERS_ONLY == 0 {
+        extract_entry(zip, index, 0, |_| true)?;
+    }
+    Ok(())
+}
+
+unsafe fn validate_archive(zip: &mut mz_zip_archive, flags: mz_uint) -> Result<(), mz_zip_error> {
+    let state = state(zip)?;
+    if zip.m_zip_mode != MZ_ZIP_MODE_READING || state.entries.len() > u32::MAX as usize {
+        return Err(MZ_ZIP_INVALID_PARAMETER);
+    }
+    for index in 0..state.entries.len() {
+        validate_file(zip, index as mz_uint, flags)?;
+    }
+    Ok(())
+}
+
+/// Extract the entry at `index` to a buffer allocated with `m_pAlloc`.
+unsafe fn extract_to_heap(
+    zip: &mut mz_zip_archive,
+    index: mz_uint,
+    size: *mut size_t,
+    flags: mz_uint,
+) -> Result<*mut c_void, mz_zip_error> {
+    if let Some(size) = size.as_mut() {
+        *size = 0;
+    }
+    let state = state(zip)?;
+    let entry = state.entry(index)?;
+    let alloc_size = if flags & MZ_ZIP_FLAG_COMPRESSED_DATA != 0 {
+        entry.compressed_size
+    } else {
+        entry.uncompressed_size
+    };
+    let alloc_size = usize::try_from(alloc_size)
+        .ok()
+        .filter(|&size| size <= isize::MAX as usize)
+        .ok_or(MZ_ZIP_INTERNAL_ERROR)?;
+
+    let buf = zip_alloc(zip, cmp::max(alloc_size, 1));
+    if buf.is_null() {
+        return Err(MZ_ZIP_ALLOC_FAILED);
+    }
+    let out = slice::from_raw_parts_mut(buf as *mut u8, alloc_size);
+    let mut pos = 0;
+    let res = extract_entry(zip, index, flags, |data| {
+        match out.get_mut(pos..pos + data.len()) {
+            Some(dst) => {
+                dst.copy_from_slice(data);
+                pos += data.len();
+                true
+            }
+            None => false,
+        }
+    });
+    if let Err(err) = res {
+        zip_free(zip, buf);
+        return Err(err);
+    }
+    if let Some(size) = size.as_mut() {
+        *size = alloc_size;
+    }
+    Ok(buf)
+}
+
+unsafe fn extract_to_mem(
+    zip: &mut mz_zip_archive,
+    index: mz_uint,
+    buf: *mut c_void,
+    buf_size: size_t,
+    flags: mz_uint,
+) -> Result<bool, mz_zip_error> {
+    if buf_size > 0 && buf.is_null() {
+        return Err(MZ_ZIP_INVALID_PARAMETER);
+    }
+    let state = state(zip)?;
+    let entry = state.entry(index)?;
+    if entry.is_dir() || entry.compressed_size == 0 {
+        return Ok(true);
+    }
+    check_extractable(entry, flags)?;
+    let needed = if flags & MZ_ZIP_FLAG_COMPRESSED_DATA != 0 {
+        entry.compressed_size
+    } else {
+        entry.uncompressed_size
+    };
+    if (buf_size as u64) < needed {
+        return Err(MZ_ZIP_BUF_TOO_SMALL);
+    }
+
+    let out = slice::from_raw_parts_mut(buf as *mut u8, buf_size);
+    let mut pos = 0;
+    extract_entry(zip, index, flags, |data| {
+        match out.get_mut(pos..pos + data.len()) {
+            Some(dst) => {
+                dst.copy_from_slice(data);
+                pos += data.len();
+                true
+            }
+            None => false,
+        }
+    })?;
+    Ok(true)
+}
+
+unsafe fn extract_to_callback(
+    zip: &mut mz_zip_archive,
+    index: mz_uint,
+    callback: Option<mz_file_write_func>,
+    opaque: *mut c_void,
+    flags: mz_uint,
+) -> Result<bool, mz_zip_error> {
+    let callback = callback.ok_or(MZ_ZIP_INVALID_PARAMETER)?;
+    let mut ofs = 0;
+    extract_entry(zip, index, flags, |data| {
+        let written = callback(opaque, ofs, data.as_ptr() as *const c_void, data.len());
+        ofs += data.len() as u64;
+        written == data.len()
+    })?;
+    Ok(true)
+}
+
+unsafe fn extract_to_cfile(
+    zip: &mut mz_zip_archive,
+    index: mz_uint,
+    file: *mut FILE,
+    flags: mz_uint,
+) -> Result<bool, mz_zip_error> {
+    if file.is_null() {
+        return Err(MZ_ZIP_INVALID_PARAMETER);
+    }
+    let state = state(zip)?;
+    let entry = state.entry(index)?;
+    if entry.is_dir() || supported(entry).is_err() {
+        return Err(MZ_ZIP_UNSUPPORTED_FEATURE);
+    }
+    extract_entry(zip, index, flags, |data| {
+        libc::fwrite(data.as_ptr() as *const c_void, 1, data.len(), file) == data.len()
+    })?;
+    Ok(true)
+}
+
+unsafe fn extract_to_file(
+    zip: &mut mz_zip_archive,
+    index: mz_uint,
+    dst_filename: *const c_char,
+    flags: mz_uint,
+) -> Result<bool, mz_zip_error> {
+    if dst_filename.is_null() {
+        return Err(MZ_ZIP_INVALID_PARAMETER);
+    }
+    let state = state(zip)?;
+    let entry = state.entry(index)?;
+    if entry.is_dir() || supported(entry).is_err() {
+        return Err(MZ_ZIP_UNSUPPORTED_FEATURE);
+    }
+    let time = dos_to_time_t(entry.last_modified);
+
+    let file = libc::fopen(dst_filename, b"wb\0".as_ptr() as *const c_char);
+    if file.is_null() {
+        return Err(MZ_ZIP_FILE_OPEN_FAILED);
+    }
+    let res = extract_to_cfile(zip, index, file, flags);
+    if libc::fclose(file) == libc::EOF {
+        res?;
+        return Err(MZ_ZIP_FILE_CLOSE_FAILED);
+    }
+    res?;
+
+    #[cfg(unix)]
+    {
+        let times = libc::utimbuf {
+            actime: time,
+            modtime: time,
+        };
+        libc::utime(dst_filename, &times);
+    }
+    #[cfg(not(unix))]
+    let _ = time;
+    Ok(true)
+}
+
+/// Add an entry with the data in memory, see `mz_zip_writer_add_mem_ex_v2`.
+#[allow(clippy::too_many_arguments)]
+unsafe fn add_mem(
+    zip: &mut mz_zip_archive,
+    archive_name: *const c_char,
+    buf: *const c_void,
+    buf_size: size_t,
+    comment: *const c_void,
+    comment_size: u16,
+    level_and_flags: mz_uint,
+    uncomp_size: u64,
+    uncomp_crc32: mz_uint32,
+    last_modified: *const time_t,
+    local_extra: &[u8],
+    central_extra: &[u8],
+) -> Result<bool, mz_zip_error> {
+    let (level, level_and_flags) = level_from_flags(level_and_flags)?;
+    let state = writing_state(zip)?;
+    let name = c_bytes(archive_name)?;
+    let data = data_slice(buf, buf_size)?;
+    let comment = data_slice(comment, usize::from(comment_size))?;
+    let compressed = level_and_flags & MZ_ZIP_FLAG_COMPRESSED_DATA != 0;
+    if !compressed && uncomp_size != 0 {
+        return Err(MZ_ZIP_INVALID_PARAMETER);
+    }
+    if !valid_archive_name(name) || name.len() > usize::from(u16::MAX) {
+        return Err(MZ_ZIP_INVALID_FILENAME);
+    }
+    let is_dir = name.last() == Some(&b'/');
+    if is_dir && (!data.is_empty() || uncomp_size != 0) {
+        return Err(MZ_ZIP_INVALID_PARAMETER);
+    }
+
+    let time = match last_modified.as_ref() {
+        Some(&time) => time,
+        None => current_time(),
+    };
+    let mut options = entry_options(level, time, comment, local_extra, central_extra);
+    if is_dir {
+        options.external_attributes = DOS_DIRECTORY_ATTRIBUTE;
+    }
+
+    let writer = state.writer.as_mut().ok_or(MZ_ZIP_INVALID_PARAMETER)?;
+    let res = if compressed {
+        let entry = ZipEntry {
+            name: name.to_vec(),
+            comment: options.comment,
+            extra: options.extra,
+            // Version 2.0 on MS-DOS, like the attributes.
+            version_made_by: 20,
+            version_needed: 20,
+            flags: 0,
+            method: methods::DEFLATED,
+            last_modified: options.last_modified,
+            crc32: uncomp_crc32,
+            compressed_size: data.len() as u64,
+            uncompressed_size: uncomp_size,
+            internal_attributes: 0,
+            external_attributes: options.external_attributes,
+            local_header_offset: 0,
+        };
+        writer.add_raw_entry(&entry, data)
+    } else if is_dir {
+        writer.add_directory(name, &options)
+    } else {
+        writer.add_entry(name, data, &options)
+    };
+    res.map_err(zip_error)?;
+    flush_writer(zip, state)?;
+    Ok(true)
+}
+
+/// Add an entry with the data read from `src_file`, see `mz_zip_writer_add_cfile`.
+#[allow(clippy::too_many_arguments)]
+unsafe fn add_cfile(
+    zip: &mut mz_zip_archive,
+    archive_name: *const c_char,
+    src_file: *mut FILE,
+    size_to_add: u64,
+    file_time: *const time_t,
+    comment: *const c_void,
+    comment_size: u16,
+    level_and_flags: mz_uint,
+    local_extra: &[u8],
+    central_extra: &[u8],
+) -> Result<bool, mz_zip_error> {
+    let (level, level_and_flags) = level_from_flags(level_and_flags)?;
+    let state = writing_state(zip)?;
+    let name = c_bytes(archive_name)?;
+    let comment = data_slice(comment, usize::from(comment_size))?;
+    if src_file.is_null() || level_and_flags & MZ_ZIP_FLAG_COMPRESSED_DATA != 0 {
+        return Err(MZ_ZIP_INVALID_PARAMETER);
+    }
+    if !valid_archive_name(name) || name.len() > usize::from(u16::MAX) {
+        return Err(MZ_ZIP_INVALID_FILENAME);
+    }
+
+    let time = match file_time.as_ref() {
+        Some(&time) => time,
+        None => current_time(),
+    };
+    let level = if size_to_add == 0 { 0 } else { level };
+    let mut options = entry_options(level, time, comment, local_extra, central_extra);
+    // Deflated data can end up slightly larger than the input, so leave plenty of headroom
+    // before the 4 GiB limit of the local header.
+    options.large_file = size_to_add > u64::from(u32::MAX) / 2;
+
+    let writer = state.writer.as_mut().ok_or(MZ_ZIP_INVALID_PARAMETER)?;
+    writer.start_entry(name, &options).map_err(zip_error)?;
+    let mut buf = vec![0; cmp::min(size_to_add, MZ_ZIP_MAX_IO_BUF_SIZE as u64) as usize];
+    let mut remaining = size_to_add;
+    while remaining > 0 {
+        let len = cmp::min(remaining, buf.len() as u64) as usize;
+        if libc::fread(buf.as_mut_ptr() as *mut c_void, 1, len, src_file) != len {
+            return Err(MZ_ZIP_FILE_READ_FAILED);
+        }
+        writer.write(&buf[..len]).map_err(zip_error)?;
+        remaining -= len as u64;
+    }
+    writer.finish_entry().map_err(zip_error)?;
+    flush_writer(zip, state)?;
+    Ok(true)
+}
+
+unsafe fn add_file(
+    zip: &mut mz_zip_archive,
+    archive_name: *const c_char,
+    src_filename: *const c_char,
+    comment: *const c_void,
+    comment_size: u16,
+    level_and_flags: mz_uint,
+) -> Result<bool, mz_zip_error> {
+    if src_filename.is_null() {
+        return Err(MZ_ZIP_INVALID_PARAMETER);
+    }
+    let time = c_path(CStr::from_ptr(src_filename))
+        .and_then(|path| path.metadata().ok())
+        .and_then(|metadata| metadata.modified().ok())
+        .ok_or(MZ_ZIP_FILE_STAT_FAILED)?;
+    let time = time
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |time| time.as_secs() as time_t);
+
+    let file = libc::fopen(src_filename, b"rb\0".as_ptr() as *const c_char);
+    if file.is_null() {
+        return Err(MZ_ZIP_FILE_OPEN_FAILED);
+    }
+    let size = if fseek64(file, 0, libc::SEEK_END) == 0 {
+        let size = ftell64(file);
+        if size >= 0 && fseek64(file, 0, libc::SEEK_SET) == 0 {
+            Ok(size as u64)
+        } else {
+            Err(MZ_ZIP_FILE_SEEK_FAILED)
+        }
+    } else {
+        Err(MZ_ZIP_FILE_SEEK_FAILED)
+    };
+    let res = size.and_then(|size| {
+        add_cfile(
+            zip,
+            archive_name,
+            file,
+            size,
+            &time,
+            comment,
+            comment_size,
+            level_and_flags,
+            &[],
+            &[],
+        )
+    });
+    libc::fclose(file);
+    res
+}
+
+unsafe fn add_from_zip_reader(
+    zip: &mut mz_zip_archive,
+    source_zip: *mut mz_zip_archive,
+    src_index: mz_uint,
+) -> Result<bool, mz_zip_error> {
+    let source_zip = source_zip.as_ref().ok_or(MZ_ZIP_INVALID_PARAMETER)?;
+    let source_state = state(source_zip)?;
+    let state = writing_state(zip)?;
+    let entry = source_state.entry(src_index)?;
+
+    let data_ofs = data_offset(source_zip, source_state, entry)?;
+    let data_len = usize::try_from(entry.compressed_size).map_err(|_| MZ_ZIP_INTERNAL_ERROR)?;
+    let mut data = vec![0; data_len];
+    read_at(source_zip, source_state, data_ofs, &mut data)?;
+
+    let writer = state.writer.as_mut().ok_or(MZ_ZIP_INVALID_PARAMETER)?;
+    writer.add_raw_entry(entry, &data).map_err(zip_error)?;
+    flush_writer(zip, state)?;
+    Ok(true)
+}
+
+unsafe fn finalize_archive(zip: &mut mz_zip_archive) -> Result<bool, mz_zip_error> {
+    let state = state(zip)?;
+    if zip.m_zip_mode != MZ_ZIP_MODE_WRITING {
+        return Err(MZ_ZIP_INVALID_PARAMETER);
+    }
+    flush_writer(zip, state)?;
+    let writer = state.writer.take().ok_or(MZ_ZIP_INVALID_PARAMETER)?;
+    let out = writer.finish().map_err(zip_error)?;
+
+    let mut records_len = END_OF_CENTRAL_DIR_LEN;
+    let zip64_ofs = out
+        .len()
+        .checked_sub(END_OF_CENTRAL_DIR_LEN + ZIP64_END_OF_CENTRAL_DIR_LEN);
+    if let Some(ofs) = zip64_ofs {
+        let sig = u32::from_le_bytes([out[ofs], out[ofs + 1], out[ofs + 2], out[ofs + 3]]);
+        if sig == ZIP64_END_OF_CENTRAL_DIR_SIG {
+            records_len += ZIP64_END_OF_CENTRAL_DIR_LEN;
+            state.zip64 = true;
+        }
+    }
+
+    let central_dir_ofs = zip.m_archive_size;
+    write_at(zip, state, central_dir_ofs, &out)?;
+    zip.m_central_directory_file_ofs = central_dir_ofs;
+    zip.m_archive_size += out.len() as u64;
+    state.central_dir_size = (out.len() - records_len) as u64;
+
+    if !state.file.is_null() && libc::fflush(state.file) == libc::EOF {
+        return Err(MZ_ZIP_FILE_CLOSE_FAILED);
+    }
+    zip.m_zip_mode = MZ_ZIP_MODE_WRITING_HAS_BEEN_FINALIZED;
+    Ok(true)
+}
+
+/// Check that the archive can be set up for writing, before touching any of its fields.
+fn check_writer_params(zip: &mz_zip_archive) -> Result<(), mz_zip_error> {
+    if !zip.m_pState.is_null()
+        || zip.m_zip_mode != MZ_ZIP_MODE_INVALID
+        || zip.m_file_offset_alignment & zip.m_file_offset_alignment.wrapping_sub(1) != 0
+    {
+        return Err(MZ_ZIP_INVALID_PARAMETER);
+    }
+    Ok(())
+}
+
+unsafe fn writer_init(
+    zip: &mut mz_zip_archive,
+    existing_size: u64,
+    flags: mz_uint,
+) -> Result<(), mz_zip_error> {
+    check_writer_params(zip)?;
+    if zip.m_pWrite.is_none()
+        || (flags & MZ_ZIP_FLAG_WRITE_ALLOW_READING != 0 && zip.m_pRead.is_none())
+    {
+        return Err(MZ_ZIP_INVALID_PARAMETER);
+    }
+    init_internal(zip, MZ_ZIP_MODE_WRITING)?;
+    state(zip)?.writer = Some(ZipWriter::with_entries(Vec::new(), existing_size));
+    zip.m_archive_size = existing_size;
+    zip.m_zip_type = MZ_ZIP_TYPE_USER;
+    Ok(())
+}
+
+/// Use the built-in read and write functions for the archive.
+fn set_archive_io(zip: &mut mz_zip_archive, flags: mz_uint) {
+    zip.m_pWrite = Some(archive_write_func);
+    if flags & MZ_ZIP_FLAG_WRITE_ALLOW_READING != 0 {
+        zip.m_pRead = Some(archive_read_func);
+    }
+    zip.m_pIO_opaque = zip as *mut mz_zip_archive as *mut c_void;
+}
+
+unsafe fn init_from_reader(
+    zip: &mut mz_zip_archive,
+    filename: *const c_char,
+) -> Result<bool, mz_zip_error> {
+    let state = state(zip)?;
+    if zip.m_zip_mode != MZ_ZIP_MODE_READING {
+        return Err(MZ_ZIP_INVALID_PARAMETER);
+    }
+    let own_io = zip.m_pIO_opaque == zip as *mut mz_zip_archive as *mut c_void;
+    match zip.m_zip_type {
+        MZ_ZIP_TYPE_FILE | MZ_ZIP_TYPE_CFILE => {
+            if !own_io {
+                return Err(MZ_ZIP_INVALID_PARAMETER);
+            }
+            if zip.m_zip_type == MZ_ZIP_TYPE_FILE {
+                if filename.is_null() {
+                    return Err(MZ_ZIP_INVALID_PARAMETER);
+                }
+                state.file =
+                    libc::freopen(filename, b"r+b\0".as_ptr() as *const c_char, state.file);
+                if state.file.is_null() {
+                    // The old file has been closed by `freopen`.
+                    let _ = end_internal(zip);
+                    return Err(MZ_ZIP_FILE_OPEN_FAILED);
+                }
+            }
+            zip.m_pWrite = Some(archive_write_func);
+        }
+        MZ_ZIP_TYPE_MEMORY => {
+            if !own_io {
+                return Err(MZ_ZIP_INVALID_PARAMETER);
+            }
+            // Like in miniz the memory block is taken over and grown with `m_pRealloc`.
+            state.heap = true;
+            state.mem_capacity = state.mem_size;
+            zip.m_pWrite = Some(archive_write_func);
+        }
+        _ => {
+            if zip.m_pWrite.is_none() {
+                return Err(MZ_ZIP_INVALID_PARAMETER);
+            }
+        }
+    }
+
+    // The new entries are written over the old central directory.
+    zip.m_archive_size = zip.m_central_directory_file_ofs;
+    zip.m_central_directory_file_ofs = 0;
+    state.writer = Some(ZipWriter::with_entries(
+        state.entries.clone(),
+        zip.m_archive_size,
+    ));
+    zip.m_zip_mode = MZ_ZIP_MODE_WRITING;
+    Ok(true)
+}
+
+unsafe fn reader_init_file(
+    zip: &mut mz_zip_archive,
+    filename: *const c_char,
+    file_start_ofs: u64,
+    archive_size: u64,
+) -> Result<bool, mz_zip_error> {
+    if filename.is_null() || file_start_ofs > i64::MAX as u64 {
+        return Err(MZ_ZIP_INVALID_PARAMETER);
+    }
+    let file = libc::fopen(filename, b"rb\0".as_ptr() as *const c_char);
+    if file.is_null() {
+        return Err(MZ_ZIP_FILE_OPEN_FAILED);
+    }
+    let archive_size = if archive_size == 0 {
+        if fseek64(file, 0, libc::SEEK_END) != 0 {
+            libc::fclose(file);
+            return Err(MZ_ZIP_FILE_SEEK_FAILED);
+        }
+        (ftell64(file) as u64).saturating_sub(file_start_ofs)
+    } else {
+        archive_size
+    };
+    if archive_size < END_OF_CENTRAL_DIR_LEN as u64 {
+        libc::fclose(file);
+        return Err(MZ_ZIP_NOT_AN_ARCHIVE);
+    }
+
+    if let Err(err) = init_internal(zip, MZ_ZIP_MODE_READING) {
+        libc::fclose(file);
+        return Err(err);
+    }
+    zip.m_zip_type = MZ_ZIP_TYPE_FILE;
+    zip.m_pRead = Some(archive_read_func);
+    zip.m_pIO_opaque = zip as *mut mz_zip_archive as *mut c_void;
+    zip.m_archive_size = archive_size;
+    let state = state(zip)?;
+    state.file = file;
+    state.file_start_ofs = file_start_ofs;
+    init_or_end(zip, |zip| read_central_directory(zip))
+}
+
+unsafe fn reader_init_cfile(
+    zip: &mut mz_zip_archive,
+    file: *mut FILE,
+    archive_size: u64,
+) -> Result<bool, mz_zip_error> {
+    if file.is_null() {
+        return Err(MZ_ZIP_FILE_OPEN_FAILED);
+    }
+    let file_start_ofs = ftell64(file);
+    if file_start_ofs < 0 {
+        return Err(MZ_ZIP_FILE_SEEK_FAILED);
+    }
+    let archive_size = if archive_size == 0 {
+        if fseek64(file, 0, libc::SEEK_END) != 0 {
+            return Err(MZ_ZIP_FILE_SEEK_FAILED);
+        }
+        (ftell64(file) - file_start_ofs) as u64
+    } else {
+        archive_size
+    };
+    if archive_size < END_OF_CENTRAL_DIR_LEN as u64 {
+        return Err(MZ_ZIP_NOT_AN_ARCHIVE);
+    }
+
+    init_internal(zip, MZ_ZIP_MODE_READING)?;
+    zip.m_zip_type = MZ_ZIP_TYPE_CFILE;
+    zip.m_pRead = Some(archive_read_func);
+    zip.m_pIO_opaque = zip as *mut mz_zip_archive as *mut c_void;
+    zip.m_archive_size = archive_size;
+    let state = state(zip)?;
+    state.file = file;
+    state.file_start_ofs = file_start_ofs as u64;
+    init_or_end(zip, |zip| read_central_directory(zip))
+}
+
+unsafe fn writer_init_file(
+    zip: &mut mz_zip_archive,
+    filename: *const c_char,
+    size_to_reserve_at_beginning: u64,
+    flags: mz_uint,
+) -> Result<bool, mz_zip_error> {
+    if filename.is_null() {
+        return Err(MZ_ZIP_INVALID_PARAMETER);
+    }
+    set_archive_io(zip, flags);
+    writer_init(zip, size_to_reserve_at_beginning, flags)?;
+    zip.m_zip_type = MZ_ZIP_TYPE_FILE;
+
+    let mode: &[u8] = if flags & MZ_ZIP_FLAG_WRITE_ALLOW_READING != 0 {
+        b"w+b\0"
+    } else {
+        b"wb\0"
+    };
+    let file = libc::fopen(filename, mode.as_ptr() as *const c_char);
+    if file.is_null() {
+        let _ = end_internal(zip);
+        return Err(MZ_ZIP_FILE_OPEN_FAILED);
+    }
+    state(zip)?.file = file;
+
+    init_or_end(zip, |zip| {
+        let state = state(zip)?;
+        let zeroes = [0; 4096];
+        let mut ofs = 0;
+        while ofs < size_to_reserve_at_beginning {
+            let len = cmp::min(size_to_reserve_at_beginning - ofs, zeroes.len() as u64) as usize;
+            write_at(zip, state, ofs, &zeroes[..len])?;
+            ofs += len as u64;
+        }
+        Ok(())
+    })
+}
+
+unsafe fn reader_end(zip: &mut mz_zip_archive) -> Result<bool, mz_zip_error> {
+    if zip.m_zip_mode != MZ_ZIP_MODE_READING {
+        return Err(MZ_ZIP_INVALID_PARAMETER);
+    }
+    end_internal(zip)?;
+    Ok(true)
+}
+
+unsafe fn writer_end(zip: &mut mz_zip_archive) -> Result<bool, mz_zip_error> {
+    if zip.m_zip_mode != MZ_ZIP_MODE_WRITING
+        && zip.m_zip_mode != MZ_ZIP_MODE_WRITING_HAS_BEEN_FINALIZED
+    {
+        return Err(MZ_ZIP_INVALID_PARAMETER);
+    }
+    end_internal(zip)?;
+    Ok(true)
+}
+
+unmangle!(
+    /// Clear the archive struct, which has to be done before it's used.
+    pub unsafe extern "C" fn mz_zip_zero_struct(pZip: *mut mz_zip_archive) {
+        if !pZip.is_null() {
+            ptr::write_bytes(pZip, 0, 1);
+        }
+    }
+
+    pub unsafe extern "C" fn mz_zip_reader_init(
+        pZip: *mut mz_zip_archive,
+        size: u64,
+        _flags: mz_uint,
+    ) -> mz_bool {
+        as_bool(with_archive(pZip, false, |zip| {
+            if zip.m_pRead.is_none() {
+                return Err(MZ_ZIP_INVALID_PARAMETER);
+            }
+            init_internal(zip, MZ_ZIP_MODE_READING)?;
+            zip.m_zip_type = MZ_ZIP_TYPE_USER;
+            zip.m_archive_size = size;
+            init_or_end(zip, |zip| read_central_directory(zip))
+        }))
+    }
+
+    pub unsafe extern "C" fn mz_zip_reader_init_mem(
+        pZip: *mut mz_zip_archive,
+        pMem: *const c_void,
+        size: size_t,
+        _flags: mz_uint,
+    ) -> mz_bool {
+        as_bool(with_archive(pZip, false, |zip| {
+            if pMem.is_null() {
+                return Err(MZ_ZIP_INVALID_PARAMETER);
+            }
+            if size < END_OF_CENTRAL_DIR_LEN {
+                return Err(MZ_ZIP_NOT_AN_ARCHIVE);
+            }
+            init_internal(zip, MZ_ZIP_MODE_READING)?;
+            zip.m_zip_type = MZ_ZIP_TYPE_MEMORY;
+            zip.m_archive_size = size as u64;
+            zip.m_pRead = Some(archive_read_func);
+            zip.m_pIO_opaque = zip as *mut mz_zip_archive as *mut c_void;
+            let state = state(zip)?;
+            state.mem = pMem as *mut u8;
+            state.mem_size = size;
+            init_or_end(zip, |zip| read_central_directory(zip))
+        }))
+    }
+
+    pub unsafe extern "C" fn mz_zip_reader_init_file(
+        pZip: *mut mz_zip_archive,
+        pFilename: *const c_char,
+        flags: mz_uint32,
+    ) -> mz_bool {
+        mz_zip_reader_init_file_v2(pZip, pFilename, flags, 0, 0)
+    }
+
+    /// Read an archive from a file. The archive starts at `file_start_ofs` and is
+    /// `archive_size` bytes long, or extends to the end of the file if `archive_size` is 0.
+    pub unsafe extern "C" fn mz_zip_reader_init_file_v2(
+        pZip: *mut mz_zip_archive,
+        pFilename: *const c_char,
+        _flags: mz_uint,
+        file_start_ofs: u64,
+        archive_size: u64,
+    ) -> mz_bool {
+        as_bool(with_archive(pZip, false, |zip| {
+            reader_init_file(zip, pFilename, file_start_ofs, archive_size)
+        }))
+    }
+
+    /// Read an archive from an open file, starting at the current position. The file is
+    /// not closed by `mz_zip_reader_end`.
+    pub unsafe extern "C" fn mz_zip_reader_init_cfile(
+        pZip: *mut mz_zip_archive,
+        pFile: *mut FILE,
+        archive_size: u64,
+        _flags: mz_uint,
+    ) -> mz_bool {
+        as_bool(with_archive(pZip, false, |zip| {
+            reader_init_cfile(zip, pFile, archive_size)
+        }))
+    }
+
+    pub unsafe extern "C" fn mz_zip_reader_end(pZip: *mut mz_zip_archive) -> mz_bool {
+        as_bool(with_archive(pZip, false, |zip| reader_end(zip)))
+    }
+
+    pub unsafe extern "C" fn mz_zip_get_mode(pZip: *mut mz_zip_archive) -> mz_zip_mode {
+        pZip.as_ref()
+            .map_or(MZ_ZIP_MODE_INVALID, |zip| zip.m_zip_mode)
+    }
+
+    pub unsafe extern "C" fn mz_zip_get_type(pZip: *mut mz_zip_archive) -> mz_zip_type {
+        pZip.as_ref()
+            .map_or(MZ_ZIP_TYPE_INVALID, |zip| zip.m_zip_type)
+    }
+
+    pub unsafe extern "C" fn mz_zip_reader_get_num_files(pZip: *mut mz_zip_archive) -> mz_uint {
+        pZip.as_ref().map_or(0, |zip| zip.m_total_files)
+    }
+
+    pub unsafe extern "C" fn mz_zip_get_archive_size(pZip: *mut mz_zip_archive) -> u64 {
+        pZip.as_ref().map_or(0, |zip| zip.m_archive_size)
+    }
+
+    pub unsafe extern "C" fn mz_zip_get_archive_file_start_offset(
+        pZip: *mut mz_zip_archive,
+    ) -> u64 {
+        pZip.as_ref()
+            .and_then(|zip| zip.m_pState.as_ref())
+            .map_or(0, |state| state.file_start_ofs)
+    }
+
+    pub unsafe extern "C" fn mz_zip_get_cfile(pZip: *mut mz_zip_archive) -> *mut FILE {
+        with_archive(pZip, ptr::null_mut(), |zip| {
+            let state = state(zip)?;
+            if state.file.is_null() {
+                return Err(MZ_ZIP_INVALID_PARAMETER);
+            }
+            Ok(state.file)
+        })
+    }
+
+    /// Read `n` bytes of raw archive data at `file_ofs`, returning the number of bytes read.
+    pub unsafe extern "C" fn mz_zip_read_archive_data(
+        pZip: *mut mz_zip_archive,
+        file_ofs: u64,
+        pBuf: *mut c_void,
+        n: size_t,
+    ) -> size_t {
+        with_archive(pZip, 0, |zip| {
+            let state = state(zip)?;
+            if pBuf.is_null() || zip.m_pRead.is_none() {
+                return Err(MZ_ZIP_INVALID_PARAMETER);
+            }
+            if n == 0 {
+                return Ok(0);
+            }
+            Ok(read_raw(
+                zip,
+                state,
+                file_ofs,
+                slice::from_raw_parts_mut(pBuf as *mut u8, n),
+            ))
+        })
+    }
+
+    /// Find an entry by name, and by comment if `pComment` isn't null.
+    ///
+    /// Returns the index of the entry, or -1 if there is none.
+    pub unsafe extern "C" fn mz_zip_locate_file(
+        pZip: *mut mz_zip_archive,
+        pName: *const c_char,
+        pComment: *const c_char,
+        flags: mz_uint,
+    ) -> c_int {
+        let mut index = 0;
+        if mz_zip_locate_file_v2(pZip, pName, pComment, flags, &mut index) != 0 {
+            index as c_int
+        } else {
+            -1
+        }
+    }
+
+    pub unsafe extern "C" fn mz_zip_locate_file_v2(
+        pZip: *mut mz_zip_archive,
+        pName: *const c_char,
+        pComment: *const c_char,
+        flags: mz_uint,
+        pIndex: *mut mz_uint32,
+    ) -> mz_bool {
+        if let Some(index) = pIndex.as_mut() {
+            *index = 0;
+        }
+        as_bool(with_archive(pZip, false, |zip| {
+            let found = locate_file(zip, pName, pComment, flags)?;
+            if let Some(index) = pIndex.as_mut() {
+                *index = found;
+            }
+            Ok(true)
+        }))
+    }
+
+    pub unsafe extern "C" fn mz_zip_reader_locate_file(
+        pZip: *mut mz_zip_archive,
+        pName: *const c_char,
+        pComment: *const c_char,
+        flags: mz_uint,
+    ) -> c_int {
+        mz_zip_locate_file(pZip, pName, pComment, flags)
+    }
+
+    pub unsafe extern "C" fn mz_zip_reader_locate_file_v2(
+        pZip: *mut mz_zip_archive,
+        pName: *const c_char,
+        pComment: *const c_char,
+        flags: mz_uint,
+        file_index: *mut mz_uint32,
+    ) -> mz_bool {
+        mz_zip_locate_file_v2(pZip, pName, pComment, flags, file_index)
+    }
+
+    /// Set the last error, returning the previous one.
+    ///
+    /// Out of range values are stored as `MZ_ZIP_UNDEFINED_ERROR`.
+    pub unsafe extern "C" fn mz_zip_set_last_error(
+        pZip: *mut mz_zip_archive,
+        err_num: c_int,
+    ) -> mz_zip_error {
+        match pZip.as_mut() {
+            Some(zip) => {
+                let err = error_from_c_int(err_num);
+                mem::replace(&mut zip.m_last_error, err)
+            }
+            None => MZ_ZIP_INVALID_PARAMETER,
+        }
+    }
+
+    pub unsafe extern "C" fn mz_zip_peek_last_error(pZip: *mut mz_zip_archive) -> mz_zip_error {
+        pZip.as_ref()
+            .map_or(MZ_ZIP_INVALID_PARAMETER, |zip| zip.m_last_error)
+    }
+
+    /// Reset the last error to `MZ_ZIP_NO_ERROR`, returning the previous one.
+    pub unsafe extern "C" fn mz_zip_clear_last_error(pZip: *mut mz_zip_archive) -> mz_zip_error {
+        mz_zip_set_last_error(pZip, MZ_ZIP_NO_ERROR as c_int)
+    }
+
+    /// Get the last error and reset it to `MZ_ZIP_NO_ERROR`.
+    pub unsafe extern "C" fn mz_zip_get_last_error(pZip: *mut mz_zip_archive) -> mz_zip_error {
+        mz_zip_clear_last_error(pZip)
+    }
+
+    /// Get a description of the error.
+    pub extern "C" fn mz_zip_get_error_string(mz_err: c_int) -> *const c_char {
+        let err = if (0..MZ_ZIP_TOTAL_ERRORS as c_int).contains(&mz_err) {
+            error_from_c_int(mz_err)
+        } else {
+            MZ_ZIP_TOTAL_ERRORS
+        };
+        let msg: &[u8] = match err {
+            MZ_ZIP_NO_ERROR => b"no error\0",
+            MZ_ZIP_UNDEFINED_ERROR => b"undefined error\0",
+            MZ_ZIP_TOO_MANY_FILES => b"too many files\0",
+            MZ_ZIP_FILE_TOO_LARGE => b"file too large\0",
+            MZ_ZIP_UNSUPPORTED_METHOD => b"unsupported method\0",
+            MZ_ZIP_UNSUPPORTED_ENCRYPTION => b"unsupported encryption\0",
+            MZ_ZIP_UNSUPPORTED_FEATURE => b"unsupported feature\0",
+            MZ_ZIP_FAILED_FINDING_CENTRAL_DIR => b"failed finding central directory\0",
+            MZ_ZIP_NOT_AN_ARCHIVE => b"not a ZIP archive\0",
+            MZ_ZIP_INVALID_HEADER_OR_CORRUPTED => b"invalid header or archive is corrupted\0",
+            MZ_ZIP_UNSUPPORTED_MULTIDISK => b"unsupported multidisk archive\0",
+            MZ_ZIP_DECOMPRESSION_FAILED => b"decompression failed or archive is corrupted\0",
+            MZ_ZIP_COMPRESSION_FAILED => b"compression failed\0",
+            MZ_ZIP_UNEXPECTED_DECOMPRESSED_SIZE => b"unexpected decompressed size\0",
+            MZ_ZIP_CRC_CHECK_FAILED => b"CRC-32 check failed\0",
+            MZ_ZIP_UNSUPPORTED_CDIR_SIZE => b"unsupported central directory size\0",
+            MZ_ZIP_ALLOC_FAILED => b"allocation failed\0",
+            MZ_ZIP_FILE_OPEN_FAILED => b"file open failed\0",
+            MZ_ZIP_FILE_CREATE_FAILED => b"file create failed\0",
+            MZ_ZIP_FILE_WRITE_FAILED => b"file write failed\0",
+            MZ_ZIP_FILE_READ_FAILED => b"file read failed\0",
+            MZ_ZIP_FILE_CLOSE_FAILED => b"file close failed\0",
+            MZ_ZIP_FILE_SEEK_FAILED => b"file seek failed\0",
+            MZ_ZIP_FILE_STAT_FAILED => b"file stat failed\0",
+            MZ_ZIP_INVALID_PARAMETER => b"invalid parameter\0",
+            MZ_ZIP_INVALID_FILENAME => b"invalid filename\0",
+            MZ_ZIP_BUF_TOO_SMALL => b"buffer too small\0",
+            MZ_ZIP_INTERNAL_ERROR => b"internal error\0",
+            MZ_ZIP_FILE_NOT_FOUND => b"file not found\0",
+            MZ_ZIP_ARCHIVE_TOO_LARGE => b"archive is too large\0",
+            MZ_ZIP_VALIDATION_FAILED => b"validation failed\0",
+            MZ_ZIP_WRITE_CALLBACK_FAILED => b"write calledback failed\0",
+            MZ_ZIP_TOTAL_ERRORS => b"unknown error\0",
+        };
+        msg.as_ptr() as *const c_char
+    }
+
+    pub unsafe extern "C" fn mz_zip_reader_is_file_a_directory(
+        pZip: *mut mz_zip_archive,
+        file_index: mz_uint,
+    ) -> mz_bool {
+        as_bool(with_archive(pZip, false, |zip| {
+            Ok(state(zip)?.entry(file_index)?.is_dir())
+        }))
+    }
+
+    pub unsafe extern "C" fn mz_zip_reader_is_file_encrypted(
+        pZip: *mut mz_zip_archive,
+        file_index: mz_uint,
+    ) -> mz_bool {
+        as_bool(with_archive(pZip, false, |zip| {
+            Ok(is_encrypted(state(zip)?.entry(file_index)?))
+        }))
+    }
+
+    /// Whether the entry can be extracted: it is stored, deflated or Deflate64
+    /// compressed, and not encrypted or a patch.
+    pub unsafe extern "C" fn mz_zip_reader_is_file_supported(
+        pZip: *mut mz_zip_archive,
+        file_index: mz_uint,
+    ) -> mz_bool {
+        as_bool(with_archive(pZip, false, |zip| {
+            supported(state(zip)?.entry(file_index)?)?;
+            Ok(true)
+        }))
+    }
+
+    /// Copy the name of the entry to `pFilename`, truncated and null terminated.
+    ///
+    /// Returns the number of bytes written including the null byte, or the size needed to
+    /// hold the whole name if `filename_buf_size` is 0.
+    pub unsafe extern "C" fn mz_zip_reader_get_filename(
+        pZip: *mut mz_zip_archive,
+        file_index: mz_uint,
+        pFilename: *mut c_char,
+        filename_buf_size: mz_uint,
+    ) -> mz_uint {
+        with_archive(pZip, 0, |zip| {
+            let name = &state(zip)?.entry(file_index)?.name;
+            if filename_buf_size == 0 || pFilename.is_null() {
+                return Ok(name.len() as mz_uint + 1);
+            }
+            let buf = slice::from_raw_parts_mut(pFilename, filename_buf_size as usize);
+            Ok(copy_c_string(name, buf) as mz_uint + 1)
+        })
+    }
+
+    pub unsafe extern "C" fn mz_zip_reader_file_stat(
+        pZip: *mut mz_zip_archive,
+        file_index: mz_uint,
+        pStat: *mut mz_zip_archive_file_stat,
+    ) -> mz_bool {
+        as_bool(with_archive(pZip, false, |zip| {
+            let stat = pStat.as_mut().ok_or(MZ_ZIP_INVALID_PARAMETER)?;
+            file_stat(zip, file_index, stat)?;
+            Ok(true)
+        }))
+    }
+
+    /// Whether the archive has ZIP64 records or any entry has a ZIP64 extra field.
+    pub unsafe extern "C" fn mz_zip_is_zip64(pZip: *mut mz_zip_archive) -> mz_bool {
+        as_bool(with_archive(pZip, false, |zip| Ok(state(zip)?.zip64)))
+    }
+
+    pub unsafe extern "C" fn mz_zip_get_central_dir_size(pZip: *mut mz_zip_archive) -> size_t {
+        with_archive(pZip, 0, |zip| Ok(state(zip)?.central_dir_size as size_t))
+    }
+
+    /// Extract an entry to `pBuf`, which has to be large enough to hold all of it.
+    ///
+    /// The read buffer is not used, the data is read in pieces of
+    /// `MZ_ZIP_MAX_IO_BUF_SIZE` bytes.
+    pub unsafe extern "C" fn mz_zip_reader_extract_to_mem_no_alloc(
+        pZip: *mut mz_zip_archive,
+        file_index: mz_uint,
+        pBuf: *mut c_void,
+        buf_size: size_t,
+        flags: mz_uint,
+        pUser_read_buf: *mut c_void,
+        user_read_buf_size: size_t,
+    ) -> mz_bool {
+        let _ = (pUser_read_buf, user_read_buf_size);
+        as_bool(with_archive(pZip, false, |zip| {
+            extract_to_mem(zip, file_index, pBuf, buf_size, flags)
+        }))
+    }
+
+    pub unsafe extern "C" fn mz_zip_reader_extract_file_to_mem_no_alloc(
+        pZip: *mut mz_zip_archive,
+        pFilename: *const c_char,
+        pBuf: *mut c_void,
+        buf_size: size_t,
+        flags: mz_uint,
+        pUser_read_buf: *mut c_void,
+        user_read_buf_size: size_t,
+    ) -> mz_bool {
+        let _ = (pUser_read_buf, user_read_buf_size);
+        as_bool(with_archive(pZip, false, |zip| {
+            let index = locate_file(zip, pFilename, ptr::null(), flags)?;
+            extract_to_mem(zip, index, pBuf, buf_size, flags)
+        }))
+    }
+
+    pub unsafe extern "C" fn mz_zip_reader_extract_to_mem(
+        pZip: *mut mz_zip_archive,
+        file_index: mz_uint,
+        pBuf: *mut c_void,
+        buf_size: size_t,
+        flags: mz_uint,
+    ) -> mz_bool {
+        as_bool(with_archive(pZip, false, |zip| {
+            extract_to_mem(zip, file_index, pBuf, buf_size, flags)
+        }))
+    }
+
+    pub unsafe extern "C" fn mz_zip_reader_extract_file_to_mem(
+        pZip: *mut mz_zip_archive,
+        pFilename: *const c_char,
+        pBuf: *mut c_void,
+        buf_size: size_t,
+        flags: mz_uint,
+    ) -> mz_bool {
+        as_bool(with_archive(pZip, false, |zip| {
+            let index = locate_file(zip, pFilename, ptr::null(), flags)?;
+            extract_to_mem(zip, index, pBuf, buf_size, flags)
+        }))
+    }
+
+    /// Extract an entry to a buffer allocated with `m_pAlloc`, storing its size in
+    /// `*pSize`.
+    ///
+    /// Returns null on failure.
+    pub unsafe extern "C" fn mz_zip_reader_extract_to_heap(
+        pZip: *mut mz_zip_archive,
+        file_index: mz_uint,
+        pSize: *mut size_t,
+        flags: mz_uint,
+    ) -> *mut c_void {
+        with_archive(pZip, ptr::null_mut(), |zip| {
+            extract_to_heap(zip, file_index, pSize, flags)
+        })
+    }
+
+    pub unsafe extern "C" fn mz_zip_reader_extract_file_to_heap(
+        pZip: *mut mz_zip_archive,
+        pFilename: *const c_char,
+        pSize: *mut size_t,
+        flags: mz_uint,
+    ) -> *mut c_void {
+        if let Some(size) = pSize.as_mut() {
+            *size = 0;
+        }
+        with_archive(pZip, ptr::null_mut(), |zip| {
+            let index = locate_file(zip, pFilename, ptr::null(), flags)?;
+            extract_to_heap(zip, index, pSize, flags)
+        })
+    }
+
+    /// Extract an entry, passing the data to `pCallback` in pieces along with the offset
+    /// of each piece.
+    pub unsafe extern "C" fn mz_zip_reader_extract_to_callback(
+        pZip: *mut mz_zip_archive,
+        file_index: mz_uint,
+        pCallback: Option<mz_file_write_func>,
+        pOpaque: *mut c_void,
+        flags: mz_uint,
+    ) -> mz_bool {
+        as_bool(with_archive(pZip, false, |zip| {
+            extract_to_callback(zip, file_index, pCallback, pOpaque, flags)
+        }))
+    }
+
+    pub unsafe extern "C" fn mz_zip_reader_extract_file_to_callback(
+        pZip: *mut mz_zip_archive,
+        pFilename: *const c_char,
+        pCallback: Option<mz_file_write_func>,
+        pOpaque: *mut c_void,
+        flags: mz_uint,
+    ) -> mz_bool {
+        as_bool(with_archive(pZip, false, |zip| {
+            let index = locate_file(zip, pFilename, ptr::null(), flags)?;
+            extract_to_callback(zip, index, pCallback, pOpaque, flags)
+        }))
+    }
+
+    /// Start extracting an entry in pieces with `mz_zip_reader_extract_iter_read`.
+    ///
+    /// Returns null on failure. The state has to be freed with
+    /// `mz_zip_reader_extract_iter_free`.
+    pub unsafe extern "C" fn mz_zip_reader_extract_iter_new(
+        pZip: *mut mz_zip_archive,
+        file_index: mz_uint,
+        flags: mz_uint,
+    ) -> *mut mz_zip_reader_extract_iter_state {
+        with_archive(pZip, ptr::null_mut(), |zip| {
+            let state = state(zip)?;
+            let entry = state.entry(file_index)?;
+            let empty = entry.is_dir() || entry.compressed_size == 0;
+            let (ofs, decoder) = if empty {
+                (0, None)
+            } else {
+                check_extractable(entry, flags)?;
+                let ofs = data_offset(zip, state, entry)?;
+                if flags & MZ_ZIP_FLAG_COMPRESSED_DATA != 0 {
+                    (ofs, None)
+                } else {
+                    (ofs, Some(EntryDecoder::new(entry).map_err(zip_error)?))
+                }
+            };
+            let remaining = if empty { 0 } else { entry.compressed_size };
+            let input_len = cmp::min(remaining, MZ_ZIP_MAX_IO_BUF_SIZE as u64) as usize;
+            Ok(Box::into_raw(Box::new(mz_zip_reader_extract_iter_state {
+                zip: zip as *mut mz_zip_archive,
+                decoder,
+                ofs,
+                remaining,
+                input: vec![0; input_len],
+                input_pos: 0,
+                input_end: 0,
+                failed: false,
+            })))
+        })
+    }
+
+    pub unsafe extern "C" fn mz_zip_reader_extract_file_iter_new(
+        pZip: *mut mz_zip_archive,
+        pFilename: *const c_char,
+        flags: mz_uint,
+    ) -> *mut mz_zip_reader_extract_iter_state {
+        match with_archive(pZip, None, |zip| {
+            locate_file(zip, pFilename, ptr::null(), flags).map(Some)
+        }) {
+            Some(index) => mz_zip_reader_extract_iter_new(pZip, index, flags),
+            None => ptr::null_mut(),
+        }
+    }
+
+    /// Extract the next piece of the entry to `pvBuf`, returning the number of bytes
+    /// written. Returns 0 at the end of the data or after an error.
+    pub unsafe extern "C" fn mz_zip_reader_extract_iter_read(
+        pState: *mut mz_zip_reader_extract_iter_state,
+        pvBuf: *mut c_void,
+        buf_size: size_t,
+    ) -> size_t {
+        let iter = match pState.as_mut() {
+            Some(iter) if !iter.failed && !pvBuf.is_null() && buf_size > 0 => iter,
+            _ => return 0,
+        };
+        let out = slice::from_raw_parts_mut(pvBuf as *mut u8, buf_size);
+        let mut total = 0;
+        let res = with_archive(iter.zip, Err(()), |zip| {
+            let state = state(zip)?;
+            while total < out.len() {
+                let done = iter
+                    .decoder
+                    .as_ref()
+                    .map_or(iter.remaining == 0, |d| d.is_done());
+                if done {
+                    break;
+                }
+                if iter.input_pos == iter.input_end && iter.remaining > 0 {
+                    let len = cmp::min(iter.remaining, iter.input.len() as u64) as usize;
+                    read_at(zip, state, iter.ofs, &mut iter.input[..len])?;
+                    iter.ofs += len as u64;
+                    iter.remaining -= len as u64;
+                    iter.input_pos = 0;
+                    iter.input_end = len;
+                }
+                let input = &iter.input[iter.input_pos..iter.input_end];
+                let (consumed, written) = match &mut iter.decoder {
+                    Some(decoder) => decoder
+                        .decode(input, &mut out[total..])
+                        .map_err(decode_error)?,
+                    None => {
+                        let len = cmp::min(input.len(), out.len() - total);
+                        out[total..total + len].copy_from_slice(&input[..len]);
+                        (len, len)
+                    }
+                };
+                iter.input_pos += consumed;
+                total += written;
+            }
+            if let Some(decoder) = &iter.decoder {
+                if decoder.is_done() {
+                    decoder.finish().map_err(finish_error)?;
+                }
+            }
+            Ok(Ok(()))
+        });
+        if res.is_err() {
+            iter.failed = true;
+        }
+        total
+    }
+
+    /// Free the state, returning whether the extraction succeeded so far.
+    pub unsafe extern "C" fn mz_zip_reader_extract_iter_free(
+        pState: *mut mz_zip_reader_extract_iter_state,
+    ) -> mz_bool {
+        if pState.is_null() {
+            return as_bool(false);
+        }
+        let iter = Box::from_raw(pState);
+        as_bool(!iter.failed)
+    }
+
+    /// Extract an entry to a new file, and set the modification time of the file to that
+    /// of the entry.
+    pub unsafe extern "C" fn mz_zip_reader_extract_to_file(
+        pZip: *mut mz_zip_archive,
+        file_index: mz_uint,
+        pDst_filename: *const c_char,
+        flags: mz_uint,
+    ) -> mz_bool {
+        as_bool(with_archive(pZip, false, |zip| {
+            extract_to_file(zip, file_index, pDst_filename, flags)
+        }))
+    }
+
+    pub unsafe extern "C" fn mz_zip_reader_extract_file_to_file(
+        pZip: *mut mz_zip_archive,
+        pArchive_filename: *const c_char,
+        pDst_filename: *const c_char,
+        flags: mz_uint,
+    ) -> mz_bool {
+        as_bool(with_archive(pZip, false, |zip| {
+            let index = locate_file(zip, pArchive_filename, ptr::null(), flags)?;
+            extract_to_file(zip, index, pDst_filename, flags)
+        }))
+    }
+
+    /// Extract an entry to an open file, at its current position.
+    pub unsafe extern "C" fn mz_zip_reader_extract_to_cfile(
+        pZip: *mut mz_zip_archive,
+        file_index: mz_uint,
+        pFile: *mut FILE,
+        flags: mz_uint,
+    ) -> mz_bool {
+        as_bool(with_archive(pZip, false, |zip| {
+            extract_to_cfile(zip, file_index, pFile, flags)
+        }))
+    }
+
+    pub unsafe extern "C" fn mz_zip_reader_extract_file_to_cfile(
+        pZip: *mut mz_zip_archive,
+        pArchive_filename: *const c_char,
+        pFile: *mut FILE,
+        flags: mz_uint,
+    ) -> mz_bool {
+        as_bool(with_archive(pZip, false, |zip| {
+            let index = locate_file(zip, pArchive_filename, ptr::null(), flags)?;
+            extract_to_cfile(zip, index, pFile, flags)
+        }))
+    }
+
+    /// Check the local header of an entry against the central directory, and that the
+    /// data can be decompressed unless `MZ_ZIP_FLAG_VALIDATE_HEADERS_ONLY` is set.
+    pub unsafe extern "C" fn mz_zip_validate_file(
+        pZip: *mut mz_zip_archive,
+        file_index: mz_uint,
+        flags: mz_uint,
+    ) -> mz_bool {
+        as_bool(with_archive(pZip, false, |zip| {
+            validate_file(zip, file_index, flags)?;
+            Ok(true)
+        }))
+    }
+
+    pub unsafe extern "C" fn mz_zip_validate_archive(
+        pZip: *mut mz_zip_archive,
+        flags: mz_uint,
+    ) -> mz_bool {
+        as_bool(with_archive(pZip, false, |zip| {
+            validate_archive(zip, flags)?;
+            Ok(true)
+        }))
+    }
+
+    pub unsafe extern "C" fn mz_zip_validate_mem_archive(
+        pMem: *const c_void,
+        size: size_t,
+        flags: mz_uint,
+        pErr: *mut mz_zip_error,
+    ) -> mz_bool {
+        let mut zip: mz_zip_archive = mem::zeroed();
+        let ok = mz_zip_reader_init_mem(&mut zip, pMem, size, flags) != 0;
+        validate_and_end(&mut zip, ok, flags, pErr)
+    }
+
+    pub unsafe extern "C" fn mz_zip_validate_file_archive(
+        pFilename: *const c_char,
+        flags: mz_uint,
+        pErr: *mut mz_zip_error,
+    ) -> mz_bool {
+        let mut zip: mz_zip_archive = mem::zeroed();
+        let ok = mz_zip_reader_init_file_v2(&mut zip, pFilename, flags, 0, 0) != 0;
+        validate_and_end(&mut zip, ok, flags, pErr)
+    }
+
+    /// End reading or writing, depending on the mode of the archive.
+    pub unsafe extern "C" fn mz_zip_end(pZip: *mut mz_zip_archive) -> mz_bool {
+        match mz_zip_get_mode(pZip) {
+            MZ_ZIP_MODE_READING => mz_zip_reader_end(pZip),
+            MZ_ZIP_MODE_WRITING | MZ_ZIP_MODE_WRITING_HAS_BEEN_FINALIZED => mz_zip_writer_end(pZip),
+            MZ_ZIP_MODE_INVALID => as_bool(false),
+        }
+    }
+
+    pub unsafe extern "C" fn mz_zip_writer_init(
+        pZip: *mut mz_zip_archive,
+        existing_size: u64,
+    ) -> mz_bool {
+        mz_zip_writer_init_v2(pZip, existing_size, 0)
+    }
+
+    /// Start writing an archive through `m_pWrite`, which has to be set. The entries are
+    /// written after the first `existing_size` bytes.
+    pub unsafe extern "C" fn mz_zip_writer_init_v2(
+        pZip: *mut mz_zip_archive,
+        existing_size: u64,
+        flags: mz_uint,
+    ) -> mz_bool {
+        as_bool(with_archive(pZip, false, |zip| {
+            writer_init(zip, existing_size, flags)?;
+            Ok(true)
+        }))
+    }
+
+    pub unsafe extern "C" fn mz_zip_writer_init_heap(
+        pZip: *mut mz_zip_archive,
+        size_to_reserve_at_beginning: size_t,
+        initial_allocation_size: size_t,
+    ) -> mz_bool {
+        mz_zip_writer_init_heap_v2(
+            pZip,
+            size_to_reserve_at_beginning,
+            initial_allocation_size,
+            0,
+        )
+    }
+
+    /// Start writing an archive to a buffer allocated with the archive's allocation
+    /// functions. The buffer is returned by `mz_zip_writer_finalize_heap_archive`.
+    pub unsafe extern "C" fn mz_zip_writer_init_heap_v2(
+        pZip: *mut mz_zip_archive,
+        size_to_reserve_at_beginning: size_t,
+        initial_allocation_size: size_t,
+        flags: mz_uint,
+    ) -> mz_bool {
+        as_bool(with_archive(pZip, false, |zip| {
+            check_writer_params(zip)?;
+            set_archive_io(zip, flags);
+            writer_init(zip, size_to_reserve_at_beginning as u64, flags)?;
+            zip.m_zip_type = MZ_ZIP_TYPE_HEAP;
+            init_or_end(zip, |zip| {
+                let state = state(zip)?;
+                state.heap = true;
+                let capacity = cmp::max(initial_allocation_size, size_to_reserve_at_beginning);
+                if capacity > 0 {
+                    state.mem = zip_alloc(zip, capacity) as *mut u8;
+                    if state.mem.is_null() {
+                        return Err(MZ_ZIP_ALLOC_FAILED);
+                    }
+                    state.mem_capacity = capacity;
+                }
+                Ok(())
+            })
+        }))
+    }
+
+    pub unsafe extern "C" fn mz_zip_writer_init_file(
+        pZip: *mut mz_zip_archive,
+        pFilename: *const c_char,
+        size_to_reserve_at_beginning: u64,
+    ) -> mz_bool {
+        mz_zip_writer_init_file_v2(pZip, pFilename, size_to_reserve_at_beginning, 0)
+    }
+
+    /// Start writing an archive to a new file. The file is closed by `mz_zip_writer_end`.
+    pub unsafe extern "C" fn mz_zip_writer_init_file_v2(
+        pZip: *mut mz_zip_archive,
+        pFilename: *const c_char,
+        size_to_reserve_at_beginning: u64,
+        flags: mz_uint,
+    ) -> mz_bool {
+        as_bool(with_archive(pZip, false, |zip| {
+            check_writer_params(zip)?;
+            writer_init_file(zip, pFilename, size_to_reserve_at_beginning, flags)
+        }))
+    }
+
+    /// Start writing an archive to an open file, at its current position.
+    pub unsafe extern "C" fn mz_zip_writer_init_cfile(
+        pZip: *mut mz_zip_archive,
+        pFile: *mut FILE,
+        flags: mz_uint,
+    ) -> mz_bool {
+        as_bool(with_archive(pZip, false, |zip| {
+            if pFile.is_null() {
+                return Err(MZ_ZIP_INVALID_PARAMETER);
+            }
+            check_writer_params(zip)?;
+            let file_start_ofs = ftell64(pFile);
+            if file_start_ofs < 0 {
+                return Err(MZ_ZIP_FILE_SEEK_FAILED);
+            }
+            set_archive_io(zip, flags);
+            writer_init(zip, 0, flags)?;
+            zip.m_zip_type = MZ_ZIP_TYPE_CFILE;
+            let state = state(zip)?;
+            state.file = pFile;
+            state.file_start_ofs = file_start_ofs as u64;
+            Ok(true)
+        }))
+    }
+
+    pub unsafe extern "C" fn mz_zip_writer_init_from_reader(
+        pZip: *mut mz_zip_archive,
+        pFilename: *const c_char,
+    ) -> mz_bool {
+        mz_zip_writer_init_from_reader_v2(pZip, pFilename, 0)
+    }
+
+    /// Turn an archive that is being read into one that is written, so that entries can be
+    /// appended to it. The new entries are written over the old central directory.
+    ///
+    /// A file archive is reopened for writing with `pFilename`, and the memory of an
+    /// archive read from memory is grown with `m_pRealloc`.
+    pub unsafe extern "C" fn mz_zip_writer_init_from_reader_v2(
+        pZip: *mut mz_zip_archive,
+        pFilename: *const c_char,
+        _flags: mz_uint,
+    ) -> mz_bool {
+        as_bool(with_archive(pZip, false, |zip| {
+            init_from_reader(zip, pFilename)
+        }))
+    }
+
+    pub unsafe extern "C" fn mz_zip_writer_add_mem(
+        pZip: *mut mz_zip_archive,
+        pArchive_name: *const c_char,
+        pBuf: *const c_void,
+        buf_size: size_t,
+        level_and_flags: mz_uint,
+    ) -> mz_bool {
+        mz_zip_writer_add_mem_ex(
+            pZip,
+            pArchive_name,
+            pBuf,
+            buf_size,
+            ptr::null(),
+            0,
+            level_and_flags,
+            0,
+            0,
+        )
+    }
+
+    pub unsafe extern "C" fn mz_zip_writer_add_mem_ex(
+        pZip: *mut mz_zip_archive,
+        pArchive_name: *const c_char,
+        pBuf: *const c_void,
+        buf_size: size_t,
+        pComment: *const c_void,
+        comment_size: u16,
+        level_and_flags: mz_uint,
+        uncomp_size: u64,
+        uncomp_crc32: mz_uint32,
+    ) -> mz_bool {
+        mz_zip_writer_add_mem_ex_v2(
+            pZip,
+            pArchive_name,
+            pBuf,
+            buf_size,
+            pComment,
+            comment_size,
+            level_and_flags,
+            uncomp_size,
+            uncomp_crc32,
+            ptr::null_mut(),
+            ptr::null(),
+            0,
+            ptr::null(),
+            0,
+        )
+    }
+
+    /// Add an entry with the data in `pBuf`. A name ending in `/` adds a directory, which
+    /// can't have any data.
+    ///
+    /// With `MZ_ZIP_FLAG_COMPRESSED_DATA` the data is raw deflated data, and
+    /// `uncomp_size` and `uncomp_crc32` describe the uncompressed data. The current time
+    /// is used if `last_modified` is null.
+    pub unsafe extern "C" fn mz_zip_writer_add_mem_ex_v2(
+        pZip: *mut mz_zip_archive,
+        pArchive_name: *const c_char,
+        pBuf: *const c_void,
+        buf_size: size_t,
+        pComment: *const c_void,
+        comment_size: u16,
+        level_and_flags: mz_uint,
+        uncomp_size: u64,
+        uncomp_crc32: mz_uint32,
+        last_modified: *mut time_t,
+        user_extra_data_local: *const c_char,
+        user_extra_data_local_len: mz_uint,
+        user_extra_data_central: *const c_char,
+        user_extra_data_central_len: mz_uint,
+    ) -> mz_bool {
+        as_bool(with_archive(pZip, false, |zip| {
+            let local_extra = data_slice(
+                user_extra_data_local as *const c_void,
+                user_extra_data_local_len as usize,
+            )?;
+            let central_extra = data_slice(
+                user_extra_data_central as *const c_void,
+                user_extra_data_central_len as usize,
+            )?;
+            add_mem(
+                zip,
+                pArchive_name,
+                pBuf,
+                buf_size,
+                pComment,
+                comment_size,
+                level_and_flags,
+                uncomp_size,
+                uncomp_crc32,
+                last_modified,
+                local_extra,
+                central_extra,
+            )
+        }))
+    }
+
+    /// Add an entry with the data of a file, using the modification time of the file.
+    pub unsafe extern "C" fn mz_zip_writer_add_file(
+        pZip: *mut mz_zip_archive,
+        pArchive_name: *const c_char,
+        pSrc_filename: *const c_char,
+        pComment: *const c_void,
+        comment_size: u16,
+        level_and_flags: mz_uint,
+    ) -> mz_bool {
+        as_bool(with_archive(pZip, false, |zip| {
+            add_file(
+                zip,
+                pArchive_name,
+                pSrc_filename,
+                pComment,
+                comment_size,
+                level_and_flags,
+            )
+        }))
+    }
+
+    /// Add an entry with `size_to_add` bytes read from an open file.
+    pub unsafe extern "C" fn mz_zip_writer_add_cfile(
+        pZip: *mut mz_zip_archive,
+        pArchive_name: *const c_char,
+        pSrc_file: *mut FILE,
+        size_to_add: u64,
+        pFile_time: *const time_t,
+        pComment: *const c_void,
+        comment_size: u16,
+        level_and_flags: mz_uint,
+        user_extra_data_local: *const c_char,
+        user_extra_data_local_len: mz_uint,
+        user_extra_data_central: *const c_char,
+        user_extra_data_central_len: mz_uint,
+    ) -> mz_bool {
+        as_bool(with_archive(pZip, false, |zip| {
+            let local_extra = data_slice(
+                user_extra_data_local as *const c_void,
+                user_extra_data_local_len as usize,
+            )?;
+            let central_extra = data_slice(
+                user_extra_data_central as *const c_void,
+                user_extra_data_central_len as usize,
+            )?;
+            add_cfile(
+                zip,
+                pArchive_name,
+                pSrc_file,
+                size_to_add,
+                pFile_time,
+                pComment,
+                comment_size,
+                level_and_flags,
+                local_extra,
+                central_extra,
+            )
+        }))
+    }
+
+    /// Copy an entry from another archive without recompressing it.
+    pub unsafe extern "C" fn mz_zip_writer_add_from_zip_reader(
+        pZip: *mut mz_zip_archive,
+        pSource_zip: *mut mz_zip_archive,
+        src_file_index: mz_uint,
+    ) -> mz_bool {
+        as_bool(with_archive(pZip, false, |zip| {
+            add_from_zip_reader(zip, pSource_zip, src_file_index)
+        }))
+    }
+
+    /// Write the central directory and end of central directory records. After this only
+    /// `mz_zip_writer_end` may be called.
+    pub unsafe extern "C" fn mz_zip_writer_finalize_archive(pZip: *mut mz_zip_archive) -> mz_bool {
+        as_bool(with_archive(pZip, false, |zip| finalize_archive(zip)))
+    }
+
+    /// Finalize a heap archive, handing over the buffer that holds it. The buffer has to be
+    /// freed with the archive's free function.
+    pub unsafe extern "C" fn mz_zip_writer_finalize_heap_archive(
+        pZip: *mut mz_zip_archive,
+        ppBuf: *mut *mut c_void,
+        pSize: *mut size_t,
+    ) -> mz_bool {
+        as_bool(with_archive(pZip, false, |zip| {
+            if ppBuf.is_null() || pSize.is_null() {
+                return Err(MZ_ZIP_INVALID_PARAMETER);
+            }
+            *ppBuf = ptr::null_mut();
+            *pSize = 0;
+            let state = state(zip)?;
+            if !state.heap {
+                return Err(MZ_ZIP_INVALID_PARAMETER);
+            }
+            finalize_archive(zip)?;
+            *ppBuf = state.mem as *mut c_void;
+            *pSize = state.mem_size;
+            state.mem = ptr::null_mut();
+            state.mem_size = 0;
+            state.mem_capacity = 0;
+            Ok(true)
+        }))
+    }
+
+    /// End writing, freeing the state and closing the file opened by
+    /// `mz_zip_writer_init_file`. The archive is not finalized.
+    pub unsafe extern "C" fn mz_zip_writer_end(pZip: *mut mz_zip_archive) -> mz_bool {
+        as_bool(with_archive(pZip, false, |zip| writer_end(zip)))
+    }
+
+    pub unsafe extern "C" fn mz_zip_add_mem_to_archive_file_in_place(
+        pZip_filename: *const c_char,
+        pArchive_name: *const c_char,
+        pBuf: *const c_void,
+        buf_size: size_t,
+        pComment: *const c_void,
+        comment_size: u16,
+        level_and_flags: mz_uint,
+    ) -> mz_bool {
+        mz_zip_add_mem_to_archive_file_in_place_v2(
+            pZip_filename,
+            pArchive_name,
+            pBuf,
+            buf_size,
+            pComment,
+            comment_size,
+            level_and_flags,
+            ptr::null_mut(),
+        )
+    }
+
+    /// Append an entry to an archive file, creating the file if it doesn't exist.
+    ///
+    /// This is not atomic: the archive is left without a central directory if it fails
+    /// half way.
+    pub unsafe extern "C" fn mz_zip_add_mem_to_archive_file_in_place_v2(
+        pZip_filename: *const c_char,
+        pArchive_name: *const c_char,
+        pBuf: *const c_void,
+        buf_size: size_t,
+        pComment: *const c_void,
+        comment_size: u16,
+        level_and_flags: mz_uint,
+        pErr: *mut mz_zip_error,
+    ) -> mz_bool {
+        let set_err = |err| {
+            if let Some(e) = pErr.as_mut() {
+                *e = err;
+            }
+        };
+        let level_and_flags = match level_from_flags(level_and_flags) {
+            Ok((_, level_and_flags)) => level_and_flags,
+            Err(err) => {
+                set_err(err);
+                return as_bool(false);
+            }
+        };
+        if pZip_filename.is_null()
+            || pArchive_name.is_null()
+            || (buf_size > 0 && pBuf.is_null())
+            || (comment_size > 0 && pComment.is_null())
+        {
+            set_err(MZ_ZIP_INVALID_PARAMETER);
+            return as_bool(false);
+        }
+        if !valid_archive_name(CStr::from_ptr(pArchive_name).to_bytes()) {
+            set_err(MZ_ZIP_INVALID_FILENAME);
+            return as_bool(false);
+        }
+
+        let mut zip: mz_zip_archive = mem::zeroed();
+        let created = c_path(CStr::from_ptr(pZip_filename)).map_or(true, |path| !path.exists());
+        if created {
+            if mz_zip_writer_init_file_v2(&mut zip, pZip_filename, 0, level_and_flags) == 0 {
+                set_err(zip.m_last_error);
+                return as_bool(false);
+            }
+        } else {
+            if mz_zip_reader_init_file_v2(
+                &mut zip,
+                pZip_filename,
+                level_and_flags | MZ_ZIP_FLAG_DO_NOT_SORT_CENTRAL_DIRECTORY,
+                0,
+                0,
+            ) == 0
+            {
+                set_err(zip.m_last_error);
+                return as_bool(false);
+            }
+            if mz_zip_writer_init_from_reader_v2(&mut zip, pZip_filename, level_and_flags) == 0 {
+                let err = zip.m_last_error;
+                mz_zip_end(&mut zip);
+                set_err(err);
+                return as_bool(false);
+            }
+        }
+
+        let mut ok = mz_zip_writer_add_mem_ex(
+            &mut zip,
+            pArchive_name,
+            pBuf,
+            buf_size,
+            pComment,
+            comment_size,
+            level_and_flags,
+            0,
+            0,
+        ) != 0;
+        let mut err = zip.m_last_error;
+        // Always finalize, even if adding failed, so the archive stays valid.
+        if mz_zip_writer_finalize_archive(&mut zip) == 0 {
+            if ok {
+                err = zip.m_last_error;
+            }
+            ok = false;
+        }
+        if mz_zip_writer_end(&mut zip) == 0 {
+            if ok {
+                err = zip.m_last_error;
+            }
+            ok = false;
+        }
+        if !ok && created {
+            if let Some(path) = c_path(CStr::from_ptr(pZip_filename)) {
+                let _ = std::fs::remove_file(path);
+            }
+        }
+        set_err(if ok { MZ_ZIP_NO_ERROR } else { err });
+        as_bool(ok)
+    }
+
+    pub unsafe extern "C" fn mz_zip_extract_archive_file_to_heap(
+        pZip_filename: *const c_char,
+        pArchive_name: *const c_char,
+        pSize: *mut size_t,
+        flags: mz_uint,
+    ) -> *mut c_void {
+        mz_zip_extract_archive_file_to_heap_v2(
+            pZip_filename,
+            pArchive_name,
+            ptr::null(),
+            pSize,
+            flags,
+            ptr::null_mut(),
+        )
+    }
+
+    /// Extract an entry from an archive file to a buffer allocated with `malloc`, only
+    /// matching an entry with the given comment if `pComment` isn't null.
+    ///
+    /// Returns null on failure.
+    pub unsafe extern "C" fn mz_zip_extract_archive_file_to_heap_v2(
+        pZip_filename: *const c_char,
+        pArchive_name: *const c_char,
+        pComment: *const c_char,
+        pSize: *mut size_t,
+        flags: mz_uint,
+        pErr: *mut mz_zip_error,
+    ) -> *mut c_void {
+        if let Some(size) = pSize.as_mut() {
+            *size = 0;
+        }
+        let mut zip: mz_zip_archive = mem::zeroed();
+        let mut buf = ptr::null_mut();
+        if pZip_filename.is_null() || pArchive_name.is_null() {
+            zip.m_last_error = MZ_ZIP_INVALID_PARAMETER;
+        } else if mz_zip_reader_init_file_v2(
+            &mut zip,
+            pZip_filename,
+            flags | MZ_ZIP_FLAG_DO_NOT_SORT_CENTRAL_DIRECTORY,
+            0,
+            0,
+        ) != 0
+        {
+            let mut index = 0;
+            if mz_zip_locate_file_v2(&mut zip, pArchive_name, pComment, flags, &mut index) != 0 {
+                buf = mz_zip_reader_extract_to_heap(&mut zip, index, pSize, flags);
+            }
+            let err = zip.m_last_error;
+            mz_zip_reader_end(&mut zip);
+            zip.m_last_error = err;
+        }
+        if let Some(e) = pErr.as_mut() {
+            *e = zip.m_last_error;
+        }
+        buf
+    }
+);
+
+/// Validate an archive that has been opened for reading if `opened` is true, then end it
+/// and store the error in `err`.
+unsafe fn validate_and_end(
+    zip: &mut mz_zip_archive,
+    opened: bool,
+    flags: mz_uint,
+    err: *mut mz_zip_error,
+) -> mz_bool {
+    let mut ok = opened;
+    if opened {
+        ok = mz_zip_validate_archive(zip, flags) != 0;
+        let validate_err = zip.m_last_error;
+        ok &= mz_zip_reader_end(zip) != 0;
+        if validate_err != MZ_ZIP_NO_ERROR {
+            zip.m_last_error = validate_err;
+        }
+    }
+    if let Some(err) = err.as_mut() {
+        *err = zip.m_last_error;
+    }
+    as_bool(ok)
+}
+
+/// Convert an error number coming from C, which may be out of range.
+fn error_from_c_int(err: c_int) -> mz_zip_error {
+    const ERRORS: [mz_zip_error; MZ_ZIP_TOTAL_ERRORS as usize + 1] = [
+        MZ_ZIP_NO_ERROR,
+        MZ_ZIP_UNDEFINED_ERROR,
+        MZ_ZIP_TOO_MANY_FILES,
+        MZ_ZIP_FILE_TOO_LARGE,
+        MZ_ZIP_UNSUPPORTED_METHOD,
+        MZ_ZIP_UNSUPPORTED_ENCRYPTION,
+        MZ_ZIP_UNSUPPORTED_FEATURE,
+        MZ_ZIP_FAILED_FINDING_CENTRAL_DIR,
+        MZ_ZIP_NOT_AN_ARCHIVE,
+        MZ_ZIP_INVALID_HEADER_OR_CORRUPTED,
+        MZ_ZIP_UNSUPPORTED_MULTIDISK,
+        MZ_ZIP_DECOMPRESSION_FAILED,
+        MZ_ZIP_COMPRESSION_FAILED,
+        MZ_ZIP_UNEXPECTED_DECOMPRESSED_SIZE,
+        MZ_ZIP_CRC_CHECK_FAILED,
+        MZ_ZIP_UNSUPPORTED_CDIR_SIZE,
+        MZ_ZIP_ALLOC_FAILED,
+        MZ_ZIP_FILE_OPEN_FAILED,
+        MZ_ZIP_FILE_CREATE_FAILED,
+        MZ_ZIP_FILE_WRITE_FAILED,
+        MZ_ZIP_FILE_READ_FAILED,
+        MZ_ZIP_FILE_CLOSE_FAILED,
+        MZ_ZIP_FILE_SEEK_FAILED,
+        MZ_ZIP_FILE_STAT_FAILED,
+        MZ_ZIP_INVALID_PARAMETER,
+        MZ_ZIP_INVALID_FILENAME,
+        MZ_ZIP_BUF_TOO_SMALL,
+        MZ_ZIP_INTERNAL_ERROR,
+        MZ_ZIP_FILE_NOT_FOUND,
+        MZ_ZIP_ARCHIVE_TOO_LARGE,
+        MZ_ZIP_VALIDATION_FAILED,
+        MZ_ZIP_WRITE_CALLBACK_FAILED,
+        MZ_ZIP_TOTAL_ERRORS,
+    ];
+    usize::try_from(err)
+        .ok()
+        .and_then(|err| ERRORS.get(err).copied())
+        .unwrap_or(MZ_ZIP_UNDEFINED_ERROR)
+}
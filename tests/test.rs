@@ -123,3 +123,1428 @@ fn c_api() {
 
     assert_eq!(data[..], decompressed[0..decompressed_size as usize]);
 }
+
+#[test]
+fn c_api_set_dictionary() {
+    use miniz_oxide::{MZError, MZStatus};
+    use miniz_oxide_c_api::{
+        mz_adler32, mz_deflate, mz_deflateEnd, mz_deflateInit, mz_deflateSetDictionary, mz_inflate,
+        mz_inflateEnd, mz_inflateInit, mz_inflateSetDictionary, mz_stream,
+    };
+    let dict = b"dictionary data used to prime the compressor";
+    let mut data = b"data that refers to the dictionary data used to prime it".to_vec();
+    let mut compressed = vec![0; 256];
+    let compressed_size;
+    let dict_adler;
+    unsafe {
+        let mut stream = mz_stream {
+            next_in: data.as_mut_ptr(),
+            avail_in: data.len() as u32,
+            next_out: compressed.as_mut_ptr(),
+            avail_out: compressed.len() as u32,
+            ..Default::default()
+        };
+
+        assert_eq!(mz_deflateInit(&mut stream, 6), MZStatus::Ok as i32);
+        assert_eq!(
+            mz_deflateSetDictionary(&mut stream, dict.as_ptr(), dict.len() as u32),
+            MZStatus::Ok as i32
+        );
+        dict_adler = mz_adler32(1, dict.as_ptr(), dict.len());
+        assert_eq!(stream.adler, dict_adler);
+        assert_eq!(mz_deflate(&mut stream, 4), MZStatus::StreamEnd as i32);
+        compressed_size = stream.total_out as usize;
+
+        // Too late to set a dictionary now.
+        assert_eq!(
+            mz_deflateSetDictionary(&mut stream, dict.as_ptr(), dict.len() as u32),
+            MZError::Param as i32
+        );
+        assert_eq!(mz_deflateEnd(&mut stream), MZStatus::Ok as i32);
+
+        assert_eq!(compressed[1] & 0x20, 0x20);
+        assert_eq!(&compressed[2..6], &(dict_adler as u32).to_be_bytes());
+    }
+
+    let mut decompressed = vec![0; data.len()];
+    unsafe {
+        let mut stream = mz_stream {
+            next_in: compressed.as_mut_ptr(),
+            avail_in: compressed_size as u32,
+            next_out: decompressed.as_mut_ptr(),
+            avail_out: decompressed.len() as u32,
+            ..Default::default()
+        };
+
+        assert_eq!(mz_inflateInit(&mut stream), MZStatus::Ok as i32);
+        assert_eq!(mz_inflate(&mut stream, 0), MZStatus::NeedDict as i32);
+        assert_eq!(stream.adler, dict_adler);
+        assert_eq!(
+            mz_inflateSetDictionary(&mut stream, data.as_ptr(), data.len() as u32),
+            MZError::Data as i32
+        );
+        assert_eq!(
+            mz_inflateSetDictionary(&mut stream, dict.as_ptr(), dict.len() as u32),
+            MZStatus::Ok as i32
+        );
+        assert_eq!(mz_inflate(&mut stream, 4), MZStatus::StreamEnd as i32);
+        assert_eq!(stream.total_out as usize, data.len());
+        assert_eq!(mz_inflateEnd(&mut stream), MZStatus::Ok as i32);
+    }
+    assert_eq!(decompressed, data);
+}
+
+#[test]
+fn c_api_deflate_params() {
+    use miniz_oxide::{MZError, MZStatus};
+    use miniz_oxide_c_api::{
+        mz_deflate, mz_deflateEnd, mz_deflateInit, mz_deflateParams, mz_stream,
+    };
+    let mut data = get_test_data();
+    let half = data.len() / 2;
+    let mut compressed = vec![0; data.len() + 100];
+    unsafe {
+        let mut stream = mz_stream {
+            next_in: data.as_mut_ptr(),
+            avail_in: half as u32,
+            next_out: compressed.as_mut_ptr(),
+            avail_out: 0,
+            ..Default::default()
+        };
+        assert_eq!(mz_deflateInit(&mut stream, 1), MZStatus::Ok as i32);
+
+        // Nothing is pending yet, so no output space is needed.
+        assert_eq!(mz_deflateParams(&mut stream, 2, 0), MZStatus::Ok as i32);
+        assert_eq!(mz_deflateParams(&mut stream, 11, 0), MZError::Stream as i32);
+        assert_eq!(mz_deflateParams(&mut stream, 6, 5), MZError::Stream as i32);
+
+        stream.avail_out = compressed.len() as u32;
+        assert_eq!(mz_deflate(&mut stream, 0), MZStatus::Ok as i32);
+        assert_eq!(stream.avail_in, 0);
+
+        // The pending data has to be flushed before the settings change.
+        let avail_out = stream.avail_out;
+        stream.avail_out = 0;
+        assert_eq!(mz_deflateParams(&mut stream, 9, 1), MZError::Buf as i32);
+        stream.avail_out = avail_out;
+        let total_out = stream.total_out;
+        assert_eq!(mz_deflateParams(&mut stream, 9, 1), MZStatus::Ok as i32);
+        assert!(stream.total_out > total_out);
+
+        stream.avail_in = (data.len() - half) as u32;
+        assert_eq!(mz_deflate(&mut stream, 4), MZStatus::StreamEnd as i32);
+        assert_eq!(stream.total_in as usize, data.len());
+        compressed.truncate(stream.total_out as usize);
+        assert_eq!(mz_deflateEnd(&mut stream), MZStatus::Ok as i32);
+    }
+
+    let decompressed = miniz_oxide::inflate::decompress_to_vec_zlib(&compressed).unwrap();
+    assert!(decompressed == data);
+}
+
+#[test]
+fn c_api_inflate_sync() {
+    use miniz_oxide::{MZError, MZStatus};
+    use miniz_oxide_c_api::{
+        mz_deflate, mz_deflateEnd, mz_deflateInit, mz_inflate, mz_inflateEnd, mz_inflateInit,
+        mz_inflateSync, mz_stream,
+    };
+    let mut data = get_test_data();
+    let half = data.len() / 2;
+    let mut compressed = vec![0; data.len() + 100];
+    let flush_point;
+    unsafe {
+        let mut stream = mz_stream {
+            next_in: data.as_mut_ptr(),
+            avail_in: half as u32,
+            next_out: compressed.as_mut_ptr(),
+            avail_out: compressed.len() as u32,
+            ..Default::default()
+        };
+        assert_eq!(mz_deflateInit(&mut stream, 6), MZStatus::Ok as i32);
+        assert_eq!(mz_deflate(&mut stream, 3), MZStatus::Ok as i32);
+        flush_point = stream.total_out as usize;
+        stream.avail_in = (data.len() - half) as u32;
+        assert_eq!(mz_deflate(&mut stream, 4), MZStatus::StreamEnd as i32);
+        compressed.truncate(stream.total_out as usize);
+        assert_eq!(mz_deflateEnd(&mut stream), MZStatus::Ok as i32);
+    }
+    assert_eq!(compressed[flush_point - 4..flush_point], [0, 0, 0xFF, 0xFF]);
+
+    let mut decompressed = vec![0; data.len()];
+    unsafe {
+        let mut stream = mz_stream {
+            next_in: compressed.as_mut_ptr(),
+            avail_in: 20,
+            next_out: decompressed.as_mut_ptr(),
+            avail_out: decompressed.len() as u32,
+            ..Default::default()
+        };
+        assert_eq!(mz_inflateInit(&mut stream), MZStatus::Ok as i32);
+        assert_eq!(mz_inflate(&mut stream, 0), MZStatus::Ok as i32);
+
+        // Lose some of the data before the flush point.
+        stream.next_in = compressed.as_mut_ptr().add(30);
+        stream.avail_in = 10;
+        assert_eq!(mz_inflateSync(&mut stream), MZError::Data as i32);
+        assert_eq!(stream.avail_in, 0);
+        assert_eq!(stream.total_in, 30);
+        stream.avail_in = (compressed.len() - 40) as u32;
+        assert_eq!(mz_inflateSync(&mut stream), MZStatus::Ok as i32);
+        assert_eq!(stream.next_in, compressed.as_mut_ptr().add(flush_point));
+
+        let total_out = stream.total_out as usize;
+        assert_eq!(mz_inflate(&mut stream, 4), MZStatus::StreamEnd as i32);
+        let output = &decompressed[total_out..stream.total_out as usize];
+        assert!(output == &data[half..]);
+        assert_eq!(mz_inflateEnd(&mut stream), MZStatus::Ok as i32);
+    }
+}
+
+#[test]
+fn c_api_prime() {
+    use miniz_oxide::{MZError, MZStatus};
+    use miniz_oxide_c_api::{
+        mz_deflate, mz_deflateEnd, mz_deflateInit, mz_deflateInit2, mz_deflatePrime, mz_inflate,
+        mz_inflateEnd, mz_inflateInit2, mz_inflatePrime, mz_stream,
+    };
+    let mut data = get_test_data();
+    let mut compressed = vec![0; data.len() + 100];
+    unsafe {
+        let mut stream = mz_stream::default();
+        assert_eq!(mz_deflateInit(&mut stream, 6), MZStatus::Ok as i32);
+        // The zlib header has not been written yet.
+        assert_eq!(mz_deflatePrime(&mut stream, 3, 5), MZError::Param as i32);
+        assert_eq!(mz_deflateEnd(&mut stream), MZStatus::Ok as i32);
+
+        let mut stream = mz_stream {
+            next_in: data.as_mut_ptr(),
+            avail_in: data.len() as u32,
+            next_out: compressed.as_mut_ptr(),
+            avail_out: compressed.len() as u32,
+            ..Default::default()
+        };
+        assert_eq!(
+            mz_deflateInit2(&mut stream, 6, 8, -15, 9, 0),
+            MZStatus::Ok as i32
+        );
+        assert_eq!(mz_deflatePrime(&mut stream, 17, 0), MZError::Stream as i32);
+        assert_eq!(mz_deflatePrime(&mut stream, -1, 0), MZError::Stream as i32);
+        assert_eq!(mz_deflatePrime(&mut stream, 3, 5), MZStatus::Ok as i32);
+        assert_eq!(mz_deflate(&mut stream, 4), MZStatus::StreamEnd as i32);
+        compressed.truncate(stream.total_out as usize);
+        assert_eq!(mz_deflateEnd(&mut stream), MZStatus::Ok as i32);
+    }
+    assert_eq!(compressed[0] & 7, 5);
+
+    let mut decompressed = vec![0; data.len()];
+    unsafe {
+        let mut stream = mz_stream {
+            next_in: compressed.as_mut_ptr().add(1),
+            avail_in: (compressed.len() - 1) as u32,
+            next_out: decompressed.as_mut_ptr(),
+            avail_out: decompressed.len() as u32,
+            ..Default::default()
+        };
+        assert_eq!(mz_inflateInit2(&mut stream, -15), MZStatus::Ok as i32);
+        assert_eq!(mz_inflatePrime(&mut stream, 17, 0), MZError::Stream as i32);
+        let rest = i32::from(compressed[0] >> 3);
+        assert_eq!(mz_inflatePrime(&mut stream, 5, rest), MZStatus::Ok as i32);
+        assert_eq!(mz_inflate(&mut stream, 4), MZStatus::StreamEnd as i32);
+        assert_eq!(stream.total_out as usize, data.len());
+        assert_eq!(mz_inflateEnd(&mut stream), MZStatus::Ok as i32);
+    }
+    assert!(decompressed == data);
+}
+
+#[test]
+fn c_api_copy() {
+    use miniz_oxide::{MZError, MZStatus};
+    use miniz_oxide_c_api::{
+        mz_deflate, mz_deflateCopy, mz_deflateEnd, mz_deflateInit, mz_inflate, mz_inflateCopy,
+        mz_inflateEnd, mz_inflateInit, mz_stream,
+    };
+    use std::mem::MaybeUninit;
+
+    let mut data = get_test_data();
+    let half = data.len() / 2;
+    let mut compressed = vec![0; data.len() + 100];
+    let mut compressed_copy = vec![0; data.len() + 100];
+    unsafe {
+        let mut stream = mz_stream {
+            next_in: data.as_mut_ptr(),
+            avail_in: half as u32,
+            next_out: compressed.as_mut_ptr(),
+            avail_out: compressed.len() as u32,
+            ..Default::default()
+        };
+        assert_eq!(mz_deflateInit(&mut stream, 6), MZStatus::Ok as i32);
+        assert_eq!(mz_deflate(&mut stream, 0), MZStatus::Ok as i32);
+
+        let mut copy = MaybeUninit::<mz_stream>::uninit();
+        assert_eq!(
+            mz_deflateCopy(copy.as_mut_ptr(), &mut stream),
+            MZStatus::Ok as i32
+        );
+        let mut copy = copy.assume_init();
+        assert_eq!(copy.total_in, stream.total_in);
+        assert_eq!(copy.next_out, stream.next_out);
+        assert_eq!(
+            mz_deflateCopy(&mut stream, &mut stream),
+            MZError::Stream as i32
+        );
+
+        // Finish the original with the rest of the data, and the copy without it.
+        let used = stream.total_out as usize;
+        compressed_copy[..used].copy_from_slice(&compressed[..used]);
+        copy.next_out = compressed_copy.as_mut_ptr().add(used);
+        stream.avail_in = (data.len() - half) as u32;
+        assert_eq!(mz_deflate(&mut stream, 4), MZStatus::StreamEnd as i32);
+        assert_eq!(mz_deflate(&mut copy, 4), MZStatus::StreamEnd as i32);
+        assert_eq!(stream.total_in as usize, data.len());
+        assert_eq!(copy.total_in as usize, half);
+        compressed.truncate(stream.total_out as usize);
+        compressed_copy.truncate(copy.total_out as usize);
+        assert_eq!(mz_deflateEnd(&mut stream), MZStatus::Ok as i32);
+        assert_eq!(mz_deflateEnd(&mut copy), MZStatus::Ok as i32);
+    }
+
+    let decompressed = miniz_oxide::inflate::decompress_to_vec_zlib(&compressed_copy).unwrap();
+    assert!(decompressed == data[..half]);
+
+    let mut decompressed = vec![0; data.len()];
+    let mut decompressed_copy = vec![0; data.len()];
+    unsafe {
+        let mut stream = mz_stream {
+            next_in: compressed.as_mut_ptr(),
+            avail_in: (compressed.len() / 2) as u32,
+            next_out: decompressed.as_mut_ptr(),
+            avail_out: decompressed.len() as u32,
+            ..Default::default()
+        };
+        assert_eq!(mz_inflateInit(&mut stream), MZStatus::Ok as i32);
+        assert_eq!(mz_inflate(&mut stream, 0), MZStatus::Ok as i32);
+
+        let mut copy = mz_stream::default();
+        assert_eq!(mz_inflateCopy(&mut copy, &mut stream), MZStatus::Ok as i32);
+        let used = stream.total_out as usize;
+        decompressed_copy[..used].copy_from_slice(&decompressed[..used]);
+        copy.next_out = decompressed_copy.as_mut_ptr().add(used);
+
+        for stream in [&mut stream, &mut copy] {
+            stream.avail_in = (compressed.len() - stream.total_in as usize) as u32;
+            assert_eq!(mz_inflate(stream, 4), MZStatus::StreamEnd as i32);
+            assert_eq!(stream.total_out as usize, data.len());
+            assert_eq!(mz_inflateEnd(stream), MZStatus::Ok as i32);
+        }
+    }
+    assert!(decompressed == data);
+    assert!(decompressed_copy == data);
+}
+
+#[test]
+fn c_api_custom_allocator() {
+    use miniz_oxide::{MZError, MZStatus};
+    use miniz_oxide_c_api::{
+        miniz_def_alloc_func, miniz_def_free_func, mz_deflate, mz_deflateCopy, mz_deflateEnd,
+        mz_deflateInit, mz_inflate, mz_inflateCopy, mz_inflateEnd, mz_inflateInit, mz_stream,
+    };
+    use std::ffi::c_void;
+
+    #[derive(Default)]
+    struct Counts {
+        allocs: usize,
+        frees: usize,
+        bytes: usize,
+        /// Number of allocations after which allocating fails.
+        limit: Option<usize>,
+    }
+
+    unsafe extern "C" fn counting_alloc(
+        opaque: *mut c_void,
+        items: usize,
+        size: usize,
+    ) -> *mut c_void {
+        let counts = &mut *(opaque as *mut Counts);
+        if Some(counts.allocs) == counts.limit {
+            return std::ptr::null_mut();
+        }
+        counts.allocs += 1;
+        counts.bytes += items * size;
+        miniz_def_alloc_func(opaque, items, size)
+    }
+
+    unsafe extern "C" fn counting_free(opaque: *mut c_void, address: *mut c_void) {
+        (*(opaque as *mut Counts)).frees += 1;
+        miniz_def_free_func(opaque, address)
+    }
+
+    let mut counts = Counts::default();
+    let opaque = &mut counts as *mut Counts as *mut c_void;
+    let mut data = get_test_data();
+    let mut compressed = vec![0; data.len() + 100];
+    unsafe {
+        let mut stream = mz_stream {
+            next_in: data.as_mut_ptr(),
+            avail_in: data.len() as u32,
+            next_out: compressed.as_mut_ptr(),
+            avail_out: compressed.len() as u32,
+            zalloc: Some(counting_alloc),
+            zfree: Some(counting_free),
+            opaque,
+            ..Default::default()
+        };
+        assert_eq!(mz_deflateInit(&mut stream, 6), MZStatus::Ok as i32);
+        // The state and the dictionary, hash chain, huffman table and output buffers of the
+        // compressor.
+        assert_eq!((counts.allocs, counts.frees), (6, 0));
+        assert!(counts.bytes > 300_000);
+        let mut copy = mz_stream::default();
+        assert_eq!(mz_deflateCopy(&mut copy, &mut stream), MZStatus::Ok as i32);
+        assert_eq!(mz_deflateEnd(&mut copy), MZStatus::Ok as i32);
+        assert_eq!((counts.allocs, counts.frees), (12, 6));
+
+        assert_eq!(mz_deflate(&mut stream, 4), MZStatus::StreamEnd as i32);
+        assert_eq!(stream.opaque, opaque);
+        compressed.truncate(stream.total_out as usize);
+        assert_eq!(mz_deflateEnd(&mut stream), MZStatus::Ok as i32);
+        assert_eq!((counts.allocs, counts.frees), (12, 12));
+
+        // Buffers allocated before a failed allocation are freed again.
+        (*(opaque as *mut Counts)).limit = Some(15);
+        assert_eq!(mz_deflateInit(&mut stream, 6), MZError::Mem as i32);
+        assert!(stream.state.is_none());
+        assert_eq!((counts.allocs, counts.frees), (15, 15));
+        *(opaque as *mut Counts) = Counts::default();
+    }
+
+    let mut decompressed = vec![0; data.len()];
+    unsafe {
+        let mut stream = mz_stream {
+            next_in: compressed.as_mut_ptr(),
+            avail_in: compressed.len() as u32,
+            next_out: decompressed.as_mut_ptr(),
+            avail_out: decompressed.len() as u32,
+            zalloc: Some(counting_alloc),
+            zfree: Some(counting_free),
+            opaque,
+            ..Default::default()
+        };
+        assert_eq!(mz_inflateInit(&mut stream), MZStatus::Ok as i32);
+        let mut copy = mz_stream::default();
+        assert_eq!(mz_inflateCopy(&mut copy, &mut stream), MZStatus::Ok as i32);
+        assert_eq!(copy.opaque, opaque);
+        assert_eq!(mz_inflateEnd(&mut copy), MZStatus::Ok as i32);
+        assert_eq!((counts.allocs, counts.frees), (2, 1));
+
+        assert_eq!(mz_inflate(&mut stream, 4), MZStatus::StreamEnd as i32);
+        assert_eq!(mz_inflateEnd(&mut stream), MZStatus::Ok as i32);
+        assert_eq!((counts.allocs, counts.frees), (2, 2));
+
+        // A failed allocation is reported as a memory error.
+        (*(opaque as *mut Counts)).limit = Some(2);
+        assert_eq!(mz_inflateInit(&mut stream), MZError::Mem as i32);
+        assert!(stream.state.is_none());
+    }
+    assert!(decompressed == data);
+}
+
+#[test]
+fn c_api_error_messages() {
+    use miniz_oxide::MZError;
+    use miniz_oxide_c_api::{
+        mz_compileFlags, mz_error, mz_inflate, mz_inflateEnd, mz_inflateInit, mz_stream, mz_version,
+    };
+    use std::ffi::CStr;
+
+    unsafe fn inflate_error(mut compressed: Vec<u8>) -> String {
+        let mut output = vec![0; 1000];
+        let mut stream = mz_stream {
+            next_in: compressed.as_mut_ptr(),
+            avail_in: compressed.len() as u32,
+            next_out: output.as_mut_ptr(),
+            avail_out: output.len() as u32,
+            ..Default::default()
+        };
+        assert_eq!(mz_inflateInit(&mut stream), 0);
+        assert!(stream.msg.is_null());
+        assert_eq!(mz_inflate(&mut stream, 4), MZError::Data as i32);
+        let msg = CStr::from_ptr(stream.msg).to_str().unwrap().to_owned();
+        mz_inflateEnd(&mut stream);
+        msg
+    }
+
+    let compressed = miniz_oxide::deflate::compress_to_vec_zlib(b"Hello, hello!", 6);
+    let mut bad_trailer = compressed.clone();
+    *bad_trailer.last_mut().unwrap() ^= 1;
+    let mut bad_header = compressed;
+    bad_header[1] ^= 1;
+    unsafe {
+        assert_eq!(inflate_error(bad_trailer), "incorrect data check");
+        assert_eq!(inflate_error(bad_header), "incorrect header check");
+
+        assert_eq!(CStr::from_ptr(mz_error(-3)).to_str(), Ok("data error"));
+        assert_eq!(CStr::from_ptr(mz_error(1)).to_str(), Ok("stream end"));
+        assert!(mz_error(-42).is_null());
+        assert_eq!(
+            CStr::from_ptr(mz_version()).to_str(),
+            Ok(env!("CARGO_PKG_VERSION"))
+        );
+    }
+
+    let flags = mz_compileFlags();
+    assert_eq!(flags & 3, 1);
+    assert_eq!((flags >> 6) & 3, 2);
+    assert_eq!(flags & (1 << 16), 0);
+    assert_eq!(flags & (1 << 17), 0);
+}
+
+#[test]
+fn c_api_inflate_back() {
+    use miniz_oxide::{MZError, MZStatus};
+    use miniz_oxide_c_api::{mz_inflateBack, mz_inflateBackEnd, mz_inflateBackInit, mz_stream};
+    use std::ffi::{c_void, CStr};
+    use std::os::raw::{c_int, c_uint};
+
+    struct Input<'a> {
+        data: &'a [u8],
+        chunk: usize,
+    }
+
+    struct Output {
+        data: Vec<u8>,
+        limit: usize,
+    }
+
+    unsafe extern "C" fn read(desc: *mut c_void, buf: *mut *const u8) -> c_uint {
+        let input = &mut *(desc as *mut Input);
+        let len = input.chunk.min(input.data.len());
+        *buf = input.data.as_ptr();
+        input.data = &input.data[len..];
+        len as c_uint
+    }
+
+    unsafe extern "C" fn write(desc: *mut c_void, buf: *mut u8, len: c_uint) -> c_int {
+        let output = &mut *(desc as *mut Output);
+        assert!(len as usize <= 1 << 15);
+        output
+            .data
+            .extend_from_slice(std::slice::from_raw_parts(buf, len as usize));
+        c_int::from(output.data.len() >= output.limit)
+    }
+
+    let data = get_test_data();
+    let mut compressed = miniz_oxide::deflate::compress_to_vec(&data, 6);
+    compressed.extend_from_slice(b"trailing");
+    let mut window = vec![0; 1 << 15];
+    unsafe {
+        let mut stream = mz_stream::default();
+        assert_eq!(
+            mz_inflateBackInit(&mut stream, 16, window.as_mut_ptr()),
+            MZError::Stream as i32
+        );
+        assert_eq!(
+            mz_inflateBackInit(&mut stream, 15, window.as_mut_ptr()),
+            MZStatus::Ok as i32
+        );
+
+        // Start with some input in the stream, the rest comes from the input function.
+        stream.next_in = compressed.as_ptr();
+        stream.avail_in = 100;
+        let mut input = Input {
+            data: &compressed[100..],
+            chunk: 1000,
+        };
+        let mut output = Output {
+            data: Vec::new(),
+            limit: usize::MAX,
+        };
+        let in_desc = &mut input as *mut Input as *mut c_void;
+        let out_desc = &mut output as *mut Output as *mut c_void;
+        let status = mz_inflateBack(&mut stream, Some(read), in_desc, Some(write), out_desc);
+        assert_eq!(status, MZStatus::StreamEnd as i32);
+        assert!(output.data == data);
+        let unused = std::slice::from_raw_parts(stream.next_in, stream.avail_in as usize);
+        assert!(b"trailing".ends_with(unused));
+
+        // Stopping in the output function.
+        stream.next_in = std::ptr::null();
+        stream.avail_in = 0;
+        (*(in_desc as *mut Input)).data = &compressed;
+        output.data.clear();
+        output.limit = 50_000;
+        let status = mz_inflateBack(&mut stream, Some(read), in_desc, Some(write), out_desc);
+        assert_eq!(status, MZError::Buf as i32);
+        assert!(!stream.next_in.is_null());
+        assert!(output.data.len() >= 50_000 && data.starts_with(&output.data));
+
+        // Running out of input.
+        (*(in_desc as *mut Input)).data = &compressed[..compressed.len() / 2];
+        output.data.clear();
+        output.limit = usize::MAX;
+        let status = mz_inflateBack(&mut stream, Some(read), in_desc, Some(write), out_desc);
+        assert_eq!(status, MZError::Buf as i32);
+        assert!(stream.next_in.is_null());
+
+        // Invalid data.
+        let bad = [0xFFu8; 16];
+        (*(in_desc as *mut Input)).data = &bad;
+        let status = mz_inflateBack(&mut stream, Some(read), in_desc, Some(write), out_desc);
+        assert_eq!(status, MZError::Data as i32);
+        assert_eq!(
+            CStr::from_ptr(stream.msg).to_str(),
+            Ok("invalid block type")
+        );
+
+        assert_eq!(mz_inflateBackEnd(&mut stream), MZStatus::Ok as i32);
+        assert!(stream.state.is_none());
+    }
+}
+
+#[test]
+fn c_api_inflate_reset_and_uncompress2() {
+    use miniz_oxide::{MZError, MZStatus};
+    use miniz_oxide_c_api::{
+        mz_inflate, mz_inflateEnd, mz_inflateInit, mz_inflateReset, mz_inflateReset2, mz_stream,
+        mz_uncompress2,
+    };
+
+    let data = get_test_data();
+    let mut zlib = miniz_oxide::deflate::compress_to_vec_zlib(&data, 6);
+    let mut raw = miniz_oxide::deflate::compress_to_vec(&data, 6);
+    let mut decompressed = vec![0; data.len()];
+    unsafe {
+        let mut stream = mz_stream::default();
+        assert_eq!(mz_inflateInit(&mut stream), MZStatus::Ok as i32);
+        for _ in 0..2 {
+            stream.next_in = zlib.as_mut_ptr();
+            stream.avail_in = zlib.len() as u32;
+            stream.next_out = decompressed.as_mut_ptr();
+            stream.avail_out = decompressed.len() as u32;
+            assert_eq!(mz_inflate(&mut stream, 4), MZStatus::StreamEnd as i32);
+            assert_eq!(stream.total_in, zlib.len() as libc::c_ulong);
+            assert_eq!(stream.total_out, data.len() as libc::c_ulong);
+            assert!(decompressed == data);
+            assert_eq!(mz_inflateReset(&mut stream), MZStatus::Ok as i32);
+            assert_eq!(stream.total_out, 0);
+        }
+
+        // Switch to raw deflate data.
+        assert_eq!(mz_inflateReset2(&mut stream, -7), MZError::Stream as i32);
+        assert_eq!(mz_inflateReset2(&mut stream, -15), MZStatus::Ok as i32);
+        decompressed.iter_mut().for_each(|b| *b = 0);
+        stream.next_in = raw.as_mut_ptr();
+        stream.avail_in = raw.len() as u32;
+        stream.next_out = decompressed.as_mut_ptr();
+        stream.avail_out = decompressed.len() as u32;
+        assert_eq!(mz_inflate(&mut stream, 4), MZStatus::StreamEnd as i32);
+        assert!(decompressed == data);
+
+        // Switch back to detecting the format, with a smaller window size.
+        assert_eq!(
+            mz_inflateReset2(&mut stream, 7 + 32),
+            MZError::Stream as i32
+        );
+        assert_eq!(mz_inflateReset2(&mut stream, 12 + 32), MZStatus::Ok as i32);
+        decompressed.iter_mut().for_each(|b| *b = 0);
+        stream.next_in = zlib.as_mut_ptr();
+        stream.avail_in = zlib.len() as u32;
+        stream.next_out = decompressed.as_mut_ptr();
+        stream.avail_out = decompressed.len() as u32;
+        assert_eq!(mz_inflate(&mut stream, 4), MZStatus::StreamEnd as i32);
+        assert!(decompressed == data);
+        assert_eq!(mz_inflateReset2(&mut stream, 12), MZStatus::Ok as i32);
+        assert_eq!(mz_inflateEnd(&mut stream), MZStatus::Ok as i32);
+        assert_eq!(mz_inflateReset(&mut stream), MZError::Stream as i32);
+
+        // The input length is updated to the bytes that were used.
+        let compressed_len = zlib.len();
+        zlib.extend_from_slice(b"trailing data");
+        let mut dest_len = decompressed.len() as libc::c_ulong;
+        let mut source_len = zlib.len() as libc::c_ulong;
+        assert_eq!(
+            mz_uncompress2(
+                decompressed.as_mut_ptr(),
+                &mut dest_len,
+                zlib.as_ptr(),
+                &mut source_len
+            ),
+            MZStatus::Ok as i32
+        );
+        assert_eq!(dest_len, data.len() as libc::c_ulong);
+        assert_eq!(source_len, compressed_len as libc::c_ulong);
+
+        let mut source_len = 100;
+        assert_eq!(
+            mz_uncompress2(
+                decompressed.as_mut_ptr(),
+                &mut dest_len,
+                zlib.as_ptr(),
+                &mut source_len
+            ),
+            MZError::Data as i32
+        );
+        assert_eq!(source_len, 100);
+        assert_eq!(
+            mz_uncompress2(
+                decompressed.as_mut_ptr(),
+                &mut dest_len,
+                zlib.as_ptr(),
+                std::ptr::null_mut()
+            ),
+            MZError::Param as i32
+        );
+    }
+}
+
+#[test]
+fn c_api_gzip_window_bits() {
+    use miniz_oxide::{MZError, MZStatus};
+    use miniz_oxide_c_api::{
+        mz_crc32, mz_deflate, mz_deflateEnd, mz_deflateInit2, mz_deflateSetHeader, mz_gz_header,
+        mz_inflate, mz_inflateEnd, mz_inflateGetHeader, mz_inflateInit2, mz_stream, MZ_DEFLATED,
+    };
+    use std::ptr;
+
+    fn empty_header() -> mz_gz_header {
+        mz_gz_header {
+            text: 0,
+            time: 0,
+            xflags: 0,
+            os: 0,
+            extra: ptr::null_mut(),
+            extra_len: 0,
+            extra_max: 0,
+            name: ptr::null_mut(),
+            name_max: 0,
+            comment: ptr::null_mut(),
+            comm_max: 0,
+            hcrc: 0,
+            done: 0,
+        }
+    }
+
+    let data = get_test_data();
+    let crc = unsafe { mz_crc32(0, data.as_ptr(), data.len()) };
+    let mut compressed = vec![0; data.len() + 100];
+    let mut extra = *b"AB\x02\x00hi";
+    let mut name = *b"miniz.c\0";
+    let compressed_len;
+    unsafe {
+        let mut stream = mz_stream::default();
+        assert_eq!(
+            mz_deflateInit2(&mut stream, 6, MZ_DEFLATED, 15 + 32, 9, 0),
+            MZError::Param as i32
+        );
+        assert_eq!(
+            mz_deflateInit2(&mut stream, 6, MZ_DEFLATED, 15 + 16, 9, 0),
+            MZStatus::Ok as i32
+        );
+        let mut header = empty_header();
+        header.time = 1_234_567;
+        header.os = 3;
+        header.extra = extra.as_mut_ptr();
+        header.extra_len = extra.len() as u32;
+        header.name = name.as_mut_ptr();
+        header.hcrc = 1;
+        assert_eq!(
+            mz_deflateSetHeader(&mut stream, &header),
+            MZStatus::Ok as i32
+        );
+        stream.next_in = data.as_ptr();
+        stream.avail_in = data.len() as u32;
+        stream.next_out = compressed.as_mut_ptr();
+        stream.avail_out = compressed.len() as u32;
+        assert_eq!(mz_deflate(&mut stream, 4), MZStatus::StreamEnd as i32);
+        assert_eq!(stream.adler, libc::c_ulong::from(crc));
+        assert_eq!(
+            mz_deflateSetHeader(&mut stream, &header),
+            MZError::Stream as i32
+        );
+        compressed_len = stream.total_out as usize;
+        assert_eq!(mz_deflateEnd(&mut stream), MZStatus::Ok as i32);
+
+        // Headers can only be set with the gzip wrapper.
+        assert_eq!(
+            mz_deflateInit2(&mut stream, 6, MZ_DEFLATED, 15, 9, 0),
+            MZStatus::Ok as i32
+        );
+        assert_eq!(
+            mz_deflateSetHeader(&mut stream, &header),
+            MZError::Stream as i32
+        );
+        assert_eq!(mz_deflateEnd(&mut stream), MZStatus::Ok as i32);
+    }
+    compressed.truncate(compressed_len);
+    assert_eq!(compressed[..2], [0x1F, 0x8B]);
+
+    let zlib = miniz_oxide::deflate::compress_to_vec_zlib(&data, 6);
+    let mut decompressed = vec![0; data.len()];
+    for (window_bits, input) in [
+        (15 + 16, &compressed),
+        (15 + 32, &compressed),
+        (15 + 32, &zlib),
+    ] {
+        let is_gzip = input[0] == 0x1F;
+        unsafe {
+            let mut stream = mz_stream::default();
+            assert_eq!(
+                mz_inflateInit2(&mut stream, window_bits),
+                MZStatus::Ok as i32
+            );
+            let mut header = empty_header();
+            let mut extra_buf = [0xFFu8; 4];
+            let mut name_buf = [0xFFu8; 32];
+            header.extra = extra_buf.as_mut_ptr();
+            header.extra_max = extra_buf.len() as u32;
+            header.name = name_buf.as_mut_ptr();
+            header.name_max = name_buf.len() as u32;
+            header.comment = name_buf.as_mut_ptr();
+            header.done = 42;
+            assert_eq!(
+                mz_inflateGetHeader(&mut stream, &mut header),
+                MZStatus::Ok as i32
+            );
+            assert_eq!(header.done, 0);
+
+            // Only give the first few bytes, which are not enough for the whole header.
+            stream.next_in = input.as_ptr();
+            stream.avail_in = 8;
+            stream.next_out = decompressed.as_mut_ptr();
+            stream.avail_out = decompressed.len() as u32;
+            assert_eq!(mz_inflate(&mut stream, 0), MZStatus::Ok as i32);
+            assert_eq!(header.done, if is_gzip { 0 } else { -1 });
+            stream.avail_in = (input.len() - 8) as u32;
+            assert_eq!(mz_inflate(&mut stream, 4), MZStatus::StreamEnd as i32);
+            assert!(decompressed == data);
+            assert_eq!(mz_inflateEnd(&mut stream), MZStatus::Ok as i32);
+
+            if is_gzip {
+                assert_eq!(stream.adler, libc::c_ulong::from(crc));
+                assert_eq!(header.done, 1);
+                assert_eq!(header.time, 1_234_567);
+                assert_eq!(header.os, 3);
+                assert_eq!(header.hcrc, 1);
+                assert_eq!(header.extra_len, 6);
+                assert_eq!(extra_buf, *b"AB\x02\x00");
+                assert_eq!(name_buf[..9], *b"miniz.c\0\xFF");
+                assert!(header.comment.is_null());
+            } else {
+                assert_eq!(header.done, -1);
+                assert_eq!(extra_buf, [0xFF; 4]);
+            }
+        }
+    }
+
+    unsafe {
+        let mut stream = mz_stream::default();
+        assert_eq!(mz_inflateInit2(&mut stream, 15), MZStatus::Ok as i32);
+        let mut header = empty_header();
+        assert_eq!(
+            mz_inflateGetHeader(&mut stream, &mut header),
+            MZError::Stream as i32
+        );
+        assert_eq!(mz_inflateEnd(&mut stream), MZStatus::Ok as i32);
+    }
+}
+
+#[test]
+fn c_api_window_bits_ranges() {
+    use miniz_oxide::{MZError, MZStatus};
+    use miniz_oxide_c_api::{
+        mz_deflate, mz_deflateEnd, mz_deflateInit2, mz_inflate, mz_inflateEnd, mz_inflateInit2,
+        mz_stream, MZ_DEFLATED,
+    };
+
+    let data = get_test_data();
+    // Smaller windows than 15 bits are accepted and use the full window, and 0 or 32 for the
+    // decompressor use the window size from the header.
+    for (deflate_bits, inflate_bits, magic) in [
+        (9 + 16, 32, Some(0x1F)),
+        (12 + 16, 16, Some(0x1F)),
+        (-9, -9, None),
+        (8, 0, Some(0x78)),
+        (15, 12 + 32, Some(0x78)),
+    ] {
+        let mut compressed = vec![0; data.len() + 100];
+        let mut decompressed = vec![0; data.len()];
+        unsafe {
+            let mut stream = mz_stream::default();
+            assert_eq!(
+                mz_deflateInit2(&mut stream, 6, MZ_DEFLATED, deflate_bits, 9, 0),
+                MZStatus::Ok as i32
+            );
+            stream.next_in = data.as_ptr();
+            stream.avail_in = data.len() as u32;
+            stream.next_out = compressed.as_mut_ptr();
+            stream.avail_out = compressed.len() as u32;
+            assert_eq!(mz_deflate(&mut stream, 4), MZStatus::StreamEnd as i32);
+            compressed.truncate(stream.total_out as usize);
+            assert_eq!(mz_deflateEnd(&mut stream), MZStatus::Ok as i32);
+            if let Some(magic) = magic {
+                assert_eq!(compressed[0], magic);
+            }
+
+            let mut stream = mz_stream::default();
+            assert_eq!(
+                mz_inflateInit2(&mut stream, inflate_bits),
+                MZStatus::Ok as i32
+            );
+            stream.next_in = compressed.as_ptr();
+            stream.avail_in = compressed.len() as u32;
+            stream.next_out = decompressed.as_mut_ptr();
+            stream.avail_out = decompressed.len() as u32;
+            assert_eq!(mz_inflate(&mut stream, 4), MZStatus::StreamEnd as i32);
+            assert!(decompressed == data);
+            assert_eq!(mz_inflateEnd(&mut stream), MZStatus::Ok as i32);
+        }
+    }
+
+    for window_bits in [0, 7, 16, -7, 32, 24 + 16] {
+        let mut stream = mz_stream::default();
+        assert_eq!(
+            unsafe { mz_deflateInit2(&mut stream, 6, MZ_DEFLATED, window_bits, 9, 0) },
+            MZError::Param as i32
+        );
+    }
+    for window_bits in [7, -7, -16, 17, 48, 12 + 64] {
+        let mut stream = mz_stream::default();
+        assert_eq!(
+            unsafe { mz_inflateInit2(&mut stream, window_bits) },
+            MZError::Param as i32
+        );
+    }
+}
+
+fn gz_temp_path(name: &str) -> (std::path::PathBuf, std::ffi::CString) {
+    let path = std::env::temp_dir().join(format!("miniz_gz_{}_{}", name, std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let c_path = std::ffi::CString::new(path.to_str().unwrap()).unwrap();
+    (path, c_path)
+}
+
+#[test]
+fn c_api_gz_write_and_read() {
+    use miniz_oxide_c_api::*;
+    use std::ffi::CStr;
+    use std::os::raw::{c_char, c_int, c_long};
+
+    let data = get_test_data();
+    let (path, c_path) = gz_temp_path("write_and_read.gz");
+    unsafe {
+        let file = mz_gzopen(c_path.as_ptr(), b"wb9\0".as_ptr() as *const c_char);
+        assert!(!file.is_null());
+        assert_eq!(mz_gzdirect(file), 0);
+        for chunk in data.chunks(10_000) {
+            let written = mz_gzwrite(file, chunk.as_ptr() as *const _, chunk.len() as u32);
+            assert_eq!(written, chunk.len() as c_int);
+        }
+        assert_eq!(mz_gzflush(file, MZFlush::Sync as c_int), 0);
+        assert_eq!(mz_gzsetparams(file, 1, 0), 0);
+        assert_eq!(
+            mz_gzputs(file, b"line one\nline two\n\0".as_ptr() as *const c_char),
+            18
+        );
+        assert_eq!(mz_gzputc(file, c_int::from(b'!')), c_int::from(b'!'));
+        // Seeking forward writes zeros.
+        let end = data.len() as c_long + 19;
+        assert_eq!(mz_gzseek(file, 5, libc::SEEK_CUR), end + 5);
+        assert_eq!(mz_gzseek(file, 0, libc::SEEK_SET), -1);
+        assert_eq!(mz_gztell(file), end + 5);
+        assert_eq!(mz_gzread(file, [0u8; 4].as_mut_ptr() as *mut _, 4), -1);
+        assert_eq!(mz_gzclose(file), 0);
+    }
+
+    let mut expected = data.clone();
+    expected.extend_from_slice(b"line one\nline two\n!\0\0\0\0\0");
+    let compressed = std::fs::read(&path).unwrap();
+    assert_eq!(compressed[..2], [0x1F, 0x8B]);
+    let decompressed = miniz_oxide::inflate::decompress_to_vec_gzip(&compressed).unwrap();
+    assert!(decompressed == expected);
+
+    unsafe {
+        let file = mz_gzopen(c_path.as_ptr(), b"rb\0".as_ptr() as *const c_char);
+        assert!(!file.is_null());
+        assert_eq!(mz_gzdirect(file), 0);
+        let mut buf = vec![0u8; data.len()];
+        let read = mz_gzread(file, buf.as_mut_ptr() as *mut _, buf.len() as u32);
+        assert_eq!(read, data.len() as c_int);
+        assert!(buf == data);
+        assert_eq!(mz_gzeof(file), 0);
+
+        let mut line = [0 as c_char; 64];
+        assert!(!mz_gzgets(file, line.as_mut_ptr(), 64).is_null());
+        assert_eq!(CStr::from_ptr(line.as_ptr()).to_bytes(), b"line one\n");
+        assert!(!mz_gzgets(file, line.as_mut_ptr(), 5).is_null());
+        assert_eq!(CStr::from_ptr(line.as_ptr()).to_bytes(), b"line");
+        assert_eq!(mz_gztell(file), data.len() as c_long + 13);
+
+        // Seek back into the data, which restarts decompression.
+        assert_eq!(mz_gzseek(file, 100, libc::SEEK_SET), 100);
+        assert_eq!(mz_gzgetc(file), c_int::from(data[100]));
+        assert_eq!(
+            mz_gzseek(file, data.len() as c_long - 101 + 14, libc::SEEK_CUR),
+            data.len() as c_long + 14
+        );
+        assert!(!mz_gzgets(file, line.as_mut_ptr(), 64).is_null());
+        assert_eq!(CStr::from_ptr(line.as_ptr()).to_bytes(), b"two\n");
+        let mut rest = [0xFFu8; 16];
+        assert_eq!(mz_gzread(file, rest.as_mut_ptr() as *mut _, 16), 6);
+        assert_eq!(rest[..6], *b"!\0\0\0\0\0");
+        assert_eq!(mz_gzeof(file), 1);
+        assert_eq!(mz_gzgetc(file), -1);
+        assert!(mz_gzgets(file, line.as_mut_ptr(), 64).is_null());
+
+        assert_eq!(mz_gzrewind(file), 0);
+        assert_eq!(mz_gzeof(file), 0);
+        assert_eq!(mz_gzgetc(file), c_int::from(data[0]));
+        let mut errnum = 1;
+        assert_eq!(
+            CStr::from_ptr(mz_gzerror(file, &mut errnum)).to_bytes(),
+            b""
+        );
+        assert_eq!(errnum, 0);
+        assert_eq!(mz_gzwrite(file, data.as_ptr() as *const _, 1), 0);
+        assert_eq!(mz_gzclose(file), 0);
+    }
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn c_api_gz_append_and_transparent() {
+    use miniz_oxide_c_api::*;
+    use std::os::raw::{c_char, c_int};
+
+    let (path, c_path) = gz_temp_path("append.gz");
+    unsafe {
+        for (mode, text) in [
+            (&b"wb\0"[..], &b"first member, "[..]),
+            (b"ab1\0", b"second"),
+        ] {
+            let file = mz_gzopen(c_path.as_ptr(), mode.as_ptr() as *const c_char);
+            assert!(!file.is_null());
+            let len = text.len() as u32;
+            assert_eq!(
+                mz_gzwrite(file, text.as_ptr() as *const _, len),
+                len as c_int
+            );
+            assert_eq!(mz_gzclose(file), 0);
+        }
+        // Exclusive mode fails as the file exists.
+        let file = mz_gzopen(c_path.as_ptr(), b"wbx\0".as_ptr() as *const c_char);
+        assert!(file.is_null());
+        assert!(mz_gzopen(c_path.as_ptr(), b"r+\0".as_ptr() as *const c_char).is_null());
+        assert!(mz_gzopen(c_path.as_ptr(), b"b\0".as_ptr() as *const c_char).is_null());
+    }
+
+    // Anything after the last member is ignored.
+    let mut compressed = std::fs::read(&path).unwrap();
+    compressed.extend_from_slice(b"garbage");
+    std::fs::write(&path, &compressed).unwrap();
+    let mut buf = [0u8; 64];
+    unsafe {
+        let file = mz_gzopen(c_path.as_ptr(), b"rb\0".as_ptr() as *const c_char);
+        let read = mz_gzread(file, buf.as_mut_ptr() as *mut _, 64);
+        assert_eq!(&buf[..read as usize], b"first member, second");
+        assert_eq!(mz_gzclose(file), 0);
+    }
+
+    // Uncompressed files are read as is, and written as is with `T`.
+    unsafe {
+        let file = mz_gzopen(c_path.as_ptr(), b"wT\0".as_ptr() as *const c_char);
+        assert_eq!(mz_gzdirect(file), 1);
+        assert_eq!(
+            mz_gzputs(file, b"plain text\0".as_ptr() as *const c_char),
+            10
+        );
+        assert_eq!(mz_gzclose(file), 0);
+    }
+    assert_eq!(std::fs::read(&path).unwrap(), b"plain text");
+    unsafe {
+        let file = mz_gzopen(c_path.as_ptr(), b"rb\0".as_ptr() as *const c_char);
+        assert_eq!(mz_gzdirect(file), 1);
+        let read = mz_gzread(file, buf.as_mut_ptr() as *mut _, 64);
+        assert_eq!(&buf[..read as usize], b"plain text");
+        assert_eq!(mz_gzclose(file), 0);
+    }
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn c_api_gz_dopen() {
+    use miniz_oxide_c_api::*;
+    use std::os::raw::{c_char, c_int};
+    use std::os::unix::io::IntoRawFd;
+
+    let (path, _) = gz_temp_path("dopen.gz");
+    let data = b"written through a file descriptor";
+    unsafe {
+        let fd = std::fs::File::create(&path).unwrap().into_raw_fd();
+        let file = mz_gzdopen(fd, b"w\0".as_ptr() as *const c_char);
+        assert!(!file.is_null());
+        let len = data.len() as u32;
+        assert_eq!(
+            mz_gzwrite(file, data.as_ptr() as *const _, len),
+            len as c_int
+        );
+        assert_eq!(mz_gzclose(file), 0);
+        assert!(mz_gzdopen(-1, b"r\0".as_ptr() as *const c_char).is_null());
+    }
+    let compressed = std::fs::read(&path).unwrap();
+    let decompressed = miniz_oxide::inflate::decompress_to_vec_gzip(&compressed).unwrap();
+    assert_eq!(decompressed, data);
+
+    unsafe {
+        let fd = std::fs::File::open(&path).unwrap().into_raw_fd();
+        let file = mz_gzdopen(fd, b"r\0".as_ptr() as *const c_char);
+        let mut buf = [0u8; 64];
+        let read = mz_gzread(file, buf.as_mut_ptr() as *mut _, 64);
+        assert_eq!(&buf[..read as usize], data);
+        assert_eq!(mz_gzclose(file), 0);
+    }
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn c_api_gz_errors() {
+    use miniz_oxide_c_api::*;
+    use std::ffi::CStr;
+    use std::os::raw::{c_char, c_int};
+
+    let data = get_test_data();
+    let compressed = miniz_oxide::deflate::compress_to_vec_gzip(&data, 6);
+    let (path, c_path) = gz_temp_path("errors.gz");
+    let mut buf = vec![0u8; data.len()];
+
+    // A truncated file gives the data that could be decompressed, then an error.
+    std::fs::write(&path, &compressed[..compressed.len() / 2]).unwrap();
+    unsafe {
+        let file = mz_gzopen(c_path.as_ptr(), b"rb\0".as_ptr() as *const c_char);
+        let read = mz_gzread(file, buf.as_mut_ptr() as *mut _, buf.len() as u32);
+        assert!(read > 0 && (read as usize) < data.len());
+        assert!(data.starts_with(&buf[..read as usize]));
+        let mut errnum = 0;
+        let msg = CStr::from_ptr(mz_gzerror(file, &mut errnum));
+        assert_eq!(msg.to_bytes(), b"unexpected end of file");
+        assert_eq!(errnum, MZError::Buf as c_int);
+        assert_eq!(mz_gzclose(file), MZError::Buf as c_int);
+    }
+
+    // Corrupt data and checksums are reported.
+    let mut corrupt = compressed.clone();
+    let len = corrupt.len();
+    corrupt[len - 8] ^= 1;
+    std::fs::write(&path, &corrupt).unwrap();
+    unsafe {
+        let file = mz_gzopen(c_path.as_ptr(), b"rb\0".as_ptr() as *const c_char);
+        let read = mz_gzread(file, buf.as_mut_ptr() as *mut _, buf.len() as u32);
+        assert_eq!(read, data.len() as c_int);
+        assert_eq!(mz_gzread(file, buf.as_mut_ptr() as *mut _, 1), -1);
+        let mut errnum = 0;
+        let msg = CStr::from_ptr(mz_gzerror(file, &mut errnum));
+        assert_eq!(msg.to_bytes(), b"incorrect data check");
+        assert_eq!(errnum, MZError::Data as c_int);
+        mz_gzclearerr(file);
+        assert_eq!(
+            CStr::from_ptr(mz_gzerror(file, &mut errnum)).to_bytes(),
+            b""
+        );
+        assert_eq!(errnum, 0);
+        assert_eq!(mz_gzclose(file), 0);
+
+        assert!(mz_gzerror(std::ptr::null_mut(), &mut errnum).is_null());
+        assert_eq!(mz_gzclose(std::ptr::null_mut()), MZError::Stream as c_int);
+        let missing = std::ffi::CString::new("does/not/exist.gz").unwrap();
+        assert!(mz_gzopen(missing.as_ptr(), b"rb\0".as_ptr() as *const c_char).is_null());
+    }
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[cfg(feature = "miniz_zip")]
+mod zip {
+    use std::ffi::{CStr, CString};
+    use std::{mem, ptr, slice};
+
+    use miniz_oxide::zip::ZipArchive;
+    use miniz_oxide_c_api::*;
+
+    unsafe fn extract_to_vec(zip: &mut mz_zip_archive, index: u32) -> Vec<u8> {
+        let mut size = 0;
+        let p = mz_zip_reader_extract_to_heap(zip, index, &mut size, 0);
+        assert!(!p.is_null(), "{:?}", zip.m_last_error);
+        let data = slice::from_raw_parts(p as *const u8, size).to_vec();
+        miniz_def_free_func(ptr::null_mut(), p);
+        data
+    }
+
+    #[test]
+    fn c_api_zip_heap_roundtrip() {
+        let data = super::get_test_data();
+        let small = b"small";
+        let mut archive: Vec<u8>;
+        unsafe {
+            let mut zip: mz_zip_archive = mem::zeroed();
+            assert_eq!(mz_zip_writer_init_heap(&mut zip, 0, 0), 1);
+            let name = CString::new("dir/data.c").unwrap();
+            assert_eq!(
+                mz_zip_writer_add_mem(&mut zip, name.as_ptr(), data.as_ptr() as _, data.len(), 9),
+                1
+            );
+            let name = CString::new("dir/").unwrap();
+            assert_eq!(
+                mz_zip_writer_add_mem(&mut zip, name.as_ptr(), ptr::null(), 0, 6),
+                1
+            );
+            let name = CString::new("Small.txt").unwrap();
+            let comment = b"a comment";
+            assert_eq!(
+                mz_zip_writer_add_mem_ex(
+                    &mut zip,
+                    name.as_ptr(),
+                    small.as_ptr() as _,
+                    small.len(),
+                    comment.as_ptr() as _,
+                    comment.len() as u16,
+                    0,
+                    0,
+                    0
+                ),
+                1
+            );
+            let name = CString::new("c:\\bad").unwrap();
+            assert_eq!(
+                mz_zip_writer_add_mem(&mut zip, name.as_ptr(), ptr::null(), 0, 6),
+                0
+            );
+            assert_eq!(
+                mz_zip_get_last_error(&mut zip),
+                mz_zip_error::MZ_ZIP_INVALID_FILENAME
+            );
+            assert_eq!(mz_zip_reader_get_num_files(&mut zip), 3);
+
+            let mut buf = ptr::null_mut();
+            let mut size = 0;
+            assert_eq!(
+                mz_zip_writer_finalize_heap_archive(&mut zip, &mut buf, &mut size),
+                1
+            );
+            assert_eq!(mz_zip_writer_end(&mut zip), 1);
+            archive = slice::from_raw_parts(buf as *const u8, size).to_vec();
+            miniz_def_free_func(ptr::null_mut(), buf);
+        }
+
+        // The archive can be read by the Rust reader.
+        let reader = ZipArchive::new(&archive).unwrap();
+        assert_eq!(reader.len(), 3);
+        assert_eq!(reader.extract_to_vec(0).unwrap(), data);
+        assert!(reader.entries()[1].is_dir());
+
+        unsafe {
+            let mut zip: mz_zip_archive = mem::zeroed();
+            assert_eq!(
+                mz_zip_reader_init_mem(&mut zip, archive.as_ptr() as _, archive.len(), 0),
+                1
+            );
+            assert_eq!(mz_zip_get_type(&mut zip), mz_zip_type::MZ_ZIP_TYPE_MEMORY);
+            assert_eq!(mz_zip_reader_get_num_files(&mut zip), 3);
+            assert_eq!(extract_to_vec(&mut zip, 0), data);
+            assert_eq!(extract_to_vec(&mut zip, 2), small);
+
+            let name = CString::new("SMALL.TXT").unwrap();
+            let comment = CString::new("a comment").unwrap();
+            assert_eq!(
+                mz_zip_reader_locate_file(&mut zip, name.as_ptr(), ptr::null(), 0),
+                2
+            );
+            assert_eq!(
+                mz_zip_reader_locate_file(&mut zip, name.as_ptr(), comment.as_ptr(), 0),
+                2
+            );
+            assert_eq!(
+                mz_zip_reader_locate_file(
+                    &mut zip,
+                    name.as_ptr(),
+                    ptr::null(),
+                    MZ_ZIP_FLAG_CASE_SENSITIVE
+                ),
+                -1
+            );
+            assert_eq!(
+                mz_zip_peek_last_error(&mut zip),
+                mz_zip_error::MZ_ZIP_FILE_NOT_FOUND
+            );
+            let name = CString::new("data.c").unwrap();
+            assert_eq!(
+                mz_zip_reader_locate_file(&mut zip, name.as_ptr(), ptr::null(), 0),
+                -1
+            );
+            assert_eq!(
+                mz_zip_reader_locate_file(
+                    &mut zip,
+                    name.as_ptr(),
+                    ptr::null(),
+                    MZ_ZIP_FLAG_IGNORE_PATH
+                ),
+                0
+            );
+
+            let mut stat: mz_zip_archive_file_stat = mem::zeroed();
+            assert_eq!(mz_zip_reader_file_stat(&mut zip, 2, &mut stat), 1);
+            assert_eq!(
+                CStr::from_ptr(stat.m_filename.as_ptr()).to_bytes(),
+                b"Small.txt"
+            );
+            assert_eq!(
+                CStr::from_ptr(stat.m_comment.as_ptr()).to_bytes(),
+                b"a comment"
+            );
+            assert_eq!(stat.m_comment_size, 9);
+            assert_eq!(stat.m_uncomp_size, small.len() as u64);
+            assert_eq!(stat.m_method, 0);
+            assert_eq!(stat.m_is_supported, 1);
+            assert_eq!(mz_zip_reader_is_file_a_directory(&mut zip, 1), 1);
+            assert_eq!(mz_zip_reader_is_file_a_directory(&mut zip, 2), 0);
+            assert_eq!(mz_zip_reader_file_stat(&mut zip, 3, &mut stat), 0);
+
+            let mut out = vec![0; data.len() - 1];
+            assert_eq!(
+                mz_zip_reader_extract_to_mem(&mut zip, 0, out.as_mut_ptr() as _, out.len(), 0),
+                0
+            );
+            assert_eq!(
+                mz_zip_get_last_error(&mut zip),
+                mz_zip_error::MZ_ZIP_BUF_TOO_SMALL
+            );
+
+            // Read the data in small pieces.
+            let iter = mz_zip_reader_extract_iter_new(&mut zip, 0, 0);
+            assert!(!iter.is_null());
+            let mut out = Vec::new();
+            let mut piece = [0; 1000];
+            loop {
+                let n = mz_zip_reader_extract_iter_read(iter, piece.as_mut_ptr() as _, 1000);
+                if n == 0 {
+                    break;
+                }
+                out.extend_from_slice(&piece[..n]);
+            }
+            assert_eq!(mz_zip_reader_extract_iter_free(iter), 1);
+            assert_eq!(out, data);
+
+            assert_eq!(mz_zip_validate_archive(&mut zip, 0), 1);
+            assert_eq!(mz_zip_reader_end(&mut zip), 1);
+            assert_eq!(mz_zip_get_mode(&mut zip), mz_zip_mode::MZ_ZIP_MODE_INVALID);
+
+            // Corrupt the compressed data of the first entry.
+            let data_start = 30 + "dir/data.c".len();
+            archive[data_start + 100] ^= 0xFF;
+            let mut err = mz_zip_error::MZ_ZIP_NO_ERROR;
+            assert_eq!(
+                mz_zip_validate_mem_archive(archive.as_ptr() as _, archive.len(), 0, &mut err),
+                0
+            );
+            assert_ne!(err, mz_zip_error::MZ_ZIP_NO_ERROR);
+            assert_eq!(
+                mz_zip_validate_mem_archive(
+                    archive.as_ptr() as _,
+                    archive.len(),
+                    MZ_ZIP_FLAG_VALIDATE_HEADERS_ONLY,
+                    &mut err
+                ),
+                1
+            );
+            assert_eq!(err, mz_zip_error::MZ_ZIP_NO_ERROR);
+        }
+    }
+
+    #[test]
+    fn c_api_zip_read_file() {
+        let path = CString::new("miniz_oxide/tests/test_data/archive.zip").unwrap();
+        let data = super::get_test_file_data(path.to_str().unwrap());
+        let reader = ZipArchive::new(&data).unwrap();
+        unsafe {
+            let mut zip: mz_zip_archive = mem::zeroed();
+            assert_eq!(mz_zip_reader_init_file(&mut zip, path.as_ptr(), 0), 1);
+            assert_eq!(mz_zip_get_type(&mut zip), mz_zip_type::MZ_ZIP_TYPE_FILE);
+            assert_eq!(mz_zip_get_archive_size(&mut zip), data.len() as u64);
+            assert_eq!(mz_zip_reader_get_num_files(&mut zip) as usize, reader.len());
+            for (i, entry) in reader.entries().iter().enumerate() {
+                let mut name = [0; 512];
+                let len = mz_zip_reader_get_filename(&mut zip, i as u32, name.as_mut_ptr(), 512);
+                assert_eq!(len as usize, entry.name.len() + 1);
+                if !entry.is_dir() {
+                    assert_eq!(
+                        extract_to_vec(&mut zip, i as u32),
+                        reader.extract_to_vec(i).unwrap()
+                    );
+                }
+            }
+            assert_eq!(mz_zip_end(&mut zip), 1);
+
+            let missing = CString::new("does/not/exist.zip").unwrap();
+            assert_eq!(mz_zip_reader_init_file(&mut zip, missing.as_ptr(), 0), 0);
+            let err = mz_zip_get_last_error(&mut zip);
+            assert_eq!(err, mz_zip_error::MZ_ZIP_FILE_OPEN_FAILED);
+            assert_eq!(
+                CStr::from_ptr(mz_zip_get_error_string(err as i32)).to_bytes(),
+                b"file open failed"
+            );
+            assert_eq!(
+                CStr::from_ptr(mz_zip_get_error_string(1000)).to_bytes(),
+                b"unknown error"
+            );
+        }
+    }
+
+    #[test]
+    fn c_api_zip_append_in_place() {
+        let path =
+            std::env::temp_dir().join(format!("miniz_zip_append_{}.zip", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let zip_path = CString::new(path.to_str().unwrap()).unwrap();
+        let names = ["first.txt", "second.txt", "third.txt"];
+        unsafe {
+            for name in &names {
+                let name = CString::new(*name).unwrap();
+                let mut err = mz_zip_error::MZ_ZIP_UNDEFINED_ERROR;
+                assert_eq!(
+                    mz_zip_add_mem_to_archive_file_in_place_v2(
+                        zip_path.as_ptr(),
+                        name.as_ptr(),
+                        name.as_ptr() as _,
+                        name.as_bytes().len(),
+                        ptr::null(),
+                        0,
+                        CAPICompressionLevel::MZ_DEFAULT_LEVEL as u32,
+                        &mut err,
+                    ),
+                    1
+                );
+                assert_eq!(err, mz_zip_error::MZ_ZIP_NO_ERROR);
+            }
+
+            for name in &names {
+                let name = CString::new(*name).unwrap();
+                let mut size = 0;
+                let p = mz_zip_extract_archive_file_to_heap(
+                    zip_path.as_ptr(),
+                    name.as_ptr(),
+                    &mut size,
+                    0,
+                );
+                assert!(!p.is_null());
+                assert_eq!(slice::from_raw_parts(p as *const u8, size), name.as_bytes());
+                miniz_def_free_func(ptr::null_mut(), p);
+            }
+            let mut err = mz_zip_error::MZ_ZIP_NO_ERROR;
+            assert_eq!(
+                mz_zip_validate_file_archive(zip_path.as_ptr(), 0, &mut err),
+                1
+            );
+        }
+        let data = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(ZipArchive::new(&data).unwrap().len(), names.len());
+    }
+}